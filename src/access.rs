@@ -0,0 +1,355 @@
+#[cfg(feature = "accesskit")]
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{channel, Receiver, Sender},
+        Arc,
+    },
+};
+
+#[cfg(feature = "accesskit")]
+use accesskit::{
+    Action, ActionHandler, ActionRequest, ActivationHandler, DeactivationHandler, Node,
+    NodeBuilder, NodeId, Rect, Role, Toggled, Tree, TreeUpdate,
+};
+#[cfg(feature = "accesskit")]
+use glam::{IVec2, UVec2};
+#[cfg(feature = "accesskit")]
+use log::warn;
+
+#[cfg(feature = "accesskit")]
+use crate::{
+    listui::{ListInterface, ListItem, ListItemEditable, ListItemType, ListUiId, UiEvent},
+    slots::SlotId,
+};
+use crate::{types::ValueStore, window::State};
+
+// Screen reader access to the lists through AccessKit, with the accesskit
+// feature. After each layout the open lists are described as a tree, a node
+// per list holding a node per shown item with its label, value and whether
+// it is selected. Focus follows the selected item of the focused list, so
+// the screen reader announces the selection as it moves. What the screen
+// reader asks for, like focusing or pressing an item, waits for
+// State::apply_access_actions.
+//
+// Only Linux has an adapter so far, through AT-SPI. Without the feature
+// this does nothing, so apps call it the same either way.
+#[derive(Default)]
+pub struct Accessibility {
+    #[cfg(feature = "accesskit")]
+    inner: Option<AccessInner>,
+}
+
+#[cfg(feature = "accesskit")]
+struct AccessInner {
+    #[cfg(target_os = "linux")]
+    adapter: accesskit_unix::Adapter,
+    // set when a screen reader starts listening, so it gets the whole tree
+    activated: Arc<AtomicBool>,
+    requests: Receiver<ActionRequest>,
+    // the tree sent last, so frames where nothing changed send nothing
+    sent: Option<(Vec<(NodeId, Node)>, NodeId)>,
+    // the list, and item in it, each node stands for
+    targets: HashMap<NodeId, (ListUiId, Option<usize>)>,
+}
+
+#[cfg(feature = "accesskit")]
+struct Activation(Arc<AtomicBool>);
+
+#[cfg(feature = "accesskit")]
+impl ActivationHandler for Activation {
+    // the tree goes out with the next update instead
+    fn request_initial_tree(&mut self) -> Option<TreeUpdate> {
+        self.0.store(true, Ordering::Relaxed);
+        None
+    }
+}
+
+#[cfg(feature = "accesskit")]
+struct Requests(Sender<ActionRequest>);
+
+#[cfg(feature = "accesskit")]
+impl ActionHandler for Requests {
+    fn do_action(&mut self, request: ActionRequest) {
+        let _ = self.0.send(request);
+    }
+}
+
+#[cfg(feature = "accesskit")]
+struct Deactivation;
+
+#[cfg(feature = "accesskit")]
+impl DeactivationHandler for Deactivation {
+    fn deactivate_accessibility(&mut self) {}
+}
+
+#[cfg(feature = "accesskit")]
+const ROOT: NodeId = NodeId(0);
+
+// Lists get the high bits and items the low ones, counted from 1 so neither
+// is ever the root.
+#[cfg(feature = "accesskit")]
+fn node_id(list: ListUiId, item: Option<usize>) -> NodeId {
+    let list = (list.key().index as u64 + 1) << 32;
+    NodeId(list | item.map_or(0, |item| item as u64 + 1))
+}
+
+#[allow(dead_code)]
+impl Accessibility {
+    // Whether screen readers are reached, i.e. shecv was built with the
+    // accesskit feature for a platform that has an adapter.
+    pub fn enabled(&self) -> bool {
+        cfg!(all(feature = "accesskit", target_os = "linux"))
+    }
+
+    #[cfg(feature = "accesskit")]
+    fn inner(&mut self) -> &mut AccessInner {
+        self.inner.get_or_insert_with(|| {
+            let activated = Arc::new(AtomicBool::new(false));
+            let (sender, requests) = channel();
+            #[cfg(target_os = "linux")]
+            let adapter = accesskit_unix::Adapter::new(
+                Activation(activated.clone()),
+                Requests(sender),
+                Deactivation,
+            );
+            #[cfg(not(target_os = "linux"))]
+            {
+                let _ = (
+                    Activation(activated.clone()),
+                    Requests(sender),
+                    Deactivation,
+                );
+                warn!("no accessibility adapter for this platform");
+            }
+            AccessInner {
+                #[cfg(target_os = "linux")]
+                adapter,
+                activated,
+                requests,
+                sent: None,
+                targets: HashMap::new(),
+            }
+        })
+    }
+
+    #[cfg(feature = "accesskit")]
+    fn send(
+        &mut self,
+        nodes: Vec<(NodeId, Node)>,
+        focus: NodeId,
+        targets: HashMap<NodeId, (ListUiId, Option<usize>)>,
+    ) {
+        let inner = self.inner();
+        inner.targets = targets;
+        let activated = inner.activated.swap(false, Ordering::Relaxed);
+        let tree = (nodes, focus);
+        if !activated && inner.sent.as_ref() == Some(&tree) {
+            return;
+        }
+        #[cfg(target_os = "linux")]
+        inner.adapter.update_if_active(|| {
+            let mut tree_info = Tree::new(ROOT);
+            tree_info.toolkit_name = Some("shecv".to_string());
+            TreeUpdate {
+                nodes: tree.0.clone(),
+                tree: Some(tree_info),
+                focus: tree.1,
+            }
+        });
+        inner.sent = Some(tree);
+    }
+}
+
+#[cfg(feature = "accesskit")]
+fn bounds(xy: IVec2, wh: UVec2, scale_factor: f32) -> Rect {
+    let (xy, wh) = (xy.as_vec2() * scale_factor, wh.as_vec2() * scale_factor);
+    Rect {
+        x0: xy.x as f64,
+        y0: xy.y as f64,
+        x1: (xy.x + wh.x) as f64,
+        y1: (xy.y + wh.y) as f64,
+    }
+}
+
+#[cfg(feature = "accesskit")]
+fn item_node(item: &ListItem, store: &ValueStore) -> NodeBuilder {
+    let editable = matches!(item.editable, ListItemEditable::Editable);
+    let mut node = NodeBuilder::new(match item.ty {
+        ListItemType::Button | ListItemType::SubList => Role::Button,
+        ListItemType::CheckBox => Role::CheckBox,
+        ListItemType::Slider => Role::Slider,
+        ListItemType::Choice => Role::ComboBox,
+        ListItemType::Progress => Role::ProgressIndicator,
+        ListItemType::Text if editable => Role::TextInput,
+        _ => Role::ListItem,
+    });
+    node.set_name(item.label.clone());
+    match item.ty {
+        ListItemType::Button | ListItemType::SubList => {}
+        ListItemType::CheckBox => {
+            let checked = item.read(store, |v| v.as_any().downcast_ref::<bool>().copied());
+            node.set_toggled(match checked {
+                Some(Some(true)) => Toggled::True,
+                _ => Toggled::False,
+            });
+        }
+        ListItemType::Slider | ListItemType::Progress => {
+            if let Some(Some(value)) = item.read(store, |v| v.as_f64()) {
+                node.set_numeric_value(value);
+            }
+            if let Some(range) = &item.range {
+                node.set_min_numeric_value(range.start);
+                node.set_max_numeric_value(range.end);
+            }
+            node.set_value(item.display_value(store));
+        }
+        _ => node.set_value(item.display_value(store)),
+    }
+    if !item.is_enabled(store) {
+        node.set_disabled();
+    }
+    if item.can_select(store) {
+        node.add_action(Action::Focus);
+        node.add_action(Action::Default);
+        if matches!(item.ty, ListItemType::Slider) {
+            node.add_action(Action::Increment);
+            node.add_action(Action::Decrement);
+        }
+    }
+    node
+}
+
+#[allow(dead_code)]
+impl State<'_> {
+    // Describe the open lists to the screen reader. Called by
+    // layout_listuis.
+    pub fn update_accessibility(&mut self, store: &ValueStore) {
+        // no one to tell
+        if !self.access.enabled() {
+            return;
+        }
+        #[cfg(feature = "accesskit")]
+        {
+            let scale_factor = self
+                .context
+                .as_ref()
+                .map_or(1.0, |context| context.scale_factor);
+            let mut nodes = vec![];
+            let mut targets = HashMap::new();
+            let mut lists = vec![];
+            let mut focus = ROOT;
+            for id in self.listuis.ids() {
+                let listui = &self.listuis[id];
+                if !listui.open || !listui.layout.visible {
+                    continue;
+                }
+                let list_node = node_id(id, None);
+                let mut items = vec![];
+                for (index, item) in listui.entries.iter().enumerate() {
+                    if !item.is_visible(store) {
+                        continue;
+                    }
+                    let item_id = node_id(id, Some(index));
+                    let mut node = item_node(item, store);
+                    if index as i32 == listui.selected_index {
+                        node.set_selected(true);
+                        if let Some((xy, wh)) = listui.layout.selected_row {
+                            node.set_bounds(bounds(xy, wh, scale_factor));
+                        }
+                        if listui.focused {
+                            focus = item_id;
+                        }
+                    }
+                    nodes.push((item_id, node.build()));
+                    targets.insert(item_id, (id, Some(index)));
+                    items.push(item_id);
+                }
+                let mut node = NodeBuilder::new(Role::List);
+                node.set_children(items);
+                node.set_bounds(bounds(listui.layout.xy, listui.layout.wh, scale_factor));
+                nodes.push((list_node, node.build()));
+                targets.insert(list_node, (id, None));
+                lists.push(list_node);
+            }
+            let mut root = NodeBuilder::new(Role::Window);
+            root.set_children(lists);
+            nodes.push((ROOT, root.build()));
+            self.access.send(nodes, focus, targets);
+        }
+        #[cfg(not(feature = "accesskit"))]
+        let _ = store;
+    }
+
+    // Do what the screen reader asked since last time: focusing an item
+    // selects it, pressing it activates it as Enter would, and sliders step
+    // up and down. Call once a frame with the store the lists edit.
+    pub fn apply_access_actions(&mut self, store: &mut ValueStore) {
+        #[cfg(feature = "accesskit")]
+        {
+            let Some(inner) = self.access.inner.as_mut() else {
+                return;
+            };
+            let requests: Vec<_> = inner.requests.try_iter().collect();
+            for request in requests {
+                let target = self
+                    .access
+                    .inner
+                    .as_ref()
+                    .unwrap()
+                    .targets
+                    .get(&request.target);
+                let Some(&(list, Some(index))) = target else {
+                    continue;
+                };
+                if !self.listuis.get(list).is_some_and(|listui| {
+                    listui
+                        .entries
+                        .get(index)
+                        .is_some_and(|item| item.can_select(store))
+                }) {
+                    continue;
+                }
+                self.access_select(list, index);
+                match request.action {
+                    Action::Default => self.activate_selected(store),
+                    Action::Increment | Action::Decrement => {
+                        let direction = match request.action {
+                            Action::Increment => 1.0,
+                            _ => -1.0,
+                        };
+                        let listui = &mut self.listuis[list];
+                        let before = listui.selected_version(store);
+                        if let Err(e) = listui.adjust_selected(direction, store) {
+                            warn!("edit rejected: {}", e);
+                        }
+                        self.ui_events
+                            .extend(ListInterface::edited_since(list, before, store));
+                    }
+                    _ => {}
+                }
+            }
+        }
+        #[cfg(not(feature = "accesskit"))]
+        let _ = store;
+    }
+
+    // Select an item the screen reader moved to, focusing its list in place
+    // of whichever had focus.
+    #[cfg(feature = "accesskit")]
+    fn access_select(&mut self, list: ListUiId, index: usize) {
+        if !self.listuis[list].focused {
+            for (_, listui) in self.listuis.entries_mut() {
+                listui.focused = false;
+            }
+            self.listuis[list].focused = true;
+        }
+        let listui = &mut self.listuis[list];
+        if listui.selected_index != index as i32 {
+            listui.selected_index = index as i32;
+            self.ui_events
+                .push(UiEvent::SelectionChanged { list, index });
+        }
+    }
+}
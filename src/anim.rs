@@ -0,0 +1,216 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use wgpu::Queue;
+
+use crate::{
+    error::ShecvError,
+    geo::{GeoManager, InstanceId},
+};
+
+// How an animation continues once it reaches its last frame.
+#[derive(Default, Copy, Clone, PartialEq, Eq)]
+#[allow(dead_code)]
+pub enum AnimationMode {
+    #[default]
+    Loop,
+    PingPong,
+    Once,
+}
+
+#[derive(Clone)]
+pub struct AnimationFrame {
+    pub sub_index: usize,
+    pub duration: Duration,
+    // from another cluster than the animation's, e.g. a frame of an
+    // imported SpriteSheet, where every frame is a cluster of its own
+    pub cluster_index: Option<usize>,
+}
+
+// A sequence of sub images from one cluster of a TextureSheet.
+#[derive(Clone)]
+pub struct Animation {
+    pub cluster_index: usize,
+    pub frames: Vec<AnimationFrame>,
+    pub mode: AnimationMode,
+}
+
+#[allow(dead_code)]
+impl Animation {
+    // Every frame shown for the same duration.
+    pub fn uniform(
+        cluster_index: usize,
+        sub_indices: impl IntoIterator<Item = usize>,
+        frame_duration: Duration,
+        mode: AnimationMode,
+    ) -> Self {
+        Self {
+            cluster_index,
+            frames: sub_indices
+                .into_iter()
+                .map(|sub_index| AnimationFrame {
+                    sub_index,
+                    duration: frame_duration,
+                    cluster_index: None,
+                })
+                .collect(),
+            mode,
+        }
+    }
+}
+
+// An Animator plays an Animation on one instance of an instance group.
+pub struct Animator {
+    pub instance: InstanceId,
+    pub animation: Animation,
+    pub current_frame: usize,
+    pub elapsed: Duration,
+    pub finished: bool,
+    reversing: bool,
+    needs_upload: bool,
+    on_complete: Option<Box<dyn FnMut()>>,
+}
+
+impl Animator {
+    // Step the animation forward by dt. Returns whether the shown frame changed.
+    fn advance(&mut self, dt: Duration) -> bool {
+        let frame_count = self.animation.frames.len();
+        if self.finished || frame_count == 0 {
+            return false;
+        }
+
+        let mut changed = false;
+        self.elapsed += dt;
+        loop {
+            // zero length frames would never let this loop finish
+            let duration = self.animation.frames[self.current_frame]
+                .duration
+                .max(Duration::from_millis(1));
+            if self.elapsed < duration {
+                break;
+            }
+            self.elapsed -= duration;
+
+            match self.animation.mode {
+                AnimationMode::Loop => {
+                    self.current_frame = (self.current_frame + 1) % frame_count;
+                }
+                AnimationMode::PingPong => {
+                    if frame_count > 1 {
+                        if self.reversing && self.current_frame == 0 {
+                            self.reversing = false;
+                        } else if !self.reversing && self.current_frame == frame_count - 1 {
+                            self.reversing = true;
+                        }
+                        if self.reversing {
+                            self.current_frame -= 1;
+                        } else {
+                            self.current_frame += 1;
+                        }
+                    }
+                }
+                AnimationMode::Once => {
+                    if self.current_frame + 1 < frame_count {
+                        self.current_frame += 1;
+                    } else {
+                        self.finished = true;
+                        self.elapsed = Duration::ZERO;
+                        if let Some(on_complete) = self.on_complete.as_mut() {
+                            on_complete();
+                        }
+                        break;
+                    }
+                }
+            }
+            changed = true;
+        }
+        changed
+    }
+}
+
+// Animators are kept in slots so an index stays valid after others are removed.
+#[derive(Default)]
+pub struct Animators {
+    pub slots: Vec<Option<Animator>>,
+}
+
+#[allow(dead_code)]
+impl Animators {
+    pub fn add(&mut self, instance: InstanceId, animation: Animation) -> usize {
+        self.add_with_completion(instance, animation, None)
+    }
+
+    // on_complete runs once when an AnimationMode::Once animation shows its last frame.
+    pub fn add_with_completion(
+        &mut self,
+        instance: InstanceId,
+        animation: Animation,
+        on_complete: Option<Box<dyn FnMut()>>,
+    ) -> usize {
+        let animator = Animator {
+            instance,
+            animation,
+            current_frame: 0,
+            elapsed: Duration::ZERO,
+            finished: false,
+            reversing: false,
+            needs_upload: true,
+            on_complete,
+        };
+        match self.slots.iter().position(|slot| slot.is_none()) {
+            Some(index) => {
+                self.slots[index] = Some(animator);
+                index
+            }
+            None => {
+                self.slots.push(Some(animator));
+                self.slots.len() - 1
+            }
+        }
+    }
+
+    pub fn remove(&mut self, index: usize) -> Option<Animator> {
+        self.slots.get_mut(index).and_then(|slot| slot.take())
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut Animator> {
+        self.slots.get_mut(index).and_then(|slot| slot.as_mut())
+    }
+
+    // Advance every animator and write changed frames into the instance buffers.
+    pub fn update(
+        &mut self,
+        dt: Duration,
+        geos: &mut GeoManager,
+        queue: Arc<Mutex<Queue>>,
+    ) -> Result<(), ShecvError> {
+        for animator in self.slots.iter_mut().flatten() {
+            let changed = animator.advance(dt);
+            if animator.animation.frames.is_empty() || (!changed && !animator.needs_upload) {
+                continue;
+            }
+
+            // instances removed or moved since are skipped
+            if geos.instance_manager(animator.instance).is_none() {
+                continue;
+            }
+            let group = &mut geos.instance_groups[animator.instance.group];
+            let frame = &animator.animation.frames[animator.current_frame];
+            let cluster_index = frame
+                .cluster_index
+                .unwrap_or(animator.animation.cluster_index);
+            let tex_transform = group
+                .sheet
+                .cluster_sub_transform(cluster_index, frame.sub_index);
+            group.instance_buffer_manager.set_tex_transform(
+                queue.clone(),
+                animator.instance.index,
+                tex_transform,
+            )?;
+            animator.needs_upload = false;
+        }
+        Ok(())
+    }
+}
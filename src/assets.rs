@@ -0,0 +1,381 @@
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    fmt, fs, io,
+    marker::PhantomData,
+    sync::{
+        mpsc::{channel, Receiver, Sender},
+        Arc,
+    },
+    thread,
+};
+
+use image::RgbaImage;
+
+use crate::error::ShecvError;
+
+pub const DEFAULT_SHADER: &str = "src/shader.wgsl";
+pub const DEFAULT_FONT: &str = "src/DejaVuSans.ttf";
+// shown in place of textures that can't be found
+pub const FALLBACK_TEXTURE: &str = "src/fallback.png";
+
+// Files compiled into the binary, so it runs without them next to it. A file
+// at the same path on disk is read instead, and hot reloads as usual.
+const EMBEDDED: &[(&str, &[u8])] = &[
+    (DEFAULT_SHADER, include_bytes!("shader.wgsl")),
+    ("src/line.wgsl", include_bytes!("line.wgsl")),
+    (DEFAULT_FONT, include_bytes!("DejaVuSans.ttf")),
+    (FALLBACK_TEXTURE, include_bytes!("fallback.png")),
+];
+
+pub fn embedded(path: &str) -> Option<&'static [u8]> {
+    EMBEDDED
+        .iter()
+        .find(|(name, _)| *name == path)
+        .map(|(_, bytes)| *bytes)
+}
+
+// Read a file from disk, or from the binary if it isn't there but was
+// compiled in.
+pub fn read(path: &str) -> io::Result<Vec<u8>> {
+    match fs::read(path) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => match embedded(path) {
+            Some(bytes) => Ok(bytes.to_vec()),
+            None => Err(e),
+        },
+        read => read,
+    }
+}
+
+pub fn read_to_string(path: &str) -> io::Result<String> {
+    String::from_utf8(read(path)?).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("'{}' is not utf-8", path),
+        )
+    })
+}
+
+// Something Assets can load, made from the bytes of a file or of data
+// embedded in the binary.
+pub trait Asset: Send + 'static {
+    fn decode(bytes: Vec<u8>, name: &str) -> Result<Self, ShecvError>
+    where
+        Self: Sized;
+}
+
+#[allow(dead_code)]
+pub struct ImageAsset(pub RgbaImage);
+
+#[allow(dead_code)]
+pub struct ShaderAsset(pub String);
+
+// The font file as loaded; TextCollection::load_font_bytes makes it usable.
+#[allow(dead_code)]
+pub struct FontAsset(pub Arc<Vec<u8>>);
+
+// The text of a ui file, see ui_file::parse_ui.
+#[allow(dead_code)]
+pub struct UiAsset(pub String);
+
+impl Asset for ImageAsset {
+    fn decode(bytes: Vec<u8>, name: &str) -> Result<Self, ShecvError> {
+        let image = image::load_from_memory(&bytes)
+            .map_err(|e| ShecvError::Parse(format!("failed to decode '{}': {}", name, e)))?;
+        Ok(ImageAsset(image.to_rgba8()))
+    }
+}
+
+impl Asset for ShaderAsset {
+    fn decode(bytes: Vec<u8>, name: &str) -> Result<Self, ShecvError> {
+        Ok(ShaderAsset(utf8(bytes, name)?))
+    }
+}
+
+impl Asset for FontAsset {
+    fn decode(bytes: Vec<u8>, _name: &str) -> Result<Self, ShecvError> {
+        Ok(FontAsset(Arc::new(bytes)))
+    }
+}
+
+impl Asset for UiAsset {
+    fn decode(bytes: Vec<u8>, name: &str) -> Result<Self, ShecvError> {
+        Ok(UiAsset(utf8(bytes, name)?))
+    }
+}
+
+#[allow(dead_code)]
+fn utf8(bytes: Vec<u8>, name: &str) -> Result<String, ShecvError> {
+    String::from_utf8(bytes).map_err(|_| ShecvError::Parse(format!("'{}' is not utf-8", name)))
+}
+
+// A typed handle to an asset in Assets. Loading the same path again gives
+// the same handle.
+pub struct Handle<T> {
+    id: u64,
+    p: PhantomData<fn() -> T>,
+}
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Handle<T> {}
+
+impl<T> PartialEq for Handle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl<T> Eq for Handle<T> {}
+
+impl<T> fmt::Debug for Handle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Handle({})", self.id)
+    }
+}
+
+type Decoded = Result<Box<dyn Any + Send>, String>;
+type Decode = fn(Vec<u8>, &str) -> Decoded;
+
+pub enum AssetState {
+    Loading,
+    Ready(Box<dyn Any + Send>),
+    Failed(String),
+}
+
+struct AssetEntry {
+    // the path, or the name embedded bytes were loaded under
+    name: String,
+    from_file: bool,
+    type_id: TypeId,
+    decode: Decode,
+    state: AssetState,
+    refs: usize,
+    // bumped each time the asset is loaded again, so whatever was made from
+    // it knows to be made again
+    generation: u64,
+}
+
+fn decode_boxed<T: Asset>(bytes: Vec<u8>, name: &str) -> Decoded {
+    T::decode(bytes, name)
+        .map(|asset| Box::new(asset) as Box<dyn Any + Send>)
+        .map_err(|e| e.to_string())
+}
+
+// Images, shaders, fonts and ui files by path or embedded bytes. Each is
+// loaded once however often it is asked for, and counts its handles: load
+// and retain add one, release takes one away and unloads it at none.
+// Files are watched through the Context's file watcher and loaded again
+// when they change.
+pub struct Assets {
+    entries: HashMap<u64, AssetEntry>,
+    by_name: HashMap<String, u64>,
+    next_id: u64,
+    // files loaded since the Context last added them to its watcher
+    unwatched: Vec<String>,
+    sender: Sender<(u64, u64, Decoded)>,
+    receiver: Receiver<(u64, u64, Decoded)>,
+}
+
+impl Default for Assets {
+    fn default() -> Self {
+        let (sender, receiver) = channel();
+        Self {
+            entries: HashMap::new(),
+            by_name: HashMap::new(),
+            next_id: 0,
+            unwatched: vec![],
+            sender,
+            receiver,
+        }
+    }
+}
+
+#[allow(dead_code)]
+impl Assets {
+    // Load a file now. A failed load is kept, so asking again gives the same
+    // error until the file changes and is reloaded.
+    pub fn load<T: Asset>(&mut self, path: &str) -> Result<Handle<T>, ShecvError> {
+        let handle = self.entry::<T>(path, true)?;
+        let entry = self.entries.get_mut(&handle.id).unwrap();
+        if let AssetState::Loading = entry.state {
+            entry.state = match read(path) {
+                Ok(bytes) => settle((entry.decode)(bytes, path)),
+                Err(e) => AssetState::Failed(format!("'{}': {}", path, e)),
+            };
+        }
+        self.check(handle)
+    }
+
+    // Load a file on another thread. The handle is good at once, and get
+    // gives None until poll has picked up the result.
+    pub fn load_async<T: Asset>(&mut self, path: &str) -> Result<Handle<T>, ShecvError> {
+        let handle = self.entry::<T>(path, true)?;
+        let entry = &self.entries[&handle.id];
+        if let (AssetState::Loading, 1) = (&entry.state, entry.refs) {
+            self.spawn_load(handle.id);
+        }
+        Ok(handle)
+    }
+
+    // Bytes compiled into the binary, e.g. with include_bytes!, under a name
+    // that doesn't clash with any path. Files in EMBEDDED can be loaded by
+    // path instead.
+    pub fn load_bytes<T: Asset>(
+        &mut self,
+        name: &str,
+        bytes: &[u8],
+    ) -> Result<Handle<T>, ShecvError> {
+        let handle = self.entry::<T>(name, false)?;
+        let entry = self.entries.get_mut(&handle.id).unwrap();
+        if let AssetState::Loading = entry.state {
+            entry.state = settle((entry.decode)(bytes.to_vec(), name));
+        }
+        self.check(handle)
+    }
+
+    pub fn get<T: Asset>(&self, handle: Handle<T>) -> Option<&T> {
+        match &self.entries.get(&handle.id)?.state {
+            AssetState::Ready(asset) => asset.downcast_ref::<T>(),
+            _ => None,
+        }
+    }
+
+    pub fn state<T>(&self, handle: Handle<T>) -> Option<&AssetState> {
+        self.entries.get(&handle.id).map(|entry| &entry.state)
+    }
+
+    pub fn generation<T>(&self, handle: Handle<T>) -> Option<u64> {
+        self.entries.get(&handle.id).map(|entry| entry.generation)
+    }
+
+    // Another owner of the handle.
+    pub fn retain<T>(&mut self, handle: Handle<T>) {
+        if let Some(entry) = self.entries.get_mut(&handle.id) {
+            entry.refs += 1;
+        }
+    }
+
+    // Give up a handle. Returns true if that unloaded the asset, after which
+    // the handle gets nothing.
+    pub fn release<T>(&mut self, handle: Handle<T>) -> bool {
+        let Some(entry) = self.entries.get_mut(&handle.id) else {
+            return false;
+        };
+        entry.refs -= 1;
+        if entry.refs > 0 {
+            return false;
+        }
+        let entry = self.entries.remove(&handle.id).unwrap();
+        self.by_name.remove(&entry.name);
+        true
+    }
+
+    // Pick up assets finished loading on other threads. Results for assets
+    // released or reloaded since are dropped.
+    pub fn poll(&mut self) {
+        while let Ok((id, generation, decoded)) = self.receiver.try_recv() {
+            match self.entries.get_mut(&id) {
+                Some(entry) if entry.generation == generation => entry.state = settle(decoded),
+                _ => {}
+            }
+        }
+    }
+
+    // Load a file again in the background, as it changed on disk. Whatever
+    // was loaded stays until the new load is done. Returns false if no asset
+    // came from path.
+    pub fn reload(&mut self, path: &str) -> bool {
+        let Some(&id) = self.by_name.get(path) else {
+            return false;
+        };
+        let entry = self.entries.get_mut(&id).unwrap();
+        if !entry.from_file {
+            return false;
+        }
+        entry.generation += 1;
+        self.spawn_load(id);
+        true
+    }
+
+    pub fn take_unwatched(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.unwatched)
+    }
+
+    // The entry for name, made if needed, with one more handle.
+    fn entry<T: Asset>(&mut self, name: &str, from_file: bool) -> Result<Handle<T>, ShecvError> {
+        let handle = Handle {
+            id: match self.by_name.get(name) {
+                Some(&id) => id,
+                None => {
+                    let id = self.next_id;
+                    self.next_id += 1;
+                    self.entries.insert(
+                        id,
+                        AssetEntry {
+                            name: name.to_string(),
+                            from_file,
+                            type_id: TypeId::of::<T>(),
+                            decode: decode_boxed::<T>,
+                            state: AssetState::Loading,
+                            refs: 0,
+                            generation: 0,
+                        },
+                    );
+                    self.by_name.insert(name.to_string(), id);
+                    if from_file {
+                        self.unwatched.push(name.to_string());
+                    }
+                    id
+                }
+            },
+            p: PhantomData,
+        };
+        let entry = self.entries.get_mut(&handle.id).unwrap();
+        if entry.type_id != TypeId::of::<T>() {
+            return Err(ShecvError::Parse(format!(
+                "'{}' is already loaded as another kind of asset",
+                name
+            )));
+        }
+        entry.refs += 1;
+        Ok(handle)
+    }
+
+    fn spawn_load(&self, id: u64) {
+        let entry = &self.entries[&id];
+        let (path, decode, generation) = (entry.name.clone(), entry.decode, entry.generation);
+        let sender = self.sender.clone();
+        thread::spawn(move || {
+            let decoded = match read(&path) {
+                Ok(bytes) => decode(bytes, &path),
+                Err(e) => Err(format!("'{}': {}", path, e)),
+            };
+            let _ = sender.send((id, generation, decoded));
+        });
+    }
+
+    // The handle if its asset loaded, or the error it failed with. A failed
+    // asset gives no handle to release, so it doesn't keep the one it took.
+    fn check<T>(&mut self, handle: Handle<T>) -> Result<Handle<T>, ShecvError> {
+        let entry = self.entries.get_mut(&handle.id).unwrap();
+        match &entry.state {
+            AssetState::Failed(message) => {
+                entry.refs -= 1;
+                Err(ShecvError::Parse(message.clone()))
+            }
+            _ => Ok(handle),
+        }
+    }
+}
+
+fn settle(decoded: Decoded) -> AssetState {
+    match decoded {
+        Ok(asset) => AssetState::Ready(asset),
+        Err(message) => AssetState::Failed(message),
+    }
+}
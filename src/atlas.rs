@@ -0,0 +1,197 @@
+use std::{collections::HashMap, fs, path::Path};
+
+use glam::UVec2;
+use image::{ImageFormat, RgbaImage};
+
+use crate::{
+    assets,
+    error::ShecvError,
+    types::{SamplerSettings, TextureSheetClusterDefinition, TextureSheetDefinition},
+};
+
+// Empty pixels kept right of and below every cluster, so filtering never
+// bleeds a neighbour in.
+const PADDING: u32 = 1;
+
+// Loose images packed into one texture, with a cluster for each:
+//
+//   let atlas = Atlas::from_folder("sprites")?;
+//   let (cluster, sub) = atlas.get("coin").unwrap();
+//   let group = geos.new_unit_square(.., atlas.sheet(), ..)?;
+//
+// Images named like walk_0.png, walk_1.png.. of the same size become the
+// frames of one cluster, in number order, for Animation to step through.
+// "walk_1" finds its frame and "walk" the first.
+pub struct Atlas {
+    pub path: String,
+    pub image: RgbaImage,
+    pub clusters: Vec<TextureSheetClusterDefinition>,
+    names: HashMap<String, (usize, usize)>,
+}
+
+#[allow(dead_code)]
+impl Atlas {
+    // Every image in dir, not looking into folders inside it. Each is named
+    // by its file name without the extension.
+    pub fn from_folder(dir: &str) -> Result<Self, ShecvError> {
+        let mut paths: Vec<_> = fs::read_dir(dir)?
+            .filter_map(|entry| Some(entry.ok()?.path()))
+            .filter(|path| path.is_file() && ImageFormat::from_path(path).is_ok())
+            .collect();
+        paths.sort();
+        let mut images = vec![];
+        for path in paths {
+            let name = path.to_string_lossy();
+            let bytes = assets::read(&name)?;
+            let image = image::load_from_memory(&bytes)
+                .map_err(|e| ShecvError::Parse(format!("failed to decode '{}': {}", name, e)))?;
+            images.push((stem(&path), image.to_rgba8()));
+        }
+        let mut atlas = Self::from_images(images)?;
+        atlas.path = dir.to_string();
+        Ok(atlas)
+    }
+
+    pub fn from_images(images: Vec<(String, RgbaImage)>) -> Result<Self, ShecvError> {
+        if images.is_empty() {
+            return Err(ShecvError::Parse("atlas has no images".into()));
+        }
+
+        // numbered images of one size make one cluster, the rest their own
+        let mut sequences: HashMap<&str, Vec<(u32, usize)>> = HashMap::new();
+        for (index, (name, _)) in images.iter().enumerate() {
+            if let Some((base, number)) = frame_number(name) {
+                sequences.entry(base).or_default().push((number, index));
+            }
+        }
+        let mut groups: Vec<(String, Vec<usize>)> = vec![];
+        let mut grouped = vec![false; images.len()];
+        for (base, mut frames) in sequences {
+            let size = images[frames[0].1].1.dimensions();
+            if frames
+                .iter()
+                .any(|&(_, i)| images[i].1.dimensions() != size)
+            {
+                continue;
+            }
+            frames.sort();
+            frames.iter().for_each(|&(_, i)| grouped[i] = true);
+            groups.push((base.to_string(), frames.iter().map(|&(_, i)| i).collect()));
+        }
+        for (index, (name, _)) in images.iter().enumerate() {
+            if !grouped[index] {
+                groups.push((name.clone(), vec![index]));
+            }
+        }
+        groups.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let frame_sizes: Vec<UVec2> = groups
+            .iter()
+            .map(|(_, frames)| images[frames[0]].1.dimensions().into())
+            .collect();
+        let sizes: Vec<UVec2> = groups
+            .iter()
+            .zip(&frame_sizes)
+            .map(|((_, frames), size)| UVec2::new(size.x * frames.len() as u32, size.y))
+            .collect();
+        let (offsets, extent) = pack(&sizes);
+
+        let mut atlas = RgbaImage::new(extent.x, extent.y);
+        let mut clusters = vec![];
+        let mut names = HashMap::new();
+        for (cluster, ((base, frames), offset)) in groups.iter().zip(&offsets).enumerate() {
+            let sub_size = frame_sizes[cluster];
+            for (sub, &index) in frames.iter().enumerate() {
+                let x = offset.x + sub as u32 * sub_size.x;
+                image::imageops::replace(&mut atlas, &images[index].1, x as i64, offset.y as i64);
+                names.insert(images[index].0.clone(), (cluster, sub));
+            }
+            names.entry(base.clone()).or_insert((cluster, 0));
+            clusters.push(TextureSheetClusterDefinition {
+                label: base.clone(),
+                offset: *offset,
+                cluster_size: sizes[cluster],
+                sub_size,
+                spacing: UVec2::ZERO,
+            });
+        }
+
+        Ok(Self {
+            path: String::new(),
+            image: atlas,
+            clusters,
+            names,
+        })
+    }
+
+    // The cluster and sub index of an image, by the name it was packed
+    // under.
+    pub fn get(&self, name: &str) -> Option<(usize, usize)> {
+        self.names.get(name).copied()
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.names.keys().map(String::as_str).collect();
+        names.sort();
+        names
+    }
+
+    // A sheet of the packed texture, for making groups with. Sampled as
+    // pixel art; see TextureSheetDefinition::with_sampler.
+    pub fn sheet(&self) -> TextureSheetDefinition {
+        TextureSheetDefinition {
+            path: self.path.clone(),
+            clusters: self.clusters.clone(),
+            image: Some(self.image.clone()),
+            sampler: SamplerSettings::default(),
+            mipmaps: false,
+            fallback: false,
+        }
+    }
+}
+
+fn stem(path: &Path) -> String {
+    path.file_stem()
+        .map_or_else(String::new, |stem| stem.to_string_lossy().into_owned())
+}
+
+// "walk_12" is frame 12 of walk.
+fn frame_number(name: &str) -> Option<(&str, u32)> {
+    let (base, number) = name.rsplit_once('_')?;
+    match base.is_empty() {
+        true => None,
+        false => Some((base, number.parse().ok()?)),
+    }
+}
+
+// Shelf packing: tallest first, left to right in rows as wide as about the
+// square root of the total area, rounded up to a power of two. Returns the
+// top left of each size, in the order given, and the size of the whole.
+fn pack(sizes: &[UVec2]) -> (Vec<UVec2>, UVec2) {
+    let padded = |size: UVec2| size + UVec2::splat(PADDING);
+    let area: u32 = sizes
+        .iter()
+        .map(|&size| padded(size).element_product())
+        .sum();
+    let widest = sizes.iter().map(|&size| padded(size).x).max().unwrap_or(1);
+    let width = ((area as f32).sqrt().ceil() as u32)
+        .max(widest)
+        .next_power_of_two();
+
+    let mut order: Vec<usize> = (0..sizes.len()).collect();
+    order.sort_by_key(|&i| std::cmp::Reverse(sizes[i].y));
+    let mut offsets = vec![UVec2::ZERO; sizes.len()];
+    let (mut x, mut y, mut shelf) = (0, 0, 0);
+    for i in order {
+        let size = padded(sizes[i]);
+        if x + size.x > width {
+            x = 0;
+            y += shelf;
+            shelf = 0;
+        }
+        offsets[i] = UVec2::new(x, y);
+        x += size.x;
+        shelf = shelf.max(size.y);
+    }
+    (offsets, UVec2::new(width, (y + shelf).max(1)))
+}
@@ -0,0 +1,167 @@
+#[cfg(feature = "audio")]
+use sdl2::{
+    mixer::{self, Channel, Chunk, LoaderRWops},
+    rwops::RWops,
+};
+
+use crate::{assets, error::ShecvError, types::ValueStore};
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct SoundId(usize);
+
+// Volumes multiply, so a ui blip plays at Master * Ui.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[allow(dead_code)]
+pub enum AudioBus {
+    Master,
+    Sounds,
+    Ui,
+}
+
+// What lists play as the selection moves, items are activated, and sublists
+// open and close. Set on ListStyle.
+#[derive(Default, Copy, Clone, Debug)]
+pub struct ListSounds {
+    pub navigate: Option<SoundId>,
+    pub activate: Option<SoundId>,
+    pub open: Option<SoundId>,
+    pub close: Option<SoundId>,
+}
+
+// Sounds for ui feedback and games, played through SDL2_mixer with the audio
+// feature. Without it sounds still load into ids and play silently, so lists
+// and apps set them up the same either way. The mixer opens with the first
+// sound loaded.
+pub struct Audio {
+    // from 0 to 1, by AudioBus
+    volumes: [f32; 3],
+    // store keys the volumes follow, see bind_volume
+    volume_keys: [Option<String>; 3],
+    paths: Vec<String>,
+    #[cfg(feature = "audio")]
+    chunks: Vec<Chunk>,
+    #[cfg(feature = "audio")]
+    opened: bool,
+}
+
+impl Default for Audio {
+    fn default() -> Self {
+        Audio {
+            volumes: [1.0; 3],
+            volume_keys: Default::default(),
+            paths: vec![],
+            #[cfg(feature = "audio")]
+            chunks: vec![],
+            #[cfg(feature = "audio")]
+            opened: false,
+        }
+    }
+}
+
+#[allow(dead_code)]
+impl Audio {
+    // Whether sounds are heard, i.e. shecv was built with the audio feature.
+    pub fn enabled(&self) -> bool {
+        cfg!(feature = "audio")
+    }
+
+    // Load a wav or ogg file, or one compiled in, see assets::read. Loading
+    // the same path again gives the same sound.
+    pub fn load(&mut self, path: &str) -> Result<SoundId, ShecvError> {
+        if let Some(index) = self.paths.iter().position(|p| p == path) {
+            return Ok(SoundId(index));
+        }
+        let bytes = assets::read(path)?;
+        #[cfg(feature = "audio")]
+        {
+            self.open()?;
+            let chunk = RWops::from_bytes(&bytes)
+                .and_then(|rwops| rwops.load_wav())
+                .map_err(|e| {
+                    ShecvError::Parse(format!("failed to load sound '{}': {}", path, e))
+                })?;
+            self.chunks.push(chunk);
+        }
+        #[cfg(not(feature = "audio"))]
+        let _ = bytes;
+        self.paths.push(path.to_string());
+        Ok(SoundId(self.paths.len() - 1))
+    }
+
+    pub fn play_sound(&mut self, sound: SoundId) {
+        self.play(sound, AudioBus::Sounds);
+    }
+
+    pub fn play_ui_sound(&mut self, sound: SoundId) {
+        self.play(sound, AudioBus::Ui);
+    }
+
+    pub fn volume(&self, bus: AudioBus) -> f32 {
+        self.volumes[bus as usize]
+    }
+
+    pub fn set_volume(&mut self, bus: AudioBus, volume: f32) {
+        self.volumes[bus as usize] = volume.clamp(0.0, 1.0);
+    }
+
+    // Have a bus follow a number in the store, e.g. one a settings list
+    // edits. Read by update.
+    pub fn bind_volume(&mut self, bus: AudioBus, key: &str) {
+        self.volume_keys[bus as usize] = Some(key.to_string());
+    }
+
+    pub fn update(&mut self, store: &ValueStore) {
+        for bus in [AudioBus::Master, AudioBus::Sounds, AudioBus::Ui] {
+            let Some(key) = &self.volume_keys[bus as usize] else {
+                continue;
+            };
+            let volume = store
+                .load_as::<f64>(key)
+                .map(|volume| volume as f32)
+                .or_else(|| store.load_as::<f32>(key));
+            if let Some(volume) = volume {
+                self.set_volume(bus, volume);
+            }
+        }
+    }
+
+    fn play(&mut self, sound: SoundId, bus: AudioBus) {
+        let volume = self.volume(AudioBus::Master) * self.volume(bus);
+        #[cfg(feature = "audio")]
+        {
+            let Some(chunk) = self.chunks.get_mut(sound.0) else {
+                return;
+            };
+            chunk.set_volume((volume * mixer::MAX_VOLUME as f32) as i32);
+            // all channels busy just drops the sound
+            if let Err(e) = Channel::all().play(chunk, 0) {
+                log::debug!("sound {} not played: {}", self.paths[sound.0], e);
+            }
+        }
+        #[cfg(not(feature = "audio"))]
+        let _ = (sound, volume);
+    }
+
+    #[cfg(feature = "audio")]
+    fn open(&mut self) -> Result<(), ShecvError> {
+        if self.opened {
+            return Ok(());
+        }
+        mixer::open_audio(44_100, mixer::DEFAULT_FORMAT, mixer::DEFAULT_CHANNELS, 512)
+            .map_err(|e| ShecvError::Device(format!("failed to open audio: {}", e)))?;
+        mixer::allocate_channels(16);
+        self.opened = true;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "audio")]
+impl Drop for Audio {
+    fn drop(&mut self) {
+        // chunks go before the mixer they play on
+        self.chunks.clear();
+        if self.opened {
+            mixer::close_audio();
+        }
+    }
+}
@@ -0,0 +1,182 @@
+use std::{cell::RefCell, path::PathBuf, rc::Rc};
+
+use glam::{IVec2, UVec2, Vec2};
+use image::RgbaImage;
+
+use wgpu::SurfaceTargetUnsafe;
+
+use crate::error::ShecvError;
+
+// The keys shecv itself responds to; everything else is Other.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Key {
+    Escape,
+    Enter,
+    Backspace,
+    Left,
+    Right,
+    Up,
+    Down,
+    F3,
+    F12,
+    Tab,
+    // the key left of 1 on most layouts, opening the console
+    Backquote,
+    C,
+    V,
+    Y,
+    Z,
+    Other,
+}
+
+// Window system events, the same whichever backend produced them.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BackendEvent {
+    Quit,
+    // the window changed size or moved to a display with another scale;
+    // read the new sizes from the window
+    Resized {
+        window_id: u64,
+    },
+    Minimized {
+        window_id: u64,
+    },
+    Restored {
+        window_id: u64,
+    },
+    CloseRequested {
+        window_id: u64,
+    },
+    KeyDown {
+        window_id: Option<u64>,
+        key: Key,
+        // either alt key was held
+        alt: bool,
+        // either ctrl key, or command on macOS
+        ctrl: bool,
+    },
+    TextInput {
+        window_id: Option<u64>,
+        text: String,
+    },
+    // text being composed in an input method, not yet committed; an empty
+    // string ends the composition
+    TextEditing {
+        window_id: Option<u64>,
+        text: String,
+    },
+    // where the cursor is and how far the mouse moved, in window units. In
+    // relative mouse mode only delta means anything
+    MouseMotion {
+        window_id: Option<u64>,
+        position: Vec2,
+        delta: Vec2,
+    },
+    // a file dragged from elsewhere and dropped on the window
+    DroppedFile {
+        window_id: Option<u64>,
+        path: PathBuf,
+    },
+    // the app went to the background or came back, see Context::suspend
+    Suspended,
+    Resumed,
+}
+
+impl BackendEvent {
+    pub fn window_id(&self) -> Option<u64> {
+        match self {
+            BackendEvent::Resized { window_id }
+            | BackendEvent::Minimized { window_id }
+            | BackendEvent::Restored { window_id }
+            | BackendEvent::CloseRequested { window_id } => Some(*window_id),
+            BackendEvent::KeyDown { window_id, .. }
+            | BackendEvent::TextInput { window_id, .. }
+            | BackendEvent::TextEditing { window_id, .. }
+            | BackendEvent::MouseMotion { window_id, .. }
+            | BackendEvent::DroppedFile { window_id, .. } => *window_id,
+            BackendEvent::Quit | BackendEvent::Suspended | BackendEvent::Resumed => None,
+        }
+    }
+}
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[allow(dead_code)]
+pub enum FullscreenMode {
+    #[default]
+    Windowed,
+    // takes over the display at its native mode
+    Exclusive,
+    // a window without decorations covering the whole desktop
+    Borderless,
+}
+
+pub trait BackendWindow {
+    fn id(&self) -> u64;
+    // in window units
+    fn size(&self) -> (u32, u32);
+    // in physical pixels
+    fn drawable_size(&self) -> (u32, u32);
+    // Safety: surfaces created from the target must not outlive the window.
+    unsafe fn surface_target(&self) -> Result<SurfaceTargetUnsafe, ShecvError>;
+    fn set_title(&mut self, title: &str) -> Result<(), ShecvError>;
+    // shown in the title bar and task switcher, where the platform has one
+    fn set_icon(&mut self, image: &RgbaImage) -> Result<(), ShecvError>;
+    fn set_fullscreen(&mut self, mode: FullscreenMode) -> Result<(), ShecvError>;
+    // where the input method should put its candidate window, in window units
+    fn set_ime_rect(&self, xy: IVec2, wh: UVec2);
+    fn set_cursor_visible(&mut self, visible: bool);
+    // keep the cursor inside the window
+    fn set_cursor_grab(&mut self, grab: bool) -> Result<(), ShecvError>;
+    // hide and hold the cursor, leaving only the deltas of MouseMotion events
+    fn set_relative_mouse_mode(&mut self, relative: bool) -> Result<(), ShecvError>;
+    // None goes back to the system's arrow
+    fn set_cursor_image(
+        &mut self,
+        image: Option<&RgbaImage>,
+        hotspot: UVec2,
+    ) -> Result<(), ShecvError>;
+}
+
+pub trait Backend {
+    fn create_window(
+        &mut self,
+        width: u32,
+        height: u32,
+        title: &str,
+    ) -> Result<Box<dyn BackendWindow>, ShecvError>;
+    fn poll_events(&mut self) -> Vec<BackendEvent>;
+    fn clipboard_text(&self) -> Option<String>;
+    fn set_clipboard_text(&mut self, text: &str) -> Result<(), ShecvError>;
+}
+
+pub type SharedBackend = Rc<RefCell<Box<dyn Backend>>>;
+
+// Which backends there are depends on the sdl and winit features; sdl is
+// the default where both are built in.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BackendKind {
+    #[cfg(feature = "sdl")]
+    Sdl,
+    #[cfg(feature = "winit")]
+    Winit,
+}
+
+impl Default for BackendKind {
+    fn default() -> Self {
+        #[cfg(feature = "sdl")]
+        return BackendKind::Sdl;
+        #[cfg(all(feature = "winit", not(feature = "sdl")))]
+        return BackendKind::Winit;
+    }
+}
+
+impl BackendKind {
+    pub fn create(self) -> Result<Box<dyn Backend>, ShecvError> {
+        match self {
+            #[cfg(feature = "sdl")]
+            BackendKind::Sdl => Ok(Box::new(crate::sdl_backend::SdlBackend::new()?)),
+            #[cfg(feature = "winit")]
+            BackendKind::Winit => Ok(Box::new(crate::winit_backend::WinitBackend::new()?)),
+        }
+    }
+}
@@ -0,0 +1,244 @@
+use std::{borrow::Cow, mem::size_of};
+
+use bytemuck::{Pod, Zeroable};
+use glam::Mat4;
+use wgpu::{
+    BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor,
+    BindGroupLayoutEntry, BindingResource, BindingType, Buffer, BufferBindingType,
+    BufferDescriptor, BufferSize, BufferUsages, ColorTargetState, ColorWrites, Device,
+    FragmentState, MultisampleState, PipelineLayout, PrimitiveState, Queue, RenderPass,
+    RenderPipeline, RenderPipelineDescriptor, SamplerBindingType, ShaderModule,
+    ShaderModuleDescriptor, ShaderSource, ShaderStages, TextureFormat, TextureSampleType,
+    TextureViewDimension, VertexState,
+};
+
+use crate::types::{ColorRGBA, TextureSheet};
+
+const SHADER: &str = "
+struct BackgroundUniform {
+    top: vec4<f32>,
+    bottom: vec4<f32>,
+    // clip space back to the world, to find where each pixel looks
+    inverse_view_projection: mat4x4<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> background: BackgroundUniform;
+@group(0) @binding(1)
+var sky: texture_2d<f32>;
+@group(0) @binding(2)
+var sky_sampler: sampler;
+
+struct BackgroundVertex {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+// one triangle covering the screen
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> BackgroundVertex {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: BackgroundVertex;
+    out.position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
+
+@fragment
+fn fs_gradient(in: BackgroundVertex) -> @location(0) vec4<f32> {
+    return mix(background.top, background.bottom, in.uv.y);
+}
+
+const PI: f32 = 3.14159265;
+
+// the sky is an equirectangular panorama, wrapped around the camera
+@fragment
+fn fs_skybox(in: BackgroundVertex) -> @location(0) vec4<f32> {
+    let ndc = vec2<f32>(in.uv.x * 2.0 - 1.0, 1.0 - in.uv.y * 2.0);
+    let near = background.inverse_view_projection * vec4<f32>(ndc, 0.0, 1.0);
+    let far = background.inverse_view_projection * vec4<f32>(ndc, 1.0, 1.0);
+    let dir = normalize(far.xyz / far.w - near.xyz / near.w);
+    let uv = vec2<f32>(
+        atan2(dir.z, dir.x) / (2.0 * PI) + 0.5,
+        acos(clamp(dir.y, -1.0, 1.0)) / PI,
+    );
+    return textureSampleLevel(sky, sky_sampler, uv, 0.0);
+}
+";
+
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable)]
+struct BackgroundUniform {
+    top: ColorRGBA,
+    bottom: ColorRGBA,
+    inverse_view_projection: Mat4,
+}
+
+// What's drawn behind every layer, over the clear color.
+#[derive(Default)]
+#[allow(dead_code)]
+pub enum Background {
+    // just the clear color, see Context::set_clear_color
+    #[default]
+    None,
+    // from the top of the frame to the bottom
+    Gradient {
+        top: ColorRGBA,
+        bottom: ColorRGBA,
+    },
+    // a panorama seen through GeoManager::camera, 360 degrees across and 180
+    // down, as most skybox images are laid out
+    Skybox(Box<TextureSheet>),
+}
+
+pub struct BackgroundPass {
+    pub background: Background,
+    bind_group_layout: BindGroupLayout,
+    uniform: Buffer,
+    gradient: RenderPipeline,
+    skybox: RenderPipeline,
+    // made when the background is set, since it holds the sky's texture
+    bind_group: Option<BindGroup>,
+}
+
+impl BackgroundPass {
+    pub fn new(device: &Device, format: TextureFormat) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("background bind group layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: BufferSize::new(size_of::<BackgroundUniform>() as u64),
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: TextureViewDimension::D2,
+                        sample_type: TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let module = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("background shader"),
+            source: ShaderSource::Wgsl(Cow::Borrowed(SHADER)),
+        });
+        let uniform = device.create_buffer(&BufferDescriptor {
+            label: Some("background uniform"),
+            size: size_of::<BackgroundUniform>() as u64,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        Self {
+            background: Background::None,
+            gradient: pipeline(device, &pipeline_layout, &module, "fs_gradient", format),
+            skybox: pipeline(device, &pipeline_layout, &module, "fs_skybox", format),
+            bind_group_layout,
+            uniform,
+            bind_group: None,
+        }
+    }
+
+    // A gradient's sheet can be any texture; it isn't read. Pass the sky's
+    // for a skybox.
+    pub fn set(&mut self, device: &Device, background: Background, fallback: &TextureSheet) {
+        let sheet = match &background {
+            Background::Skybox(sheet) => sheet,
+            _ => fallback,
+        };
+        self.bind_group = Some(device.create_bind_group(&BindGroupDescriptor {
+            label: Some("background bind group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: self.uniform.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::TextureView(&sheet.view),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: BindingResource::Sampler(&sheet.sampler),
+                },
+            ],
+        }));
+        self.background = background;
+    }
+
+    // view_projection is the camera the skybox is seen through.
+    pub fn draw(&self, pass: &mut RenderPass, queue: &Queue, view_projection: Mat4) {
+        let (pipeline, top, bottom) = match &self.background {
+            Background::None => return,
+            Background::Gradient { top, bottom } => (&self.gradient, *top, *bottom),
+            Background::Skybox(_) => (&self.skybox, ColorRGBA::white(), ColorRGBA::white()),
+        };
+        let Some(bind_group) = &self.bind_group else {
+            return;
+        };
+        let uniform = BackgroundUniform {
+            top,
+            bottom,
+            inverse_view_projection: view_projection.inverse(),
+        };
+        queue.write_buffer(&self.uniform, 0, bytemuck::cast_slice(&[uniform]));
+        pass.set_pipeline(pipeline);
+        pass.set_bind_group(0, bind_group, &[]);
+        pass.draw(0..3, 0..1);
+    }
+}
+
+fn pipeline(
+    device: &Device,
+    layout: &PipelineLayout,
+    module: &ShaderModule,
+    entry_point: &str,
+    format: TextureFormat,
+) -> RenderPipeline {
+    device.create_render_pipeline(&RenderPipelineDescriptor {
+        label: Some(&*format!("background pipeline {}", entry_point)),
+        layout: Some(layout),
+        vertex: VertexState {
+            module,
+            entry_point: "vs_main",
+            buffers: &[],
+            compilation_options: Default::default(),
+        },
+        fragment: Some(FragmentState {
+            module,
+            entry_point,
+            targets: &[Some(ColorTargetState {
+                format,
+                blend: None,
+                write_mask: ColorWrites::ALL,
+            })],
+            compilation_options: Default::default(),
+        }),
+        primitive: PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: MultisampleState::default(),
+        multiview: None,
+        cache: None,
+    })
+}
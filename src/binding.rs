@@ -0,0 +1,121 @@
+use std::ops::Deref;
+
+use crate::{
+    error::ShecvError,
+    types::{parse_text, ListItemData, ValueStore},
+};
+
+type Check = Box<dyn Fn(&dyn ListItemData) -> Result<(), String>>;
+type Convert = Box<dyn Fn(&dyn ListItemData) -> Option<Box<dyn ListItemData>>>;
+
+// How a list item reads and writes its store key. Every edit made through a
+// list goes through write, be it typed text, a paste, a slider step or a
+// checkbox toggle, so a value is checked however it's changed. Lists read
+// through it every layout, so changes made to the store elsewhere show up on
+// their own.
+#[derive(Default)]
+pub struct Binding {
+    checks: Vec<Check>,
+    // the stored value as shown, and a shown value back as stored
+    view: Option<(Convert, Convert)>,
+}
+
+#[allow(dead_code)]
+impl Binding {
+    // Reject values that fail check, with its message. Values that aren't a T
+    // are rejected too.
+    pub fn check<T: ListItemData>(
+        mut self,
+        check: impl Fn(&T) -> Result<(), String> + 'static,
+    ) -> Self {
+        self.checks.push(Box::new(move |value| {
+            match value.as_any().downcast_ref::<T>() {
+                Some(value) => check(value),
+                None => Err("not the type this item holds".to_string()),
+            }
+        }));
+        self
+    }
+
+    // Show the stored T as to_view makes it, e.g. radians as degrees, and
+    // store what's entered as from_view makes it.
+    pub fn view<T: ListItemData>(
+        mut self,
+        to_view: impl Fn(&T) -> T + 'static,
+        from_view: impl Fn(&T) -> T + 'static,
+    ) -> Self {
+        self.view = Some((convert(to_view), convert(from_view)));
+        self
+    }
+
+    // Hand the value as shown to f. None if nothing is stored under key.
+    pub fn read<R>(
+        &self,
+        store: &ValueStore,
+        key: &str,
+        f: impl FnOnce(&dyn ListItemData) -> R,
+    ) -> Option<R> {
+        self.show(store.map.get(key)?.deref(), f)
+    }
+
+    // Hand stored, as shown, to f. None if the view can't show it.
+    pub fn show<R>(
+        &self,
+        stored: &dyn ListItemData,
+        f: impl FnOnce(&dyn ListItemData) -> R,
+    ) -> Option<R> {
+        match &self.view {
+            Some((to_view, _)) => Some(f(&*to_view(stored)?)),
+            None => Some(f(stored)),
+        }
+    }
+
+    // Store a shown value. It has to come out as the type already stored.
+    pub fn write(
+        &self,
+        store: &mut ValueStore,
+        key: &str,
+        value: Box<dyn ListItemData>,
+    ) -> Result<(), ShecvError> {
+        let wrong_type = || ShecvError::Store(format!("wrong type of value for '{}'", key));
+        let stored = store
+            .map
+            .get(key)
+            .ok_or_else(|| ShecvError::Store(format!("no value stored under '{}'", key)))?;
+        let value = match &self.view {
+            Some((_, from_view)) => from_view(&*value).ok_or_else(wrong_type)?,
+            None => value,
+        };
+        if (*value).as_any().type_id() != stored.deref().as_any().type_id() {
+            return Err(wrong_type());
+        }
+        for check in &self.checks {
+            check(&*value).map_err(|e| ShecvError::Store(format!("{}: {}", key, e)))?;
+        }
+        store.write(key, value);
+        Ok(())
+    }
+
+    // Parse text as the shown value's type, relative edits included, and
+    // write it.
+    pub fn write_text(
+        &self,
+        store: &mut ValueStore,
+        key: &str,
+        text: &str,
+    ) -> Result<(), ShecvError> {
+        let value = self
+            .read(store, key, |shown| parse_text(shown, key, text))
+            .ok_or_else(|| ShecvError::Store(format!("no value stored under '{}'", key)))??;
+        self.write(store, key, value)
+    }
+}
+
+fn convert<T: ListItemData>(f: impl Fn(&T) -> T + 'static) -> Convert {
+    Box::new(move |value| {
+        value
+            .as_any()
+            .downcast_ref::<T>()
+            .map(|value| Box::new(f(value)) as Box<dyn ListItemData>)
+    })
+}
@@ -0,0 +1,380 @@
+use std::ops::Range;
+
+use glam::IVec2;
+
+use crate::{
+    error::ShecvError,
+    listui::{
+        ListAction, ListAnchor, ListItem, ListItemEditable, ListItemSelectable, ListItemType,
+        ListOverflow, ListPopoutBehavior, ListStyle, ListUiId, ValueFormat,
+    },
+    samples::Samples,
+    types::{ListItemData, TextureSheetDefinition, ValueStore},
+    window::State,
+};
+
+enum PendingItem {
+    Label(String),
+    Value(String),
+    Input(String),
+    Slider(String, Range<f64>),
+    CheckBox(String),
+    Vector(String, Box<dyn ListItemData>),
+    Choice(String, Vec<String>),
+    Progress(String, Range<f64>),
+    Sparkline(String, usize),
+    Button(String, ListAction),
+    SubList(String, Box<ListBuilder>),
+}
+
+// Options that apply to the item added before them.
+struct Pending {
+    item: PendingItem,
+    format: ValueFormat,
+    tooltip: Option<String>,
+    icon: Option<(usize, usize)>,
+    label_key: Option<String>,
+    flash_on_change: bool,
+}
+
+// Composes a ListInterface in one expression:
+//
+//   ListBuilder::new("menu")
+//       .label("settings")
+//       .slider("volume", 0.0..1.0)
+//       .format(ValueFormat::Percent(0))
+//       .checkbox("vsync")
+//       .sublist("more", ListBuilder::new("more").input("name"))
+//       .button("reset", |store| store.set("volume", 0.5))
+//       .build(&mut state, &mut store)?;
+//
+// Value items bind to the store key they are named after, inserting a default
+// if the key is missing. Labels, buttons and sublists get a key under the
+// builder's name, e.g. "menu.settings".
+pub struct ListBuilder {
+    name: String,
+    anchor: Option<ListAnchor>,
+    margin: IVec2,
+    overflow: ListOverflow,
+    popout: ListPopoutBehavior,
+    style: Option<ListStyle>,
+    visible_key: Option<String>,
+    // the texture sheet icons come from
+    sheet: Option<TextureSheetDefinition>,
+    items: Vec<Pending>,
+}
+
+#[allow(dead_code)]
+impl ListBuilder {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            anchor: None,
+            margin: IVec2::ZERO,
+            overflow: ListOverflow::Offscreen,
+            popout: ListPopoutBehavior::AlwaysVisible,
+            style: None,
+            visible_key: None,
+            sheet: None,
+            items: vec![],
+        }
+    }
+
+    // Top level lists default to Left, sublists to Right.
+    pub fn anchor(mut self, anchor: ListAnchor) -> Self {
+        self.anchor = Some(anchor);
+        self
+    }
+
+    // Pixels between the list and the screen edges it is anchored to.
+    pub fn margin(mut self, margin: IVec2) -> Self {
+        self.margin = margin;
+        self
+    }
+
+    // Columns wraps rows that would go past the bottom of the screen.
+    pub fn overflow(mut self, overflow: ListOverflow) -> Self {
+        self.overflow = overflow;
+        self
+    }
+
+    // HiddenWhenUnfocused slides the list out while another list has focus.
+    pub fn popout(mut self, popout: ListPopoutBehavior) -> Self {
+        self.popout = popout;
+        self
+    }
+
+    pub fn style(mut self, style: ListStyle) -> Self {
+        self.style = Some(style);
+        self
+    }
+
+    pub fn bind_visible(mut self, key: &str) -> Self {
+        self.visible_key = Some(key.to_string());
+        self
+    }
+
+    // Draw the list from a texture sheet, for icons and nine-slice panels.
+    pub fn sheet(mut self, sheet: TextureSheetDefinition) -> Self {
+        self.sheet = Some(sheet);
+        self
+    }
+
+    pub fn label(self, text: &str) -> Self {
+        self.push(PendingItem::Label(text.to_string()))
+    }
+
+    // Read only. Missing keys start as an empty string.
+    pub fn value(self, key: &str) -> Self {
+        self.push(PendingItem::Value(key.to_string()))
+    }
+
+    // Editable as text. Missing keys start as an empty string.
+    pub fn input(self, key: &str) -> Self {
+        self.push(PendingItem::Input(key.to_string()))
+    }
+
+    // Left and right step through the range in twentieths. Missing keys start at range.start.
+    pub fn slider(self, key: &str, range: Range<f64>) -> Self {
+        self.push(PendingItem::Slider(key.to_string(), range))
+    }
+
+    pub fn checkbox(self, key: &str) -> Self {
+        self.push(PendingItem::CheckBox(key.to_string()))
+    }
+
+    // A read only bar filling as the value goes from range.start to
+    // range.end, with the value written over it. Missing keys start at
+    // range.start.
+    pub fn progress(self, key: &str, range: Range<f64>) -> Self {
+        self.push(PendingItem::Progress(key.to_string(), range))
+    }
+
+    // A graph of the Samples under key, read only. Missing keys start
+    // empty, with room for capacity samples.
+    pub fn sparkline(self, key: &str, capacity: usize) -> Self {
+        self.push(PendingItem::Sparkline(key.to_string(), capacity))
+    }
+
+    // Left, right and enter cycle through options. The key can hold the
+    // option's text or its index; missing keys start as the first option's
+    // text.
+    pub fn choice(self, key: &str, options: &[&str]) -> Self {
+        let options = options.iter().map(|option| option.to_string()).collect();
+        self.push(PendingItem::Choice(key.to_string(), options))
+    }
+
+    // A Vec2, Vec3, IVec2 or ColorRGBA; left and right step the part picked
+    // with alt and left or right. Missing keys start at default.
+    pub fn vector(self, key: &str, default: impl ListItemData) -> Self {
+        self.push(PendingItem::Vector(key.to_string(), Box::new(default)))
+    }
+
+    pub fn button(self, label: &str, action: impl FnMut(&mut ValueStore) + 'static) -> Self {
+        self.push(PendingItem::Button(label.to_string(), Box::new(action)))
+    }
+
+    pub fn sublist(self, label: &str, sublist: ListBuilder) -> Self {
+        self.push(PendingItem::SubList(label.to_string(), Box::new(sublist)))
+    }
+
+    // How the item added last shows its value.
+    pub fn format(mut self, format: ValueFormat) -> Self {
+        if let Some(last) = self.items.last_mut() {
+            last.format = format;
+        }
+        self
+    }
+
+    // Shown beside the item added last once it has been selected for a moment.
+    pub fn tooltip(mut self, tooltip: &str) -> Self {
+        if let Some(last) = self.items.last_mut() {
+            last.tooltip = Some(tooltip.to_string());
+        }
+        self
+    }
+
+    // An image from the list's sheet, left of the label of the item added
+    // last.
+    pub fn icon(mut self, cluster_index: usize, sub_index: usize) -> Self {
+        if let Some(last) = self.items.last_mut() {
+            last.icon = Some((cluster_index, sub_index));
+        }
+        self
+    }
+
+    // Label the item added last with the text for key in the current
+    // language, see Localization. Value items stay bound to the key they are
+    // named after.
+    pub fn label_key(mut self, key: &str) -> Self {
+        if let Some(last) = self.items.last_mut() {
+            last.label_key = Some(key.to_string());
+        }
+        self
+    }
+
+    // Flash the item added last as its value changes, see
+    // ListItem::flash_on_change.
+    pub fn flash_on_change(mut self) -> Self {
+        if let Some(last) = self.items.last_mut() {
+            last.flash_on_change = true;
+        }
+        self
+    }
+
+    fn push(mut self, item: PendingItem) -> Self {
+        self.items.push(Pending {
+            item,
+            format: ValueFormat::Default,
+            tooltip: None,
+            icon: None,
+            label_key: None,
+            flash_on_change: false,
+        });
+        self
+    }
+
+    // Create the list and any sublists. Returns its id in State::listuis.
+    // The list takes focus; sublists get it as they're opened.
+    pub fn build(self, state: &mut State, store: &mut ValueStore) -> Result<ListUiId, ShecvError> {
+        let id = self.build_list(state, store, ListAnchor::Left)?;
+        state.listuis[id].focused = true;
+        Ok(id)
+    }
+
+    fn build_list(
+        self,
+        state: &mut State,
+        store: &mut ValueStore,
+        default_anchor: ListAnchor,
+    ) -> Result<ListUiId, ShecvError> {
+        let id = match self.sheet {
+            Some(sheet) => state.new_listui_with_sheet(sheet)?,
+            None => state.new_listui()?,
+        };
+        {
+            let listui = &mut state.listuis[id];
+            listui.name = self.name.clone();
+            listui.anchor = self.anchor.unwrap_or(default_anchor);
+            listui.margin = self.margin;
+            listui.overflow = self.overflow;
+            listui.popout.behavior = self.popout;
+            if let Some(style) = self.style {
+                listui.style = style;
+            }
+            listui.visible_key = self.visible_key;
+        }
+
+        for pending in self.items {
+            let mut item = match pending.item {
+                PendingItem::Label(text) => ListItem::without_value(
+                    &text,
+                    ListItemType::Text,
+                    ListItemSelectable::NotSelectable,
+                ),
+                PendingItem::Value(key) => ListItem::new(
+                    &key,
+                    ListItemType::Text,
+                    ListItemSelectable::Selectable,
+                    ListItemEditable::NotEditable,
+                    store.bind(&key, String::new()),
+                ),
+                PendingItem::Input(key) => ListItem::new(
+                    &key,
+                    ListItemType::Text,
+                    ListItemSelectable::Selectable,
+                    ListItemEditable::Editable,
+                    store.bind(&key, String::new()),
+                ),
+                PendingItem::Slider(key, range) => {
+                    let mut item = ListItem::new(
+                        &key,
+                        ListItemType::Slider,
+                        ListItemSelectable::Selectable,
+                        ListItemEditable::Editable,
+                        store.bind(&key, range.start),
+                    );
+                    item.range = Some(range);
+                    item
+                }
+                PendingItem::CheckBox(key) => ListItem::new(
+                    &key,
+                    ListItemType::CheckBox,
+                    ListItemSelectable::Selectable,
+                    ListItemEditable::NotEditable,
+                    store.bind(&key, false),
+                ),
+                PendingItem::Vector(key, default) => ListItem::new(
+                    &key,
+                    ListItemType::Vector,
+                    ListItemSelectable::Selectable,
+                    ListItemEditable::Editable,
+                    store.bind_boxed(&key, default),
+                ),
+                PendingItem::Progress(key, range) => {
+                    let mut item = ListItem::new(
+                        &key,
+                        ListItemType::Progress,
+                        ListItemSelectable::Selectable,
+                        ListItemEditable::NotEditable,
+                        store.bind(&key, range.start),
+                    );
+                    item.range = Some(range);
+                    item
+                }
+                PendingItem::Sparkline(key, capacity) => ListItem::new(
+                    &key,
+                    ListItemType::Sparkline,
+                    ListItemSelectable::Selectable,
+                    ListItemEditable::NotEditable,
+                    store.bind(&key, Samples::new(capacity)),
+                ),
+                PendingItem::Choice(key, options) => {
+                    let default = options.first().cloned().unwrap_or_default();
+                    let mut item = ListItem::new(
+                        &key,
+                        ListItemType::Choice,
+                        ListItemSelectable::Selectable,
+                        ListItemEditable::NotEditable,
+                        store.bind(&key, default),
+                    );
+                    item.options = options;
+                    item
+                }
+                PendingItem::Button(label, action) => {
+                    let mut item = ListItem::without_value(
+                        &label,
+                        ListItemType::Button,
+                        ListItemSelectable::Selectable,
+                    );
+                    item.action = Some(action);
+                    item
+                }
+                PendingItem::SubList(label, builder) => {
+                    let child = builder.build_list(state, store, ListAnchor::Right)?;
+                    let child_list = &mut state.listuis[child];
+                    child_list.open = false;
+                    child_list.parent = Some(id);
+                    let mut item = ListItem::without_value(
+                        &label,
+                        ListItemType::SubList,
+                        ListItemSelectable::Selectable,
+                    );
+                    item.sublist = Some(child);
+                    item
+                }
+            };
+            item.format = pending.format;
+            item.tooltip = pending.tooltip;
+            item.icon = pending.icon;
+            item.flash_on_change = pending.flash_on_change;
+            if let Some(key) = pending.label_key {
+                item.label = state.locale.tr(&key).to_string();
+                item.label_key = Some(key);
+            }
+            state.listuis[id].entries.push(item);
+        }
+
+        Ok(id)
+    }
+}
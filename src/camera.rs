@@ -0,0 +1,149 @@
+use glam::{Mat4, Quat, Vec2, Vec3};
+
+// The view for every GeoViewType::Perspective group. GeoManager writes it to
+// the groups each frame, and keeps the aspect ratio in step with the window.
+#[derive(Copy, Clone)]
+pub struct Camera {
+    pub position: Vec3,
+    pub target: Vec3,
+    pub up: Vec3,
+    // vertical, in radians
+    pub fov_y: f32,
+    pub near: f32,
+    pub far: f32,
+    pub aspect: f32,
+}
+
+impl Default for Camera {
+    // at the origin looking down -z, as perspective groups always were
+    fn default() -> Self {
+        Self {
+            position: Vec3::ZERO,
+            target: Vec3::NEG_Z,
+            up: Vec3::Y,
+            fov_y: 2.0,
+            near: 0.01,
+            far: 100.0,
+            aspect: 4.0 / 3.0,
+        }
+    }
+}
+
+#[allow(dead_code)]
+impl Camera {
+    pub fn view(&self) -> Mat4 {
+        Mat4::look_at_rh(self.position, self.target, self.up)
+    }
+
+    pub fn projection(&self) -> Mat4 {
+        Mat4::perspective_rh(self.fov_y, self.aspect, self.near, self.far)
+    }
+
+    pub fn view_projection(&self) -> Mat4 {
+        self.projection() * self.view()
+    }
+
+    pub fn set_aspect(&mut self, width: u32, height: u32) {
+        if width > 0 && height > 0 {
+            self.aspect = width as f32 / height as f32;
+        }
+    }
+
+    pub fn distance(&self) -> f32 {
+        self.position.distance(self.target)
+    }
+
+    // Circle the target: yaw turns around up, positive pitch rises over it.
+    // Both in radians. Pitch stops short of looking straight along up.
+    pub fn orbit(&mut self, yaw: f32, pitch: f32) {
+        let up = self.up.normalize();
+        let mut offset = self.position - self.target;
+        let right = offset.cross(up).normalize_or_zero();
+        if right != Vec3::ZERO {
+            let angle = offset.angle_between(up);
+            let pitch = pitch.clamp(angle - std::f32::consts::PI + 0.01, angle - 0.01);
+            offset = Quat::from_axis_angle(right, pitch) * offset;
+        }
+        self.position = self.target + Quat::from_axis_angle(up, yaw) * offset;
+    }
+
+    // Slide the camera and target together across the view. dx and dy are
+    // fractions of the view's height at the target, so a drag across the whole
+    // screen moves by one screen.
+    pub fn pan(&mut self, dx: f32, dy: f32) {
+        let forward = (self.target - self.position).normalize_or_zero();
+        let right = forward.cross(self.up).normalize_or_zero();
+        let up = right.cross(forward);
+        let height = 2.0 * self.distance() * (self.fov_y / 2.0).tan();
+        let by = (right * dx * self.aspect + up * dy) * height;
+        self.position += by;
+        self.target += by;
+    }
+
+    // Move toward the target by a factor: 0.5 halves the distance, 2.0 doubles it.
+    pub fn zoom(&mut self, factor: f32) {
+        let offset = (self.position - self.target) * factor.max(0.0);
+        // never reach the target, where the view has no direction
+        self.position = self.target + offset.clamp_length_min(self.near);
+    }
+}
+
+// The view for every GeoViewType::World group: a 2D scene that scrolls and
+// zooms on its own, under the UI. One world unit is one logical pixel at
+// zoom 1, and y points up like the unit square expects.
+#[derive(Copy, Clone)]
+pub struct WorldCamera {
+    // the world point at the center of the screen
+    pub offset: Vec2,
+    pub zoom: f32,
+    // in logical pixels, kept up to date by GeoManager::update_view
+    pub screen_size: Vec2,
+}
+
+impl Default for WorldCamera {
+    fn default() -> Self {
+        Self {
+            offset: Vec2::ZERO,
+            zoom: 1.0,
+            screen_size: Vec2::new(800.0, 600.0),
+        }
+    }
+}
+
+#[allow(dead_code)]
+impl WorldCamera {
+    pub fn matrix(&self) -> Mat4 {
+        let half = self.screen_size / (2.0 * self.zoom);
+        Mat4::orthographic_lh(
+            self.offset.x - half.x,
+            self.offset.x + half.x,
+            self.offset.y - half.y,
+            self.offset.y + half.y,
+            -1.0,
+            1.0,
+        )
+    }
+
+    // Screen positions are logical pixels from the top left.
+    pub fn world_to_screen(&self, world: Vec2) -> Vec2 {
+        let from_center = (world - self.offset) * self.zoom;
+        self.screen_size / 2.0 + Vec2::new(from_center.x, -from_center.y)
+    }
+
+    pub fn screen_to_world(&self, screen: Vec2) -> Vec2 {
+        let from_center = screen - self.screen_size / 2.0;
+        self.offset + Vec2::new(from_center.x, -from_center.y) / self.zoom
+    }
+
+    // Drag the scene by a distance in logical pixels, so it follows the mouse.
+    pub fn pan(&mut self, screen_delta: Vec2) {
+        self.offset -= Vec2::new(screen_delta.x, -screen_delta.y) / self.zoom;
+    }
+
+    // Zoom by a factor, keeping the world point under screen_pos where it is.
+    pub fn zoom_at(&mut self, factor: f32, screen_pos: Vec2) {
+        let before = self.screen_to_world(screen_pos);
+        self.zoom = (self.zoom * factor).clamp(0.01, 100.0);
+        self.offset += before - self.screen_to_world(screen_pos);
+    }
+}
@@ -0,0 +1,294 @@
+use std::{
+    collections::VecDeque,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::mpsc::{channel, Receiver, Sender, TryRecvError},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use image::RgbaImage;
+use log::warn;
+use wgpu::{
+    Buffer, BufferAddress, BufferAsyncError, BufferDescriptor, BufferUsages, CommandEncoder,
+    Device, Extent3d, ImageCopyBuffer, ImageCopyTexture, ImageDataLayout, Maintain, MapMode,
+    Origin3d, Texture, TextureAspect, TextureFormat, COPY_BYTES_PER_ROW_ALIGNMENT,
+};
+
+use crate::error::ShecvError;
+
+// A copy of a rendered frame on its way back to the cpu. It is recorded
+// into the frame's encoder, so the window's surface texture can be read
+// before it is presented, and read once the encoder is submitted.
+pub struct FrameCopy {
+    buffer: Buffer,
+    width: u32,
+    height: u32,
+    // rows in the buffer are padded out to wgpu's copy alignment
+    padded_row: u32,
+    // hears when the buffer is mapped, once start_read asked for it
+    mapping: Option<Receiver<Result<(), BufferAsyncError>>>,
+}
+
+impl FrameCopy {
+    pub fn record(
+        device: &Device,
+        encoder: &mut CommandEncoder,
+        texture: &Texture,
+        width: u32,
+        height: u32,
+    ) -> Self {
+        let padded_row =
+            (4 * width).div_ceil(COPY_BYTES_PER_ROW_ALIGNMENT) * COPY_BYTES_PER_ROW_ALIGNMENT;
+        let buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("read back buffer"),
+            size: (padded_row * height) as BufferAddress,
+            usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        encoder.copy_texture_to_buffer(
+            ImageCopyTexture {
+                texture,
+                mip_level: 0,
+                origin: Origin3d::ZERO,
+                aspect: TextureAspect::All,
+            },
+            ImageCopyBuffer {
+                buffer: &buffer,
+                layout: ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_row),
+                    rows_per_image: Some(height),
+                },
+            },
+            Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+        Self {
+            buffer,
+            width,
+            height,
+            padded_row,
+            mapping: None,
+        }
+    }
+
+    // Wait for the copy and unpack it. format is the texture's, as window
+    // surfaces are often bgra.
+    pub fn read(mut self, device: &Device, format: TextureFormat) -> Result<RgbaImage, ShecvError> {
+        self.start_read();
+        device.poll(Maintain::Wait);
+        self.try_read(format)
+            .unwrap_or_else(|| Err(ShecvError::Device("read back was never mapped".into())))
+    }
+
+    // Ask for the copy back without waiting for it, once the encoder it was
+    // recorded into is submitted.
+    pub fn start_read(&mut self) {
+        if self.mapping.is_some() {
+            return;
+        }
+        let (sender, receiver) = channel();
+        self.buffer
+            .slice(..)
+            .map_async(MapMode::Read, move |result| {
+                let _ = sender.send(result);
+            });
+        self.mapping = Some(receiver);
+    }
+
+    // The copy if the gpu has handed it back, None while it hasn't. That
+    // happens as the device is polled.
+    pub fn try_read(&mut self, format: TextureFormat) -> Option<Result<RgbaImage, ShecvError>> {
+        let mapped = match self.mapping.as_ref()?.try_recv() {
+            Ok(mapped) => mapped,
+            Err(TryRecvError::Empty) => return None,
+            Err(TryRecvError::Disconnected) => {
+                return Some(Err(ShecvError::Device("read back was never mapped".into())))
+            }
+        };
+        Some(
+            mapped
+                .map_err(ShecvError::from)
+                .and_then(|()| self.unpack(format)),
+        )
+    }
+
+    fn unpack(&self, format: TextureFormat) -> Result<RgbaImage, ShecvError> {
+        let unpadded_row = 4 * self.width as usize;
+        let data = self.buffer.slice(..).get_mapped_range();
+        let mut pixels = Vec::with_capacity(unpadded_row * self.height as usize);
+        for row in data.chunks(self.padded_row as usize) {
+            pixels.extend_from_slice(&row[..unpadded_row]);
+        }
+        drop(data);
+        self.buffer.unmap();
+
+        if matches!(
+            format,
+            TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb
+        ) {
+            pixels
+                .chunks_exact_mut(4)
+                .for_each(|pixel| pixel.swap(0, 2));
+        }
+        RgbaImage::from_raw(self.width, self.height, pixels)
+            .ok_or_else(|| ShecvError::Device("read back size mismatch".into()))
+    }
+}
+
+// copies still on their way back before frames are dropped to catch up
+const MAX_PENDING: usize = 4;
+
+// Frames saved as they're rendered, at most fps of them a second, see
+// Context::start_recording. A path ending in .gif is recorded as an
+// animated gif, with the gif feature; anything else is a folder, filled
+// with frame_00000.png and on. Frames come back from the gpu a few frames
+// late, without waiting on it, and are encoded on a thread of their own.
+// Frames the gpu or the encoder can't keep up with are skipped.
+pub struct Recording {
+    pub path: PathBuf,
+    interval: Duration,
+    next: Option<Instant>,
+    pending: VecDeque<FrameCopy>,
+    pub frames: usize,
+    pub skipped: usize,
+    sender: Option<Sender<RgbaImage>>,
+    writer: Option<JoinHandle<Result<(), ShecvError>>>,
+}
+
+#[allow(dead_code)]
+impl Recording {
+    pub fn start(path: &str, fps: u32) -> Result<Self, ShecvError> {
+        let fps = fps.max(1);
+        let path = PathBuf::from(path);
+        let gif = path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("gif"));
+        if gif && !cfg!(feature = "gif") {
+            return Err(ShecvError::Io(io::Error::other(format!(
+                "recording {} needs the gif feature",
+                path.display()
+            ))));
+        }
+        if !gif {
+            fs::create_dir_all(&path)?;
+        }
+        let (sender, frames) = channel();
+        let target = path.clone();
+        let writer = thread::spawn(move || match gif {
+            true => write_gif(&target, frames, fps),
+            false => write_folder(&target, frames),
+        });
+        Ok(Self {
+            path,
+            interval: Duration::from_secs(1) / fps,
+            next: None,
+            pending: VecDeque::new(),
+            frames: 0,
+            skipped: 0,
+            sender: Some(sender),
+            writer: Some(writer),
+        })
+    }
+
+    // Whether this frame is due. Called once a frame, before it's rendered.
+    pub fn wants_frame(&mut self) -> bool {
+        let now = Instant::now();
+        let next = *self.next.get_or_insert(now);
+        if now < next {
+            return false;
+        }
+        // a slow frame doesn't make the next ones rush to catch up
+        self.next = Some((next + self.interval).max(now));
+        if self.pending.len() >= MAX_PENDING {
+            self.skipped += 1;
+            return false;
+        }
+        true
+    }
+
+    // A copy of a frame wants_frame asked for, after its encoder is
+    // submitted.
+    pub fn push(&mut self, mut copy: FrameCopy) {
+        copy.start_read();
+        self.pending.push_back(copy);
+    }
+
+    // Hand the frames that are back to the writer, in order, without
+    // waiting for the rest.
+    pub fn collect(&mut self, device: &Device, format: TextureFormat) {
+        device.poll(Maintain::Poll);
+        while let Some(copy) = self.pending.front_mut() {
+            let Some(read) = copy.try_read(format) else {
+                break;
+            };
+            self.pending.pop_front();
+            match read {
+                Ok(image) => self.send(image),
+                Err(e) => {
+                    warn!("recording {}: {}", self.path.display(), e);
+                    self.skipped += 1;
+                }
+            }
+        }
+    }
+
+    fn send(&mut self, image: RgbaImage) {
+        // a writer that stopped has its error waiting for finish
+        let sent = self
+            .sender
+            .as_ref()
+            .is_some_and(|sender| sender.send(image).is_ok());
+        match sent {
+            true => self.frames += 1,
+            false => self.skipped += 1,
+        }
+    }
+
+    // Wait for the frames still on their way back and for the writer to
+    // finish with them. Returns how many frames were saved.
+    pub fn finish(mut self, device: &Device, format: TextureFormat) -> Result<usize, ShecvError> {
+        device.poll(Maintain::Wait);
+        self.collect(device, format);
+        self.sender = None;
+        if let Some(writer) = self.writer.take() {
+            writer
+                .join()
+                .map_err(|_| ShecvError::Io(io::Error::other("recording writer panicked")))??;
+        }
+        Ok(self.frames)
+    }
+}
+
+fn write_folder(dir: &Path, frames: Receiver<RgbaImage>) -> Result<(), ShecvError> {
+    for (index, image) in frames.iter().enumerate() {
+        image.save(dir.join(format!("frame_{:05}.png", index)))?;
+    }
+    Ok(())
+}
+
+#[cfg(feature = "gif")]
+fn write_gif(path: &Path, frames: Receiver<RgbaImage>, fps: u32) -> Result<(), ShecvError> {
+    use image::{
+        codecs::gif::{GifEncoder, Repeat},
+        Delay, Frame,
+    };
+
+    let file = io::BufWriter::new(fs::File::create(path)?);
+    let mut encoder = GifEncoder::new_with_speed(file, 10);
+    encoder.set_repeat(Repeat::Infinite)?;
+    let delay = Delay::from_numer_denom_ms(1000, fps);
+    for image in frames {
+        encoder.encode_frame(Frame::from_parts(image, 0, 0, delay))?;
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "gif"))]
+fn write_gif(_: &Path, _: Receiver<RgbaImage>, _: u32) -> Result<(), ShecvError> {
+    Ok(())
+}
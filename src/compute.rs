@@ -0,0 +1,164 @@
+use std::borrow::Cow;
+
+use glam::UVec3;
+use wgpu::{
+    BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor,
+    BindGroupLayoutEntry, BindingType, Buffer, BufferBindingType, CommandEncoder,
+    ComputePassDescriptor, ComputePipeline, ComputePipelineDescriptor, Device, PipelineLayout,
+    ShaderModuleDescriptor, ShaderSource, ShaderStages,
+};
+
+use crate::assets::read_to_string;
+use crate::error::{validate, ShecvError};
+
+// What a job's shader declares at each binding of group 0, in order.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[allow(dead_code)]
+pub enum ComputeBindingKind {
+    Storage { read_only: bool },
+    Uniform,
+}
+
+// A compute shader entry point dispatched once a frame before rendering.
+// The layout is fixed when the job is added, so the bind group survives the
+// shader being reloaded.
+pub struct ComputeJob {
+    pub shader_path: String,
+    pub entry_point: String,
+    pub workgroups: UVec3,
+    pub enabled: bool,
+    bind_group_layout: BindGroupLayout,
+    pipeline_layout: PipelineLayout,
+    pipeline: ComputePipeline,
+    bind_group: Option<BindGroup>,
+}
+
+#[derive(Default)]
+pub struct ComputeJobs {
+    pub jobs: Vec<ComputeJob>,
+}
+
+impl ComputeJobs {
+    // Jobs dispatch in the order they're added, once they're bound.
+    pub fn add(
+        &mut self,
+        device: &Device,
+        shader_path: &str,
+        entry_point: &str,
+        bindings: &[ComputeBindingKind],
+    ) -> Result<usize, ShecvError> {
+        let entries: Vec<BindGroupLayoutEntry> = bindings
+            .iter()
+            .enumerate()
+            .map(|(binding, kind)| BindGroupLayoutEntry {
+                binding: binding as u32,
+                visibility: ShaderStages::COMPUTE,
+                ty: BindingType::Buffer {
+                    ty: match kind {
+                        ComputeBindingKind::Storage { read_only } => BufferBindingType::Storage {
+                            read_only: *read_only,
+                        },
+                        ComputeBindingKind::Uniform => BufferBindingType::Uniform,
+                    },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            })
+            .collect();
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some(&*format!("compute bind group layout {}", shader_path)),
+            entries: &entries,
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = compute_pipeline(device, &pipeline_layout, shader_path, entry_point)?;
+        self.jobs.push(ComputeJob {
+            shader_path: shader_path.to_string(),
+            entry_point: entry_point.to_string(),
+            workgroups: UVec3::ONE,
+            enabled: true,
+            bind_group_layout,
+            pipeline_layout,
+            pipeline,
+            bind_group: None,
+        });
+        Ok(self.jobs.len() - 1)
+    }
+
+    // Give a job its buffers, one per binding. Instance buffers can be
+    // bound as storage, e.g. to move instances on the gpu.
+    pub fn bind(&mut self, device: &Device, index: usize, buffers: &[&Buffer]) {
+        let job = &mut self.jobs[index];
+        let entries: Vec<BindGroupEntry> = buffers
+            .iter()
+            .enumerate()
+            .map(|(binding, buffer)| BindGroupEntry {
+                binding: binding as u32,
+                resource: buffer.as_entire_binding(),
+            })
+            .collect();
+        job.bind_group = Some(device.create_bind_group(&BindGroupDescriptor {
+            label: Some(&*format!("compute bind group {}", job.shader_path)),
+            layout: &job.bind_group_layout,
+            entries: &entries,
+        }));
+    }
+
+    pub fn reload_shader(&mut self, device: &Device, shader_path: &str) -> Result<(), ShecvError> {
+        for job in self.jobs.iter_mut() {
+            if job.shader_path == shader_path {
+                job.pipeline =
+                    compute_pipeline(device, &job.pipeline_layout, shader_path, &job.entry_point)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Record every enabled, bound job into one compute pass.
+    pub fn dispatch(&self, encoder: &mut CommandEncoder) {
+        let mut jobs = self
+            .jobs
+            .iter()
+            .filter(|job| job.enabled && job.bind_group.is_some())
+            .peekable();
+        if jobs.peek().is_none() {
+            return;
+        }
+        let mut pass = encoder.begin_compute_pass(&ComputePassDescriptor {
+            label: Some("compute pass"),
+            timestamp_writes: None,
+        });
+        for job in jobs {
+            pass.set_pipeline(&job.pipeline);
+            pass.set_bind_group(0, job.bind_group.as_ref().unwrap(), &[]);
+            pass.dispatch_workgroups(job.workgroups.x, job.workgroups.y, job.workgroups.z);
+        }
+    }
+}
+
+fn compute_pipeline(
+    device: &Device,
+    pipeline_layout: &PipelineLayout,
+    shader_path: &str,
+    entry_point: &str,
+) -> Result<ComputePipeline, ShecvError> {
+    let source = read_to_string(shader_path)?;
+    validate(device, shader_path, || {
+        let module = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some(shader_path),
+            source: ShaderSource::Wgsl(Cow::Borrowed(&*source)),
+        });
+        device.create_compute_pipeline(&ComputePipelineDescriptor {
+            label: Some(&*format!("compute pipeline {}", shader_path)),
+            layout: Some(pipeline_layout),
+            module: &module,
+            entry_point,
+            compilation_options: Default::default(),
+            cache: None,
+        })
+    })
+}
@@ -0,0 +1,477 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    rc::Rc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use glam::{IVec2, UVec2};
+
+use crate::{
+    backend::{BackendEvent, Key},
+    error::ShecvError,
+    layer::RenderLayer,
+    listui::{Glide, ListMotion},
+    theme::ThemeRole,
+    types::{ColorRGBA, ComponentTransform, PixelRect, ValueStore},
+    window::{FlowCommand, State},
+};
+
+// lines kept for scrolling back through, oldest dropped first
+const SCROLLBACK: usize = 200;
+
+// Run with the words typed after the command's name. What it returns is
+// printed, and errors are printed in red.
+pub type ConsoleCommand =
+    Rc<dyn Fn(&mut State, &mut ValueStore, &[&str]) -> Result<String, ShecvError>>;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ConsoleLineKind {
+    // what was typed, echoed back
+    Input,
+    Output,
+    Error,
+}
+
+pub struct ConsoleLine {
+    pub text: String,
+    pub kind: ConsoleLineKind,
+}
+
+// A drop-down console, toggled by toggle_key, for poking at a running app:
+//
+//   state.register_console_command("spawn", "spawn <n>: add n enemies", |state, store, args| {
+//       ..
+//       Ok(format!("spawned {}", n))
+//   });
+//
+// Comes with help, get, set, keys, reload, screenshot, record, script,
+// clear and quit. Up and Down step through what was typed before and Tab
+// completes command names, then store keys. While it's down it takes every key.
+pub struct Console {
+    pub open: bool,
+    pub toggle_key: Key,
+    // the part of the window's height it covers when down
+    pub height: f32,
+    pub motion: ListMotion,
+    pub input: String,
+    pub lines: VecDeque<ConsoleLine>,
+    pub history: Vec<String>,
+    // where Up has got to in history, None while typing a new line
+    browsing: Option<usize>,
+    // the new line, put back when Down walks past the end of history
+    draft: String,
+    // the key that toggles it also types itself; that text is dropped
+    just_toggled: bool,
+    commands: BTreeMap<String, (String, ConsoleCommand)>,
+    slide: Glide<f32>,
+}
+
+impl Default for Console {
+    fn default() -> Self {
+        let mut console = Self {
+            open: false,
+            toggle_key: Key::Backquote,
+            height: 0.4,
+            motion: ListMotion::default(),
+            input: String::new(),
+            lines: VecDeque::new(),
+            history: vec![],
+            browsing: None,
+            draft: String::new(),
+            just_toggled: false,
+            commands: BTreeMap::new(),
+            slide: Glide::default(),
+        };
+        console.register("help", "help: list the commands", |state, _, _| {
+            let commands = &state.console.commands;
+            Ok(commands
+                .values()
+                .map(|(help, _)| help.as_str())
+                .collect::<Vec<_>>()
+                .join("\n"))
+        });
+        console.register(
+            "get",
+            "get <key>..: show stored values",
+            |_, store, args| {
+                if args.is_empty() {
+                    return Err(usage("get <key>.."));
+                }
+                let mut values = vec![];
+                for key in args {
+                    let value = store.map.get(*key).ok_or_else(|| {
+                        ShecvError::Store(format!("no value stored under '{}'", key))
+                    })?;
+                    values.push(format!("{} = {}", key, value));
+                }
+                Ok(values.join("\n"))
+            },
+        );
+        console.register(
+            "set",
+            "set <key> <value>: store a value, parsed as the type already there",
+            |_, store, args| {
+                let [key, value @ ..] = args else {
+                    return Err(usage("set <key> <value>"));
+                };
+                if value.is_empty() {
+                    return Err(usage("set <key> <value>"));
+                }
+                store.commit_text(key, &value.join(" "))?;
+                Ok(format!("{} = {}", key, store.map[*key]))
+            },
+        );
+        console.register(
+            "keys",
+            "keys [prefix]: list stored keys",
+            |_, store, args| {
+                let prefix = args.first().copied().unwrap_or("");
+                let mut keys: Vec<&str> = store
+                    .map
+                    .keys()
+                    .map(String::as_str)
+                    .filter(|key| key.starts_with(prefix))
+                    .collect();
+                keys.sort();
+                Ok(keys.join("\n"))
+            },
+        );
+        console.register(
+            "reload",
+            "reload: load every watched shader and ui file again",
+            |state, _, _| {
+                state.reload_assets();
+                Ok("reloading".into())
+            },
+        );
+        console.register(
+            "screenshot",
+            "screenshot [path]: save the next frame, to screenshot-<time>.png by default",
+            |state, _, args| {
+                let path = match args.first() {
+                    Some(path) => path.to_string(),
+                    None => format!("screenshot-{}.png", unix_secs()),
+                };
+                let context = state.context.as_mut().ok_or_else(ShecvError::no_context)?;
+                if !context.can_copy_frames()? {
+                    return Err(ShecvError::Device(
+                        "screenshots need a surface that can be copied from".into(),
+                    ));
+                }
+                context.request_screenshot(&path);
+                Ok(format!("saving {}", path))
+            },
+        );
+        console.register(
+            "record",
+            "record [path] [fps]: start saving frames, to recording-<time> by default, or stop",
+            |state, _, args| {
+                let context = state.context.as_mut().ok_or_else(ShecvError::no_context)?;
+                if context.is_recording() {
+                    let frames = context.stop_recording()?;
+                    return Ok(format!("saved {} frames", frames));
+                }
+                let path = match args.first() {
+                    Some(path) => path.to_string(),
+                    None => format!("recording-{}", unix_secs()),
+                };
+                let fps = match args.get(1) {
+                    Some(fps) => fps.parse().map_err(|_| usage("record [path] [fps]"))?,
+                    None => 30,
+                };
+                context.start_recording(&path, fps)?;
+                Ok(format!("recording to {} at {} fps", path, fps))
+            },
+        );
+        console.register(
+            "script",
+            "script <code>: run a line of rhai, with the loaded scripts' functions",
+            |state, store, args| state.eval_script(&args.join(" "), store),
+        );
+        console.register("clear", "clear: empty the console", |state, _, _| {
+            state.console.lines.clear();
+            Ok(String::new())
+        });
+        console.register("quit", "quit: close the app", |state, _, _| {
+            state.flow_command = FlowCommand::Quit;
+            Ok(String::new())
+        });
+        console
+    }
+}
+
+fn unix_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+fn usage(usage: &str) -> ShecvError {
+    ShecvError::Parse(format!("usage: {}", usage))
+}
+
+#[allow(dead_code)]
+impl Console {
+    // help is shown by the help command, e.g. "spawn <n>: add n enemies".
+    // A command of the same name is replaced.
+    pub fn register(
+        &mut self,
+        name: &str,
+        help: &str,
+        command: impl Fn(&mut State, &mut ValueStore, &[&str]) -> Result<String, ShecvError> + 'static,
+    ) {
+        self.commands
+            .insert(name.to_string(), (help.to_string(), Rc::new(command)));
+    }
+
+    pub fn unregister(&mut self, name: &str) {
+        self.commands.remove(name);
+    }
+
+    pub fn print(&mut self, text: &str) {
+        self.push(text, ConsoleLineKind::Output);
+    }
+
+    pub fn print_error(&mut self, text: &str) {
+        self.push(text, ConsoleLineKind::Error);
+    }
+
+    fn push(&mut self, text: &str, kind: ConsoleLineKind) {
+        for line in text.lines() {
+            self.lines.push_back(ConsoleLine {
+                text: line.to_string(),
+                kind,
+            });
+        }
+        while self.lines.len() > SCROLLBACK {
+            self.lines.pop_front();
+        }
+    }
+
+    fn browse_back(&mut self) {
+        let Some(last) = self.history.len().checked_sub(1) else {
+            return;
+        };
+        let index = match self.browsing {
+            Some(index) => index.saturating_sub(1),
+            None => {
+                self.draft = std::mem::take(&mut self.input);
+                last
+            }
+        };
+        self.browsing = Some(index);
+        self.input = self.history[index].clone();
+    }
+
+    fn browse_forward(&mut self) {
+        let Some(index) = self.browsing else {
+            return;
+        };
+        match self.history.get(index + 1) {
+            Some(line) => {
+                self.input = line.clone();
+                self.browsing = Some(index + 1);
+            }
+            None => {
+                self.input = std::mem::take(&mut self.draft);
+                self.browsing = None;
+            }
+        }
+    }
+
+    // The first word completes to a command name and the rest to store
+    // keys. Several matches fill in what they share and are listed.
+    fn complete(&mut self, store: &ValueStore) {
+        let start = self.input.rfind(' ').map_or(0, |space| space + 1);
+        let (head, word) = self.input.split_at(start);
+        let names: Vec<&String> = match head.is_empty() {
+            true => self.commands.keys().collect(),
+            false => store.map.keys().collect(),
+        };
+        let mut matches: Vec<String> = names
+            .into_iter()
+            .filter(|name| name.starts_with(word))
+            .cloned()
+            .collect();
+        matches.sort();
+        let Some(first) = matches.first() else {
+            return;
+        };
+        let shared = matches
+            .iter()
+            .fold(first.as_str(), |shared, name| common_prefix(shared, name));
+        let mut input = format!("{}{}", head, shared);
+        if matches.len() == 1 {
+            input.push(' ');
+        } else {
+            self.print(&matches.join("  "));
+        }
+        self.input = input;
+    }
+}
+
+fn common_prefix<'a>(a: &'a str, b: &str) -> &'a str {
+    let len = a
+        .chars()
+        .zip(b.chars())
+        .take_while(|(x, y)| x == y)
+        .map(|(x, _)| x.len_utf8())
+        .sum();
+    &a[..len]
+}
+
+impl State<'_> {
+    #[allow(dead_code)]
+    pub fn register_console_command(
+        &mut self,
+        name: &str,
+        help: &str,
+        command: impl Fn(&mut State, &mut ValueStore, &[&str]) -> Result<String, ShecvError> + 'static,
+    ) {
+        self.console.register(name, help, command);
+    }
+
+    // Run a line as if it were typed, e.g. from a startup script.
+    pub fn run_console_command(&mut self, line: &str, store: &mut ValueStore) {
+        let line = line.trim();
+        if line.is_empty() {
+            return;
+        }
+        self.console
+            .push(&format!("> {}", line), ConsoleLineKind::Input);
+        if self.console.history.last().map(String::as_str) != Some(line) {
+            self.console.history.push(line.to_string());
+        }
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let Some((_, command)) = self.console.commands.get(words[0]) else {
+            let message = format!("unknown command '{}', try help", words[0]);
+            self.console.print_error(&message);
+            return;
+        };
+        let command = command.clone();
+        match command(self, store, &words[1..]) {
+            Ok(output) => self.console.print(&output),
+            Err(e) => self.console.print_error(&e.to_string()),
+        }
+    }
+
+    // Called by handle_event before anything else sees the event.
+    pub fn console_event(&mut self, event: &BackendEvent, store: &mut ValueStore) -> bool {
+        let just_toggled = std::mem::take(&mut self.console.just_toggled);
+        if let BackendEvent::KeyDown { key, .. } = event {
+            if *key == self.console.toggle_key {
+                self.console.open = !self.console.open;
+                self.console.just_toggled = true;
+                return true;
+            }
+        }
+        if !self.console.open {
+            return just_toggled && matches!(event, BackendEvent::TextInput { .. });
+        }
+        let console = &mut self.console;
+        match event {
+            BackendEvent::KeyDown { key, .. } => match key {
+                Key::Escape => console.open = false,
+                Key::Enter => {
+                    let line = std::mem::take(&mut console.input);
+                    console.browsing = None;
+                    self.run_console_command(&line, store);
+                }
+                Key::Backspace => {
+                    console.input.pop();
+                }
+                Key::Up => console.browse_back(),
+                Key::Down => console.browse_forward(),
+                Key::Tab => console.complete(store),
+                _ => {}
+            },
+            BackendEvent::TextInput { .. } if just_toggled => {}
+            BackendEvent::TextInput { text, .. } => console.input.push_str(text),
+            BackendEvent::TextEditing { .. } => {}
+            // the window still resizes, closes and so on
+            _ => return false,
+        }
+        true
+    }
+
+    // Called by layout_listuis. Slides down from the top of the window over
+    // everything else, newest lines at the bottom just above the prompt.
+    pub fn layout_console(&mut self) -> Result<(), ShecvError> {
+        if !self.console.open && self.console.slide.current <= 0.0 {
+            return Ok(());
+        }
+
+        let context = self.context.as_mut().ok_or_else(ShecvError::no_context)?;
+        let group_index =
+            context.overlay_group(&mut self.console_group, 4, RenderLayer::Overlay)?;
+        let console = &mut self.console;
+        let target = match console.open {
+            true => 1.0,
+            false => 0.0,
+        };
+        let down = console
+            .slide
+            .update(target, console.motion, &mut context.tweens);
+        let extent = context.logical_size()?;
+        let group = &mut context.geos.instance_groups[group_index];
+        group.instance_buffer_manager.clear();
+        if down <= 0.0 {
+            return Ok(());
+        }
+        let theme = self.themes.current();
+
+        let (line_height, pad) = (18u32, 6u32);
+        let height = (extent.y as f32 * console.height).round() as u32;
+        let top = -((height as f32 * (1.0 - down)).round() as i32);
+        group.add_new(
+            context.queue.clone(),
+            ComponentTransform::unit_square_transform_from_pixel_rect(PixelRect {
+                xy: IVec2::new(0, top),
+                wh: UVec2::new(extent.x, height),
+                extent,
+            }),
+            0,
+            0,
+            theme.color(ThemeRole::Background).with_alpha(0.92),
+        )?;
+        group.add_new(
+            context.queue.clone(),
+            ComponentTransform::unit_square_transform_from_pixel_rect(PixelRect {
+                xy: IVec2::new(0, top + height as i32),
+                wh: UVec2::new(extent.x, 1),
+                extent,
+            }),
+            0,
+            0,
+            theme.color(ThemeRole::Border),
+        )?;
+
+        let width = extent.x.saturating_sub(pad * 2) as f64;
+        let mut line = |text: &str, y: i32, color: ColorRGBA| {
+            let index = context.texts.new_text(
+                (pad as f64, y as f64, width, line_height as f64),
+                text,
+                1.0,
+                color,
+            );
+            context.texts.set_layer(index, RenderLayer::Overlay);
+        };
+        let mut y = top + height as i32 - (pad + line_height) as i32;
+        let prompt = format!("> {}_", console.input);
+        line(&prompt, y, theme.color(ThemeRole::Text));
+        for scrollback in console.lines.iter().rev() {
+            y -= line_height as i32;
+            if y < top + pad as i32 {
+                break;
+            }
+            let color = match scrollback.kind {
+                ConsoleLineKind::Input => theme.color(ThemeRole::DisabledText),
+                ConsoleLineKind::Output => theme.color(ThemeRole::Text),
+                ConsoleLineKind::Error => ColorRGBA::new(1.0, 0.4, 0.4, 1.0),
+            };
+            line(&scrollback.text, y, color);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,240 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use glam::{IVec2, UVec2, Vec2};
+
+use crate::{
+    error::ShecvError,
+    geo::GroupId,
+    layer::RenderLayer,
+    line::LinePoint,
+    timing::RenderStats,
+    types::{ColorRGBA, InstanceShape, PixelRect},
+    window::State,
+};
+
+const HISTORY_LEN: usize = 120;
+
+pub struct FrameSample {
+    // time since the previous frame
+    pub interval: Duration,
+    // time spent handling events, updating and rendering
+    pub cpu: Duration,
+}
+
+// FPS, a graph of recent cpu frame times, instance counts per group, text
+// label counts and render timings, drawn in the top right corner.
+#[derive(Default)]
+pub struct DebugOverlay {
+    pub enabled: bool,
+    pub history: VecDeque<FrameSample>,
+    last_frame: Option<Instant>,
+    group: Option<GroupId>,
+    line_group: Option<usize>,
+}
+
+impl DebugOverlay {
+    pub fn record(&mut self, cpu: Duration) {
+        let now = Instant::now();
+        let interval = self
+            .last_frame
+            .map(|last_frame| now - last_frame)
+            .unwrap_or_default();
+        self.last_frame = Some(now);
+        if self.history.len() == HISTORY_LEN {
+            self.history.pop_front();
+        }
+        self.history.push_back(FrameSample { interval, cpu });
+    }
+
+    pub fn fps(&self) -> f64 {
+        let total: Duration = self.history.iter().map(|sample| sample.interval).sum();
+        if total.is_zero() {
+            return 0.0;
+        }
+        self.history.len() as f64 / total.as_secs_f64()
+    }
+
+    pub fn average_cpu(&self) -> Duration {
+        if self.history.is_empty() {
+            return Duration::ZERO;
+        }
+        self.history
+            .iter()
+            .map(|sample| sample.cpu)
+            .sum::<Duration>()
+            / self.history.len() as u32
+    }
+}
+
+impl State<'_> {
+    pub fn enable_debug_overlay(&mut self) -> Result<(), ShecvError> {
+        self.debug_overlay.enabled = true;
+        // gpu pass times are shown when the adapter supports them
+        if let Some(context) = self.context.as_mut() {
+            context.enable_gpu_timing()?;
+        }
+        Ok(())
+    }
+
+    pub fn disable_debug_overlay(&mut self) {
+        self.debug_overlay.enabled = false;
+    }
+
+    pub fn toggle_debug_overlay(&mut self) -> Result<(), ShecvError> {
+        if self.debug_overlay.enabled {
+            self.disable_debug_overlay();
+            Ok(())
+        } else {
+            self.enable_debug_overlay()
+        }
+    }
+
+    pub fn layout_debug_overlay(&mut self) -> Result<(), ShecvError> {
+        let context = self.context.as_mut().ok_or_else(ShecvError::no_context)?;
+        let overlay = &mut self.debug_overlay;
+        if let Some(group_index) = overlay.group {
+            context.geos.instance_groups[group_index]
+                .instance_buffer_manager
+                .clear();
+        }
+        if let Some(line_group) = overlay.line_group {
+            context.geos.line_groups[line_group].clear();
+        }
+        if !overlay.enabled {
+            return Ok(());
+        }
+
+        let (format, size) = {
+            let config = context.config.lock()?;
+            (config.format, (config.width, config.height))
+        };
+        let extent = context.logical_size()?;
+        // drawn over everything, text included
+        let group_index = context.overlay_group(&mut overlay.group, 4, RenderLayer::Overlay)?;
+        let line_group = match overlay.line_group {
+            Some(line_group) => line_group,
+            None => {
+                let line_group =
+                    context
+                        .geos
+                        .new_line_group(HISTORY_LEN + 1, format, size, "src/line.wgsl")?;
+                context.geos.line_groups[line_group].layer = RenderLayer::Overlay;
+                overlay.line_group = Some(line_group);
+                line_group
+            }
+        };
+
+        let glyphs: usize = context
+            .texts
+            .texts
+            .iter()
+            .flat_map(|text| text.buffer.layout_runs())
+            .map(|run| run.glyphs.len())
+            .sum();
+        let instance_counts: Vec<String> = context
+            .geos
+            .instance_groups
+            .iter()
+            .map(|group| group.instance_buffer_manager.data.len().to_string())
+            .collect();
+        let lines = [
+            format!(
+                "{:.0} fps  cpu {:.2}ms",
+                overlay.fps(),
+                overlay.average_cpu().as_secs_f64() * 1000.0
+            ),
+            format!(
+                "instances: {}  culled {} of {}",
+                instance_counts.join(" "),
+                context.geos.cull_stats.culled,
+                context.geos.cull_stats.drawn + context.geos.cull_stats.culled
+            ),
+            format!(
+                "text: {} labels, {} glyphs",
+                context.texts.texts.len(),
+                glyphs
+            ),
+            render_stats_line(&context.render_stats),
+        ];
+
+        let (line_height, pad) = (20, 6);
+        let graph_wh = UVec2::new(HISTORY_LEN as u32 * 2, 60);
+        let wh = UVec2::new(
+            graph_wh.x + pad * 2,
+            lines.len() as u32 * line_height + graph_wh.y + pad * 3,
+        );
+        let xy = IVec2::new(extent.x as i32 - wh.x as i32, 0);
+        context.geos.instance_groups[group_index].add_shape(
+            context.queue.clone(),
+            PixelRect { xy, wh, extent },
+            InstanceShape::default(),
+            ColorRGBA::new(0.0, 0.0, 0.0, 0.75),
+        )?;
+        for (i, line) in lines.iter().enumerate() {
+            let text_index = context.texts.new_text(
+                (
+                    (xy.x + pad as i32) as f64,
+                    (xy.y + pad as i32 + (i as u32 * line_height) as i32) as f64,
+                    graph_wh.x as f64,
+                    line_height as f64,
+                ),
+                line,
+                1.0,
+                ColorRGBA::white(),
+            );
+            context.texts.set_layer(text_index, RenderLayer::Overlay);
+        }
+
+        // one bar per frame; the graph tops out at two 60 fps frames,
+        // with a line marking one
+        let bottom = (xy.y + wh.y as i32 - pad as i32) as f32;
+        let left = (xy.x + pad as i32) as f32;
+        let budget = Duration::from_micros(16_667);
+        let ms_height = graph_wh.y as f32 / (budget.as_secs_f32() * 2000.0);
+        let lines = &mut context.geos.line_groups[line_group];
+        for (i, sample) in overlay.history.iter().enumerate() {
+            let ms = sample.cpu.as_secs_f32() * 1000.0;
+            let x = left + i as f32 * 2.0 + 1.0;
+            let top = bottom - (ms * ms_height).min(graph_wh.y as f32);
+            let color = if sample.cpu > budget {
+                ColorRGBA::new(1.0, 0.3, 0.3, 1.0)
+            } else {
+                ColorRGBA::new(0.3, 1.0, 0.4, 1.0)
+            };
+            lines.add_line(
+                context.queue.clone(),
+                LinePoint::new(Vec2::new(x, bottom), color, 1.0),
+                LinePoint::new(Vec2::new(x, top), color, 1.0),
+            )?;
+        }
+        let budget_y = bottom - graph_wh.y as f32 / 2.0;
+        let budget_color = ColorRGBA::new(1.0, 1.0, 1.0, 0.4);
+        lines.add_line(
+            context.queue.clone(),
+            LinePoint::new(Vec2::new(left, budget_y), budget_color, 1.0),
+            LinePoint::new(
+                Vec2::new(left + graph_wh.x as f32, budget_y),
+                budget_color,
+                1.0,
+            ),
+        )?;
+
+        Ok(())
+    }
+}
+
+fn render_stats_line(stats: &RenderStats) -> String {
+    let ms = |duration: Duration| duration.as_secs_f64() * 1000.0;
+    let mut line = format!("render cpu {:.2}ms", ms(stats.cpu_render));
+    if let (Some(geo_pass), Some(text_pass)) = (stats.geo_pass, stats.text_pass) {
+        line += &format!(
+            "  gpu geo {:.2}ms text {:.2}ms",
+            ms(geo_pass),
+            ms(text_pass)
+        );
+    }
+    line
+}
@@ -0,0 +1,40 @@
+use crate::types::{ListItemData, ValueStore};
+
+type Compute = Box<dyn Fn(&ValueStore) -> Option<Box<dyn ListItemData>>>;
+
+// A key whose value is computed from other keys, see ValueStore::derive.
+pub struct Derived {
+    pub key: String,
+    pub deps: Vec<String>,
+    compute: Compute,
+    // the version of each dep when last computed
+    seen: Vec<Option<u64>>,
+}
+
+impl Derived {
+    pub fn new<T: ListItemData>(
+        key: &str,
+        deps: &[&str],
+        compute: impl Fn(&ValueStore) -> Option<T> + 'static,
+    ) -> Self {
+        Self {
+            key: key.to_string(),
+            deps: deps.iter().map(|dep| dep.to_string()).collect(),
+            compute: Box::new(move |store| {
+                compute(store).map(|value| Box::new(value) as Box<dyn ListItemData>)
+            }),
+            seen: vec![],
+        }
+    }
+
+    // Compute the value again if any dep changed since last time. None if
+    // nothing changed, or compute had nothing to give, e.g. a dep is missing.
+    pub fn update(&mut self, store: &ValueStore) -> Option<Box<dyn ListItemData>> {
+        let versions: Vec<Option<u64>> = self.deps.iter().map(|dep| store.version(dep)).collect();
+        if versions == self.seen {
+            return None;
+        }
+        self.seen = versions;
+        (self.compute)(store)
+    }
+}
@@ -0,0 +1,327 @@
+use glam::{IVec2, UVec2};
+
+use crate::{
+    backend::{BackendEvent, Key},
+    error::ShecvError,
+    layer::RenderLayer,
+    text::TextCollection,
+    theme::ThemeRole,
+    types::{Border, ColorRGBA, ComponentTransform, InstanceShape, PixelRect, ValueStore},
+    window::State,
+};
+
+// Told apart by show_dialog, to poll with take_dialog_result.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct DialogId(u64);
+
+// How a dialog was closed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DialogResult {
+    // the index of the button chosen, None if escape dismissed it
+    pub button: Option<usize>,
+    // what was typed, for dialogs with an input box
+    pub text: Option<String>,
+}
+
+impl DialogResult {
+    // The first button, "ok" for the dialogs made by DialogSpec.
+    #[allow(dead_code)]
+    pub fn is_ok(&self) -> bool {
+        self.button == Some(0)
+    }
+}
+
+pub type DialogCallback = Box<dyn FnOnce(&DialogResult, &mut ValueStore)>;
+
+pub struct DialogSpec {
+    pub title: String,
+    pub message: String,
+    pub buttons: Vec<String>,
+    // Some for an input box, starting with this text
+    pub input: Option<String>,
+    // run when the dialog closes; without one the result waits for
+    // take_dialog_result
+    pub on_close: Option<DialogCallback>,
+}
+
+#[allow(dead_code)]
+impl DialogSpec {
+    pub fn message(title: &str, message: &str) -> Self {
+        Self {
+            title: title.to_string(),
+            message: message.to_string(),
+            buttons: vec!["ok".to_string()],
+            input: None,
+            on_close: None,
+        }
+    }
+
+    pub fn confirm(title: &str, message: &str) -> Self {
+        Self {
+            buttons: vec!["ok".to_string(), "cancel".to_string()],
+            ..Self::message(title, message)
+        }
+    }
+
+    pub fn input(title: &str, message: &str, initial: &str) -> Self {
+        Self {
+            input: Some(initial.to_string()),
+            ..Self::confirm(title, message)
+        }
+    }
+
+    pub fn buttons(mut self, buttons: &[&str]) -> Self {
+        self.buttons = buttons.iter().map(|button| button.to_string()).collect();
+        self
+    }
+
+    pub fn on_close(
+        mut self,
+        on_close: impl FnOnce(&DialogResult, &mut ValueStore) + 'static,
+    ) -> Self {
+        self.on_close = Some(Box::new(on_close));
+        self
+    }
+}
+
+pub struct Dialog {
+    pub id: DialogId,
+    pub spec: DialogSpec,
+    pub selected: usize,
+}
+
+// Dialogs stack, the newest on top. While any is open it takes every key and
+// all typed text, and the lists and grids behind it see none of them.
+pub struct Dialogs {
+    pub open: Vec<Dialog>,
+    // results of closed dialogs without a callback, until taken
+    pub results: Vec<(DialogId, DialogResult)>,
+    next_id: u64,
+    // the screen behind the dialogs is dimmed by this
+    pub dim: ColorRGBA,
+}
+
+impl Default for Dialogs {
+    fn default() -> Self {
+        Self {
+            open: vec![],
+            results: vec![],
+            next_id: 0,
+            dim: ColorRGBA::new(0.0, 0.0, 0.0, 0.5),
+        }
+    }
+}
+
+impl Dialogs {
+    pub fn is_open(&self) -> bool {
+        !self.open.is_empty()
+    }
+}
+
+impl State<'_> {
+    #[allow(dead_code)]
+    pub fn show_dialog(&mut self, spec: DialogSpec) -> DialogId {
+        let dialogs = &mut self.dialogs;
+        let id = DialogId(dialogs.next_id);
+        dialogs.next_id += 1;
+        dialogs.open.push(Dialog {
+            id,
+            spec,
+            selected: 0,
+        });
+        id
+    }
+
+    // The result of a closed dialog shown without a callback, once.
+    #[allow(dead_code)]
+    pub fn take_dialog_result(&mut self, id: DialogId) -> Option<DialogResult> {
+        let results = &mut self.dialogs.results;
+        let index = results.iter().position(|(result_id, _)| *result_id == id)?;
+        Some(results.remove(index).1)
+    }
+
+    fn close_dialog(&mut self, button: Option<usize>, store: &mut ValueStore) {
+        let Some(mut dialog) = self.dialogs.open.pop() else {
+            return;
+        };
+        let result = DialogResult {
+            button,
+            text: dialog.spec.input.take(),
+        };
+        match dialog.spec.on_close.take() {
+            Some(on_close) => on_close(&result, store),
+            None => self.dialogs.results.push((dialog.id, result)),
+        }
+    }
+
+    // Give an event to the top dialog. False if no dialog is open, so the
+    // event goes on to everything else.
+    pub fn dialog_event(&mut self, event: &BackendEvent, store: &mut ValueStore) -> bool {
+        let Some(dialog) = self.dialogs.open.last_mut() else {
+            return false;
+        };
+        let count = dialog.spec.buttons.len().max(1);
+        match event {
+            BackendEvent::KeyDown { key, .. } => match key {
+                Key::Left => dialog.selected = (dialog.selected + count - 1) % count,
+                Key::Right => dialog.selected = (dialog.selected + 1) % count,
+                Key::Backspace => {
+                    if let Some(input) = dialog.spec.input.as_mut() {
+                        input.pop();
+                    }
+                }
+                Key::Enter => {
+                    let button = (!dialog.spec.buttons.is_empty()).then_some(dialog.selected);
+                    self.close_dialog(button, store);
+                }
+                Key::Escape => self.close_dialog(None, store),
+                _ => {}
+            },
+            BackendEvent::TextInput { text, .. } => {
+                if let Some(input) = dialog.spec.input.as_mut() {
+                    input.push_str(text);
+                }
+            }
+            BackendEvent::TextEditing { .. } => {}
+            // the window still resizes, closes and so on
+            _ => return false,
+        }
+        true
+    }
+
+    pub fn layout_dialogs(&mut self) -> Result<(), ShecvError> {
+        if !self.dialogs.is_open() && self.dialog_group.is_none() {
+            return Ok(());
+        }
+
+        let context = self.context.as_mut().ok_or_else(ShecvError::no_context)?;
+        // over the lists and their text
+        let group_index =
+            context.overlay_group(&mut self.dialog_group, 64, RenderLayer::Overlay)?;
+        let extent = context.logical_size()?;
+        let group = &mut context.geos.instance_groups[group_index];
+        group.instance_buffer_manager.clear();
+        let Some(dialog) = self.dialogs.open.last() else {
+            return Ok(());
+        };
+        let theme = self.themes.current();
+
+        group.add_new(
+            context.queue.clone(),
+            ComponentTransform::unit_square_transform_from_pixel_rect(PixelRect {
+                xy: IVec2::ZERO,
+                wh: extent,
+                extent,
+            }),
+            0,
+            0,
+            self.dialogs.dim,
+        )?;
+
+        let (line_height, pad) = (20u32, 10u32);
+        let metrics = TextCollection::line_metrics(line_height as f32);
+        let spec = &dialog.spec;
+        let input = spec.input.as_ref().map(|input| format!("{}_", input));
+        let lines: Vec<&str> = spec.message.lines().collect();
+        let button_widths: Vec<u32> = spec
+            .buttons
+            .iter()
+            .map(|button| context.texts.measure(button, metrics).0.ceil() as u32 + pad * 2)
+            .collect();
+        let buttons_width = button_widths.iter().sum::<u32>() + pad * button_widths.len() as u32;
+        let width = [spec.title.as_str()]
+            .iter()
+            .chain(&lines)
+            .chain(input.as_deref().iter())
+            .map(|text| context.texts.measure(text, metrics).0.ceil() as u32)
+            .max()
+            .unwrap_or(0)
+            .max(buttons_width)
+            .max(240)
+            + pad * 2;
+        let rows = 2 + lines.len() as u32 + input.is_some() as u32;
+        let height = rows * line_height + pad * (rows + 1);
+        let xy = IVec2::new(
+            (extent.x as i32 - width as i32) / 2,
+            (extent.y as i32 - height as i32) / 2,
+        );
+
+        let wh = UVec2::new(width, height);
+        group.add_shape(
+            context.queue.clone(),
+            PixelRect { xy, wh, extent },
+            InstanceShape::rounded(
+                wh,
+                6.0,
+                Some(Border {
+                    width: 1.0,
+                    color: theme.color(ThemeRole::Border),
+                }),
+            ),
+            theme.color(ThemeRole::Background),
+        )?;
+
+        let mut y = xy.y + pad as i32;
+        let mut line = |text: &str, x: i32, y: i32, width: u32, role: ThemeRole| {
+            let index = context.texts.new_text(
+                (x as f64, y as f64, width as f64 + 1.0, line_height as f64),
+                text,
+                1.0,
+                theme.color(role),
+            );
+            context.texts.set_layer(index, RenderLayer::Overlay);
+        };
+        line(
+            &spec.title,
+            xy.x + pad as i32,
+            y,
+            width,
+            ThemeRole::AccentText,
+        );
+        y += (line_height + pad) as i32;
+        for text in &lines {
+            line(text, xy.x + pad as i32, y, width, ThemeRole::Text);
+            y += (line_height + pad) as i32;
+        }
+        if let Some(input) = &input {
+            let field = PixelRect {
+                xy: IVec2::new(xy.x + pad as i32 - 2, y - 2),
+                wh: UVec2::new(width - pad * 2 + 4, line_height + 4),
+                extent,
+            };
+            context.geos.instance_groups[group_index].add_new(
+                context.queue.clone(),
+                ComponentTransform::unit_square_transform_from_pixel_rect(field),
+                0,
+                0,
+                theme.color(ThemeRole::Surface),
+            )?;
+            line(input, xy.x + pad as i32, y, width, ThemeRole::Text);
+            y += (line_height + pad) as i32;
+        }
+
+        // buttons sit along the bottom, right aligned
+        let mut x = xy.x + width as i32 - buttons_width as i32;
+        for (i, (button, button_width)) in spec.buttons.iter().zip(&button_widths).enumerate() {
+            let (fg, bg) = match i == dialog.selected {
+                true => (ThemeRole::SelectionText, ThemeRole::Selection),
+                false => (ThemeRole::Text, ThemeRole::Surface),
+            };
+            let rect = PixelRect {
+                xy: IVec2::new(x, y),
+                wh: UVec2::new(*button_width, line_height),
+                extent,
+            };
+            context.geos.instance_groups[group_index].add_shape(
+                context.queue.clone(),
+                rect,
+                InstanceShape::rounded(rect.wh, 4.0, None),
+                theme.color(bg),
+            )?;
+            line(button, x + pad as i32, y, *button_width, fg);
+            x += (button_width + pad) as i32;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,337 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+};
+
+use flax::{component, entity_ids, Entity, Query, World};
+use glam::{Mat4, Quat, Vec3};
+use wgpu::Queue;
+
+use crate::{
+    error::ShecvError,
+    geo::{GeoManager, GroupId, InstanceId},
+    types::{ColorRGBA, ComponentTransform},
+    window::Context,
+};
+
+// Where an entity is drawn, relative to its parent if it has one, otherwise
+// in its group's space.
+#[derive(Copy, Clone, PartialEq)]
+pub struct Transform {
+    pub location: Vec3,
+    pub rotation: Quat,
+    pub scale: Vec3,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self {
+            location: Vec3::ZERO,
+            rotation: Quat::IDENTITY,
+            scale: Vec3::ONE,
+        }
+    }
+}
+
+impl Transform {
+    pub fn to_mat4(self) -> Mat4 {
+        Mat4::from_scale_rotation_translation(self.scale, self.rotation, self.location)
+    }
+
+    // Shear from non-uniform scale under a rotation doesn't survive this.
+    pub fn from_mat4(matrix: Mat4) -> Self {
+        let (scale, rotation, location) = matrix.to_scale_rotation_translation();
+        Self {
+            location,
+            rotation,
+            scale,
+        }
+    }
+
+    pub fn to_component(self) -> ComponentTransform {
+        ComponentTransform {
+            pixel_rect: None,
+            location: self.location,
+            rotation: self.rotation,
+            scale: self.scale,
+        }
+    }
+}
+
+// What an entity is drawn as: a cell of its group's texture sheet.
+#[derive(Copy, Clone, PartialEq)]
+pub struct Sprite {
+    pub group: GroupId,
+    pub cluster_index: usize,
+    pub sub_index: usize,
+    pub color: ColorRGBA,
+}
+
+// Entities without one are visible, unless a parent is hidden.
+#[derive(Default, Copy, Clone, PartialEq, Eq)]
+#[allow(dead_code)]
+pub enum Visibility {
+    #[default]
+    Visible,
+    Hidden,
+}
+
+component! {
+    pub transform: Transform,
+    pub sprite: Sprite,
+    pub visibility: Visibility,
+    // the entity this one's transform is relative to
+    pub parent: Entity,
+}
+
+// Parent chains deeper than this are treated as cycles.
+const MAX_DEPTH: usize = 64;
+
+// An entity's transform and visibility once its parents are applied.
+#[derive(Copy, Clone)]
+struct Resolved {
+    matrix: Mat4,
+    hidden: bool,
+}
+
+struct Node {
+    local: Transform,
+    hidden: bool,
+    parent: Option<Entity>,
+}
+
+// The propagation pass: every entity with a transform, in world space.
+// A parent without a transform counts as the origin.
+fn resolve_hierarchy(world: &World) -> HashMap<Entity, Resolved> {
+    let mut nodes = HashMap::new();
+    let mut query = Query::new((
+        entity_ids(),
+        transform(),
+        visibility().opt(),
+        parent().opt(),
+    ));
+    for (id, &local, visibility, parent) in &mut query.borrow(world) {
+        let node = Node {
+            local,
+            hidden: visibility.copied().unwrap_or_default() == Visibility::Hidden,
+            parent: parent.copied(),
+        };
+        nodes.insert(id, node);
+    }
+
+    let mut resolved = HashMap::new();
+    for &id in nodes.keys() {
+        resolve(id, &nodes, &mut resolved, 0);
+    }
+    resolved
+}
+
+fn resolve(
+    id: Entity,
+    nodes: &HashMap<Entity, Node>,
+    resolved: &mut HashMap<Entity, Resolved>,
+    depth: usize,
+) -> Option<Resolved> {
+    if let Some(done) = resolved.get(&id) {
+        return Some(*done);
+    }
+    let node = nodes.get(&id)?;
+    let local = Resolved {
+        matrix: node.local.to_mat4(),
+        hidden: node.hidden,
+    };
+    let parent = match node.parent {
+        Some(parent) if depth < MAX_DEPTH => resolve(parent, nodes, resolved, depth + 1),
+        _ => None,
+    };
+    let result = match parent {
+        Some(parent) => Resolved {
+            matrix: parent.matrix * local.matrix,
+            hidden: parent.hidden || local.hidden,
+        },
+        None => local,
+    };
+    resolved.insert(id, result);
+    Some(result)
+}
+
+// One drawn entity, and what it was last drawn with.
+struct RenderInstance {
+    instance: InstanceId,
+    transform: Transform,
+    sprite: Sprite,
+}
+
+// Which instance each entity with a transform and a sprite is drawn with.
+// The instances of a sprite's group belong to the sync: it moves them around
+// as entities come and go, so leave those groups to it.
+#[derive(Default)]
+pub struct RenderSync {
+    instances: HashMap<Entity, RenderInstance>,
+}
+
+impl RenderSync {
+    // Add instances for new or shown entities, update moved or changed ones,
+    // and remove those of hidden or despawned ones. Parents are applied first,
+    // so moving or hiding one moves or hides everything under it.
+    pub fn sync(
+        &mut self,
+        world: &World,
+        geos: &mut GeoManager,
+        queue: Arc<Mutex<Queue>>,
+    ) -> Result<(), ShecvError> {
+        let hierarchy = resolve_hierarchy(world);
+        let mut drawn = HashSet::new();
+        let mut query = Query::new((entity_ids(), sprite()));
+        for (id, &sprite) in &mut query.borrow(world) {
+            let Some(resolved) = hierarchy.get(&id) else {
+                continue;
+            };
+            if resolved.hidden {
+                continue;
+            }
+            let transform = Transform::from_mat4(resolved.matrix);
+            drawn.insert(id);
+            match self.instances.get_mut(&id) {
+                Some(instance) if instance.instance.group == sprite.group => {
+                    update_instance(instance, transform, sprite, geos, queue.clone())?;
+                }
+                Some(_) => {
+                    self.remove(id, geos, queue.clone())?;
+                    self.add(id, transform, sprite, geos, queue.clone())?;
+                }
+                None => self.add(id, transform, sprite, geos, queue.clone())?,
+            }
+        }
+
+        let gone: Vec<Entity> = self
+            .instances
+            .keys()
+            .filter(|id| !drawn.contains(id))
+            .copied()
+            .collect();
+        for id in gone {
+            self.remove(id, geos, queue.clone())?;
+        }
+        Ok(())
+    }
+
+    fn add(
+        &mut self,
+        id: Entity,
+        transform: Transform,
+        sprite: Sprite,
+        geos: &mut GeoManager,
+        queue: Arc<Mutex<Queue>>,
+    ) -> Result<(), ShecvError> {
+        // sprites pointing at a group that doesn't exist are skipped
+        let Some(group) = geos.instance_groups.get_mut(sprite.group) else {
+            return Ok(());
+        };
+        let index = group.add_new(
+            queue,
+            transform.to_component(),
+            sprite.cluster_index,
+            sprite.sub_index,
+            sprite.color,
+        )?;
+        let Some(instance) = geos.instance_id(sprite.group, index) else {
+            return Ok(());
+        };
+        self.instances.insert(
+            id,
+            RenderInstance {
+                instance,
+                transform,
+                sprite,
+            },
+        );
+        Ok(())
+    }
+
+    fn remove(
+        &mut self,
+        id: Entity,
+        geos: &mut GeoManager,
+        queue: Arc<Mutex<Queue>>,
+    ) -> Result<(), ShecvError> {
+        let Some(removed) = self.instances.remove(&id) else {
+            return Ok(());
+        };
+        // a removed group took its instances with it
+        let Some(group) = geos.instance_groups.get_mut(removed.instance.group) else {
+            return Ok(());
+        };
+        let manager = &mut group.instance_buffer_manager;
+        if !manager.is_live(removed.instance.index, removed.instance.generation) {
+            return Ok(());
+        }
+        let last = manager.data.len() - 1;
+        let moved_from = InstanceId {
+            group: removed.instance.group,
+            index: last,
+            generation: manager.generation(last),
+        };
+        if manager
+            .swap_remove(queue, removed.instance.index)?
+            .is_none()
+        {
+            return Ok(());
+        }
+        // the group's last instance took the removed one's place
+        let moved_to = InstanceId {
+            index: removed.instance.index,
+            generation: manager.generation(removed.instance.index),
+            ..removed.instance
+        };
+        if let Some(moved) = self
+            .instances
+            .values_mut()
+            .find(|instance| instance.instance == moved_from)
+        {
+            moved.instance = moved_to;
+        }
+        Ok(())
+    }
+}
+
+fn update_instance(
+    instance: &mut RenderInstance,
+    transform: Transform,
+    sprite: Sprite,
+    geos: &mut GeoManager,
+    queue: Arc<Mutex<Queue>>,
+) -> Result<(), ShecvError> {
+    if geos.instance_manager(instance.instance).is_none() {
+        return Ok(());
+    }
+    let group = &mut geos.instance_groups[instance.instance.group];
+    let manager = &mut group.instance_buffer_manager;
+    if instance.transform != transform {
+        manager.data[instance.instance.index].transform = transform.to_component();
+    }
+    if instance.sprite.cluster_index != sprite.cluster_index
+        || instance.sprite.sub_index != sprite.sub_index
+    {
+        manager.data[instance.instance.index].tex_transform = group
+            .sheet
+            .cluster_sub_transform(sprite.cluster_index, sprite.sub_index);
+    }
+    if instance.sprite.color != sprite.color {
+        manager.data[instance.instance.index].color = sprite.color;
+    }
+    if instance.transform != transform || instance.sprite != sprite {
+        manager.write_instance(queue, instance.instance.index)?;
+        instance.transform = transform;
+        instance.sprite = sprite;
+    }
+    Ok(())
+}
+
+impl Context<'_> {
+    // Run once a frame, before rendering, so the world decides what's drawn.
+    pub fn sync_render_system(&mut self, world: &World) -> Result<(), ShecvError> {
+        self.render_sync
+            .sync(world, &mut self.geos, self.queue.clone())
+    }
+}
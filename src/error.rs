@@ -0,0 +1,133 @@
+use std::{error::Error, fmt, io, sync::PoisonError};
+
+use wgpu::{
+    BufferAsyncError, CreateSurfaceError, Device, ErrorFilter, RequestDeviceError, SurfaceError,
+};
+
+// The error returned from shecv's public functions. Surface and Io keep the
+// underlying error so callers can match on it; the rest carry a message.
+#[derive(Debug)]
+pub enum ShecvError {
+    // acquiring a frame from the window surface
+    Surface(SurfaceError),
+    // the window, adapter, device, surface setup, gpu readback and text renderer
+    Device(String),
+    Io(io::Error),
+    // files and strings that were read but not understood: images, meshes,
+    // fonts, markup and colors
+    Parse(String),
+    // building and laying out lists and overlays
+    Layout(String),
+    // reading or editing values in a ValueStore
+    Store(String),
+    // wgsl that didn't compile, or a pipeline wgpu wouldn't build from it
+    Shader(String),
+    // scripts that didn't compile, or failed while running
+    Script(String),
+}
+
+impl ShecvError {
+    pub fn no_context() -> Self {
+        ShecvError::Device("no context; call new_context or new_headless_context first".into())
+    }
+}
+
+// Run make with wgpu's validation errors caught instead of panicking, for
+// building shaders and pipelines from files that may be mid-edit.
+pub fn validate<T>(device: &Device, what: &str, make: impl FnOnce() -> T) -> Result<T, ShecvError> {
+    device.push_error_scope(ErrorFilter::Validation);
+    let made = make();
+    match pollster::block_on(device.pop_error_scope()) {
+        Some(e) => Err(ShecvError::Shader(format!("{}: {}", what, e))),
+        None => Ok(made),
+    }
+}
+
+impl fmt::Display for ShecvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShecvError::Surface(e) => write!(f, "surface error: {}", e),
+            ShecvError::Device(message) => write!(f, "device error: {}", message),
+            ShecvError::Io(e) => write!(f, "{}", e),
+            ShecvError::Parse(message) => write!(f, "{}", message),
+            ShecvError::Layout(message) => write!(f, "layout error: {}", message),
+            ShecvError::Store(message) => write!(f, "{}", message),
+            ShecvError::Shader(message) => write!(f, "shader error: {}", message),
+            ShecvError::Script(message) => write!(f, "script error: {}", message),
+        }
+    }
+}
+
+impl Error for ShecvError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ShecvError::Surface(e) => Some(e),
+            ShecvError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ShecvError {
+    fn from(e: io::Error) -> Self {
+        ShecvError::Io(e)
+    }
+}
+
+impl From<SurfaceError> for ShecvError {
+    fn from(e: SurfaceError) -> Self {
+        ShecvError::Surface(e)
+    }
+}
+
+impl From<CreateSurfaceError> for ShecvError {
+    fn from(e: CreateSurfaceError) -> Self {
+        ShecvError::Device(e.to_string())
+    }
+}
+
+impl From<RequestDeviceError> for ShecvError {
+    fn from(e: RequestDeviceError) -> Self {
+        ShecvError::Device(e.to_string())
+    }
+}
+
+impl From<BufferAsyncError> for ShecvError {
+    fn from(e: BufferAsyncError) -> Self {
+        ShecvError::Device(e.to_string())
+    }
+}
+
+impl From<wgpu::rwh::HandleError> for ShecvError {
+    fn from(e: wgpu::rwh::HandleError) -> Self {
+        ShecvError::Device(e.to_string())
+    }
+}
+
+impl From<glyphon::PrepareError> for ShecvError {
+    fn from(e: glyphon::PrepareError) -> Self {
+        ShecvError::Device(format!("text prepare failed: {}", e))
+    }
+}
+
+impl From<glyphon::RenderError> for ShecvError {
+    fn from(e: glyphon::RenderError) -> Self {
+        ShecvError::Device(format!("text render failed: {}", e))
+    }
+}
+
+impl From<image::ImageError> for ShecvError {
+    fn from(e: image::ImageError) -> Self {
+        match e {
+            image::ImageError::IoError(e) => ShecvError::Io(e),
+            e => ShecvError::Parse(e.to_string()),
+        }
+    }
+}
+
+// a lock is only poisoned if another thread panicked while holding it
+impl<T> From<PoisonError<T>> for ShecvError {
+    fn from(_: PoisonError<T>) -> Self {
+        ShecvError::Device("a shared lock was poisoned".into())
+    }
+}
@@ -0,0 +1,202 @@
+use std::{iter::Peekable, str::Chars};
+
+use crate::error::ShecvError;
+
+// Evaluates text typed into a numeric entry.
+// A simple arithmetic expression sets the value outright: "1920/2", "0.5*3".
+// A leading +, * or / makes the edit relative to the current value: "+10",
+// "*2", "/4". A leading - stays absolute so negative values can be typed,
+// "-5" sets -5; subtract with "-=5" instead. "+=", "*=" and "/=" work too,
+// the same as their single character forms. Results that aren't finite,
+// e.g. from dividing by zero, are errors.
+pub fn eval_numeric(input: &str, current: f64) -> Result<f64, ShecvError> {
+    let input = input.trim();
+    let (op, rest) = match input.get(..2) {
+        Some(op @ ("+=" | "-=" | "*=" | "/=")) => (op.chars().next(), &input[2..]),
+        _ => match input.chars().next() {
+            Some(op @ ('+' | '*' | '/')) => (Some(op), &input[1..]),
+            _ => (None, input),
+        },
+    };
+    let result = match op {
+        Some('+') => current + eval(rest)?,
+        Some('-') => current - eval(rest)?,
+        Some('*') => current * eval(rest)?,
+        Some('/') => current / eval(rest)?,
+        _ => eval(input)?,
+    };
+
+    if result.is_finite() {
+        Ok(result)
+    } else {
+        Err(ShecvError::Store(format!(
+            "'{}' does not evaluate to a finite number",
+            input
+        )))
+    }
+}
+
+fn eval(input: &str) -> Result<f64, ShecvError> {
+    let mut chars = input.chars().peekable();
+    let value = expression(&mut chars)?;
+    skip_whitespace(&mut chars);
+    match chars.next() {
+        None => Ok(value),
+        Some(c) => Err(ShecvError::Store(format!(
+            "unexpected '{}' in '{}'",
+            c, input
+        ))),
+    }
+}
+
+// expression := term (('+' | '-') term)*
+fn expression(chars: &mut Peekable<Chars>) -> Result<f64, ShecvError> {
+    let mut value = term(chars)?;
+    loop {
+        skip_whitespace(chars);
+        match chars.peek() {
+            Some('+') => {
+                chars.next();
+                value += term(chars)?;
+            }
+            Some('-') => {
+                chars.next();
+                value -= term(chars)?;
+            }
+            _ => return Ok(value),
+        }
+    }
+}
+
+// term := factor (('*' | '/' | '%') factor)*
+fn term(chars: &mut Peekable<Chars>) -> Result<f64, ShecvError> {
+    let mut value = factor(chars)?;
+    loop {
+        skip_whitespace(chars);
+        match chars.peek() {
+            Some('*') => {
+                chars.next();
+                value *= factor(chars)?;
+            }
+            Some('/') => {
+                chars.next();
+                value /= factor(chars)?;
+            }
+            Some('%') => {
+                chars.next();
+                value %= factor(chars)?;
+            }
+            _ => return Ok(value),
+        }
+    }
+}
+
+// factor := ('-' | '+') factor | '(' expression ')' | number
+fn factor(chars: &mut Peekable<Chars>) -> Result<f64, ShecvError> {
+    skip_whitespace(chars);
+    match chars.peek() {
+        Some('-') => {
+            chars.next();
+            Ok(-factor(chars)?)
+        }
+        Some('+') => {
+            chars.next();
+            factor(chars)
+        }
+        Some('(') => {
+            chars.next();
+            let value = expression(chars)?;
+            skip_whitespace(chars);
+            match chars.next() {
+                Some(')') => Ok(value),
+                _ => Err(ShecvError::Store("missing ')'".into())),
+            }
+        }
+        Some(c) if c.is_ascii_digit() || *c == '.' => {
+            let mut number = String::new();
+            while let Some(c) = chars.peek() {
+                if c.is_ascii_digit() || *c == '.' {
+                    number.push(*c);
+                    chars.next();
+                } else {
+                    break;
+                }
+            }
+            number
+                .parse::<f64>()
+                .map_err(|_| ShecvError::Store(format!("'{}' is not a number", number)))
+        }
+        Some(c) => Err(ShecvError::Store(format!("unexpected '{}'", c))),
+        None => Err(ShecvError::Store("unexpected end of expression".into())),
+    }
+}
+
+fn skip_whitespace(chars: &mut Peekable<Chars>) {
+    while chars.peek().is_some_and(|c| c.is_whitespace()) {
+        chars.next();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval_ok(input: &str, current: f64) -> f64 {
+        eval_numeric(input, current).unwrap()
+    }
+
+    #[test]
+    fn precedence_and_parentheses() {
+        assert_eq!(eval_ok("1 + 2 * 3", 0.0), 7.0);
+        assert_eq!(eval_ok("(1 + 2) * 3", 0.0), 9.0);
+        assert_eq!(eval_ok("10 - 4 - 3", 0.0), 3.0);
+        assert_eq!(eval_ok("1920/2", 0.0), 960.0);
+        assert_eq!(eval_ok("7 % 4 * 2", 0.0), 6.0);
+        assert_eq!(eval_ok("-(2+3)", 0.0), -5.0);
+        assert_eq!(eval_ok("2 * -3", 0.0), -6.0);
+        assert_eq!(eval_ok(" .5 ", 0.0), 0.5);
+    }
+
+    #[test]
+    fn plain_input_is_absolute() {
+        assert_eq!(eval_ok("5", 100.0), 5.0);
+        // a leading minus is a negative value, not a subtraction
+        assert_eq!(eval_ok("-5", 100.0), -5.0);
+        assert_eq!(eval_ok("-(2+3)", 100.0), -5.0);
+    }
+
+    #[test]
+    fn relative_edits() {
+        assert_eq!(eval_ok("+10", 5.0), 15.0);
+        assert_eq!(eval_ok("*2", 5.0), 10.0);
+        assert_eq!(eval_ok("/4", 5.0), 1.25);
+        assert_eq!(eval_ok("+ 2 * 3", 1.0), 7.0);
+        assert_eq!(eval_ok("+-1", 1.0), 0.0);
+        assert_eq!(eval_ok("+=10", 5.0), 15.0);
+        assert_eq!(eval_ok("-= 5", 5.0), 0.0);
+        assert_eq!(eval_ok("*=2", 5.0), 10.0);
+        assert_eq!(eval_ok("/=4", 5.0), 1.25);
+        assert_eq!(eval_ok("+= 2 * 3", 1.0), 7.0);
+        assert_eq!(eval_ok("-=-1", 1.0), 2.0);
+    }
+
+    #[test]
+    fn division_by_zero_is_an_error() {
+        assert!(eval_numeric("1/0", 0.0).is_err());
+        assert!(eval_numeric("/=0", 3.0).is_err());
+        assert!(eval_numeric("/0", 3.0).is_err());
+        assert!(eval_numeric("0/0", 0.0).is_err());
+        assert!(eval_numeric("5 % 0", 0.0).is_err());
+    }
+
+    #[test]
+    fn malformed_input_is_an_error() {
+        assert!(eval_numeric("", 0.0).is_err());
+        assert!(eval_numeric("(1 + 2", 0.0).is_err());
+        assert!(eval_numeric("1 2", 0.0).is_err());
+        assert!(eval_numeric("1..2", 0.0).is_err());
+        assert!(eval_numeric("abc", 0.0).is_err());
+        assert!(eval_numeric("+=", 0.0).is_err());
+        assert!(eval_numeric("*", 0.0).is_err());
+    }
+}
@@ -1,48 +1,115 @@
+use crate::assets::{self, read_to_string};
+use crate::camera::{Camera, WorldCamera};
+use crate::error::ShecvError;
+use crate::layer::{RenderLayer, RenderLayers};
+use crate::line::LineGroup;
+use crate::params::{Globals, ShaderParam, ShaderParams};
+use crate::pipeline::{PipelineCache, PipelineKey};
+use crate::slots::{SlotId, SlotKey, Slots};
 use crate::types::{
     ColorRGBA, ComponentTransform, GeoUniformMatrix, GeoUniformVec2, InstanceBufferManager,
-    RenderPipelineRecord, TextureSheet, TextureSheetDefinition, UNIT_SQUARE_BUFFER_LAYOUT,
-    UNIT_SQUARE_INDICES, UNIT_SQUARE_VERTICES,
+    InstanceShape, NineSlice, PixelRect, SamplerSettings, TextureSheet, TextureSheetDefinition,
+    Vertex, UNIT_SQUARE_INDICES, UNIT_SQUARE_VERTICES,
 };
-use image::ImageReader;
-use image::RgbaImage;
+use image::{
+    imageops::{self, FilterType},
+    ImageReader, Rgba, RgbaImage,
+};
+use log::error;
 use std::{
-    borrow::Cow,
-    error::Error,
-    fs::read_to_string,
-    mem::size_of,
-    path::Path,
+    collections::HashMap,
+    io::{self, Cursor},
+    ops::Range,
     sync::{Arc, Mutex},
+    time::{Duration, Instant},
 };
 use wgpu::{
     util::{BufferInitDescriptor, DeviceExt},
-    BlendState, ColorTargetState, ColorWrites,
+    BlendState,
 };
 
-use glam::{Mat4, UVec2, Vec2};
+use glam::{IVec2, Mat4, UVec2, Vec2, Vec3, Vec4};
 use wgpu::{
-    BindGroup, BindGroupEntry, BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource,
-    BindingType, Buffer, BufferBindingType, BufferSize, BufferUsages, Device, Extent3d, Face,
-    FragmentState, MultisampleState, PrimitiveState, Queue, RenderPipelineDescriptor,
-    ShaderModuleDescriptor, ShaderSource, ShaderStages, TextureDescriptor, TextureFormat,
-    VertexState,
+    BindGroup, BindGroupEntry, BindingResource, Buffer, BufferUsages, Device, Extent3d, Queue,
+    TextureDescriptor, TextureFormat,
 };
 
 pub enum GeoViewType {
     Orthographic,
     Perspective,
+    // a 2D scene seen through GeoManager::world_camera
+    #[allow(dead_code)]
+    World,
+}
+
+// A handle to an instance group in GeoManager::instance_groups.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct GroupId(SlotKey);
+
+impl SlotId for GroupId {
+    fn from_key(key: SlotKey) -> Self {
+        GroupId(key)
+    }
+
+    fn key(self) -> SlotKey {
+        self.0
+    }
+}
+
+// A handle to a render target in GeoManager::render_targets.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct RenderTargetId(SlotKey);
+
+impl SlotId for RenderTargetId {
+    fn from_key(key: SlotKey) -> Self {
+        RenderTargetId(key)
+    }
+
+    fn key(self) -> SlotKey {
+        self.0
+    }
+}
+
+// One instance of a group, from GeoManager::instance_id. Instances are packed
+// for drawing, so removing one moves another into its place; the generation
+// makes ids to either, or to a cleared group, find nothing afterwards.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct InstanceId {
+    pub group: GroupId,
+    pub index: usize,
+    pub generation: u32,
 }
 
 // various things needed to render geometry.
 pub struct GeoInstances {
-    pub render_pipeline_record: RenderPipelineRecord,
+    // the pipeline it draws with, in GeoManager::pipelines
+    pub pipeline: PipelineKey,
+    // the group's own values for its shader, even when sharing a pipeline
+    pub params: ShaderParams,
     pub bind_group: BindGroup,
     pub vertex_buffer: Buffer,
     pub index_buffer: Buffer,
+    pub index_count: u32,
     pub sheet: TextureSheet,
     pub view_type: GeoViewType,
     pub view_matrix_uniform: GeoUniformMatrix,
     pub screen_size_uniform: GeoUniformVec2,
     pub instance_buffer_manager: InstanceBufferManager,
+    // the mesh in its own space, kept for picking
+    pub triangles: Vec<[Vec3; 3]>,
+    // the mesh's box in its own space; each instance's bounds are this box
+    // under its transform
+    pub bounds: [Vec3; 2],
+    // runs of instances left after culling, see GeoManager::cull
+    pub draw_ranges: Vec<Range<u32>>,
+    // hidden groups keep their instances but aren't drawn or picked
+    pub visible: bool,
+    // only the instances in this range are drawn, if set
+    pub draw_range: Option<Range<u32>>,
+    // World for perspective and world groups, Ui for the rest to start with
+    pub layer: RenderLayer,
+    // the render target the group is drawn into, instead of the screen
+    pub target: Option<RenderTargetId>,
 }
 
 impl GeoInstances {
@@ -53,15 +120,181 @@ impl GeoInstances {
         cluster_index: usize,
         sub_index: usize,
         color: ColorRGBA,
-    ) -> usize {
+    ) -> Result<usize, ShecvError> {
         let index = self.instance_buffer_manager.data.len();
         self.instance_buffer_manager.add_instance(
             queue,
             transform,
             self.sheet.cluster_sub_transform(cluster_index, sub_index),
             color,
-        );
-        index
+        )?;
+        Ok(index)
+    }
+
+    // Add an instance showing a sub image of one layer of the group's
+    // texture array, cut up by that layer's sheet definition.
+    #[allow(dead_code)]
+    pub fn add_from_layer(
+        &mut self,
+        queue: Arc<Mutex<Queue>>,
+        transform: ComponentTransform,
+        layer: u32,
+        cluster_index: usize,
+        sub_index: usize,
+        color: ColorRGBA,
+    ) -> Result<usize, ShecvError> {
+        let layer = layer.min(self.sheet.layer_count() - 1);
+        let index = self.add_with_tex_transform(
+            queue.clone(),
+            transform,
+            self.sheet
+                .layer_sub_transform(layer, cluster_index, sub_index),
+            color,
+        )?;
+        self.instance_buffer_manager
+            .set_layer(queue, index, layer)?;
+        Ok(index)
+    }
+
+    // Give the group's shader a named value, at the next free slot of its
+    // params array.
+    #[allow(dead_code)]
+    pub fn declare_param(&mut self, name: &str, initial: ShaderParam) -> Result<usize, ShecvError> {
+        self.params.declare(name, initial)
+    }
+
+    #[allow(dead_code)]
+    pub fn set_param(
+        &mut self,
+        name: &str,
+        value: impl Into<ShaderParam>,
+    ) -> Result<(), ShecvError> {
+        self.params.set(name, value)
+    }
+
+    pub fn set_visible(&mut self, visible: bool) {
+        self.visible = visible;
+    }
+
+    // Draw only some of the instances, e.g. the filled part of a pool, or
+    // all of them again with None.
+    #[allow(dead_code)]
+    pub fn set_draw_range(&mut self, range: Option<Range<u32>>) {
+        self.draw_range = range;
+    }
+
+    // Free the group's buffers, and its texture if it owns one.
+    fn destroy(self) {
+        self.vertex_buffer.destroy();
+        self.index_buffer.destroy();
+        self.instance_buffer_manager.buffer.destroy();
+        self.view_matrix_uniform.buffer.destroy();
+        self.screen_size_uniform.buffer.destroy();
+        self.params.buffer.destroy();
+        if let Some(texture) = &self.sheet.texture {
+            texture.destroy();
+        }
+    }
+
+    // Write every instance to the instance buffer again, e.g. after a resume.
+    pub fn upload_all(&mut self, queue: Arc<Mutex<Queue>>) -> Result<(), ShecvError> {
+        for index in 0..self.instance_buffer_manager.data.len() {
+            self.instance_buffer_manager
+                .write_instance(queue.clone(), index)?;
+        }
+        Ok(())
+    }
+
+    // Add an instance showing an arbitrary region of the texture sheet.
+    pub fn add_with_tex_transform(
+        &mut self,
+        queue: Arc<Mutex<Queue>>,
+        transform: ComponentTransform,
+        tex_transform: ComponentTransform,
+        color: ColorRGBA,
+    ) -> Result<usize, ShecvError> {
+        let index = self.instance_buffer_manager.data.len();
+        self.instance_buffer_manager
+            .add_instance(queue, transform, tex_transform, color)?;
+        Ok(index)
+    }
+
+    // Add the nine instances of a nine-slice panel covering rect.
+    // Returns the index of the first; the rest follow in row order.
+    pub fn add_nine_slice(
+        &mut self,
+        queue: Arc<Mutex<Queue>>,
+        nine_slice: &NineSlice,
+        rect: PixelRect,
+        color: ColorRGBA,
+    ) -> Result<usize, ShecvError> {
+        let first = self.instance_buffer_manager.data.len();
+        for (screen, tex) in nine_slice.slices(&self.sheet, rect) {
+            self.add_with_tex_transform(
+                queue.clone(),
+                ComponentTransform::unit_square_transform_from_pixel_rect(screen),
+                ComponentTransform::tex_transform_from_pixel_rect(tex),
+                color,
+            )?;
+        }
+        Ok(first)
+    }
+
+    // Add a screen space instance with a shape drawn by the shader.
+    pub fn add_shape(
+        &mut self,
+        queue: Arc<Mutex<Queue>>,
+        rect: PixelRect,
+        shape: InstanceShape,
+        color: ColorRGBA,
+    ) -> Result<usize, ShecvError> {
+        let index = self.add_new(
+            queue.clone(),
+            ComponentTransform::unit_square_transform_from_pixel_rect(rect),
+            0,
+            0,
+            color,
+        )?;
+        self.instance_buffer_manager
+            .set_shape(queue, index, shape)?;
+        Ok(index)
+    }
+
+    #[allow(dead_code)]
+    pub fn add_ellipse(
+        &mut self,
+        queue: Arc<Mutex<Queue>>,
+        rect: PixelRect,
+        color: ColorRGBA,
+    ) -> Result<usize, ShecvError> {
+        self.add_shape(queue, rect, InstanceShape::ellipse(rect.wh), color)
+    }
+
+    #[allow(dead_code)]
+    pub fn add_circle(
+        &mut self,
+        queue: Arc<Mutex<Queue>>,
+        center: IVec2,
+        radius: u32,
+        extent: UVec2,
+        color: ColorRGBA,
+    ) -> Result<usize, ShecvError> {
+        let rect = PixelRect {
+            xy: center - IVec2::splat(radius as i32),
+            wh: UVec2::splat(radius * 2),
+            extent,
+        };
+        self.add_ellipse(queue, rect, color)
+    }
+
+    #[allow(dead_code)]
+    pub fn add_capsule(
+        &mut self,
+        queue: Arc<Mutex<Queue>>,
+        rect: PixelRect,
+        color: ColorRGBA,
+    ) -> Result<usize, ShecvError> {
+        self.add_shape(queue, rect, InstanceShape::capsule(rect.wh), color)
     }
 
     pub fn mark_all_for_update(&mut self) {
@@ -76,41 +309,205 @@ impl GeoInstances {
     // }
 }
 
-fn load_texture(
+// Vertex and index data for an instance group. Triangles wind counter-clockwise.
+pub struct Mesh {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u16>,
+}
+
+#[allow(dead_code)]
+impl Mesh {
+    pub fn unit_square() -> Self {
+        Self {
+            vertices: UNIT_SQUARE_VERTICES.to_vec(),
+            indices: UNIT_SQUARE_INDICES.to_vec(),
+        }
+    }
+
+    // Positions, texture coordinates and faces from a Wavefront OBJ file.
+    // Polygons are fanned into triangles; normals and materials are ignored.
+    pub fn load_obj(path: &str) -> Result<Self, ShecvError> {
+        let source = read_to_string(path)?;
+        let mut positions: Vec<Vec3> = vec![];
+        let mut tex_coords: Vec<Vec2> = vec![];
+        let mut mesh = Mesh {
+            vertices: vec![],
+            indices: vec![],
+        };
+        // each distinct position/tex coord pair becomes one vertex
+        let mut seen: HashMap<(usize, Option<usize>), u16> = HashMap::new();
+
+        for (line_number, line) in source.lines().enumerate() {
+            let mut words = line.split_whitespace();
+            let bad_line = || {
+                ShecvError::Parse(format!(
+                    "{}:{}: malformed '{}'",
+                    path,
+                    line_number + 1,
+                    line
+                ))
+            };
+            match words.next() {
+                Some("v") => {
+                    let v: Vec<f32> = words
+                        .take(3)
+                        .map(str::parse)
+                        .collect::<Result<_, _>>()
+                        .map_err(|_| bad_line())?;
+                    if v.len() < 3 {
+                        return Err(bad_line());
+                    }
+                    positions.push(Vec3::new(v[0], v[1], v[2]));
+                }
+                Some("vt") => {
+                    let vt: Vec<f32> = words
+                        .take(2)
+                        .map(str::parse)
+                        .collect::<Result<_, _>>()
+                        .map_err(|_| bad_line())?;
+                    if vt.len() < 2 {
+                        return Err(bad_line());
+                    }
+                    // obj puts v = 0 at the bottom of the image
+                    tex_coords.push(Vec2::new(vt[0], 1.0 - vt[1]));
+                }
+                Some("f") => {
+                    let mut face = vec![];
+                    for word in words {
+                        let mut parts = word.split('/');
+                        let position =
+                            obj_index(parts.next(), positions.len()).ok_or_else(bad_line)?;
+                        let tex_coord = obj_index(parts.next(), tex_coords.len());
+                        let index = match seen.get(&(position, tex_coord)) {
+                            Some(&index) => index,
+                            None => {
+                                let index = u16::try_from(mesh.vertices.len()).map_err(|_| {
+                                    ShecvError::Parse(format!("{} has too many vertices", path))
+                                })?;
+                                mesh.vertices.push(Vertex {
+                                    location: positions[position],
+                                    tex_coords: tex_coord.map_or(Vec2::ZERO, |t| tex_coords[t]),
+                                });
+                                seen.insert((position, tex_coord), index);
+                                index
+                            }
+                        };
+                        face.push(index);
+                    }
+                    if face.len() < 3 {
+                        return Err(bad_line());
+                    }
+                    for i in 1..face.len() - 1 {
+                        mesh.indices.extend([face[0], face[i], face[i + 1]]);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Ok(mesh)
+    }
+}
+
+// OBJ indices start at 1, and negative ones count back from the end.
+fn obj_index(word: Option<&str>, len: usize) -> Option<usize> {
+    let index: i64 = word?.parse().ok()?;
+    let index = match index {
+        i if i > 0 => i as usize - 1,
+        i if i < 0 => len.checked_sub(i.unsigned_abs() as usize)?,
+        _ => return None,
+    };
+    (index < len).then_some(index)
+}
+
+// Decode the image a sheet definition points at. An empty path means the sheet
+// is untextured and gets a 1x1 white pixel, so instance colors come through as-is.
+// A missing image is an error, unless the sheet was made with_fallback.
+pub fn read_sheet_image(sheet_info: &TextureSheetDefinition) -> Result<RgbaImage, ShecvError> {
+    if let Some(image) = &sheet_info.image {
+        return Ok(image.clone());
+    }
+    if sheet_info.path.is_empty() {
+        return Ok(RgbaImage::from_pixel(1, 1, Rgba([255, 255, 255, 255])));
+    }
+
+    let (path, bytes) = match assets::read(&sheet_info.path) {
+        Ok(bytes) => (sheet_info.path.as_str(), bytes),
+        Err(e) if e.kind() == io::ErrorKind::NotFound && sheet_info.fallback => {
+            error!(
+                "texture sheet image '{}' does not exist, using {}",
+                sheet_info.path,
+                assets::FALLBACK_TEXTURE
+            );
+            (
+                assets::FALLBACK_TEXTURE,
+                assets::read(assets::FALLBACK_TEXTURE)?,
+            )
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            return Err(ShecvError::Io(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("texture sheet image '{}' does not exist", sheet_info.path),
+            )))
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    let image = ImageReader::new(Cursor::new(bytes))
+        .with_guessed_format()?
+        .decode()
+        .map_err(|e| ShecvError::Parse(format!("failed to decode '{}': {}", path, e)))?;
+    Ok(image.to_rgba8())
+}
+
+pub fn load_texture(
     device: Arc<Mutex<Device>>,
     queue: Arc<Mutex<Queue>>,
     sheet_info: TextureSheetDefinition,
-) -> Result<TextureSheet, Box<dyn Error>> {
-    let device = device.lock().unwrap();
-    let queue = queue.lock().unwrap();
-    let (image, path): (RgbaImage, String) = {
-        let texture_exists = Path::new(&sheet_info.path).try_exists()?;
-        if texture_exists {
-            let result: (RgbaImage, String) = (
-                ImageReader::open(sheet_info.path.clone())?
-                    .decode()?
-                    .to_rgba8(),
-                sheet_info.path.clone(),
-            );
-            result
-        } else {
-            let result: (RgbaImage, String) = (
-                image::load_from_memory(include_bytes!("../res/px.png"))?.to_rgba8(),
-                "../res/px.png".to_string(),
-            );
-            result
-        }
+) -> Result<TextureSheet, ShecvError> {
+    load_texture_array(device, queue, vec![sheet_info])
+}
+
+// A texture with a layer per sheet, so a group can draw from all of them at
+// once. Layers are as big as the biggest image, the others sitting in their
+// top left corners.
+pub fn load_texture_array(
+    device: Arc<Mutex<Device>>,
+    queue: Arc<Mutex<Queue>>,
+    mut sheet_infos: Vec<TextureSheetDefinition>,
+) -> Result<TextureSheet, ShecvError> {
+    if sheet_infos.is_empty() {
+        return Err(ShecvError::Parse("texture array has no sheets".into()));
+    }
+    let images = sheet_infos
+        .iter()
+        .map(read_sheet_image)
+        .collect::<Result<Vec<_>, _>>()?;
+    let path = match sheet_infos[0].path.as_str() {
+        "" if sheet_infos[0].image.is_some() => "generated texture".to_string(),
+        "" => "default white texture".to_string(),
+        path if sheet_infos.len() > 1 => format!("{} and {} more", path, sheet_infos.len() - 1),
+        path => path.to_string(),
     };
 
-    let dimensions = image.dimensions();
-    let extent = Extent3d {
-        width: dimensions.0,
-        height: dimensions.1,
-        depth_or_array_layers: 1,
+    let device = device.lock()?;
+    let queue = queue.lock()?;
+
+    let dimensions = images.iter().fold((1, 1), |(w, h), image| {
+        (w.max(image.width()), h.max(image.height()))
+    });
+    // halving down to a single texel
+    let mip_level_count = match sheet_infos[0].mipmaps {
+        true => u32::BITS - dimensions.0.max(dimensions.1).leading_zeros(),
+        false => 1,
     };
     let texture = device.create_texture(&TextureDescriptor {
-        size: extent,
-        mip_level_count: 1,
+        size: Extent3d {
+            width: dimensions.0,
+            height: dimensions.1,
+            depth_or_array_layers: images.len() as u32,
+        },
+        mip_level_count,
         sample_count: 1,
         dimension: wgpu::TextureDimension::D2,
         format: wgpu::TextureFormat::Rgba8UnormSrgb,
@@ -118,49 +515,123 @@ fn load_texture(
         label: Some(&path),
         view_formats: &[],
     });
-    queue.write_texture(
-        wgpu::ImageCopyTexture {
-            texture: &texture,
-            mip_level: 0,
-            origin: wgpu::Origin3d::ZERO,
-            aspect: wgpu::TextureAspect::All,
-        },
-        &image,
-        wgpu::ImageDataLayout {
-            offset: 0,
-            bytes_per_row: Some(4 * dimensions.0),
-            rows_per_image: Some(dimensions.1),
-        },
-        extent,
-    );
-    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
-    let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
-        address_mode_u: wgpu::AddressMode::Repeat,
-        address_mode_v: wgpu::AddressMode::Repeat,
-        address_mode_w: wgpu::AddressMode::Repeat,
-        mag_filter: wgpu::FilterMode::Nearest,
-        min_filter: wgpu::FilterMode::Nearest,
-        mipmap_filter: wgpu::FilterMode::Nearest,
+    for (layer, image) in images.iter().enumerate() {
+        write_texture_layer(&queue, &texture, image, 0, layer as u32);
+        if mip_level_count == 1 {
+            continue;
+        }
+        // each level from the one before, the padding around smaller
+        // images included
+        let mut level = RgbaImage::new(dimensions.0, dimensions.1);
+        imageops::replace(&mut level, image, 0, 0);
+        for mip_level in 1..mip_level_count {
+            let width = (dimensions.0 >> mip_level).max(1);
+            let height = (dimensions.1 >> mip_level).max(1);
+            level = imageops::resize(&level, width, height, FilterType::Triangle);
+            write_texture_layer(&queue, &texture, &level, mip_level, layer as u32);
+        }
+    }
+    let view = texture.create_view(&wgpu::TextureViewDescriptor {
+        dimension: Some(wgpu::TextureViewDimension::D2),
+        array_layer_count: Some(1),
         ..Default::default()
     });
+    let array_view = texture.create_view(&wgpu::TextureViewDescriptor {
+        dimension: Some(wgpu::TextureViewDimension::D2Array),
+        ..Default::default()
+    });
+    // every layer samples the same way, as the first asks
+    let sampler = device.create_sampler(&sheet_infos[0].sampler.to_descriptor());
 
+    let sheet_info = sheet_infos.remove(0);
     Ok(TextureSheet {
         sheet_info,
+        more_layers: sheet_infos,
         dimensions: UVec2::new(dimensions.0, dimensions.1),
-        texture,
+        texture: Some(texture),
         sampler,
         view,
+        array_view,
     })
 }
 
+fn write_texture_layer(
+    queue: &Queue,
+    texture: &wgpu::Texture,
+    image: &RgbaImage,
+    mip_level: u32,
+    layer: u32,
+) {
+    queue.write_texture(
+        wgpu::ImageCopyTexture {
+            texture,
+            mip_level,
+            origin: wgpu::Origin3d {
+                x: 0,
+                y: 0,
+                z: layer,
+            },
+            aspect: wgpu::TextureAspect::All,
+        },
+        image,
+        wgpu::ImageDataLayout {
+            offset: 0,
+            bytes_per_row: Some(4 * image.width()),
+            rows_per_image: Some(image.height()),
+        },
+        Extent3d {
+            width: image.width(),
+            height: image.height(),
+            depth_or_array_layers: 1,
+        },
+    );
+}
+
+// An offscreen texture that some groups are drawn into instead of the screen,
+// see Context::create_render_target. Other groups show it through a sheet
+// from GeoManager::target_sheet.
+pub struct RenderTarget {
+    pub texture: wgpu::Texture,
+    pub view: wgpu::TextureView,
+    pub width: u32,
+    pub height: u32,
+    pub clear: ColorRGBA,
+}
+
 pub struct GeoManager {
     pub device: Arc<Mutex<Device>>,
     pub queue: Arc<Mutex<Queue>>,
     #[allow(dead_code)]
     pub format: TextureFormat,
     pub view_ortho: Mat4,
-    pub view_persp: Mat4,
-    pub instance_groups: Vec<GeoInstances>,
+    // the view of every perspective group, written to them each frame
+    pub camera: Camera,
+    // the view of every world group
+    pub world_camera: WorldCamera,
+    // what the groups were last given, to skip unchanged frames
+    camera_matrix: Mat4,
+    world_matrix: Mat4,
+    // removing a group leaves the others' ids working
+    pub instance_groups: Slots<GroupId, GeoInstances>,
+    pub line_groups: Vec<LineGroup>,
+    // lines are positioned in logical pixels, see Context::scale_factor
+    pub scale_factor: f32,
+    // from the last GeoManager::cull
+    pub cull_stats: CullStats,
+    pub layers: RenderLayers,
+    // removing a target sends its groups back to the screen
+    pub render_targets: Slots<RenderTargetId, RenderTarget>,
+    // shared by groups drawing the same shader the same way
+    pub pipelines: PipelineCache,
+    pub globals: Globals,
+    globals_buffer: Buffer,
+    start: Instant,
+}
+
+#[derive(Default, Copy, Clone)]
+pub struct CullStats {
+    pub drawn: u32,
+    pub culled: u32,
 }
 
 impl GeoManager {
@@ -168,33 +639,101 @@ impl GeoManager {
         device: Arc<Mutex<Device>>,
         queue: Arc<Mutex<Queue>>,
         format: TextureFormat,
-    ) -> Self {
-        Self {
+    ) -> Result<Self, ShecvError> {
+        let (globals_buffer, pipelines) = {
+            let device = device.lock()?;
+            let globals_buffer = device.create_buffer_init(&BufferInitDescriptor {
+                label: Some("globals"),
+                contents: bytemuck::cast_slice(&[Globals::default()]),
+                usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            });
+            (globals_buffer, PipelineCache::new(&device))
+        };
+        Ok(Self {
             device,
             queue,
             format,
             view_ortho: Mat4::orthographic_lh(-1.0, 1.0, -1.0, 1.0, -1.0, 1.0),
-            view_persp: Mat4::perspective_lh(2.0, 4.0 / 3.0, -1.0, 1000.0),
-            instance_groups: vec![],
-        }
+            camera: Camera::default(),
+            camera_matrix: Camera::default().view_projection(),
+            world_camera: WorldCamera::default(),
+            world_matrix: WorldCamera::default().matrix(),
+            instance_groups: Slots::default(),
+            scale_factor: 1.0,
+            line_groups: vec![],
+            cull_stats: CullStats::default(),
+            layers: RenderLayers::default(),
+            render_targets: Slots::default(),
+            pipelines,
+            globals: Globals::default(),
+            globals_buffer,
+            start: Instant::now(),
+        })
+    }
+
+    // Lines are drawn after every instance group, in pixel coordinates.
+    #[allow(dead_code)]
+    pub fn new_line_group(
+        &mut self,
+        max_segments: usize,
+        format: TextureFormat,
+        wh: (u32, u32),
+        shader_path: &str,
+    ) -> Result<usize, ShecvError> {
+        let device = self.device.lock()?;
+        let wh = (
+            (wh.0 as f32 / self.scale_factor).round() as u32,
+            (wh.1 as f32 / self.scale_factor).round() as u32,
+        );
+        let line_group = LineGroup::new(
+            &device,
+            max_segments,
+            format,
+            wh,
+            shader_path,
+            &self.globals_buffer,
+        )?;
+        self.line_groups.push(line_group);
+        Ok(self.line_groups.len() - 1)
+    }
+
+    // Remove a group and free what it holds on the gpu. Anything still
+    // holding its id finds nothing there.
+    pub fn remove_group(&mut self, group: GroupId) -> Result<(), ShecvError> {
+        let group = self
+            .instance_groups
+            .remove(group)
+            .ok_or_else(|| ShecvError::Layout(format!("no render group {:?}", group)))?;
+        group.destroy();
+        Ok(())
     }
 
-    pub fn num_instances(&self, group_index: usize) -> u32 {
-        self.instance_groups[group_index]
+    #[allow(dead_code)]
+    pub fn num_instances(&self, group: GroupId) -> u32 {
+        self.instance_groups[group]
             .instance_buffer_manager
             .data
             .len() as u32
     }
 
-    pub fn update_view(&mut self, queue: Arc<Mutex<Queue>>, width: u32, height: u32) {
-        let queue = queue.lock().unwrap();
+    pub fn update_view(
+        &mut self,
+        queue: Arc<Mutex<Queue>>,
+        width: u32,
+        height: u32,
+    ) -> Result<(), ShecvError> {
+        let queue = queue.lock()?;
         // let _view_matrix = Mat4::orthographic_lh(-1.0, 1.0, -1.0, 1.0, -1.0, 1.0);
-        self.view_persp = Mat4::perspective_rh(2.0, width as f32 / height as f32, 0.0, 100.0);
+        self.camera.set_aspect(width, height);
+        self.camera_matrix = self.camera.view_projection();
         let screen_size = Vec2::new(width as f32, height as f32);
+        self.world_camera.screen_size = screen_size / self.scale_factor;
+        self.world_matrix = self.world_camera.matrix();
         for ig in self.instance_groups.iter_mut() {
             ig.view_matrix_uniform.matrix = match ig.view_type {
                 GeoViewType::Orthographic => self.view_ortho,
-                GeoViewType::Perspective => self.view_persp,
+                GeoViewType::Perspective => self.camera_matrix,
+                GeoViewType::World => self.world_matrix,
             };
             queue.write_buffer(
                 &ig.view_matrix_uniform.buffer,
@@ -208,56 +747,90 @@ impl GeoManager {
                 bytemuck::cast_slice(&[screen_size]),
             );
         }
+        let logical_size = screen_size / self.scale_factor;
+        for lg in self.line_groups.iter_mut() {
+            lg.screen_size_uniform.vec = logical_size;
+            queue.write_buffer(
+                &lg.screen_size_uniform.buffer,
+                0,
+                bytemuck::cast_slice(&[logical_size]),
+            );
+        }
+        Ok(())
+    }
+
+    // Advance time and write the globals every pipeline reads. resolution is
+    // in physical pixels.
+    pub fn update_globals(
+        &mut self,
+        queue: Arc<Mutex<Queue>>,
+        dt: Duration,
+        resolution: Vec2,
+    ) -> Result<(), ShecvError> {
+        self.globals.time = self.start.elapsed().as_secs_f32();
+        self.globals.dt = dt.as_secs_f32();
+        self.globals.resolution = resolution;
+        queue.lock()?.write_buffer(
+            &self.globals_buffer,
+            0,
+            bytemuck::cast_slice(&[self.globals]),
+        );
+        Ok(())
+    }
+
+    // Write shader params that changed, and time params, for every group.
+    pub fn upload_params(&mut self, queue: Arc<Mutex<Queue>>) -> Result<(), ShecvError> {
+        let queue = queue.lock()?;
+        for ig in self.instance_groups.iter_mut() {
+            ig.params.upload(&queue);
+        }
+        for lg in self.line_groups.iter_mut() {
+            lg.params.upload(&queue);
+        }
+        Ok(())
+    }
+
+    // Writes the cameras to their groups if they moved since last time.
+    pub fn update_camera(&mut self, queue: Arc<Mutex<Queue>>) -> Result<(), ShecvError> {
+        let camera_matrix = self.camera.view_projection();
+        let world_matrix = self.world_camera.matrix();
+        if camera_matrix == self.camera_matrix && world_matrix == self.world_matrix {
+            return Ok(());
+        }
+        self.camera_matrix = camera_matrix;
+        self.world_matrix = world_matrix;
+        let queue = queue.lock()?;
+        for ig in self.instance_groups.iter_mut() {
+            let matrix = match ig.view_type {
+                GeoViewType::Orthographic => continue,
+                GeoViewType::Perspective => camera_matrix,
+                GeoViewType::World => world_matrix,
+            };
+            if ig.view_matrix_uniform.matrix == matrix {
+                continue;
+            }
+            ig.view_matrix_uniform.matrix = matrix;
+            queue.write_buffer(
+                &ig.view_matrix_uniform.buffer,
+                0,
+                bytemuck::cast_slice(&[matrix]),
+            );
+        }
+        Ok(())
     }
 
     pub fn reload_shader(
         &mut self,
         device: Arc<Mutex<wgpu::Device>>,
         shader_path: &str,
-    ) -> Result<(), Box<dyn Error>> {
-        let device = device.lock().unwrap();
-
-        // for every instance group...
-        for ig in self.instance_groups.iter_mut() {
-            // does the instance group use this shader path?
-            if ig.render_pipeline_record.shader_path == *shader_path.to_string() {
-                // if so, rebuild the shader.
-                ig.render_pipeline_record.shader_module =
-                    device.create_shader_module(ShaderModuleDescriptor {
-                        label: Some(&*format!("shader {}", shader_path)),
-                        source: ShaderSource::Wgsl(Cow::Borrowed(&*read_to_string(shader_path)?)),
-                    });
-
-                // and rebuild the render pipeline.
-                ig.render_pipeline_record.render_pipeline =
-                    device.create_render_pipeline(&RenderPipelineDescriptor {
-                        label: Some(&*format!("pipeline {}", shader_path)),
-                        layout: Some(&ig.render_pipeline_record.pipeline_layout),
-                        vertex: VertexState {
-                            module: &ig.render_pipeline_record.shader_module,
-                            entry_point: "vs_main",
-                            buffers: &UNIT_SQUARE_BUFFER_LAYOUT,
-                            compilation_options: Default::default(),
-                        },
-                        fragment: Some(FragmentState {
-                            module: &ig.render_pipeline_record.shader_module,
-                            entry_point: "fs_main",
-                            targets: &[Some(ColorTargetState {
-                                format: ig.render_pipeline_record.format,
-                                blend: Some(BlendState::PREMULTIPLIED_ALPHA_BLENDING),
-                                write_mask: ColorWrites::ALL,
-                            })],
-                            compilation_options: Default::default(),
-                        }),
-                        primitive: PrimitiveState {
-                            cull_mode: None,
-                            ..Default::default()
-                        },
-                        depth_stencil: None,
-                        multisample: MultisampleState::default(),
-                        multiview: None,
-                        cache: None,
-                    });
+    ) -> Result<(), ShecvError> {
+        let device = device.lock()?;
+        // every group drawing with the shader shares its pipelines
+        self.pipelines
+            .reload_shader(&device, shader_path, &self.layers)?;
+        for lg in self.line_groups.iter_mut() {
+            if lg.render_pipeline_record.shader_path == shader_path {
+                lg.reload_shader(&device)?;
             }
         }
         Ok(())
@@ -271,59 +844,132 @@ impl GeoManager {
         wh: (u32, u32),
         sheet_info: TextureSheetDefinition,
         shader_path: &str,
-    ) -> Result<usize, Box<dyn Error>> {
-        let width = wh.0;
-        let height = wh.1;
+    ) -> Result<GroupId, ShecvError> {
+        self.new_mesh(
+            Mesh::unit_square(),
+            view_type,
+            max_instances,
+            format,
+            wh,
+            sheet_info,
+            shader_path,
+        )
+    }
+
+    // A unit square group drawing from a layer per sheet, so sprites from
+    // different sheets batch into one draw. Instances pick their layer with
+    // GeoInstances::add_from_layer.
+    #[allow(dead_code)]
+    pub fn new_unit_square_array(
+        &mut self,
+        view_type: GeoViewType,
+        max_instances: usize,
+        format: TextureFormat,
+        wh: (u32, u32),
+        sheet_infos: Vec<TextureSheetDefinition>,
+        shader_path: &str,
+    ) -> Result<GroupId, ShecvError> {
+        let sheet = load_texture_array(self.device.clone(), self.queue.clone(), sheet_infos)?;
+        self.new_mesh_with_sheet(
+            Mesh::unit_square(),
+            view_type,
+            max_instances,
+            format,
+            wh,
+            sheet,
+            shader_path,
+        )
+    }
+
+    // An instance group drawing any mesh, with the same instancing, texture sheet
+    // and shader reloading as the unit square.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_mesh(
+        &mut self,
+        mesh: Mesh,
+        view_type: GeoViewType,
+        max_instances: usize,
+        format: TextureFormat,
+        wh: (u32, u32),
+        sheet_info: TextureSheetDefinition,
+        shader_path: &str,
+    ) -> Result<GroupId, ShecvError> {
+        if mesh.indices.is_empty() {
+            return Err(ShecvError::Parse("mesh has no triangles".into()));
+        }
         // prepare texture sheet data
         let sheet = load_texture(self.device.clone(), self.queue.clone(), sheet_info)?;
+        self.new_mesh_with_sheet(
+            mesh,
+            view_type,
+            max_instances,
+            format,
+            wh,
+            sheet,
+            shader_path,
+        )
+    }
 
-        let device = self.device.lock().unwrap();
+    // Like new_mesh, with a sheet that's already made, e.g. one showing a
+    // render target.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_mesh_with_sheet(
+        &mut self,
+        mesh: Mesh,
+        view_type: GeoViewType,
+        max_instances: usize,
+        format: TextureFormat,
+        wh: (u32, u32),
+        sheet: TextureSheet,
+        shader_path: &str,
+    ) -> Result<GroupId, ShecvError> {
+        if mesh.indices.is_empty() {
+            return Err(ShecvError::Parse("mesh has no triangles".into()));
+        }
+        let width = wh.0;
+        let height = wh.1;
+        let device = self.device.lock()?;
 
-        // compile shader code
-        let shader_module = device.create_shader_module(ShaderModuleDescriptor {
-            label: Some(shader_path),
-            source: ShaderSource::Wgsl(Cow::Borrowed(&*read_to_string(shader_path)?)),
-        });
+        let layer = match view_type {
+            GeoViewType::Orthographic => RenderLayer::Ui,
+            GeoViewType::Perspective | GeoViewType::World => RenderLayer::World,
+        };
+
+        // compile the shader and build the pipeline, unless another group
+        // already did
+        let pipeline = PipelineKey {
+            shader_path: shader_path.to_string(),
+            format,
+            layer,
+        };
+        self.pipelines.prepare(&device, &pipeline, &self.layers)?;
 
         // vertex and index buffers
         let vertex_buffer = device.create_buffer_init(&BufferInitDescriptor {
-            label: Some("unit square vertices"),
-            contents: bytemuck::cast_slice(&UNIT_SQUARE_VERTICES),
+            label: Some("mesh vertices"),
+            contents: bytemuck::cast_slice(&mesh.vertices),
             usage: BufferUsages::VERTEX,
         });
         let index_buffer = device.create_buffer_init(&BufferInitDescriptor {
-            label: Some("unit square indices"),
-            contents: bytemuck::cast_slice(&UNIT_SQUARE_INDICES),
+            label: Some("mesh indices"),
+            contents: bytemuck::cast_slice(&mesh.indices),
             usage: BufferUsages::INDEX,
         });
 
         // view matrix uniform setup
-        let (_view_matrix, view_matrix_uniform) = {
-            match view_type {
-                GeoViewType::Orthographic => {
-                    let view_matrix = Mat4::orthographic_lh(-1.0, 1.0, -1.0, 1.0, -1.0, 1.0);
-                    let view_matrix_uniform = GeoUniformMatrix {
-                        matrix: view_matrix,
-                        buffer: device.create_buffer_init(&BufferInitDescriptor {
-                            label: Some("unit square view_matrix"),
-                            contents: bytemuck::cast_slice(&[view_matrix]),
-                            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
-                        }),
-                    };
-                    (view_matrix, view_matrix_uniform)
-                }
-                GeoViewType::Perspective => {
-                    let view_matrix = Mat4::perspective_rh(2.0, 4.0 / 3.0, 0.01, 100.0);
-                    let view_matrix_uniform = GeoUniformMatrix {
-                        matrix: view_matrix,
-                        buffer: device.create_buffer_init(&BufferInitDescriptor {
-                            label: Some("unit square view_matrix"),
-                            contents: bytemuck::cast_slice(&[view_matrix]),
-                            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
-                        }),
-                    };
-                    (view_matrix, view_matrix_uniform)
-                }
+        let view_matrix_uniform = {
+            let view_matrix = match view_type {
+                GeoViewType::Orthographic => Mat4::orthographic_lh(-1.0, 1.0, -1.0, 1.0, -1.0, 1.0),
+                GeoViewType::Perspective => self.camera_matrix,
+                GeoViewType::World => self.world_matrix,
+            };
+            GeoUniformMatrix {
+                matrix: view_matrix,
+                buffer: device.create_buffer_init(&BufferInitDescriptor {
+                    label: Some("unit square view_matrix"),
+                    contents: bytemuck::cast_slice(&[view_matrix]),
+                    usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+                }),
             }
         };
 
@@ -341,50 +987,10 @@ impl GeoManager {
             }),
         };
 
-        // bind group layout and bind group creation; pipeline layout
-        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
-            label: None,
-            entries: &[
-                BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: ShaderStages::VERTEX,
-                    ty: BindingType::Buffer {
-                        ty: BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: BufferSize::new((size_of::<Mat4>()) as u64),
-                    },
-                    count: None,
-                },
-                BindGroupLayoutEntry {
-                    binding: 1,
-                    visibility: ShaderStages::FRAGMENT,
-                    ty: BindingType::Buffer {
-                        ty: BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: BufferSize::new((size_of::<Vec2>()) as u64),
-                    },
-                    count: None,
-                },
-                BindGroupLayoutEntry {
-                    binding: 2,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Texture {
-                        multisampled: false,
-                        view_dimension: wgpu::TextureViewDimension::D2,
-                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                    },
-                    count: None,
-                },
-                BindGroupLayoutEntry {
-                    binding: 3,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                    count: None,
-                },
-            ],
-        });
+        // bind group creation, in the layout every group shares
+        let params = ShaderParams::new(&device);
         let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &bind_group_layout,
+            layout: &self.pipelines.bind_group_layout,
             entries: &[
                 BindGroupEntry {
                     binding: 0,
@@ -402,67 +1008,321 @@ impl GeoManager {
                     binding: 3,
                     resource: BindingResource::Sampler(&sheet.sampler),
                 },
+                BindGroupEntry {
+                    binding: 4,
+                    resource: params.buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 5,
+                    resource: self.globals_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 6,
+                    resource: BindingResource::TextureView(&sheet.array_view),
+                },
             ],
             label: None,
         });
-        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: None,
-            bind_group_layouts: &[&bind_group_layout],
-            push_constant_ranges: &[],
-        });
-
-        // render pipeline itself, with necessary components for reconstruction retained.
-        let render_pipeline_record = RenderPipelineRecord {
-            render_pipeline: device.create_render_pipeline(&RenderPipelineDescriptor {
-                label: Some("unit square pipeline"),
-                layout: Some(&pipeline_layout),
-                vertex: VertexState {
-                    module: &shader_module,
-                    entry_point: "vs_main",
-                    buffers: &UNIT_SQUARE_BUFFER_LAYOUT,
-                    compilation_options: Default::default(),
-                },
-                fragment: Some(FragmentState {
-                    module: &shader_module,
-                    entry_point: "fs_main",
-                    targets: &[Some(ColorTargetState {
-                        format,
-                        blend: Some(BlendState::PREMULTIPLIED_ALPHA_BLENDING),
-                        write_mask: ColorWrites::ALL,
-                    })],
-                    compilation_options: Default::default(),
-                }),
-                primitive: PrimitiveState {
-                    cull_mode: Some(Face::Back),
-                    ..Default::default()
-                },
-                depth_stencil: None,
-                multisample: MultisampleState::default(),
-                multiview: None,
-                cache: None,
-            }),
-            pipeline_layout,
-            shader_module,
-            shader_path: shader_path.to_string(),
-            format,
-        };
-
         // drop device here because it's used to make the instance buffer below.
         drop(device);
 
-        let index = self.instance_groups.len();
-        self.instance_groups.push(GeoInstances {
-            render_pipeline_record,
+        let triangles = mesh
+            .indices
+            .chunks_exact(3)
+            .map(|face| [0, 1, 2].map(|i| mesh.vertices[face[i] as usize].location))
+            .collect();
+        let bounds = mesh.vertices.iter().fold(
+            [Vec3::splat(f32::MAX), Vec3::splat(f32::MIN)],
+            |[min, max], vertex| [min.min(vertex.location), max.max(vertex.location)],
+        );
+
+        let index = self.instance_groups.insert(GeoInstances {
+            pipeline,
+            params,
             bind_group,
             vertex_buffer,
             index_buffer,
+            index_count: mesh.indices.len() as u32,
             sheet,
             view_type,
             view_matrix_uniform,
             screen_size_uniform,
-            instance_buffer_manager: InstanceBufferManager::new(max_instances, self.device.clone()),
+            instance_buffer_manager: InstanceBufferManager::new(
+                max_instances,
+                self.device.clone(),
+            )?,
+            triangles,
+            bounds,
+            draw_ranges: vec![],
+            visible: true,
+            draw_range: None,
+            layer,
+            target: None,
         });
 
         Ok(index)
     }
+
+    // Targets are drawn in the format groups are made with, so pass the
+    // same one.
+    pub fn new_render_target(
+        &mut self,
+        width: u32,
+        height: u32,
+        format: TextureFormat,
+    ) -> Result<RenderTargetId, ShecvError> {
+        let device = self.device.lock()?;
+        let texture = device.create_texture(&TextureDescriptor {
+            label: Some("render target"),
+            size: Extent3d {
+                width: width.max(1),
+                height: height.max(1),
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        Ok(self.render_targets.insert(RenderTarget {
+            texture,
+            view,
+            width: width.max(1),
+            height: height.max(1),
+            clear: ColorRGBA::new(0.0, 0.0, 0.0, 0.0),
+        }))
+    }
+
+    // Groups drawn into the target go back to the screen. Sheets already made
+    // from it keep showing its last frame.
+    #[allow(dead_code)]
+    pub fn remove_render_target(&mut self, target: RenderTargetId) -> Option<RenderTarget> {
+        for group in self.instance_groups.iter_mut() {
+            if group.target == Some(target) {
+                group.target = None;
+            }
+        }
+        self.render_targets.remove(target)
+    }
+
+    // A sheet of one cell covering the whole target, smoothly filtered.
+    #[allow(dead_code)]
+    pub fn target_sheet(&self, target: RenderTargetId) -> Result<TextureSheet, ShecvError> {
+        let target = self
+            .render_targets
+            .get(target)
+            .ok_or_else(|| ShecvError::Device(format!("no render target {:?}", target)))?;
+        let device = self.device.lock()?;
+        let mut sheet_info = TextureSheetDefinition::none().with_sampler(SamplerSettings::smooth());
+        let sampler = device.create_sampler(&sheet_info.sampler.to_descriptor());
+        let dimensions = UVec2::new(target.width, target.height);
+        sheet_info.clusters[0].sub_size = dimensions;
+        Ok(TextureSheet {
+            sheet_info,
+            more_layers: vec![],
+            dimensions,
+            texture: None,
+            sampler,
+            view: target
+                .texture
+                .create_view(&wgpu::TextureViewDescriptor::default()),
+            array_view: target.texture.create_view(&wgpu::TextureViewDescriptor {
+                dimension: Some(wgpu::TextureViewDimension::D2Array),
+                ..Default::default()
+            }),
+        })
+    }
+
+    // Draw a group into a render target instead of the screen, or back on
+    // the screen with None.
+    #[allow(dead_code)]
+    pub fn set_group_target(&mut self, group: GroupId, target: Option<RenderTargetId>) {
+        self.instance_groups[group].target = target;
+    }
+
+    // The id of the instance now at index in group, None if there's none.
+    #[allow(dead_code)]
+    pub fn instance_id(&self, group: GroupId, index: usize) -> Option<InstanceId> {
+        let manager = &self.instance_groups.get(group)?.instance_buffer_manager;
+        (index < manager.data.len()).then(|| InstanceId {
+            group,
+            index,
+            generation: manager.generation(index),
+        })
+    }
+
+    // The instances holding id's instance, None if it's been removed or moved.
+    pub fn instance_manager(&mut self, id: InstanceId) -> Option<&mut InstanceBufferManager> {
+        let manager = &mut self
+            .instance_groups
+            .get_mut(id.group)?
+            .instance_buffer_manager;
+        manager.is_live(id.index, id.generation).then_some(manager)
+    }
+
+    // Move a group to another layer, switching it to the pipeline drawn with
+    // that layer's blending.
+    #[allow(dead_code)]
+    pub fn set_group_layer(
+        &mut self,
+        group: GroupId,
+        layer: RenderLayer,
+    ) -> Result<(), ShecvError> {
+        let group = &mut self.instance_groups[group];
+        let pipeline = PipelineKey {
+            layer,
+            ..group.pipeline.clone()
+        };
+        let device = self.device.lock()?;
+        self.pipelines.prepare(&device, &pipeline, &self.layers)?;
+        group.layer = layer;
+        group.pipeline = pipeline;
+        Ok(())
+    }
+
+    // Change how a layer blends, rebuilding the pipelines of its groups.
+    #[allow(dead_code)]
+    pub fn set_layer_blend(
+        &mut self,
+        layer: RenderLayer,
+        blend: BlendState,
+    ) -> Result<(), ShecvError> {
+        self.layers.get_mut(layer).blend = blend;
+        let device = self.device.lock()?;
+        self.pipelines.rebuild_layer(&device, layer, blend);
+        Ok(())
+    }
+
+    // Work out which instances of each group can be seen, as runs of instance
+    // indices to draw. An instance is culled when every corner of its bounds
+    // is past the same edge of the view, or in front of or behind it.
+    pub fn cull(&mut self) {
+        let mut stats = CullStats::default();
+        for group in self.instance_groups.iter_mut() {
+            group.draw_ranges.clear();
+            if !group.visible {
+                continue;
+            }
+            let [min, max] = group.bounds;
+            let corners = [0, 1, 2, 3, 4, 5, 6, 7].map(|i| {
+                Vec4::new(
+                    if i & 1 == 0 { min.x } else { max.x },
+                    if i & 2 == 0 { min.y } else { max.y },
+                    if i & 4 == 0 { min.z } else { max.z },
+                    1.0,
+                )
+            });
+            let view = group.view_matrix_uniform.matrix;
+            let instances = &group.instance_buffer_manager.data;
+            let count = instances.len() as u32;
+            let range = group.draw_range.clone().unwrap_or(0..count);
+            for index in range.start.min(count)..range.end.min(count) {
+                let instance = &instances[index as usize];
+                let to_clip = view * instance.transform.to_mat4();
+                if outside_view(corners.map(|corner| to_clip * corner)) {
+                    stats.culled += 1;
+                    continue;
+                }
+                stats.drawn += 1;
+                match group.draw_ranges.last_mut() {
+                    Some(range) if range.end == index => range.end += 1,
+                    _ => group.draw_ranges.push(index..index + 1),
+                }
+            }
+        }
+        self.cull_stats = stats;
+    }
+
+    // The topmost instance under screen_pos, in logical pixels. Later groups
+    // and instances draw over earlier ones, so they are tried first.
+    #[allow(dead_code)]
+    pub fn pick(&self, screen_pos: Vec2) -> Option<InstanceId> {
+        self.instance_groups.ids().rev().find_map(|group| {
+            self.pick_instance(group, screen_pos)
+                .and_then(|index| self.instance_id(group, index))
+        })
+    }
+
+    // Like pick, but only trying the given groups.
+    #[allow(dead_code)]
+    pub fn pick_in(&self, groups: &[GroupId], screen_pos: Vec2) -> Option<InstanceId> {
+        let mut groups = groups.to_vec();
+        // in the order they're drawn
+        groups.sort_unstable_by_key(|group| group.0.index);
+        groups.into_iter().rev().find_map(|group| {
+            self.pick_instance(group, screen_pos)
+                .and_then(|index| self.instance_id(group, index))
+        })
+    }
+
+    // Instances placed from a pixel rect are hit inside that rect. Instances in
+    // world groups are hit when screen_pos, taken into the world, falls in one
+    // of their triangles. Others are projected like the vertex shader does.
+    fn pick_instance(&self, group: GroupId, screen_pos: Vec2) -> Option<usize> {
+        let group = self.instance_groups.get(group)?;
+        if !group.visible {
+            return None;
+        }
+        if let GeoViewType::World = group.view_type {
+            let world = self.world_camera.screen_to_world(screen_pos);
+            let instances = &group.instance_buffer_manager.data;
+            return (0..instances.len()).rev().find(|&index| {
+                let to_world = instances[index].transform.to_mat4();
+                group.triangles.iter().any(|triangle| {
+                    let corners =
+                        triangle.map(|corner| to_world.transform_point3(corner).truncate());
+                    in_triangle(world, corners)
+                })
+            });
+        }
+        let screen_size = group.screen_size_uniform.vec / self.scale_factor;
+        if screen_size.x <= 0.0 || screen_size.y <= 0.0 {
+            return None;
+        }
+        let ndc = Vec2::new(
+            screen_pos.x / screen_size.x * 2.0 - 1.0,
+            1.0 - screen_pos.y / screen_size.y * 2.0,
+        );
+        let instances = &group.instance_buffer_manager.data;
+        (0..instances.len()).rev().find(|&index| {
+            let transform = &instances[index].transform;
+            if let Some(rect) = transform.pixel_rect {
+                let min = rect.xy.as_vec2() / rect.extent.as_vec2() * screen_size;
+                let max =
+                    (rect.xy.as_vec2() + rect.wh.as_vec2()) / rect.extent.as_vec2() * screen_size;
+                return screen_pos.cmpge(min).all() && screen_pos.cmplt(max).all();
+            }
+            let to_clip = group.view_matrix_uniform.matrix * transform.to_mat4();
+            group.triangles.iter().any(|triangle| {
+                let corners = triangle.map(|corner| to_clip * corner.extend(1.0));
+                // anything behind the camera can't be hit
+                if corners.iter().any(|corner| corner.w <= 0.0) {
+                    return false;
+                }
+                let corners = corners.map(|corner| corner.truncate().truncate() / corner.w);
+                in_triangle(ndc, corners)
+            })
+        })
+    }
+}
+
+// Corners are in clip space, where the view is -w..w across and 0..w deep.
+fn outside_view(corners: [Vec4; 8]) -> bool {
+    let all = |outside: fn(&Vec4) -> bool| corners.iter().all(outside);
+    all(|c| c.x < -c.w)
+        || all(|c| c.x > c.w)
+        || all(|c| c.y < -c.w)
+        || all(|c| c.y > c.w)
+        || all(|c| c.z < 0.0)
+        || all(|c| c.z > c.w)
+}
+
+fn in_triangle(p: Vec2, [a, b, c]: [Vec2; 3]) -> bool {
+    let side = |from: Vec2, to: Vec2| (to - from).perp_dot(p - from);
+    let (ab, bc, ca) = (side(a, b), side(b, c), side(c, a));
+    // either winding counts
+    (ab >= 0.0 && bc >= 0.0 && ca >= 0.0) || (ab <= 0.0 && bc <= 0.0 && ca <= 0.0)
 }
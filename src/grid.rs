@@ -0,0 +1,276 @@
+use glam::{IVec2, UVec2};
+
+use crate::{
+    backend::Key,
+    error::ShecvError,
+    geo::GroupId,
+    listui::{ListStyle, ValueFormat},
+    slots::{SlotId, SlotKey},
+    text::TextCollection,
+    types::{ComponentTransform, PixelRect, TextureSheetDefinition, ValueStore},
+    window::State,
+};
+
+// A handle to a grid in State::grids.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct GridId(SlotKey);
+
+impl SlotId for GridId {
+    fn from_key(key: SlotKey) -> Self {
+        GridId(key)
+    }
+
+    fn key(self) -> SlotKey {
+        self.0
+    }
+}
+
+pub struct GridColumn {
+    pub header: String,
+    // in pixels; 0 fits the column to its header and cells
+    pub width: u32,
+}
+
+#[allow(dead_code)]
+impl GridColumn {
+    pub fn new(header: &str, width: u32) -> Self {
+        Self {
+            header: header.to_string(),
+            width,
+        }
+    }
+}
+
+// A cell shows the value under a store key.
+pub struct GridCell {
+    pub key: String,
+    pub format: ValueFormat,
+}
+
+// Runs on Enter with the store and the selected row and column.
+pub type GridAction = Box<dyn FnMut(&mut ValueStore, usize, usize)>;
+
+// Rows and columns of cells under a row of headers, e.g. an inventory or a
+// table of stats. Drawn with a ListStyle: headers like activated rows, the
+// selected cell like a selected row. While focused and visible a grid takes
+// the arrow keys and Enter ahead of any list.
+pub struct GridInterface {
+    pub style: ListStyle,
+    pub xy: IVec2,
+    pub columns: Vec<GridColumn>,
+    // cells left None are drawn empty but can still be selected
+    pub rows: Vec<Vec<Option<GridCell>>>,
+    // row, then column
+    pub selected: (usize, usize),
+    pub focused: bool,
+    pub visible_key: Option<String>,
+    pub on_activate: Option<GridAction>,
+    pub render_group: GroupId,
+    pub row_height: u32,
+}
+
+#[allow(dead_code)]
+impl GridInterface {
+    pub fn new(render_group: GroupId, columns: Vec<GridColumn>) -> Self {
+        Self {
+            style: ListStyle::default(),
+            xy: IVec2::ZERO,
+            columns,
+            rows: vec![],
+            selected: (0, 0),
+            focused: false,
+            visible_key: None,
+            on_activate: None,
+            render_group,
+            row_height: 20,
+        }
+    }
+
+    // A row of cells bound to keys, one per column. An empty key leaves its
+    // cell empty.
+    pub fn add_row(&mut self, keys: &[&str]) -> &mut Self {
+        let row = (0..self.columns.len())
+            .map(|column| match keys.get(column) {
+                Some(key) if !key.is_empty() => Some(GridCell {
+                    key: key.to_string(),
+                    format: ValueFormat::Default,
+                }),
+                _ => None,
+            })
+            .collect();
+        self.rows.push(row);
+        self
+    }
+
+    pub fn on_activate(
+        &mut self,
+        action: impl FnMut(&mut ValueStore, usize, usize) + 'static,
+    ) -> &mut Self {
+        self.on_activate = Some(Box::new(action));
+        self
+    }
+
+    pub fn is_visible(&self, store: &ValueStore) -> bool {
+        match &self.visible_key {
+            Some(key) => store.load_as::<bool>(key).unwrap_or(true),
+            None => true,
+        }
+    }
+
+    // Move the selection, wrapping around at the edges like a list does.
+    pub fn move_selection(&mut self, rows: i32, columns: i32) {
+        if self.rows.is_empty() || self.columns.is_empty() {
+            return;
+        }
+        let (row, column) = self.selected;
+        let row = (row as i32 + rows).rem_euclid(self.rows.len() as i32);
+        let column = (column as i32 + columns).rem_euclid(self.columns.len() as i32);
+        self.selected = (row as usize, column as usize);
+    }
+
+    pub fn cell_text(&self, store: &ValueStore, row: usize, column: usize) -> String {
+        let cell = self.rows.get(row).and_then(|cells| cells.get(column));
+        match cell {
+            Some(Some(cell)) => match store.map.get(&cell.key) {
+                Some(value) => cell.format.apply(value.as_ref()),
+                None => "<missing>".to_string(),
+            },
+            _ => String::new(),
+        }
+    }
+}
+
+impl State<'_> {
+    #[allow(dead_code)]
+    pub fn new_grid(&mut self, columns: Vec<GridColumn>) -> Result<GridId, ShecvError> {
+        let context = self.context.as_mut().ok_or_else(ShecvError::no_context)?;
+        let render_group = context.new_screen_group(512, TextureSheetDefinition::default())?;
+        Ok(self.grids.insert(GridInterface::new(render_group, columns)))
+    }
+
+    pub fn layout_grids(&mut self, store: &ValueStore) -> Result<(), ShecvError> {
+        let context = self.context.as_mut().ok_or_else(ShecvError::no_context)?;
+        let extent = context.logical_size()?;
+        let theme = self.themes.current();
+        let pad = 4;
+
+        for grid in self.grids.iter() {
+            let group = &mut context.geos.instance_groups[grid.render_group];
+            let visible = grid.is_visible(store);
+            group.set_visible(visible);
+            if !visible {
+                continue;
+            }
+            group.instance_buffer_manager.clear();
+
+            let metrics = TextCollection::line_metrics(grid.row_height as f32);
+            let widths: Vec<u32> = grid
+                .columns
+                .iter()
+                .enumerate()
+                .map(|(c, column)| {
+                    if column.width > 0 {
+                        return column.width;
+                    }
+                    let widest = (0..grid.rows.len())
+                        .map(|r| grid.cell_text(store, r, c))
+                        .chain([column.header.clone()])
+                        .map(|text| context.texts.measure(&text, metrics).0.ceil() as u32)
+                        .max()
+                        .unwrap_or(0);
+                    widest + pad * 2
+                })
+                .collect();
+            let width: u32 = widths.iter().sum::<u32>() + pad * 2;
+            let height = (grid.rows.len() as u32 + 1) * grid.row_height + pad * 2;
+
+            let group = &mut context.geos.instance_groups[grid.render_group];
+            group.add_new(
+                context.queue.clone(),
+                ComponentTransform::unit_square_transform_from_pixel_rect(PixelRect {
+                    xy: grid.xy,
+                    wh: UVec2::new(width, height),
+                    extent,
+                }),
+                0,
+                0,
+                grid.style.bg.resolve(theme),
+            )?;
+
+            // the headers, then every row; row -1 is the header row
+            for r in -1..grid.rows.len() as i32 {
+                let y = grid.xy.y + pad as i32 + (r + 1) * grid.row_height as i32;
+                let mut x = grid.xy.x + pad as i32;
+                for (c, column) in grid.columns.iter().enumerate() {
+                    let selected = grid.focused && grid.selected == (r as usize, c);
+                    let (fg, bg) = match (r, selected) {
+                        (-1, _) => (grid.style.li_activated, grid.style.li_activated_bg),
+                        (_, true) => (grid.style.li_selected, grid.style.li_selected_bg),
+                        _ => (grid.style.li_unselected, grid.style.li_unselected_bg),
+                    };
+                    let cell_rect = PixelRect {
+                        xy: IVec2::new(x + 1, y + 1),
+                        wh: UVec2::new(
+                            widths[c].saturating_sub(2),
+                            grid.row_height.saturating_sub(2),
+                        ),
+                        extent,
+                    };
+                    context.geos.instance_groups[grid.render_group].add_new(
+                        context.queue.clone(),
+                        ComponentTransform::unit_square_transform_from_pixel_rect(cell_rect),
+                        0,
+                        0,
+                        bg.resolve(theme),
+                    )?;
+                    let text = match r {
+                        -1 => column.header.clone(),
+                        _ => grid.cell_text(store, r as usize, c),
+                    };
+                    let index = context.texts.new_text(
+                        (
+                            (x + pad as i32) as f64,
+                            y as f64 + 2.5,
+                            widths[c].saturating_sub(pad * 2) as f64,
+                            grid.row_height as f64,
+                        ),
+                        &text,
+                        1.0,
+                        fg.resolve(theme),
+                    );
+                    context
+                        .texts
+                        .set_effect(index, grid.style.text_effect(theme));
+                    x += widths[c] as i32;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Arrows and Enter for the focused grids. False if no grid took the key,
+    // so it goes on to the lists.
+    pub fn grid_key(&mut self, key: Key, store: &mut ValueStore) -> bool {
+        let mut taken = false;
+        for grid in self.grids.iter_mut() {
+            if !grid.focused || !grid.is_visible(store) {
+                continue;
+            }
+            taken = true;
+            match key {
+                Key::Up => grid.move_selection(-1, 0),
+                Key::Down => grid.move_selection(1, 0),
+                Key::Left => grid.move_selection(0, -1),
+                Key::Right => grid.move_selection(0, 1),
+                Key::Enter => {
+                    let (row, column) = grid.selected;
+                    if let Some(action) = grid.on_activate.as_mut() {
+                        action(store, row, column);
+                    }
+                }
+                _ => taken = false,
+            }
+        }
+        taken
+    }
+}
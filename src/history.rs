@@ -0,0 +1,150 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use crate::types::ListItemData;
+
+// One key's value from before a change, or None if the key wasn't there.
+struct Change {
+    key: String,
+    value: Option<Box<dyn ListItemData>>,
+}
+
+// Everything undone or redone by one Ctrl+Z.
+struct Step {
+    changes: Vec<Change>,
+    // the last time a change was merged into this step, None once it's been
+    // undone so nothing merges into it again
+    at: Option<Instant>,
+}
+
+// Replacements made to a ValueStore, see ValueStore::enable_history. Changes
+// to the same key in quick succession, like a slider held down, merge into
+// the step that began them, so they're undone together.
+pub struct History {
+    undo: Vec<Step>,
+    redo: Vec<Step>,
+    // steps kept before the oldest are dropped
+    pub limit: usize,
+    pub merge_window: Duration,
+    // changes made inside ValueStore::transaction, kept as one step
+    open: Option<Vec<Change>>,
+}
+
+#[allow(dead_code)]
+impl History {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            undo: vec![],
+            redo: vec![],
+            limit,
+            merge_window: Duration::from_millis(500),
+            open: None,
+        }
+    }
+
+    // Note what key held before a change. Any redo steps are dropped, since
+    // they followed from a value that's gone now.
+    pub fn record(&mut self, key: &str, value: Option<Box<dyn ListItemData>>) {
+        self.redo.clear();
+        let change = Change {
+            key: key.to_string(),
+            value,
+        };
+        if let Some(open) = &mut self.open {
+            if !open.iter().any(|c| c.key == change.key) {
+                open.push(change);
+            }
+            return;
+        }
+        let now = Instant::now();
+        if let Some(last) = self.undo.last_mut() {
+            let same_key = last.changes.len() == 1 && last.changes[0].key == change.key;
+            let recent = last
+                .at
+                .is_some_and(|at| now.duration_since(at) < self.merge_window);
+            if same_key && recent {
+                last.at = Some(now);
+                return;
+            }
+        }
+        self.push(Step {
+            changes: vec![change],
+            at: Some(now),
+        });
+    }
+
+    pub fn begin(&mut self) {
+        self.open.get_or_insert_with(Vec::new);
+    }
+
+    pub fn end(&mut self) {
+        if let Some(changes) = self.open.take() {
+            if !changes.is_empty() {
+                self.push(Step { changes, at: None });
+            }
+        }
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+
+    // Swap every change of the last step with what's in the map now, moving
+    // the step onto the redo stack. Returns the keys changed.
+    pub fn undo(&mut self, map: &mut HashMap<String, Box<dyn ListItemData>>) -> Vec<String> {
+        let Some(step) = self.undo.pop() else {
+            return vec![];
+        };
+        let step = swap(step, map);
+        let keys = step.changes.iter().map(|c| c.key.clone()).collect();
+        self.redo.push(step);
+        keys
+    }
+
+    pub fn redo(&mut self, map: &mut HashMap<String, Box<dyn ListItemData>>) -> Vec<String> {
+        let Some(step) = self.redo.pop() else {
+            return vec![];
+        };
+        let step = swap(step, map);
+        let keys = step.changes.iter().map(|c| c.key.clone()).collect();
+        self.undo.push(step);
+        keys
+    }
+
+    fn push(&mut self, step: Step) {
+        self.undo.push(step);
+        if self.undo.len() > self.limit {
+            self.undo.remove(0);
+        }
+    }
+}
+
+fn swap(step: Step, map: &mut HashMap<String, Box<dyn ListItemData>>) -> Step {
+    let changes = step
+        .changes
+        .into_iter()
+        .rev()
+        .map(|change| {
+            let value = match change.value {
+                Some(value) => map.insert(change.key.clone(), value),
+                None => map.remove(&change.key),
+            };
+            Change {
+                key: change.key,
+                value,
+            }
+        })
+        .collect();
+    Step { changes, at: None }
+}
@@ -0,0 +1,267 @@
+use glam::{IVec2, UVec2};
+
+use crate::{
+    error::ShecvError,
+    geo::GroupId,
+    layer::RenderLayer,
+    listui::{ListAnchor, ValueFormat},
+    progress::ProgressBar,
+    slots::{SlotId, SlotKey},
+    text::TextCollection,
+    theme::{StyleColor, ThemeRole},
+    types::{ColorRGBA, ComponentTransform, PixelRect, TextureSheetDefinition, ValueStore},
+    window::State,
+};
+
+// A handle to a widget in State::hud.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct HudId(SlotKey);
+
+impl SlotId for HudId {
+    fn from_key(key: SlotKey) -> Self {
+        HudId(key)
+    }
+
+    fn key(self) -> SlotKey {
+        self.0
+    }
+}
+
+pub enum HudKind {
+    // fixed text, or a store value when key is set
+    Label {
+        text: String,
+        key: Option<String>,
+        format: ValueFormat,
+        font_size: f32,
+        color: StyleColor,
+    },
+    // an image from the widget's texture sheet
+    Image {
+        cluster_index: usize,
+        sub_index: usize,
+        wh: UVec2,
+        tint: ColorRGBA,
+    },
+    // the bar's xy is set by layout, from the widget's anchor
+    Progress(ProgressBar),
+}
+
+// A label, image or bar on its own, kept at a screen anchor as the window
+// resizes, e.g. a score readout or health bar that isn't part of a list.
+// See State::add_hud.
+pub struct HudWidget {
+    pub kind: HudKind,
+    pub anchor: ListAnchor,
+    // pixels from the screen edges it is anchored to, as a list's margin
+    pub offset: IVec2,
+    pub visible_key: Option<String>,
+    // top left and size where it was last laid out, if shown
+    pub rect: Option<(IVec2, UVec2)>,
+    // images draw from a group of their own, made from sheet by add_hud
+    sheet: Option<TextureSheetDefinition>,
+    group: Option<GroupId>,
+}
+
+#[allow(dead_code)]
+impl HudWidget {
+    pub fn new(kind: HudKind, anchor: ListAnchor) -> Self {
+        Self {
+            kind,
+            anchor,
+            offset: IVec2::ZERO,
+            visible_key: None,
+            rect: None,
+            sheet: None,
+            group: None,
+        }
+    }
+
+    pub fn label(text: &str, anchor: ListAnchor) -> Self {
+        Self::new(
+            HudKind::Label {
+                text: text.to_string(),
+                key: None,
+                format: ValueFormat::Default,
+                font_size: 16.0,
+                color: ThemeRole::Text.into(),
+            },
+            anchor,
+        )
+    }
+
+    // A label showing the value at key, as format writes it.
+    pub fn value(key: &str, format: ValueFormat, anchor: ListAnchor) -> Self {
+        Self::new(
+            HudKind::Label {
+                text: String::new(),
+                key: Some(key.to_string()),
+                format,
+                font_size: 16.0,
+                color: ThemeRole::Text.into(),
+            },
+            anchor,
+        )
+    }
+
+    pub fn image(
+        sheet: TextureSheetDefinition,
+        cluster_index: usize,
+        sub_index: usize,
+        wh: UVec2,
+        anchor: ListAnchor,
+    ) -> Self {
+        let mut widget = Self::new(
+            HudKind::Image {
+                cluster_index,
+                sub_index,
+                wh,
+                tint: ColorRGBA::white(),
+            },
+            anchor,
+        );
+        widget.sheet = Some(sheet);
+        widget
+    }
+
+    // A bar filling with the value at key, see ProgressBar for its colors
+    // and range.
+    pub fn progress(key: &str, wh: UVec2, anchor: ListAnchor) -> Self {
+        Self::new(
+            HudKind::Progress(ProgressBar::new(key, IVec2::ZERO, wh)),
+            anchor,
+        )
+    }
+
+    pub fn offset(mut self, offset: IVec2) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    pub fn bind_visible(mut self, key: &str) -> Self {
+        self.visible_key = Some(key.to_string());
+        self
+    }
+}
+
+#[allow(dead_code)]
+impl State<'_> {
+    pub fn add_hud(&mut self, mut widget: HudWidget) -> Result<HudId, ShecvError> {
+        if let HudKind::Image { .. } = widget.kind {
+            let sheet = widget.sheet.take().unwrap_or_default();
+            let context = self.context.as_mut().ok_or_else(ShecvError::no_context)?;
+            widget.group = Some(context.new_screen_group(4, sheet)?);
+        }
+        Ok(self.hud.insert(widget))
+    }
+
+    pub fn remove_hud(&mut self, id: HudId) -> Result<(), ShecvError> {
+        let widget = self
+            .hud
+            .remove(id)
+            .ok_or_else(|| ShecvError::Layout(format!("no hud widget {:?}", id)))?;
+        if let (Some(group), Some(context)) = (widget.group, self.context.as_mut()) {
+            context.geos.remove_group(group)?;
+        }
+        Ok(())
+    }
+
+    pub fn layout_hud(&mut self, store: &ValueStore) -> Result<(), ShecvError> {
+        if self.hud.is_empty() && self.hud_group.is_none() {
+            return Ok(());
+        }
+
+        // every bar draws into one group
+        let context = self.context.as_mut().ok_or_else(ShecvError::no_context)?;
+        let group_index = context.overlay_group(&mut self.hud_group, 32, RenderLayer::Ui)?;
+        let extent = context.logical_size()?;
+        context.geos.instance_groups[group_index]
+            .instance_buffer_manager
+            .clear();
+        let theme = self.themes.current();
+
+        for widget in self.hud.iter_mut() {
+            let visible = match &widget.visible_key {
+                Some(key) => store.load_as::<bool>(key).unwrap_or(true),
+                None => true,
+            };
+            if let Some(group) = widget.group {
+                let group = &mut context.geos.instance_groups[group];
+                group.set_visible(visible);
+                group.instance_buffer_manager.clear();
+            }
+            widget.rect = None;
+            if !visible {
+                continue;
+            }
+            let (anchor, offset) = (&widget.anchor, widget.offset);
+            let rect = match &mut widget.kind {
+                HudKind::Label {
+                    text,
+                    key,
+                    format,
+                    font_size,
+                    color,
+                } => {
+                    let text = match key {
+                        Some(key) => match store.map.get(key.as_str()) {
+                            Some(value) => format.apply(value.as_ref()),
+                            None => continue,
+                        },
+                        None => text.clone(),
+                    };
+                    let line_height = *font_size / 0.8;
+                    let metrics = TextCollection::line_metrics(line_height);
+                    let width = context.texts.measure(&text, metrics).0.ceil();
+                    let wh = UVec2::new(width as u32 + 1, line_height.ceil() as u32);
+                    let xy = anchor.position(wh.as_ivec2(), extent, offset);
+                    context.texts.new_text(
+                        (xy.x as f64, xy.y as f64, wh.x as f64, line_height as f64),
+                        &text,
+                        1.0,
+                        color.resolve(theme),
+                    );
+                    (xy, wh)
+                }
+                HudKind::Image {
+                    cluster_index,
+                    sub_index,
+                    wh,
+                    tint,
+                } => {
+                    let xy = anchor.position(wh.as_ivec2(), extent, offset);
+                    if let Some(group) = widget.group {
+                        context.geos.instance_groups[group].add_new(
+                            context.queue.clone(),
+                            ComponentTransform::unit_square_transform_from_pixel_rect(
+                                PixelRect::new(xy, *wh, extent),
+                            ),
+                            *cluster_index,
+                            *sub_index,
+                            *tint,
+                        )?;
+                    }
+                    (xy, *wh)
+                }
+                HudKind::Progress(bar) => {
+                    let Some(value) = store.map.get(&bar.key) else {
+                        continue;
+                    };
+                    bar.xy = anchor.position(bar.wh.as_ivec2(), extent, offset);
+                    bar.draw(
+                        value.as_ref(),
+                        &mut context.geos.instance_groups[group_index],
+                        context.queue.clone(),
+                        &mut context.texts,
+                        theme,
+                        extent,
+                    )?;
+                    (bar.xy, bar.wh)
+                }
+            };
+            widget.rect = Some(rect);
+        }
+
+        Ok(())
+    }
+}
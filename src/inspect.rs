@@ -0,0 +1,159 @@
+use glam::{IVec2, UVec2};
+
+use crate::{
+    error::ShecvError,
+    geo::GroupId,
+    layer::RenderLayer,
+    slots::SlotId,
+    types::{ColorRGBA, ComponentTransform, PixelRect, ValueStore},
+    window::{Context, State},
+};
+
+// The layout inspector shows where layout_listui placed a list and why:
+// its rect, anchor, padding, content size and the store keys it is bound to.
+// The inspected region is highlighted on screen.
+impl State<'_> {
+    // F12 steps through the lists, then turns the inspector off again.
+    pub fn cycle_inspector(&mut self) {
+        self.inspect_listui = match self.inspect_listui {
+            None => self.listuis.ids().next(),
+            Some(current) => self.listuis.ids().skip_while(|&id| id != current).nth(1),
+        };
+    }
+
+    pub fn layout_inspector(&mut self, store: &ValueStore) -> Result<(), ShecvError> {
+        let context = self.context.as_mut().ok_or_else(ShecvError::no_context)?;
+        let group_index = context.overlay_group(&mut self.inspector_group, 64, RenderLayer::Ui)?;
+        context.geos.instance_groups[group_index]
+            .instance_buffer_manager
+            .clear();
+
+        let Some(listui) = self.inspect_listui.and_then(|i| self.listuis.get(i)) else {
+            return Ok(());
+        };
+        let layout = listui.layout;
+        let extent = context.logical_size()?;
+
+        let mut lines = vec![
+            format!("list {}", self.inspect_listui.unwrap().key().index),
+            format!("anchor: {:?}", listui.anchor),
+        ];
+        if layout.visible {
+            lines.push(format!(
+                "rect: {}, {}  {}x{}",
+                layout.xy.x, layout.xy.y, layout.wh.x, layout.wh.y
+            ));
+            lines.push(format!(
+                "padding: {} {}",
+                layout.padding.x, layout.padding.y
+            ));
+            lines.push(format!(
+                "content: {}x{}",
+                layout.content.x, layout.content.y
+            ));
+        } else {
+            lines.push("hidden".to_string());
+        }
+        lines.push("keys:".to_string());
+        for (key, role) in listui.bound_keys() {
+            let value = match store.map.get(&key) {
+                Some(value) => format!("{}", value),
+                None => "<missing>".to_string(),
+            };
+            lines.push(format!("  {} = {} ({})", key, value, role));
+        }
+
+        // highlight the list region with a translucent fill and an outline
+        if layout.visible {
+            let (xy, wh) = (layout.xy, layout.wh);
+            let highlight = ColorRGBA::new(1.0, 0.0, 1.0, 0.15);
+            let outline = ColorRGBA::magenta();
+            let t = 2;
+            add_rect(context, group_index, xy, wh, extent, highlight)?;
+            add_rect(
+                context,
+                group_index,
+                xy,
+                UVec2::new(wh.x, t),
+                extent,
+                outline,
+            )?;
+            add_rect(
+                context,
+                group_index,
+                IVec2::new(xy.x, xy.y + wh.y as i32 - t as i32),
+                UVec2::new(wh.x, t),
+                extent,
+                outline,
+            )?;
+            add_rect(
+                context,
+                group_index,
+                xy,
+                UVec2::new(t, wh.y),
+                extent,
+                outline,
+            )?;
+            add_rect(
+                context,
+                group_index,
+                IVec2::new(xy.x + wh.x as i32 - t as i32, xy.y),
+                UVec2::new(t, wh.y),
+                extent,
+                outline,
+            )?;
+        }
+
+        // the info panel goes on the side of the screen away from the list
+        let line_height = 20;
+        let panel_wh = UVec2::new(260, lines.len() as u32 * line_height + 8);
+        let list_center = layout.xy.x + layout.wh.x as i32 / 2;
+        let panel_xy = if list_center < extent.x as i32 / 2 {
+            IVec2::new(extent.x as i32 - panel_wh.x as i32, 0)
+        } else {
+            IVec2::new(0, 0)
+        };
+        add_rect(
+            context,
+            group_index,
+            panel_xy,
+            panel_wh,
+            extent,
+            ColorRGBA::new(0.0, 0.0, 0.0, 0.8),
+        )?;
+
+        for (i, line) in lines.iter().enumerate() {
+            context.texts.new_text(
+                (
+                    panel_xy.x as f64 + 6.0,
+                    (panel_xy.y + 4 + (i as u32 * line_height) as i32) as f64,
+                    panel_wh.x as f64,
+                    line_height as f64,
+                ),
+                line,
+                1.0,
+                ColorRGBA::white(),
+            );
+        }
+
+        Ok(())
+    }
+}
+
+fn add_rect(
+    context: &mut Context,
+    group: GroupId,
+    xy: IVec2,
+    wh: UVec2,
+    extent: UVec2,
+    color: ColorRGBA,
+) -> Result<(), ShecvError> {
+    context.geos.instance_groups[group].add_new(
+        context.queue.clone(),
+        ComponentTransform::unit_square_transform_from_pixel_rect(PixelRect { xy, wh, extent }),
+        0,
+        0,
+        color,
+    )?;
+    Ok(())
+}
@@ -0,0 +1,64 @@
+use wgpu::BlendState;
+
+use crate::types::ColorRGBA;
+
+// Everything drawn belongs to a layer, and layers are drawn in this order:
+// the world under the ui, and overlays over both. Within a layer, instance
+// groups go first, then lines, then text.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum RenderLayer {
+    World,
+    #[default]
+    Ui,
+    Overlay,
+}
+
+impl RenderLayer {
+    pub const ALL: [RenderLayer; 3] = [RenderLayer::World, RenderLayer::Ui, RenderLayer::Overlay];
+}
+
+#[derive(Copy, Clone)]
+pub struct LayerSettings {
+    // clear the frame before drawing the layer, wiping the layers under it
+    pub clear: Option<ColorRGBA>,
+    // how the layer's instance groups blend over what's under them; text
+    // always blends with premultiplied alpha
+    pub blend: BlendState,
+}
+
+impl Default for LayerSettings {
+    fn default() -> Self {
+        Self {
+            clear: None,
+            blend: BlendState::PREMULTIPLIED_ALPHA_BLENDING,
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+pub struct RenderLayers {
+    // the frame is cleared to this before any layer is drawn
+    pub background: ColorRGBA,
+    settings: [LayerSettings; RenderLayer::ALL.len()],
+}
+
+impl Default for RenderLayers {
+    fn default() -> Self {
+        Self {
+            background: ColorRGBA::new(0.05, 0.01, 0.03, 1.0),
+            settings: [LayerSettings::default(); RenderLayer::ALL.len()],
+        }
+    }
+}
+
+impl RenderLayers {
+    pub fn get(&self, layer: RenderLayer) -> &LayerSettings {
+        &self.settings[layer as usize]
+    }
+
+    // Changing blend here doesn't reach existing groups; see
+    // GeoManager::set_layer_blend.
+    pub fn get_mut(&mut self, layer: RenderLayer) -> &mut LayerSettings {
+        &mut self.settings[layer as usize]
+    }
+}
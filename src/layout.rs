@@ -0,0 +1,238 @@
+use glam::{IVec2, UVec2};
+
+use crate::types::PixelRect;
+
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LayoutKind {
+    // something to place, whose rect comes back from layout
+    #[default]
+    Item,
+    // empty room, growing to push its neighbours apart
+    Spacer,
+    // children top to bottom
+    VBox,
+    // children left to right
+    HBox,
+    // children on top of each other, each over the whole box
+    Stack,
+}
+
+// Where children sit across a box, or within a stack. Stretch fills it.
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq)]
+#[allow(dead_code)]
+pub enum Align {
+    Start,
+    Center,
+    End,
+    #[default]
+    Stretch,
+}
+
+// A tree of boxes that hands out rects to its items, for HUDs and panels
+// that aren't lists:
+//
+//   let hud = LayoutNode::hbox(vec![
+//       LayoutNode::item(UVec2::new(200, 40)),
+//       LayoutNode::spacer(),
+//       LayoutNode::vbox(vec![
+//           LayoutNode::item(UVec2::new(120, 20)),
+//           LayoutNode::item(UVec2::new(120, 20)),
+//       ])
+//       .gap(4),
+//   ])
+//   .padding(UVec2::splat(8))
+//   .align(Align::Start);
+//   let [health, ammo, score] = hud.layout(PixelRect::full(extent))[..] else { .. };
+//
+// Along a box, children get their min size and share what room is left by
+// grow; items and boxes don't grow unless asked, spacers do. Across it they
+// are placed by the box's align.
+#[derive(Default, Debug, Clone)]
+pub struct LayoutNode {
+    pub kind: LayoutKind,
+    pub children: Vec<LayoutNode>,
+    // boxes are at least as big as their children need
+    pub min: UVec2,
+    pub max: Option<UVec2>,
+    pub grow: f32,
+    // between children along a box
+    pub gap: u32,
+    // inside a box or stack, horizontal and vertical
+    pub padding: UVec2,
+    pub align: Align,
+}
+
+#[allow(dead_code)]
+impl LayoutNode {
+    pub fn item(min: UVec2) -> Self {
+        Self {
+            min,
+            ..Default::default()
+        }
+    }
+
+    pub fn spacer() -> Self {
+        Self {
+            kind: LayoutKind::Spacer,
+            grow: 1.0,
+            ..Default::default()
+        }
+    }
+
+    pub fn vbox(children: Vec<LayoutNode>) -> Self {
+        Self::container(LayoutKind::VBox, children)
+    }
+
+    pub fn hbox(children: Vec<LayoutNode>) -> Self {
+        Self::container(LayoutKind::HBox, children)
+    }
+
+    pub fn stack(children: Vec<LayoutNode>) -> Self {
+        Self::container(LayoutKind::Stack, children)
+    }
+
+    fn container(kind: LayoutKind, children: Vec<LayoutNode>) -> Self {
+        Self {
+            kind,
+            children,
+            ..Default::default()
+        }
+    }
+
+    pub fn min(mut self, min: UVec2) -> Self {
+        self.min = min;
+        self
+    }
+
+    pub fn max(mut self, max: UVec2) -> Self {
+        self.max = Some(max);
+        self
+    }
+
+    pub fn grow(mut self, grow: f32) -> Self {
+        self.grow = grow;
+        self
+    }
+
+    pub fn gap(mut self, gap: u32) -> Self {
+        self.gap = gap;
+        self
+    }
+
+    pub fn padding(mut self, padding: UVec2) -> Self {
+        self.padding = padding;
+        self
+    }
+
+    pub fn align(mut self, align: Align) -> Self {
+        self.align = align;
+        self
+    }
+
+    // The smallest the node can be.
+    pub fn measure(&self) -> UVec2 {
+        let sizes = self.children.iter().map(LayoutNode::measure);
+        let gaps = self.gap * (self.children.len() as u32).saturating_sub(1);
+        let content = match self.kind {
+            LayoutKind::Item | LayoutKind::Spacer => UVec2::ZERO,
+            LayoutKind::VBox => sizes.fold(UVec2::new(0, gaps), |total, size| {
+                UVec2::new(total.x.max(size.x), total.y + size.y)
+            }),
+            LayoutKind::HBox => sizes.fold(UVec2::new(gaps, 0), |total, size| {
+                UVec2::new(total.x + size.x, total.y.max(size.y))
+            }),
+            LayoutKind::Stack => sizes.fold(UVec2::ZERO, UVec2::max),
+        };
+        let size = self.min.max(content + self.padding * 2);
+        match self.max {
+            Some(max) => size.min(max),
+            None => size,
+        }
+    }
+
+    // The rect of every item in rect, in the order they were added, spacers
+    // and boxes left out.
+    pub fn layout(&self, rect: PixelRect) -> Vec<PixelRect> {
+        let mut rects = vec![];
+        self.place(rect, &mut rects);
+        rects
+    }
+
+    fn place(&self, rect: PixelRect, rects: &mut Vec<PixelRect>) {
+        let rect = match self.max {
+            Some(max) => PixelRect {
+                wh: rect.wh.min(max),
+                ..rect
+            },
+            None => rect,
+        };
+        let inner = rect.inset(self.padding.as_ivec2());
+        match self.kind {
+            LayoutKind::Item => rects.push(rect),
+            LayoutKind::Spacer => {}
+            LayoutKind::Stack => {
+                for child in &self.children {
+                    let min = child.measure();
+                    let (x, w) = align(self.align, inner.xy.x, inner.wh.x, min.x);
+                    let (y, h) = align(self.align, inner.xy.y, inner.wh.y, min.y);
+                    child.place(
+                        PixelRect::new(IVec2::new(x, y), UVec2::new(w, h), rect.extent),
+                        rects,
+                    );
+                }
+            }
+            LayoutKind::VBox | LayoutKind::HBox => {
+                // main is the axis children follow each other along
+                let main = |v: UVec2| match self.kind {
+                    LayoutKind::VBox => v.y,
+                    _ => v.x,
+                };
+                let cross = |v: UVec2| match self.kind {
+                    LayoutKind::VBox => v.x,
+                    _ => v.y,
+                };
+                let mins: Vec<UVec2> = self.children.iter().map(LayoutNode::measure).collect();
+                let gaps = self.gap * (self.children.len() as u32).saturating_sub(1);
+                let used = mins.iter().map(|&min| main(min)).sum::<u32>() + gaps;
+                let room = main(inner.wh).saturating_sub(used) as f32;
+                let total_grow: f32 = self.children.iter().map(|child| child.grow).sum();
+                let mut along = match self.kind {
+                    LayoutKind::VBox => inner.xy.y,
+                    _ => inner.xy.x,
+                };
+                for (child, min) in self.children.iter().zip(mins) {
+                    let extra = match total_grow > 0.0 {
+                        true => (room * child.grow / total_grow) as u32,
+                        false => 0,
+                    };
+                    let length = main(min) + extra;
+                    let (across, thickness) = match self.kind {
+                        LayoutKind::VBox => align(self.align, inner.xy.x, inner.wh.x, cross(min)),
+                        _ => align(self.align, inner.xy.y, inner.wh.y, cross(min)),
+                    };
+                    let (xy, wh) = match self.kind {
+                        LayoutKind::VBox => {
+                            (IVec2::new(across, along), UVec2::new(thickness, length))
+                        }
+                        _ => (IVec2::new(along, across), UVec2::new(length, thickness)),
+                    };
+                    child.place(PixelRect::new(xy, wh, rect.extent), rects);
+                    along += (length + self.gap) as i32;
+                }
+            }
+        }
+    }
+}
+
+// Where a thing of size goes in the span from start of length, and how big
+// it ends up.
+fn align(align: Align, start: i32, length: u32, size: u32) -> (i32, u32) {
+    let size = size.min(length);
+    let spare = (length - size) as i32;
+    match align {
+        Align::Start => (start, size),
+        Align::Center => (start + spare / 2, size),
+        Align::End => (start + spare, size),
+        Align::Stretch => (start, length),
+    }
+}
@@ -0,0 +1,320 @@
+use std::{
+    borrow::Cow,
+    mem::size_of,
+    sync::{Arc, Mutex},
+};
+
+use bytemuck::{Pod, Zeroable};
+use glam::{Vec2, Vec4};
+use log::warn;
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    BindGroup, BindGroupEntry, BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingType,
+    BlendState, Buffer, BufferAddress, BufferBindingType, BufferSize, BufferUsages,
+    ColorTargetState, ColorWrites, Device, FragmentState, MultisampleState, PipelineLayout,
+    PrimitiveState, Queue, RenderPipeline, RenderPipelineDescriptor, ShaderModule,
+    ShaderModuleDescriptor, ShaderSource, ShaderStages, TextureFormat, VertexAttribute,
+    VertexBufferLayout, VertexFormat, VertexState, VertexStepMode,
+};
+
+use crate::assets::read_to_string;
+use crate::error::{validate, ShecvError};
+use crate::layer::RenderLayer;
+use crate::params::{Globals, ShaderParams};
+use crate::types::{ColorRGBA, GeoUniformVec2, RenderPipelineRecord};
+
+// One end of a line. xy is in pixels from the top left of the screen.
+#[derive(Copy, Clone)]
+pub struct LinePoint {
+    pub xy: Vec2,
+    pub color: ColorRGBA,
+    pub thickness: f32,
+}
+
+#[allow(dead_code)]
+impl LinePoint {
+    pub fn new(xy: Vec2, color: ColorRGBA, thickness: f32) -> Self {
+        Self {
+            xy,
+            color,
+            thickness,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable)]
+pub struct LineSegmentData {
+    pub a: Vec4,
+    pub b: Vec4,
+    pub color_a: ColorRGBA,
+    pub color_b: ColorRGBA,
+}
+
+impl LineSegmentData {
+    fn new(a: LinePoint, b: LinePoint) -> Self {
+        Self {
+            a: Vec4::new(a.xy.x, a.xy.y, a.thickness, 0.0),
+            b: Vec4::new(b.xy.x, b.xy.y, b.thickness, 0.0),
+            color_a: a.color,
+            color_b: b.color,
+        }
+    }
+}
+
+const LINE_SEGMENT_BUFFER_LAYOUT: [VertexBufferLayout<'_>; 1] = [VertexBufferLayout {
+    array_stride: size_of::<LineSegmentData>() as BufferAddress,
+    step_mode: VertexStepMode::Instance,
+    attributes: &[
+        VertexAttribute {
+            offset: 0,
+            shader_location: 0,
+            format: VertexFormat::Float32x4,
+        },
+        VertexAttribute {
+            offset: size_of::<[f32; 4]>() as BufferAddress,
+            shader_location: 1,
+            format: VertexFormat::Float32x4,
+        },
+        VertexAttribute {
+            offset: size_of::<[f32; 8]>() as BufferAddress,
+            shader_location: 2,
+            format: VertexFormat::Float32x4,
+        },
+        VertexAttribute {
+            offset: size_of::<[f32; 12]>() as BufferAddress,
+            shader_location: 3,
+            format: VertexFormat::Float32x4,
+        },
+    ],
+}];
+
+// Antialiased lines drawn in screen space, one instanced quad per segment.
+// Each end of a segment has its own color and thickness.
+pub struct LineGroup {
+    pub render_pipeline_record: RenderPipelineRecord,
+    pub params: ShaderParams,
+    pub bind_group: BindGroup,
+    pub screen_size_uniform: GeoUniformVec2,
+    pub segments: Vec<LineSegmentData>,
+    pub buffer: Buffer,
+    pub max_segments: usize,
+    pub layer: RenderLayer,
+}
+
+#[allow(dead_code)]
+impl LineGroup {
+    pub fn new(
+        device: &Device,
+        max_segments: usize,
+        format: TextureFormat,
+        wh: (u32, u32),
+        shader_path: &str,
+        globals: &Buffer,
+    ) -> Result<Self, ShecvError> {
+        let shader_module = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some(shader_path),
+            source: ShaderSource::Wgsl(Cow::Borrowed(&*read_to_string(shader_path)?)),
+        });
+
+        let screen_size = Vec2::new(wh.0 as f32, wh.1 as f32);
+        let screen_size_uniform = GeoUniformVec2 {
+            vec: screen_size,
+            buffer: device.create_buffer_init(&BufferInitDescriptor {
+                label: Some("line screen_size"),
+                contents: bytemuck::cast_slice(&[screen_size]),
+                usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            }),
+        };
+
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::VERTEX,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: BufferSize::new(size_of::<Vec2>() as u64),
+                    },
+                    count: None,
+                },
+                ShaderParams::layout_entry(1),
+                Globals::layout_entry(2),
+            ],
+        });
+        let params = ShaderParams::new(device);
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &bind_group_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: screen_size_uniform.buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: params.buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: globals.as_entire_binding(),
+                },
+            ],
+            label: None,
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("line segment buffer"),
+            contents: bytemuck::cast_slice(&vec![LineSegmentData::zeroed(); max_segments]),
+            usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+        });
+
+        Ok(Self {
+            render_pipeline_record: RenderPipelineRecord {
+                render_pipeline: line_pipeline(device, &pipeline_layout, &shader_module, format),
+                pipeline_layout,
+                shader_module,
+                shader_path: shader_path.to_string(),
+                format,
+            },
+            params,
+            bind_group,
+            screen_size_uniform,
+            segments: vec![],
+            buffer,
+            max_segments,
+            layer: RenderLayer::default(),
+        })
+    }
+
+    // Leaves the current pipeline in place if the new shader doesn't build.
+    pub fn reload_shader(&mut self, device: &Device) -> Result<(), ShecvError> {
+        let record = &mut self.render_pipeline_record;
+        let source = read_to_string(&record.shader_path)?;
+        let (shader_module, render_pipeline) = validate(device, &record.shader_path, || {
+            let shader_module = device.create_shader_module(ShaderModuleDescriptor {
+                label: Some(&*format!("shader {}", record.shader_path)),
+                source: ShaderSource::Wgsl(Cow::Borrowed(&*source)),
+            });
+            let render_pipeline = line_pipeline(
+                device,
+                &record.pipeline_layout,
+                &shader_module,
+                record.format,
+            );
+            (shader_module, render_pipeline)
+        })?;
+        record.shader_module = shader_module;
+        record.render_pipeline = render_pipeline;
+        Ok(())
+    }
+
+    // Returns the segment index, or None once the group is full.
+    pub fn add_line(
+        &mut self,
+        queue: Arc<Mutex<Queue>>,
+        a: LinePoint,
+        b: LinePoint,
+    ) -> Result<Option<usize>, ShecvError> {
+        if self.segments.len() >= self.max_segments {
+            warn!("line group is full at {} segments", self.max_segments);
+            return Ok(None);
+        }
+        let index = self.segments.len();
+        self.segments.push(LineSegmentData::new(a, b));
+        self.write_segment(queue, index)?;
+        Ok(Some(index))
+    }
+
+    // Connect the points in order. Returns the index of the first segment.
+    pub fn add_polyline(
+        &mut self,
+        queue: Arc<Mutex<Queue>>,
+        points: &[LinePoint],
+    ) -> Result<Option<usize>, ShecvError> {
+        let first = self.segments.len();
+        for pair in points.windows(2) {
+            if self.add_line(queue.clone(), pair[0], pair[1])?.is_none() {
+                return Ok(None);
+            }
+        }
+        Ok((self.segments.len() > first).then_some(first))
+    }
+
+    pub fn set_line(
+        &mut self,
+        queue: Arc<Mutex<Queue>>,
+        index: usize,
+        a: LinePoint,
+        b: LinePoint,
+    ) -> Result<(), ShecvError> {
+        self.segments[index] = LineSegmentData::new(a, b);
+        self.write_segment(queue, index)
+    }
+
+    pub fn clear(&mut self) {
+        self.segments.clear();
+    }
+
+    pub fn num_segments(&self) -> u32 {
+        self.segments.len() as u32
+    }
+
+    pub fn upload_all(&mut self, queue: Arc<Mutex<Queue>>) -> Result<(), ShecvError> {
+        for index in 0..self.segments.len() {
+            self.write_segment(queue.clone(), index)?;
+        }
+        Ok(())
+    }
+
+    fn write_segment(&self, queue: Arc<Mutex<Queue>>, index: usize) -> Result<(), ShecvError> {
+        queue.lock()?.write_buffer(
+            &self.buffer,
+            (index * size_of::<LineSegmentData>()) as BufferAddress,
+            bytemuck::cast_slice(&[self.segments[index]]),
+        );
+        Ok(())
+    }
+}
+
+fn line_pipeline(
+    device: &Device,
+    layout: &PipelineLayout,
+    module: &ShaderModule,
+    format: TextureFormat,
+) -> RenderPipeline {
+    device.create_render_pipeline(&RenderPipelineDescriptor {
+        label: Some("line pipeline"),
+        layout: Some(layout),
+        vertex: VertexState {
+            module,
+            entry_point: "vs_main",
+            buffers: &LINE_SEGMENT_BUFFER_LAYOUT,
+            compilation_options: Default::default(),
+        },
+        fragment: Some(FragmentState {
+            module,
+            entry_point: "fs_main",
+            targets: &[Some(ColorTargetState {
+                format,
+                blend: Some(BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+                write_mask: ColorWrites::ALL,
+            })],
+            compilation_options: Default::default(),
+        }),
+        primitive: PrimitiveState {
+            cull_mode: None,
+            ..Default::default()
+        },
+        depth_stencil: None,
+        multisample: MultisampleState::default(),
+        multiview: None,
+        cache: None,
+    })
+}
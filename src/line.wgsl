@@ -0,0 +1,81 @@
+@group(0) @binding(0)
+var<uniform> screen_size: vec2<f32>;
+// values from the group's ShaderParams, in the order they were declared
+@group(0) @binding(1)
+var<uniform> params: array<vec4<f32>, 16>;
+
+// the same for every pipeline, see params::Globals
+struct Globals {
+    time: f32,
+    dt: f32,
+    resolution: vec2<f32>,
+    cursor: vec2<f32>,
+    _pad: vec2<f32>,
+};
+@group(0) @binding(2)
+var<uniform> globals: Globals;
+
+// one instance per segment. positions are pixels from the top left of the screen.
+struct SegmentInput {
+    // xy position, z thickness
+    @location(0) a: vec4<f32>,
+    @location(1) b: vec4<f32>,
+    @location(2) color_a: vec4<f32>,
+    @location(3) color_b: vec4<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+    // distance from the center of the line in pixels, and half the thickness there
+    @location(1) across: f32,
+    @location(2) half_thickness: f32,
+};
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) vertex_index: u32,
+    segment: SegmentInput,
+) -> VertexOutput {
+    // two triangles covering the segment. x picks the end, y the side.
+    var corners = array<vec2<f32>, 6>(
+        vec2(0.0, -1.0),
+        vec2(1.0, -1.0),
+        vec2(1.0, 1.0),
+        vec2(0.0, -1.0),
+        vec2(1.0, 1.0),
+        vec2(0.0, 1.0),
+    );
+    let corner = corners[vertex_index];
+
+    let delta = segment.b.xy - segment.a.xy;
+    let len = max(length(delta), 0.0001);
+    let dir = delta / len;
+    let normal = vec2(-dir.y, dir.x);
+
+    let half_thickness = mix(segment.a.z, segment.b.z, corner.x) * 0.5;
+    // an extra pixel on every side leaves room for the antialiased edge.
+    // extending past the ends squares off the caps and covers polyline joints.
+    let extent = half_thickness + 1.0;
+    let along = mix(-extent, len + extent, corner.x);
+    let pixel = segment.a.xy + dir * along + normal * corner.y * extent;
+
+    var result: VertexOutput;
+    result.position = vec4(
+        pixel.x / screen_size.x * 2.0 - 1.0,
+        1.0 - pixel.y / screen_size.y * 2.0,
+        0.0,
+        1.0,
+    );
+    result.color = mix(segment.color_a, segment.color_b, corner.x);
+    result.across = corner.y * extent;
+    result.half_thickness = half_thickness;
+    return result;
+}
+
+@fragment
+fn fs_main(vout: VertexOutput) -> @location(0) vec4<f32> {
+    let coverage = clamp(vout.half_thickness + 0.5 - abs(vout.across), 0.0, 1.0);
+    let alpha = vout.color.a * coverage;
+    return vec4(vout.color.rgb * alpha, alpha);
+}
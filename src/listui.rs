@@ -1,36 +1,246 @@
-use std::{cell::RefCell, rc::Rc};
+use std::{
+    ops::{Add, Mul, Range, Sub},
+    time::{Duration, Instant},
+};
 
-use crate::types::{ColorRGBA, ListItemData, Value};
+use glam::{IVec2, UVec2, Vec4};
 
+use crate::audio::ListSounds;
+use crate::binding::Binding;
+use crate::error::ShecvError;
+use crate::geo::GroupId;
+use crate::locale::Localization;
+use crate::progress::progress_fraction;
+use crate::samples::Samples;
+use crate::slots::{SlotId, SlotKey};
+use crate::text::TextEffect;
+use crate::theme::{StyleBorder, StyleColor, StyleTextShadow, Theme, ThemeRole};
+use crate::tween::{Easing, TweenTarget, Tweens};
+use crate::types::{ColorRGBA, ListItemData, NineSlice, ValueHandle, ValueStore};
+
+// Colors follow the State's current theme unless set to StyleColor::Fixed.
 #[allow(dead_code)]
 pub struct ListStyle {
-    pub bg: ColorRGBA,
+    pub bg: StyleColor,
+
+    pub li_selected: StyleColor,
+    pub li_selected_bg: StyleColor,
+
+    pub li_unselected: StyleColor,
+    pub li_unselected_bg: StyleColor,
+
+    pub li_activated: StyleColor,
+    pub li_activated_bg: StyleColor,
+
+    pub li_disabled: StyleColor,
+    pub li_disabled_bg: StyleColor,
 
-    pub li_selected: ColorRGBA,
-    pub li_selected_bg: ColorRGBA,
+    // what rows flash to as their value changes, fading back over
+    // flash_duration; see ListItem::flash_on_change
+    pub li_flash_bg: StyleColor,
+    pub flash_duration: Duration,
 
-    pub li_unselected: ColorRGBA,
-    pub li_unselected_bg: ColorRGBA,
+    // how the selection highlight moves between rows and a scrolling list
+    // follows it
+    pub motion: ListMotion,
 
-    pub li_activated: ColorRGBA,
-    pub li_activated_bg: ColorRGBA,
+    // the filled and empty parts of Progress items; Sparkline graphs are
+    // drawn in progress too
+    pub progress: StyleColor,
+    pub progress_track: StyleColor,
 
-    pub li_disabled: ColorRGBA,
-    pub li_disabled_bg: ColorRGBA,
+    // Draw the list background as a nine-slice panel from the list's texture sheet
+    // instead of a flat rect. bg tints the panel.
+    pub panel: Option<NineSlice>,
+
+    // Rounded corners in pixels for the list and its rows, and an optional
+    // border around the list. Ignored when a panel is drawn.
+    pub corner_radius: f32,
+    pub border: Option<StyleBorder>,
+
+    // Row height and how far rows are inset within it, in pixels. Text is
+    // font_size pixels, centered in its row.
+    pub row_height: u32,
+    pub h_padding: u32,
+    pub v_padding: u32,
+    pub font_size: f32,
+    // The list is at least min_width wide, split evenly between labels and
+    // values, and values are cut short past max_width.
+    pub min_width: u32,
+    pub max_width: Option<u32>,
+    // between the columns of a list that overflows into more of them
+    pub column_gap: u32,
+    // played on the ui bus of State::audio as the list is used
+    pub sounds: ListSounds,
+    // keep labels readable over busy backgrounds, see TextEffect
+    pub text_shadow: Option<StyleTextShadow>,
+    pub text_outline: Option<StyleBorder>,
 }
 
-impl ListStyle {
+impl Default for ListStyle {
     fn default() -> ListStyle {
         ListStyle {
-            bg: ColorRGBA::grey_darkest(),
-            li_selected: ColorRGBA::white(),
-            li_selected_bg: ColorRGBA::grey_medium(),
-            li_activated: ColorRGBA::white(),
-            li_activated_bg: ColorRGBA::grey_lighter(),
-            li_unselected: ColorRGBA::grey_light(),
-            li_unselected_bg: ColorRGBA::grey_dark(),
-            li_disabled: ColorRGBA::grey_dark(),
-            li_disabled_bg: ColorRGBA::grey_darker(),
+            bg: ThemeRole::Background.into(),
+            li_selected: ThemeRole::SelectionText.into(),
+            li_selected_bg: ThemeRole::Selection.into(),
+            li_activated: ThemeRole::AccentText.into(),
+            li_activated_bg: ThemeRole::Accent.into(),
+            li_unselected: ThemeRole::Text.into(),
+            li_unselected_bg: ThemeRole::Surface.into(),
+            li_disabled: ThemeRole::DisabledText.into(),
+            li_disabled_bg: ThemeRole::Disabled.into(),
+            li_flash_bg: ThemeRole::Accent.into(),
+            flash_duration: Duration::from_millis(400),
+            motion: ListMotion::default(),
+            progress: ThemeRole::Accent.into(),
+            progress_track: ThemeRole::Disabled.into(),
+            panel: None,
+            corner_radius: 0.0,
+            border: None,
+            row_height: 20,
+            h_padding: 4,
+            v_padding: 4,
+            font_size: 16.0,
+            min_width: 120,
+            max_width: None,
+            column_gap: 8,
+            sounds: ListSounds::default(),
+            text_shadow: None,
+            text_outline: None,
+        }
+    }
+}
+
+impl ListStyle {
+    // The height of a line of font_size text, as TextCollection::line_metrics
+    // lays it out.
+    pub fn line_height(&self) -> f32 {
+        self.font_size / 0.8
+    }
+
+    // The text and background pairs rows are drawn with that fall short of
+    // WCAG AA in theme: a contrast of 4.5, or 3 for text of 24 pixels and
+    // up. Activated rows only flash by, and disabled text is exempt, so only
+    // selected and unselected rows are checked.
+    pub fn contrast_warnings(&self, theme: &Theme) -> Vec<String> {
+        let minimum = match self.font_size >= 24.0 {
+            true => 3.0,
+            false => 4.5,
+        };
+        [
+            ("selected", self.li_selected, self.li_selected_bg),
+            ("unselected", self.li_unselected, self.li_unselected_bg),
+        ]
+        .into_iter()
+        .filter_map(|(rows, text, bg)| {
+            let (text, bg) = (text.resolve(theme), bg.resolve(theme));
+            let ratio = text.contrast_ratio(bg);
+            (ratio < minimum).then(|| {
+                format!(
+                    "{} rows have {} text on {}, a contrast of {:.1} where {} is needed",
+                    rows, text, bg, ratio, minimum
+                )
+            })
+        })
+        .collect()
+    }
+
+    pub fn text_effect(&self, theme: &Theme) -> TextEffect {
+        TextEffect {
+            shadow: self.text_shadow.map(|shadow| shadow.resolve(theme)),
+            outline: self.text_outline.map(|outline| outline.resolve(theme)),
+        }
+    }
+}
+
+// How long the selection highlight and scrolling take to catch up with the
+// selection, and how they ease there. ListMotion::instant() snaps them, for
+// players who would rather nothing slid about.
+#[derive(Copy, Clone, PartialEq)]
+pub struct ListMotion {
+    pub duration: Duration,
+    pub easing: Easing,
+}
+
+impl Default for ListMotion {
+    fn default() -> Self {
+        Self {
+            duration: Duration::from_millis(120),
+            easing: Easing::CubicOut,
+        }
+    }
+}
+
+#[allow(dead_code)]
+impl ListMotion {
+    pub fn instant() -> Self {
+        Self {
+            duration: Duration::ZERO,
+            easing: Easing::Linear,
+        }
+    }
+
+    pub fn is_instant(&self) -> bool {
+        self.duration.is_zero()
+    }
+}
+
+// A value that eases toward wherever it was last sent, through a Value tween
+// in Context::tweens running from 0 to 1. Sending it somewhere new midway
+// starts over from where it is, so it never jumps.
+#[derive(Default)]
+pub struct Glide<T> {
+    pub current: T,
+    from: T,
+    to: T,
+    tween: Option<usize>,
+    started: bool,
+}
+
+impl<T> Glide<T>
+where
+    T: Copy + PartialEq + Add<Output = T> + Sub<Output = T> + Mul<f32, Output = T>,
+{
+    // Head for target and return where the value is now. The first target
+    // is taken as is.
+    pub fn update(&mut self, target: T, motion: ListMotion, tweens: &mut Tweens) -> T {
+        if let Some(tween) = self.tween {
+            let t = tweens.value(tween).unwrap_or(1.0);
+            self.current = self.from + (self.to - self.from) * t;
+            if tweens.is_finished(tween) {
+                tweens.remove(tween);
+                self.tween = None;
+            }
+        }
+        if !self.started || motion.is_instant() {
+            self.stop(tweens);
+            self.started = true;
+            (self.current, self.to) = (target, target);
+        } else if target != self.to {
+            self.stop(tweens);
+            (self.from, self.to) = (self.current, target);
+            let target = TweenTarget::Value { from: 0.0, to: 1.0 };
+            self.tween = Some(tweens.add(target, motion.duration, motion.easing));
+        }
+        self.current
+    }
+
+    // Where the value is headed.
+    pub fn target(&self) -> T {
+        self.to
+    }
+
+    // Jump straight to value.
+    pub fn set(&mut self, value: T, tweens: &mut Tweens) {
+        self.stop(tweens);
+        (self.current, self.to) = (value, value);
+        self.started = true;
+    }
+
+    // Stay where it is.
+    pub fn stop(&mut self, tweens: &mut Tweens) {
+        if let Some(tween) = self.tween.take() {
+            tweens.remove(tween);
         }
     }
 }
@@ -43,34 +253,136 @@ pub enum ListPopoutBehavior {
     HiddenWhenUnfocused,
 }
 
-#[allow(dead_code)]
 pub struct ListPopoutState {
-    behavior: ListPopoutBehavior,
-    speed: f32,
-    delta: f32,
+    pub behavior: ListPopoutBehavior,
+    // lists per second; 4.0 slides a whole list in or out in a quarter second
+    pub speed: f32,
+    // how much of the list is out, from 0 hidden to 1 shown
+    pub delta: f32,
+    // the slide in Context::tweens, and where it is headed
+    pub tween: Option<usize>,
+    target: f32,
 }
 
 impl Default for ListPopoutState {
     fn default() -> Self {
         ListPopoutState {
             behavior: ListPopoutBehavior::AlwaysVisible,
-            speed: 1.0,
+            speed: 4.0,
             delta: 1.0,
+            tween: None,
+            target: 1.0,
+        }
+    }
+}
+
+impl ListPopoutState {
+    // Read delta from the running slide, and start a new one when the list
+    // should head somewhere else.
+    pub fn update(&mut self, target: f32, tweens: &mut Tweens) {
+        if let Some(tween) = self.tween {
+            self.delta = tweens.value(tween).unwrap_or(self.target);
+            if tweens.is_finished(tween) {
+                self.delta = self.target;
+            }
+            if tweens.is_finished(tween) || self.target != target {
+                tweens.remove(tween);
+                self.tween = None;
+            }
+        }
+        if self.tween.is_none() && self.delta != target {
+            let seconds = (target - self.delta).abs() / self.speed.max(0.01);
+            self.tween = Some(tweens.add(
+                TweenTarget::Value {
+                    from: self.delta,
+                    to: target,
+                },
+                Duration::from_secs_f32(seconds),
+                Easing::CubicOut,
+            ));
+            self.target = target;
         }
     }
 }
 
+// Where layout_listui last placed a list, kept for inspection.
+#[derive(Default, Clone, Copy)]
+pub struct ListLayout {
+    pub visible: bool,
+    pub xy: IVec2,
+    pub wh: UVec2,
+    // horizontal and vertical
+    pub padding: UVec2,
+    pub content: UVec2,
+    // top left and size of the selected row, if it is shown
+    pub selected_row: Option<(IVec2, UVec2)>,
+    // where typed text goes during an edit, covering any composition
+    pub caret: Option<(IVec2, UVec2)>,
+}
+
+// A handle to a list in State::listuis.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct ListUiId(SlotKey);
+
+impl SlotId for ListUiId {
+    fn from_key(key: SlotKey) -> Self {
+        ListUiId(key)
+    }
+
+    fn key(self) -> SlotKey {
+        self.0
+    }
+}
+
 // A ListInterface provides navigation of a vertical list of items.
 #[allow(dead_code)]
 pub struct ListInterface {
+    // set by ListBuilder, and what the list is saved as by save_ui_state
+    pub name: String,
     pub style: ListStyle,
     pub anchor: ListAnchor,
+    // pixels between the list and the screen edges it is anchored to
+    pub margin: IVec2,
+    pub overflow: ListOverflow,
     pub focused: bool,
     pub popout: ListPopoutState,
     pub resume: ListResumeBehavior,
     pub selected_index: i32,
     pub entries: Vec<ListItem>,
-    pub render_group_index: usize,
+    pub render_group: GroupId,
+    pub visible_key: Option<String>,
+    pub edit_text: Option<String>,
+    // uncommitted input method text, shown after edit_text
+    pub composition: Option<String>,
+    pub layout: ListLayout,
+    // Sublists start closed and remember the list that opened them.
+    pub open: bool,
+    pub parent: Option<ListUiId>,
+    // How long an item stays selected before its tooltip shows,
+    // and which item was selected since when.
+    pub tooltip_delay: Duration,
+    pub selected_since: (i32, Instant),
+    // keeps the entries in step with part of the store, see watch_store
+    pub watch: Option<StoreWatch>,
+    // the part of a selected Vector item that Left and Right step
+    pub component: usize,
+    // what has been typed to jump to an item by its label, and when it was
+    // last typed; it starts over once search_timeout passes
+    pub search: (String, Instant),
+    pub search_timeout: Duration,
+    // the selection highlight, as x, y, width and height from the top left
+    // of the rows, and how far down the rows are scrolled, in pixels
+    pub cursor: Glide<Vec4>,
+    pub scroll: Glide<f32>,
+}
+
+// The store keys a watching list shows: every key starting with prefix, or
+// every key at all with an empty one.
+pub struct StoreWatch {
+    pub prefix: String,
+    // numbers and text can be typed over, and bools toggled; otherwise they
+    // are only shown
+    pub editable: bool,
 }
 
 // ListInterface implements custom rendering.
@@ -78,60 +390,518 @@ pub struct ListInterface {
 // To facilitate this, it holds a state machine
 // and renders in immediate mode.
 impl ListInterface {
-    pub fn default(render_group_index: usize) -> Self {
+    pub fn default(render_group: GroupId) -> Self {
         Self {
+            name: String::new(),
             style: ListStyle::default(),
             anchor: ListAnchor::Left,
+            margin: IVec2::ZERO,
+            overflow: ListOverflow::Offscreen,
             focused: false,
             popout: ListPopoutState::default(),
             resume: ListResumeBehavior::First,
             selected_index: 0,
             entries: vec![],
-            render_group_index,
+            render_group,
+            visible_key: None,
+            edit_text: None,
+            composition: None,
+            layout: ListLayout::default(),
+            open: true,
+            parent: None,
+            tooltip_delay: Duration::from_millis(600),
+            selected_since: (0, Instant::now()),
+            watch: None,
+            component: 0,
+            search: (String::new(), Instant::now()),
+            search_timeout: Duration::from_millis(1000),
+            cursor: Glide::default(),
+            scroll: Glide::default(),
         }
     }
 
-    pub fn add_labeled_value(&mut self, label: &str, value: Rc<RefCell<Value<dyn ListItemData>>>) {
-        self.entries.push(ListItem {
-            label: label.to_string(),
-            ty: ListItemType::Text,
-            selectable: ListItemSelectable::Selectable,
-            editable: ListItemEditable::NotEditable,
-            value,
+    // Let go of the tweens moving the list, as it is freed or removed.
+    pub fn remove_tweens(&mut self, tweens: &mut Tweens) {
+        if let Some(tween) = self.popout.tween.take() {
+            tweens.remove(tween);
+        }
+        self.cursor.stop(tweens);
+        self.scroll.stop(tweens);
+        for item in self.entries.iter_mut() {
+            if let Some(tween) = item.flash.take() {
+                tweens.remove(tween);
+            }
+        }
+    }
+
+    // Every store key this list reads, paired with what it is bound to.
+    pub fn bound_keys(&self) -> Vec<(String, String)> {
+        let mut keys = vec![];
+        if let Some(key) = &self.visible_key {
+            keys.push((key.clone(), "list visible".to_string()));
+        }
+        for item in &self.entries {
+            if let Some(key) = item.key() {
+                keys.push((key, item.label.clone()));
+            }
+            if let Some(key) = &item.visible_key {
+                keys.push((key.clone(), format!("{} visible", item.label)));
+            }
+            if let Some(key) = &item.enabled_key {
+                keys.push((key.clone(), format!("{} enabled", item.label)));
+            }
+        }
+        keys
+    }
+
+    // Bind visibility of the whole list to a boolean key in the ValueStore.
+    #[allow(dead_code)]
+    pub fn bind_visible(&mut self, key: &str) {
+        self.visible_key = Some(key.to_string());
+    }
+
+    pub fn is_visible(&self, store: &ValueStore) -> bool {
+        self.open && self.anchor != ListAnchor::Hidden && bound_bool(store, &self.visible_key)
+    }
+
+    pub fn is_editing(&self) -> bool {
+        self.edit_text.is_some()
+    }
+
+    // Start text entry on the selected item, seeded with its current value.
+    pub fn begin_edit(&mut self, store: &ValueStore) -> bool {
+        let Some(item) = self.entries.get(self.selected_index as usize) else {
+            return false;
+        };
+        if !matches!(item.editable, ListItemEditable::Editable) || !item.is_enabled(store) {
+            return false;
+        }
+        let Some(value) = item.read(store, |v| format!("{}", v)) else {
+            return false;
+        };
+        self.edit_text = Some(value);
+        self.composition = None;
+        true
+    }
+
+    // Write the entered text back to the store. The edit is consumed either way.
+    pub fn commit_edit(&mut self, store: &mut ValueStore) -> Result<(), ShecvError> {
+        self.composition = None;
+        let Some(text) = self.edit_text.take() else {
+            return Ok(());
+        };
+        self.entries[self.selected_index as usize].write_text(store, &text)
+    }
+
+    pub fn cancel_edit(&mut self) {
+        self.edit_text = None;
+        self.composition = None;
+    }
+
+    // The running edit, or the selected item's value as it would be typed in.
+    pub fn selected_text(&self, store: &ValueStore) -> Option<String> {
+        if let Some(edit_text) = &self.edit_text {
+            return Some(edit_text.clone());
+        }
+        let item = self.entries.get(self.selected_index as usize)?;
+        match item.ty {
+            ListItemType::Button | ListItemType::SubList => None,
+            _ => item.read(store, |v| format!("{}", v)),
+        }
+    }
+
+    // Pasting during an edit appends to it. Otherwise the text replaces the
+    // selected editable item's value, and is rejected if it doesn't parse as
+    // the stored type.
+    pub fn paste(&mut self, text: &str, store: &mut ValueStore) -> Result<(), ShecvError> {
+        // entries hold a single line
+        let text = text.lines().next().unwrap_or_default();
+        if let Some(edit_text) = self.edit_text.as_mut() {
+            edit_text.push_str(text);
+            return Ok(());
+        }
+        let Some(item) = self.entries.get(self.selected_index as usize) else {
+            return Ok(());
+        };
+        if !matches!(item.editable, ListItemEditable::Editable) || !item.is_enabled(store) {
+            return Ok(());
+        }
+        item.write_text(store, text)
+    }
+
+    // Nudge the selected slider, or the selected part of a vector, by its
+    // step in the given direction.
+    pub fn adjust_selected(
+        &mut self,
+        direction: f64,
+        store: &mut ValueStore,
+    ) -> Result<(), ShecvError> {
+        let Some(item) = self.entries.get(self.selected_index as usize) else {
+            return Ok(());
+        };
+        if !item.is_enabled(store) {
+            return Ok(());
+        }
+        match item.ty {
+            ListItemType::Vector => return item.step_component(self.component, direction, store),
+            ListItemType::Choice => return item.cycle_choice(direction as i32, store),
+            _ => {}
+        }
+        let Some(range) = &item.range else {
+            return Ok(());
+        };
+        let step = (range.end - range.start) / 20.0;
+        let shown = item.read(store, |v| {
+            (
+                v.is_float().then(|| v.as_f64()).flatten(),
+                v.as_any().is::<f32>(),
+            )
+        });
+        let Some((Some(v), is_f32)) = shown else {
+            return Ok(());
+        };
+        let v = (v + step * direction).clamp(range.start, range.end);
+        let value: Box<dyn ListItemData> = match is_f32 {
+            true => Box::new(v as f32),
+            false => Box::new(v),
+        };
+        item.write(store, value)
+    }
+
+    // Move which part of the selected Vector item is stepped, wrapping
+    // around at either end.
+    pub fn select_component(&mut self, direction: i32, store: &ValueStore) {
+        let Some(item) = self.entries.get(self.selected_index as usize) else {
+            return;
+        };
+        let count = item.read(store, |v| v.components().map(|c| c.len()));
+        if let Some(Some(count)) = count {
+            self.component = (self.component as i32 + direction).rem_euclid(count as i32) as usize;
+        }
+    }
+
+    // The store key of the selected item and its version, to tell afterwards
+    // whether it was written to, see edited_since.
+    pub fn selected_version(&self, store: &ValueStore) -> Option<(usize, String, Option<u64>)> {
+        let index = self.selected_index as usize;
+        let key = self.entries.get(index)?.key()?;
+        let version = store.version(&key);
+        Some((index, key, version))
+    }
+
+    pub fn edited_since(
+        id: ListUiId,
+        before: Option<(usize, String, Option<u64>)>,
+        store: &ValueStore,
+    ) -> Option<UiEvent> {
+        let (index, key, version) = before?;
+        (store.version(&key) != version).then_some(UiEvent::ValueEdited {
+            list: id,
+            index,
+            key,
         })
     }
 
+    // Step the selection to the next item that can be selected, wrapping
+    // around at either end. It stays put if there is no other.
+    pub fn move_selection(&mut self, direction: i32, store: &ValueStore) {
+        let count = self.entries.len() as i32;
+        let found = (1..=count)
+            .map(|n| (self.selected_index + n * direction).rem_euclid(count))
+            .find(|&i| self.entries[i as usize].can_select(store));
+        if let Some(index) = found {
+            self.selected_index = index;
+        }
+    }
+
+    // Select the first item that can be selected, or the first at all.
+    pub fn select_first(&mut self, store: &ValueStore) {
+        self.selected_index = self
+            .entries
+            .iter()
+            .position(|item| item.can_select(store))
+            .unwrap_or(0) as i32;
+    }
+
+    // The typed search, while it hasn't timed out.
+    pub fn active_search(&self) -> Option<&str> {
+        let (text, since) = &self.search;
+        (!text.is_empty() && since.elapsed() < self.search_timeout).then_some(text.as_str())
+    }
+
+    // Add typed text to the search and select the first shown, enabled item
+    // whose label starts with it, ignoring case. Typing the same letter over
+    // steps through the items starting with it instead.
+    pub fn type_ahead(&mut self, text: &str, store: &ValueStore) {
+        if self.active_search().is_none() {
+            self.search.0.clear();
+        }
+        self.search.0.push_str(&text.to_lowercase());
+        self.search.1 = Instant::now();
+        self.jump_to_search(store);
+    }
+
+    // Take the last character off the search. False if there was no search.
+    pub fn search_backspace(&mut self, store: &ValueStore) -> bool {
+        if self.active_search().is_none() {
+            return false;
+        }
+        self.search.0.pop();
+        self.search.1 = Instant::now();
+        self.jump_to_search(store);
+        true
+    }
+
+    pub fn clear_search(&mut self) -> bool {
+        let active = self.active_search().is_some();
+        self.search.0.clear();
+        active
+    }
+
+    fn jump_to_search(&mut self, store: &ValueStore) {
+        let search = self.search.0.as_str();
+        if search.is_empty() || self.entries.is_empty() {
+            return;
+        }
+        let mut chars = search.chars();
+        let first = chars.next();
+        let repeated = search.chars().count() > 1 && chars.all(|c| Some(c) == first);
+        // a repeated letter looks past the selection, a longer search from the top
+        let (prefix, start) = match repeated {
+            true => (
+                &search[..first.map_or(0, char::len_utf8)],
+                self.selected_index + 1,
+            ),
+            false => (search, 0),
+        };
+        let count = self.entries.len() as i32;
+        let found = (0..count)
+            .map(|n| (start + n).rem_euclid(count))
+            .find(|&i| {
+                let item = &self.entries[i as usize];
+                item.can_select(store) && item.label.to_lowercase().starts_with(prefix)
+            });
+        if let Some(index) = found {
+            self.selected_index = index;
+        }
+    }
+
+    // Show a row for every store key under prefix, labelled with the rest of
+    // the key. Rows come and go with the keys as the list is laid out, so the
+    // list works as a quick panel for looking at and tweaking values.
+    #[allow(dead_code)]
+    pub fn watch_store(&mut self, prefix: &str, editable: bool) {
+        self.watch = Some(StoreWatch {
+            prefix: prefix.to_string(),
+            editable,
+        });
+    }
+
+    // Relabel items that have a label_key, after a language switch or new
+    // text being loaded.
+    pub fn translate(&mut self, locale: &Localization) {
+        for item in &mut self.entries {
+            let Some(key) = &item.label_key else {
+                continue;
+            };
+            let text = locale.tr(key);
+            if item.label != text {
+                item.label = text.to_string();
+            }
+        }
+    }
+
+    // Make the entries match the watched keys, in key order. Entries whose
+    // keys are still there are kept as they are.
+    pub fn sync_watch(&mut self, store: &ValueStore) {
+        let Some(watch) = &self.watch else {
+            return;
+        };
+        let mut keys: Vec<&String> = store
+            .map
+            .keys()
+            .filter(|key| key.starts_with(&watch.prefix) && Some(*key) != self.visible_key.as_ref())
+            .collect();
+        keys.sort();
+        if keys.len() == self.entries.len()
+            && keys
+                .iter()
+                .zip(&self.entries)
+                .all(|(key, item)| item.key().as_ref() == Some(*key))
+        {
+            return;
+        }
+
+        let mut old = std::mem::take(&mut self.entries);
+        for key in keys {
+            if let Some(index) = old.iter().position(|item| item.key().as_ref() == Some(key)) {
+                self.entries.push(old.swap_remove(index));
+                continue;
+            }
+            let label = key[watch.prefix.len()..].trim_start_matches('.');
+            let label = if label.is_empty() { key } else { label };
+            let is_bool = store.load_as::<bool>(key).is_some();
+            let is_vector = store.map[key].components().is_some();
+            let (ty, editable) = match (is_bool, watch.editable) {
+                (true, true) => (ListItemType::CheckBox, ListItemEditable::NotEditable),
+                (false, true) if is_vector => (ListItemType::Vector, ListItemEditable::Editable),
+                (false, true) => (ListItemType::Text, ListItemEditable::Editable),
+                (_, false) => (ListItemType::Text, ListItemEditable::NotEditable),
+            };
+            self.entries.push(ListItem::new(
+                label,
+                ty,
+                ListItemSelectable::Selectable,
+                editable,
+                store.handle(key),
+            ));
+        }
+        self.selected_index = self
+            .selected_index
+            .min(self.entries.len() as i32 - 1)
+            .max(0);
+    }
+
+    #[allow(dead_code)]
+    pub fn add_labeled_value(&mut self, label: &str, value: ValueHandle) -> &mut ListItem {
+        self.entries.push(ListItem::new(
+            label,
+            ListItemType::Text,
+            ListItemSelectable::Selectable,
+            ListItemEditable::NotEditable,
+            value,
+        ));
+        self.entries.last_mut().unwrap()
+    }
+
+    #[allow(dead_code)]
     pub fn add_entry(
         &mut self,
         label: &str,
         ty: ListItemType,
         selectable: ListItemSelectable,
         editable: ListItemEditable,
-        value: Rc<RefCell<Value<dyn ListItemData>>>,
-    ) {
-        self.entries.push(ListItem {
-            label: label.to_string(),
-            ty,
-            selectable,
-            editable,
-            value,
-        })
+        value: ValueHandle,
+    ) -> &mut ListItem {
+        self.entries
+            .push(ListItem::new(label, ty, selectable, editable, value));
+        self.entries.last_mut().unwrap()
+    }
+}
+
+// An unbound state is always true. A bound key reads as true unless
+// the store holds a bool false under it.
+fn bound_bool(store: &ValueStore, key: &Option<String>) -> bool {
+    match key {
+        Some(key) => store.load_as::<bool>(key).unwrap_or(true),
+        None => true,
     }
 }
 
-// ListInterface can be anchored to left, middle, or right of screen.
+// ListInterface can be anchored to left, middle, or right of screen, as a
+// column down from the top. The rest of the anchors fit the list to its rows:
+// along an edge, in a corner, in the center, or at a pixel position.
 // When on a SubList, this determines the opening direction.
 // Opening a SubList to Middle causes it to replace the parent.
-#[derive(Default, PartialEq, Eq)]
+#[derive(Default, Debug, PartialEq, Eq)]
 #[allow(dead_code)]
 pub enum ListAnchor {
     #[default]
     Left,
     Middle,
     Right,
+    Top,
+    Center,
+    Bottom,
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+    // the top left corner, from the top left of the screen
+    Positioned(IVec2),
     Hidden,
 }
 
+impl ListAnchor {
+    // Left, Middle and Right reach down to the bottom of the screen.
+    pub fn fills_height(&self) -> bool {
+        matches!(
+            self,
+            ListAnchor::Left | ListAnchor::Middle | ListAnchor::Right
+        )
+    }
+
+    // Lists on the right slide out to the right, the rest to the left.
+    pub fn is_right(&self) -> bool {
+        matches!(
+            self,
+            ListAnchor::Right | ListAnchor::TopRight | ListAnchor::BottomRight
+        )
+    }
+
+    // Where the top left of a list of size goes. margin keeps it off the
+    // edges it is anchored to, and offsets a Positioned list.
+    pub fn position(&self, size: IVec2, extent: UVec2, margin: IVec2) -> IVec2 {
+        let extent = extent.as_ivec2();
+        let (left, middle, right) = (
+            margin.x,
+            (extent.x - size.x) / 2,
+            extent.x - size.x - margin.x,
+        );
+        let (top, center, bottom) = (
+            margin.y,
+            (extent.y - size.y) / 2,
+            extent.y - size.y - margin.y,
+        );
+        match self {
+            ListAnchor::Left | ListAnchor::TopLeft | ListAnchor::Hidden => IVec2::new(left, top),
+            ListAnchor::Middle | ListAnchor::Top => IVec2::new(middle, top),
+            ListAnchor::Right | ListAnchor::TopRight => IVec2::new(right, top),
+            ListAnchor::Center => IVec2::new(middle, center),
+            ListAnchor::Bottom => IVec2::new(middle, bottom),
+            ListAnchor::BottomLeft => IVec2::new(left, bottom),
+            ListAnchor::BottomRight => IVec2::new(right, bottom),
+            ListAnchor::Positioned(xy) => *xy + margin,
+        }
+    }
+}
+
+// What the lists did, for app code to react to without looking inside them.
+// Queued on State until taken with State::take_ui_events. Indices are into
+// the list's entries.
+#[derive(Debug, Clone, PartialEq)]
+pub enum UiEvent {
+    SelectionChanged {
+        list: ListUiId,
+        index: usize,
+    },
+    // Enter on a button, checkbox, choice or sublist
+    Activated {
+        list: ListUiId,
+        index: usize,
+    },
+    // the list wrote to the store key of an item
+    ValueEdited {
+        list: ListUiId,
+        index: usize,
+        key: String,
+    },
+    ListOpened(ListUiId),
+    ListClosed(ListUiId),
+}
+
+// What a list does with rows past the bottom of the screen: draw them there
+// anyway, wrap them into more columns to the right, style.column_gap apart,
+// or stop at the screen's edge and scroll to keep the selection in view.
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq)]
+#[allow(dead_code)]
+pub enum ListOverflow {
+    #[default]
+    Offscreen,
+    Columns,
+    Scroll,
+}
+
 // When a ListInterface is re-entered, this determines where the cursor starts.
 #[derive(Default)]
 #[allow(dead_code)]
@@ -153,7 +923,16 @@ pub enum ListResumeBehavior {
 //  - Requires ListInterface reference.
 // SubList causes a submenu to open left or right.
 //  - Requires a ListInterface reference; anchor is open direction.
-#[derive(Default)]
+// Vector steps one part of a Vec2, Vec3, IVec2 or ColorRGBA at a time.
+//  - Requires reference to memory value; colors show a swatch.
+// Choice cycles through a list of options.
+//  - Requires reference to memory value, an index or the option's text.
+// Progress fills a bar behind its value, which goes from 0 to 1 unless the
+// item has a range.
+//  - Requires reference to memory value; read only.
+// Sparkline graphs the Samples it's bound to, newest on the right.
+//  - Requires reference to memory value; read only.
+#[derive(Default, Clone, Copy, PartialEq, Eq)]
 #[allow(dead_code)]
 pub enum ListItemType {
     #[default]
@@ -163,6 +942,10 @@ pub enum ListItemType {
     Button,
     RowGroup,
     SubList,
+    Vector,
+    Choice,
+    Progress,
+    Sparkline,
 }
 
 #[derive(Default)]
@@ -187,12 +970,444 @@ pub enum OperatorResult {
     Irrelevant,
 }
 
+// What a Button runs when activated.
+pub type ListAction = Box<dyn FnMut(&mut ValueStore)>;
+
+pub type FormatFn = Box<dyn Fn(&dyn ListItemData) -> String>;
+
+// How a ListItem shows its value. Options other than Custom only change
+// numeric values, vectors and colors a part at a time; strings and bools are
+// always shown as they are.
+#[derive(Default)]
+#[allow(dead_code)]
+pub enum ValueFormat {
+    // floats with two decimals, integers as they are
+    #[default]
+    Default,
+    // fixed decimals
+    Precision(usize),
+    // 0.25 shows as "25%" with Percent(0)
+    Percent(usize),
+    // 1500 shows as "1.50k" with Si(2)
+    Si(usize),
+    Custom(FormatFn),
+}
+
+impl ValueFormat {
+    pub fn apply(&self, value: &dyn ListItemData) -> String {
+        if let ValueFormat::Custom(format) = self {
+            return format(value);
+        }
+        if let Some(components) = value.components() {
+            return self.apply_components(value, &components).join(", ");
+        }
+        let Some(v) = value.as_f64() else {
+            return format!("{}", value);
+        };
+        self.apply_f64(v, value.is_float())
+    }
+
+    // Each part of a vector as apply would show it on its own.
+    pub fn apply_components(&self, value: &dyn ListItemData, components: &[f64]) -> Vec<String> {
+        let is_float = !value.as_any().is::<IVec2>();
+        components
+            .iter()
+            .map(|&v| self.apply_f64(v, is_float))
+            .collect()
+    }
+
+    fn apply_f64(&self, v: f64, is_float: bool) -> String {
+        match self {
+            ValueFormat::Default if is_float => format!("{:.2}", v),
+            ValueFormat::Precision(p) => format!("{:.*}", p, v),
+            ValueFormat::Percent(p) => format!("{:.*}%", p, v * 100.0),
+            ValueFormat::Si(p) => {
+                const PREFIXES: [(f64, &str); 7] = [
+                    (1e12, "T"),
+                    (1e9, "G"),
+                    (1e6, "M"),
+                    (1e3, "k"),
+                    (1.0, ""),
+                    (1e-3, "m"),
+                    (1e-6, "µ"),
+                ];
+                let (scale, prefix) = PREFIXES
+                    .iter()
+                    .find(|(scale, _)| v.abs() >= *scale)
+                    .copied()
+                    .unwrap_or(if v == 0.0 { (1.0, "") } else { (1e-9, "n") });
+                format!("{:.*}{}", p, v / scale, prefix)
+            }
+            _ => format!("{}", v),
+        }
+    }
+}
+
 // ListItems have these options. They also contain data references.
 #[allow(dead_code)]
 pub struct ListItem {
     pub label: String,
+    // when set, label is the text for this key in the current language
+    pub label_key: Option<String>,
     pub ty: ListItemType,
     pub selectable: ListItemSelectable,
     pub editable: ListItemEditable,
-    pub value: Rc<RefCell<Value<dyn ListItemData>>>,
+    // labels, buttons and sublists have none
+    pub value: Option<ValueHandle>,
+    pub visible_key: Option<String>,
+    pub enabled_key: Option<String>,
+    // Slider bounds, both ends included.
+    pub range: Option<Range<f64>>,
+    pub action: Option<ListAction>,
+    // The list a SubList opens.
+    pub sublist: Option<ListUiId>,
+    // Draw the value as TextSpan markup, e.g. "12[#ff8000]ms[/]".
+    pub markup: bool,
+    pub format: ValueFormat,
+    pub tooltip: Option<String>,
+    // checks and converts what's written to the value, see Binding
+    pub binding: Binding,
+    // what a Choice picks between
+    pub options: Vec<String>,
+    // cluster and sub index of an image in the list's texture sheet, drawn
+    // left of the label
+    pub icon: Option<(usize, usize)>,
+    // switched off by set_enabled, on top of enabled_key
+    pub enabled: bool,
+    // flash the row when the value shown changes, and the tween fading it
+    // in Context::tweens and the value last shown
+    pub flash_on_change: bool,
+    pub flash: Option<usize>,
+    pub flash_value: Option<String>,
+}
+
+// Visible and enabled state of an item can also be bound to boolean store keys.
+// Hidden items are skipped by layout; disabled and unselectable items draw with
+// the disabled style, and Up and Down pass over them.
+#[allow(dead_code)]
+impl ListItem {
+    pub fn new(
+        label: &str,
+        ty: ListItemType,
+        selectable: ListItemSelectable,
+        editable: ListItemEditable,
+        value: ValueHandle,
+    ) -> Self {
+        Self {
+            editable,
+            value: Some(value),
+            ..Self::without_value(label, ty, selectable)
+        }
+    }
+
+    // A row that only shows its label, like a heading, button or sublist.
+    pub fn without_value(label: &str, ty: ListItemType, selectable: ListItemSelectable) -> Self {
+        Self {
+            label: label.to_string(),
+            label_key: None,
+            ty,
+            selectable,
+            editable: ListItemEditable::NotEditable,
+            value: None,
+            visible_key: None,
+            enabled_key: None,
+            range: None,
+            action: None,
+            sublist: None,
+            markup: false,
+            format: ValueFormat::Default,
+            tooltip: None,
+            binding: Binding::default(),
+            options: vec![],
+            icon: None,
+            enabled: true,
+            flash_on_change: false,
+            flash: None,
+            flash_value: None,
+        }
+    }
+
+    pub fn bind_visible(&mut self, key: &str) -> &mut Self {
+        self.visible_key = Some(key.to_string());
+        self
+    }
+
+    pub fn bind_enabled(&mut self, key: &str) -> &mut Self {
+        self.enabled_key = Some(key.to_string());
+        self
+    }
+
+    // Disabled items are drawn with the disabled style and skipped by Up and
+    // Down, whatever enabled_key says.
+    pub fn set_enabled(&mut self, enabled: bool) -> &mut Self {
+        self.enabled = enabled;
+        self
+    }
+
+    // Label the item with the text for key in the current language,
+    // following State::set_language.
+    pub fn with_label_key(&mut self, key: &str) -> &mut Self {
+        self.label_key = Some(key.to_string());
+        self
+    }
+
+    pub fn with_markup(&mut self) -> &mut Self {
+        self.markup = true;
+        self
+    }
+
+    pub fn with_format(&mut self, format: ValueFormat) -> &mut Self {
+        self.format = format;
+        self
+    }
+
+    pub fn with_tooltip(&mut self, tooltip: &str) -> &mut Self {
+        self.tooltip = Some(tooltip.to_string());
+        self
+    }
+
+    // Flash the row in the style's li_flash_bg when the value shown
+    // changes, e.g. for a score or a setting changed elsewhere.
+    pub fn flash_on_change(&mut self) -> &mut Self {
+        self.flash_on_change = true;
+        self
+    }
+
+    // The list needs a render group with a sheet to draw it from, see
+    // State::new_listui_with_sheet.
+    pub fn with_icon(&mut self, cluster_index: usize, sub_index: usize) -> &mut Self {
+        self.icon = Some((cluster_index, sub_index));
+        self
+    }
+
+    pub fn with_options(&mut self, options: &[&str]) -> &mut Self {
+        self.options = options.iter().map(|option| option.to_string()).collect();
+        self
+    }
+
+    pub fn with_binding(&mut self, binding: Binding) -> &mut Self {
+        self.binding = binding;
+        self
+    }
+
+    // The store key of the value, if the item has one.
+    pub fn key(&self) -> Option<String> {
+        self.value.as_ref().map(ValueHandle::key)
+    }
+
+    // The value as shown, handed to f. None once the key is gone, or for
+    // items without a value.
+    pub fn read<R>(&self, store: &ValueStore, f: impl FnOnce(&dyn ListItemData) -> R) -> Option<R> {
+        let stored = self.value.as_ref()?.load(store)?;
+        self.binding.show(stored, f)
+    }
+
+    pub fn write(
+        &self,
+        store: &mut ValueStore,
+        value: Box<dyn ListItemData>,
+    ) -> Result<(), ShecvError> {
+        let key = self.written_key()?;
+        self.binding.write(store, &key, value)
+    }
+
+    pub fn write_text(&self, store: &mut ValueStore, text: &str) -> Result<(), ShecvError> {
+        let key = self.written_key()?;
+        self.binding.write_text(store, &key, text)
+    }
+
+    fn written_key(&self) -> Result<String, ShecvError> {
+        self.key()
+            .ok_or_else(|| ShecvError::Store(format!("'{}' has no value to write", self.label)))
+    }
+
+    // Step one part of a vector. A range bounds every part and sets the step
+    // as for a slider; without one, floats step by a tenth, integers by one
+    // and color channels by a twentieth.
+    pub fn step_component(
+        &self,
+        component: usize,
+        direction: f64,
+        store: &mut ValueStore,
+    ) -> Result<(), ShecvError> {
+        let stepped = self.read(store, |v| {
+            let mut components = v.components()?;
+            let component = component.min(components.len() - 1);
+            let (step, range) = match &self.range {
+                Some(range) => ((range.end - range.start) / 20.0, range.clone()),
+                None if v.as_any().is::<IVec2>() => (1.0, f64::MIN..f64::MAX),
+                None if v.as_any().is::<ColorRGBA>() => (0.05, 0.0..1.0),
+                None => (0.1, f64::MIN..f64::MAX),
+            };
+            let part = components.get_mut(component)?;
+            *part = (*part + step * direction).clamp(range.start, range.end);
+            v.with_components(&components)
+        });
+        match stepped {
+            Some(Some(value)) => self.write(store, value),
+            _ => Ok(()),
+        }
+    }
+
+    // Which option a Choice is on. A String value is looked up among the
+    // options, a number is the index itself.
+    pub fn choice_index(&self, store: &ValueStore) -> Option<usize> {
+        self.read(store, |v| match v.as_any().downcast_ref::<String>() {
+            Some(text) => self.options.iter().position(|option| option == text),
+            None => v.as_f64().map(|index| index as usize),
+        })
+        .flatten()
+        .filter(|&index| index < self.options.len())
+    }
+
+    // Move a Choice to the next or previous option, wrapping around, and
+    // write it as the stored value's type: the option's text or its index.
+    pub fn cycle_choice(&self, direction: i32, store: &mut ValueStore) -> Result<(), ShecvError> {
+        if self.options.is_empty() {
+            return Ok(());
+        }
+        let count = self.options.len() as i32;
+        let index = match self.choice_index(store) {
+            Some(index) => (index as i32 + direction).rem_euclid(count) as usize,
+            None => 0,
+        };
+        let is_text = self.read(store, |v| v.as_any().is::<String>()) == Some(true);
+        let text = match is_text {
+            true => self.options[index].clone(),
+            false => index.to_string(),
+        };
+        self.write_text(store, &text)
+    }
+
+    // How full a Progress item's bar is.
+    pub fn progress(&self, store: &ValueStore) -> Option<f32> {
+        let range = self.range.clone().unwrap_or(0.0..1.0);
+        self.read(store, |v| v.as_f64().map(|v| progress_fraction(v, &range)))
+            .flatten()
+    }
+
+    // The newest samples of a Sparkline item that fit in count columns, each
+    // from 0 to 1 up the graph. The item's range sets the bottom and top of
+    // the graph, or else the lowest and highest samples do.
+    pub fn sparkline(&self, store: &ValueStore, count: usize) -> Option<Vec<f32>> {
+        self.read(store, |v| {
+            let samples = v.as_any().downcast_ref::<Samples>()?;
+            let range = match &self.range {
+                Some(range) => range.start as f32..range.end as f32,
+                None => samples.bounds()?,
+            };
+            let skip = samples.values.len().saturating_sub(count);
+            let heights = samples
+                .values
+                .iter()
+                .skip(skip)
+                .map(|&v| progress_fraction(v as f64, &(range.start as f64..range.end as f64)));
+            Some(heights.collect())
+        })
+        .flatten()
+    }
+
+    // The color a ColorRGBA item shows beside its value.
+    pub fn swatch(&self, store: &ValueStore) -> Option<ColorRGBA> {
+        self.read(store, |v| v.as_any().downcast_ref::<ColorRGBA>().copied())
+            .flatten()
+    }
+
+    pub fn is_visible(&self, store: &ValueStore) -> bool {
+        bound_bool(store, &self.visible_key)
+    }
+
+    pub fn is_enabled(&self, store: &ValueStore) -> bool {
+        self.enabled && bound_bool(store, &self.enabled_key)
+    }
+
+    pub fn is_selectable(&self) -> bool {
+        matches!(self.selectable, ListItemSelectable::Selectable)
+    }
+
+    // Whether Up and Down stop on the item: shown, enabled and selectable.
+    pub fn can_select(&self, store: &ValueStore) -> bool {
+        self.is_selectable() && self.is_visible(store) && self.is_enabled(store)
+    }
+}
+
+impl ListItem {
+    // Buttons and sublists read as plain labels, as do text items with nothing
+    // in them or no value at all.
+    pub fn display_label(&self, store: &ValueStore) -> String {
+        let empty = self
+            .read(store, |v| format!("{}", v).is_empty())
+            .unwrap_or(self.value.is_none());
+        match self.ty {
+            ListItemType::Button | ListItemType::SubList => self.label.clone(),
+            ListItemType::Text if empty => self.label.clone(),
+            _ => format!("{}: ", self.label),
+        }
+    }
+
+    pub fn display_value(&self, store: &ValueStore) -> String {
+        match self.ty {
+            ListItemType::Button => String::new(),
+            ListItemType::SubList => " >".to_string(),
+            _ if self.value.is_none() => String::new(),
+            ListItemType::Choice => match self.choice_index(store) {
+                Some(index) => format!("< {} >", self.options[index]),
+                None => "<missing>".to_string(),
+            },
+            ListItemType::CheckBox => {
+                match self.read(store, |v| v.as_any().downcast_ref::<bool>().copied()) {
+                    Some(Some(true)) => "[x]".to_string(),
+                    Some(Some(false)) => "[ ]".to_string(),
+                    _ => "<missing>".to_string(),
+                }
+            }
+            _ => self
+                .read(store, |v| self.format.apply(v))
+                .unwrap_or_else(|| "<missing>".to_string()),
+        }
+    }
+
+    // A Vector item's value with the part being stepped wrapped in markup
+    // colored color, to draw as TextSpans.
+    pub fn display_component_markup(
+        &self,
+        store: &ValueStore,
+        component: usize,
+        color: ColorRGBA,
+    ) -> Option<String> {
+        self.read(store, |v| {
+            let components = v.components()?;
+            let mut parts = self.format.apply_components(v, &components);
+            let part = parts.get_mut(component.min(components.len() - 1))?;
+            *part = format!("[{}]{}[/]", color.to_hex(), part);
+            Some(parts.join(", "))
+        })
+        .flatten()
+    }
+
+    // Start a flash if the value shown changed since the last layout, and
+    // let go of the last one once it has faded.
+    pub fn update_flash(&mut self, store: &ValueStore, duration: Duration, tweens: &mut Tweens) {
+        let value = self.display_value(store);
+        let changed = self.flash_value.as_ref().is_some_and(|last| *last != value);
+        if let Some(tween) = self
+            .flash
+            .filter(|&tween| changed || tweens.is_finished(tween))
+        {
+            tweens.remove(tween);
+            self.flash = None;
+        }
+        if changed {
+            let target = TweenTarget::Value { from: 1.0, to: 0.0 };
+            self.flash = Some(tweens.add(target, duration, Easing::QuadOut));
+        }
+        self.flash_value = Some(value);
+    }
+
+    // How far the row is toward li_flash_bg, from 0 to 1.
+    pub fn flash_amount(&self, tweens: &Tweens) -> f32 {
+        self.flash
+            .and_then(|tween| tweens.value(tween))
+            .unwrap_or(0.0)
+    }
 }
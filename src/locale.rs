@@ -0,0 +1,129 @@
+use std::collections::HashMap;
+
+use toml::Table;
+
+use crate::{assets, error::ShecvError, window::State};
+
+// Translated text by language and key. Items with a label_key are labelled
+// with the text for their key in the current language, so switching language
+// relabels every list from its next layout.
+#[derive(Default)]
+pub struct Localization {
+    languages: HashMap<String, HashMap<String, String>>,
+    current: Option<String>,
+    // looked in when the current language has no text for a key
+    fallback: Option<String>,
+}
+
+#[allow(dead_code)]
+impl Localization {
+    // Adds to a language's table, replacing any text already under the same
+    // keys. The first language added becomes current and the fallback.
+    pub fn add(&mut self, language: &str, texts: impl IntoIterator<Item = (String, String)>) {
+        self.languages
+            .entry(language.to_string())
+            .or_default()
+            .extend(texts);
+        if self.current.is_none() {
+            self.current = Some(language.to_string());
+        }
+        if self.fallback.is_none() {
+            self.fallback = Some(language.to_string());
+        }
+    }
+
+    // Adds a table of text per language from a TOML file. Keys may be
+    // nested tables, joined with dots:
+    //
+    //   [en]
+    //   menu.settings = "settings"
+    //   menu.quit = "quit"
+    //
+    //   [fr]
+    //   menu.settings = "paramètres"
+    //   menu.quit = "quitter"
+    pub fn load_file(&mut self, path: &str) -> Result<(), ShecvError> {
+        let text = assets::read_to_string(path)?;
+        let parse_error = |message: String| ShecvError::Parse(format!("{}: {}", path, message));
+        let document = text
+            .parse::<Table>()
+            .map_err(|e| parse_error(e.to_string()))?;
+        for (language, table) in &document {
+            let table = table
+                .as_table()
+                .ok_or_else(|| parse_error(format!("'{}' should be a table", language)))?;
+            let mut texts = vec![];
+            flatten(table, "", &mut texts).map_err(parse_error)?;
+            self.add(language, texts);
+        }
+        Ok(())
+    }
+
+    pub fn set_language(&mut self, language: &str) -> Result<(), ShecvError> {
+        if !self.languages.contains_key(language) {
+            return Err(ShecvError::Parse(format!(
+                "no language named '{}'",
+                language
+            )));
+        }
+        self.current = Some(language.to_string());
+        Ok(())
+    }
+
+    pub fn set_fallback(&mut self, language: &str) -> Result<(), ShecvError> {
+        if !self.languages.contains_key(language) {
+            return Err(ShecvError::Parse(format!(
+                "no language named '{}'",
+                language
+            )));
+        }
+        self.fallback = Some(language.to_string());
+        Ok(())
+    }
+
+    pub fn language(&self) -> Option<&str> {
+        self.current.as_deref()
+    }
+
+    // Sorted, for a choice item to switch between.
+    pub fn languages(&self) -> Vec<&str> {
+        let mut languages: Vec<&str> = self.languages.keys().map(String::as_str).collect();
+        languages.sort();
+        languages
+    }
+
+    // The text for key in the current language, else in the fallback, else
+    // the key itself so missing text shows up where it's missing.
+    pub fn tr<'a>(&'a self, key: &'a str) -> &'a str {
+        [&self.current, &self.fallback]
+            .into_iter()
+            .flatten()
+            .find_map(|language| self.languages.get(language)?.get(key))
+            .map_or(key, String::as_str)
+    }
+}
+
+fn flatten(table: &Table, prefix: &str, texts: &mut Vec<(String, String)>) -> Result<(), String> {
+    for (key, value) in table {
+        let key = match prefix {
+            "" => key.clone(),
+            _ => format!("{}.{}", prefix, key),
+        };
+        if let Some(text) = value.as_str() {
+            texts.push((key, text.to_string()));
+        } else if let Some(table) = value.as_table() {
+            flatten(table, &key, texts)?;
+        } else {
+            return Err(format!("'{}' should be a string", key));
+        }
+    }
+    Ok(())
+}
+
+#[allow(dead_code)]
+impl State<'_> {
+    // Every list relabels in the language from its next layout.
+    pub fn set_language(&mut self, language: &str) -> Result<(), ShecvError> {
+        self.locale.set_language(language)
+    }
+}
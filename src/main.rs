@@ -5,14 +5,67 @@ use log::info;
 use std::ops::DerefMut;
 use std::time::{Duration, Instant};
 use std::{cell::RefCell, error::Error, rc::Rc};
+mod access;
+mod anim;
+mod assets;
+mod atlas;
+mod audio;
+mod backend;
+mod background;
+mod binding;
+mod builder;
+mod camera;
+mod capture;
+mod compute;
+mod console;
+mod debug;
+mod derived;
+mod dialog;
+mod ecs;
+mod error;
+mod expr;
 mod geo;
+mod grid;
+mod history;
+mod hud;
+mod inspect;
+mod layer;
+mod layout;
+mod line;
 mod listui;
+mod locale;
+mod notify;
+mod params;
+mod particle;
+mod pipeline;
+mod post;
+mod progress;
+mod samples;
+mod script;
+#[cfg(feature = "sdl")]
+mod sdl_backend;
+mod slots;
+mod sprite_sheet;
 mod text;
+mod theme;
+mod timing;
+mod tooltip;
+mod tween;
 mod types;
+mod ui_file;
+mod ui_state;
 mod window;
+#[cfg(feature = "winit")]
+mod winit_backend;
+mod world_text;
 
-use types::{ColorRGBA, ComponentTransform, PixelRect, TextureSheetDefinition, ValueStore};
-use window::{process_events, State};
+#[cfg(not(any(feature = "sdl", feature = "winit")))]
+compile_error!("shecv needs a window backend, enable the sdl or winit feature");
+
+use error::ShecvError;
+use types::{ColorRGBA, PixelRect, TextureSheetDefinition, ValueStore};
+use ui_file::UiActions;
+use window::{process_events, FlowCommand, State, WindowState};
 
 fn main() -> Result<(), Box<dyn Error>> {
     pollster::block_on(init_loop())?;
@@ -23,76 +76,65 @@ async fn init_loop() -> Result<(), Box<dyn Error>> {
     env_logger::init();
     let app_start_time = Instant::now();
     let mut store = ValueStore::new();
-    let time = store.insert("time", 0.0_f64);
+    store.enable_history(100);
+    store.insert("time", 0.0_f64);
+    store.insert("frame_time", 0.0_f64);
+    store.derive("fps", &["frame_time"], |store| {
+        let frame_time = store.load_as::<f64>("frame_time")?;
+        (frame_time > 0.0).then(|| (1.0 / frame_time).round())
+    });
+    store.insert("scale", 1.0_f64);
+    store.insert("show_hud", true);
 
     let (width, height) = (640, 480);
-    let (sdl, mut state) = State::new(width, height, "SDL2/wgpu")?;
+    let (backend, mut state) = State::new(width, height, "SDL2/wgpu")?;
 
-    let listui_index = {
+    {
         state.new_context().await?;
-        let listui_index = state.new_listui()?;
-        let listui = &mut state.listuis[listui_index];
-        listui.add_labeled_value("time", Rc::clone(&time));
-        state.layout_listui(&store, listui_index)?;
-        listui_index
-    };
-
-    #[derive(Debug, Clone)]
-    #[allow(dead_code)]
-    struct ComponentRenderGroupInstance {
-        group: usize,
-        geo: usize,
+        // edit the file while running to rebuild the menu
+        state.load_ui_file("src/hud.toml", UiActions::new(), &mut store)?;
+        state.layout_listuis(&store)?;
     }
 
-    let (render_group_index, geo_index) = {
-        let context = state.context.as_mut().unwrap();
-        let config = context.config.lock().unwrap();
-        context.file_watcher.add_path("src/shader.wgsl");
-        let render_group_index = {
-            let shader_path = "src/shader.wgsl";
-            context.file_watcher.add_path(shader_path);
-            context.geos.new_unit_square(
-                GeoViewType::Perspective,
-                512,
-                config.format,
-                (config.width, config.height),
-                TextureSheetDefinition::default(),
-                shader_path,
-            )?
-        };
-
-        // let geo_wh = UVec2::new(70, 70);
-        let geo_index = context.geos.instance_groups[render_group_index].add_new(
-            context.queue.clone(),
-            ComponentTransform {
-                pixel_rect: None,
-                location: Vec3::new(-0.5, 0.5, -4.0) * 0.25,
-                rotation: Quat::IDENTITY,
-                scale: Vec3::ONE * 0.25,
-            },
-            0,
-            0,
-            ColorRGBA::magenta(),
-        );
-
-        (render_group_index, geo_index)
+    let render_group = {
+        let context = state.context.as_mut().ok_or_else(ShecvError::no_context)?;
+        let config = context.config.lock().map_err(ShecvError::from)?;
+        let shader_path = assets::DEFAULT_SHADER;
+        context.file_watcher.add_path(shader_path)?;
+        context.geos.new_unit_square(
+            GeoViewType::Perspective,
+            512,
+            config.format,
+            (config.width, config.height),
+            TextureSheetDefinition::default(),
+            shader_path,
+        )?
     };
 
     component! {
         playable: (),
-        render_instance: ComponentRenderGroupInstance,
     }
 
     let mut world = World::new();
 
-    // Spawn an entity
+    // Spawn an entity; the render sync gives it an instance
     EntityBuilder::new()
         .tag(playable())
         .set(
-            render_instance(),
-            ComponentRenderGroupInstance {
-                group: render_group_index,
-                geo: geo_index,
+            ecs::transform(),
+            ecs::Transform {
+                location: Vec3::new(-0.5, 0.5, -4.0) * 0.25,
+                rotation: Quat::IDENTITY,
+                scale: Vec3::ONE * 0.25,
+            },
+        )
+        .set(
+            ecs::sprite(),
+            ecs::Sprite {
+                group: render_group,
+                cluster_index: 0,
+                sub_index: 0,
+                color: ColorRGBA::magenta(),
             },
         )
         .spawn(&mut world);
@@ -100,43 +142,75 @@ async fn init_loop() -> Result<(), Box<dyn Error>> {
     let mut query = Query::new(playable());
     for _p in &mut query.borrow(&world) {}
 
-    let mut query = Query::new((playable(), render_instance().as_mut()));
-    for (_p, _ri) in &mut query.borrow(&world) {}
-
     {
-        use std::thread::sleep;
-
-        let sdl = Rc::new(RefCell::new(sdl));
+        let backend = Rc::new(RefCell::new(backend));
         let state = Rc::new(RefCell::new(state));
         let store = Rc::new(RefCell::new(store));
 
-        // nanos per frame at 60 fps: 16_666_667
-        // nanos per frame at 30 fps: 33_333_333
-        // nanos per frame at 15 fps: 66_666_667
-        let desired_frametime = Duration::new(0, 66_666_667);
+        // time stands still while paused
+        let mut paused = false;
+        let mut paused_for = Duration::ZERO;
 
         loop {
             let loop_start = Instant::now();
 
-            process_events(Rc::clone(&state), Rc::clone(&sdl), Rc::clone(&store))();
+            process_events(Rc::clone(&state), Rc::clone(&backend), Rc::clone(&store))();
 
             let mut state = state.borrow_mut();
-            match state.flow_command {
-                window::FlowCommand::Quit => break,
-                window::FlowCommand::None => {}
+            for command in store.borrow_mut().take_commands() {
+                match command {
+                    FlowCommand::Quit => state.flow_command = FlowCommand::Quit,
+                    FlowCommand::Pause => paused = true,
+                    FlowCommand::Resume => paused = false,
+                    FlowCommand::ReloadAssets => state.reload_assets(),
+                    FlowCommand::Custom(name) => info!("command: {}", name),
+                    FlowCommand::None => {}
+                }
             }
-            state.layout_listui(&store.borrow_mut(), listui_index)?;
-
-            let elapsed = loop_start.elapsed();
-            info!("ft: {:?}", elapsed);
-            if elapsed < desired_frametime {
-                sleep(desired_frametime - elapsed);
+            if state.flow_command == FlowCommand::Quit {
+                break;
             }
+            for window_event in state.take_window_events() {
+                match window_event {
+                    WindowState::Minimized => info!("minimized, pausing rendering"),
+                    WindowState::Restored => info!("restored"),
+                    // a dropped shader draws over the scene as a post effect
+                    WindowState::FileDropped(path) => {
+                        let is_shader = path.extension().is_some_and(|ext| ext == "wgsl");
+                        match (is_shader, state.context.as_mut()) {
+                            (true, Some(context)) => {
+                                if let Err(e) = context.add_post_effect(&path.to_string_lossy()) {
+                                    info!("couldn't load {}: {}", path.display(), e);
+                                }
+                            }
+                            _ => info!("dropped {}", path.display()),
+                        }
+                    }
+                }
+            }
+            for ui_event in state.take_ui_events() {
+                info!("ui: {:?}", ui_event);
+            }
+            if let Some(context) = state.context.as_mut() {
+                context.sync_render_system(&world)?;
+            }
+            store.borrow_mut().refresh_derived();
+            state.layout_listuis(&store.borrow())?;
 
-            let running_time = Box::new(app_start_time.elapsed().as_secs_f64());
+            info!("ft: {:?}", loop_start.elapsed());
+            state.pacer.wait();
+
+            if paused {
+                paused_for += loop_start.elapsed();
+            }
+            let running_time = Box::new((app_start_time.elapsed() - paused_for).as_secs_f64());
             let mut store_borrow = store.borrow_mut();
             let store = store_borrow.deref_mut();
             store.get("time").replace(running_time, store);
+            let frame_time = Box::new(loop_start.elapsed().as_secs_f64());
+            store.get("frame_time").replace(frame_time, store);
+            let frame_ms = loop_start.elapsed().as_secs_f32() * 1000.0;
+            store.push_sample("frame_ms", frame_ms, 60)?;
         }
     }
 
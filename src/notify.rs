@@ -0,0 +1,211 @@
+use std::time::{Duration, Instant};
+
+use glam::{IVec2, UVec2};
+
+use crate::{
+    error::ShecvError,
+    layer::RenderLayer,
+    text::TextCollection,
+    types::{ColorRGBA, InstanceShape, PixelRect},
+    window::State,
+};
+
+#[derive(Default, Copy, Clone, PartialEq, Eq)]
+#[allow(dead_code)]
+pub enum NotificationCorner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    #[default]
+    BottomRight,
+}
+
+pub struct Notification {
+    pub text: String,
+    pub color: ColorRGBA,
+    pub created: Instant,
+    pub duration: Duration,
+    // how many times it was pushed while still showing
+    pub count: u32,
+}
+
+impl Notification {
+    // The text, with how many times it was pushed if more than once.
+    pub fn label(&self) -> String {
+        match self.count {
+            0 | 1 => self.text.clone(),
+            count => format!("{} (x{})", self.text, count),
+        }
+    }
+}
+
+// Short lived messages stacked in a corner of the screen, newest nearest the corner.
+// Each fades out over the last `fade` of its duration and is then dropped.
+pub struct Notifications {
+    pub queue: Vec<Notification>,
+    pub corner: NotificationCorner,
+    pub max_visible: usize,
+    pub duration: Duration,
+    pub fade: Duration,
+    pub bg: ColorRGBA,
+}
+
+impl Default for Notifications {
+    fn default() -> Self {
+        Self {
+            queue: vec![],
+            corner: NotificationCorner::default(),
+            max_visible: 5,
+            duration: Duration::from_secs(3),
+            fade: Duration::from_millis(500),
+            bg: ColorRGBA::new(0.0, 0.0, 0.0, 0.8),
+        }
+    }
+}
+
+#[allow(dead_code)]
+impl Notifications {
+    pub fn push(&mut self, text: &str) {
+        self.push_with(text, ColorRGBA::white(), self.duration);
+    }
+
+    pub fn push_error(&mut self, text: &str) {
+        self.push_with(text, ColorRGBA::new(1.0, 0.4, 0.4, 1.0), self.duration * 2);
+    }
+
+    // Pushing what's already showing restarts it as the newest, counted,
+    // rather than stacking copies.
+    pub fn push_with(&mut self, text: &str, color: ColorRGBA, duration: Duration) {
+        let count = match self
+            .queue
+            .iter()
+            .position(|notification| notification.text == text && notification.color == color)
+        {
+            Some(index) => self.queue.remove(index).count + 1,
+            None => 1,
+        };
+        self.queue.push(Notification {
+            text: text.to_string(),
+            color,
+            created: Instant::now(),
+            duration,
+            count,
+        });
+    }
+
+    pub fn expire(&mut self) {
+        self.queue
+            .retain(|notification| notification.created.elapsed() < notification.duration);
+    }
+
+    fn opacity(&self, notification: &Notification) -> f32 {
+        let remaining = notification
+            .duration
+            .saturating_sub(notification.created.elapsed());
+        if self.fade.is_zero() {
+            return 1.0;
+        }
+        (remaining.as_secs_f32() / self.fade.as_secs_f32()).min(1.0)
+    }
+}
+
+impl State<'_> {
+    #[allow(dead_code)]
+    pub fn notify(&mut self, text: &str) {
+        self.notifications.push(text);
+    }
+
+    pub fn layout_notifications(&mut self) -> Result<(), ShecvError> {
+        self.notifications.expire();
+        if self.notifications.queue.is_empty() && self.notification_group.is_none() {
+            return Ok(());
+        }
+
+        let context = self.context.as_mut().ok_or_else(ShecvError::no_context)?;
+        let group_index =
+            context.overlay_group(&mut self.notification_group, 32, RenderLayer::Ui)?;
+        context.geos.instance_groups[group_index]
+            .instance_buffer_manager
+            .clear();
+        let extent = context.logical_size()?;
+
+        let notifications = &self.notifications;
+        let (line_height, pad, margin) = (20.0, 6, 8);
+        let metrics = TextCollection::line_metrics(line_height);
+        let mut offset = margin;
+        for notification in notifications
+            .queue
+            .iter()
+            .rev()
+            .take(notifications.max_visible)
+        {
+            let opacity = notifications.opacity(notification);
+            let label = notification.label();
+            let text_wh = context.texts.measure(&label, metrics);
+            let wh = UVec2::new(text_wh.0.ceil() as u32 + pad * 2, line_height as u32 + pad);
+
+            let x = match notifications.corner {
+                NotificationCorner::TopLeft | NotificationCorner::BottomLeft => margin,
+                _ => extent.x as i32 - wh.x as i32 - margin,
+            };
+            let y = match notifications.corner {
+                NotificationCorner::TopLeft | NotificationCorner::TopRight => offset,
+                _ => extent.y as i32 - wh.y as i32 - offset,
+            };
+            let xy = IVec2::new(x, y);
+            offset += wh.y as i32 + pad as i32;
+
+            let mut bg = notifications.bg;
+            bg.a *= opacity;
+            context.geos.instance_groups[group_index].add_shape(
+                context.queue.clone(),
+                PixelRect { xy, wh, extent },
+                InstanceShape::rounded(wh, 4.0, None),
+                bg,
+            )?;
+            let mut color = notification.color;
+            color.a *= opacity;
+            context.texts.new_text(
+                (
+                    (xy.x + pad as i32) as f64,
+                    (xy.y + pad as i32 / 2) as f64,
+                    text_wh.0.ceil() as f64 + 1.0,
+                    line_height as f64,
+                ),
+                &label,
+                1.0,
+                color,
+            );
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repeats_are_counted_not_stacked() {
+        let mut notifications = Notifications::default();
+        notifications.push_error("shader.wgsl: not found");
+        notifications.push("reloaded ui.toml");
+        notifications.push_error("shader.wgsl: not found");
+        assert_eq!(notifications.queue.len(), 2);
+        // the repeat moved to the newest
+        let newest = notifications.queue.last().unwrap();
+        assert_eq!(newest.count, 2);
+        assert_eq!(newest.label(), "shader.wgsl: not found (x2)");
+        assert_eq!(notifications.queue[0].label(), "reloaded ui.toml");
+    }
+
+    #[test]
+    fn same_text_in_another_color_is_separate() {
+        let mut notifications = Notifications::default();
+        notifications.push("done");
+        notifications.push_error("done");
+        assert_eq!(notifications.queue.len(), 2);
+        assert!(notifications.queue.iter().all(|n| n.count == 1));
+    }
+}
@@ -0,0 +1,171 @@
+use std::{mem::size_of, time::Instant};
+
+use bytemuck::{Pod, Zeroable};
+use glam::{Vec2, Vec4};
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    BindGroupLayoutEntry, BindingType, Buffer, BufferBindingType, BufferSize, BufferUsages, Device,
+    Queue, ShaderStages,
+};
+
+use crate::error::ShecvError;
+
+// Shared by every pipeline GeoManager makes, as `globals` in the shaders.
+// Positions are in physical pixels from the top left, like a fragment's
+// @builtin(position).
+#[derive(Copy, Clone, Default, Pod, Zeroable)]
+#[repr(C)]
+pub struct Globals {
+    // seconds since the GeoManager was made
+    pub time: f32,
+    // seconds since the last update
+    pub dt: f32,
+    pub resolution: Vec2,
+    // set by the app with Context::set_cursor_position
+    pub cursor: Vec2,
+    pub _pad: Vec2,
+}
+
+impl Globals {
+    pub fn layout_entry(binding: u32) -> BindGroupLayoutEntry {
+        BindGroupLayoutEntry {
+            binding,
+            visibility: ShaderStages::VERTEX | ShaderStages::FRAGMENT,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: BufferSize::new(size_of::<Globals>() as u64),
+            },
+            count: None,
+        }
+    }
+}
+
+// The shader sees these as `array<vec4<f32>, 16>`, one vec4 per param.
+pub const MAX_SHADER_PARAMS: usize = 16;
+
+#[derive(Copy, Clone, PartialEq)]
+#[allow(dead_code)]
+pub enum ShaderParam {
+    // in the slot's x
+    Float(f32),
+    Vec4(Vec4),
+    // seconds since the params were made, written every frame
+    Time,
+}
+
+impl From<f32> for ShaderParam {
+    fn from(value: f32) -> Self {
+        ShaderParam::Float(value)
+    }
+}
+
+impl From<Vec4> for ShaderParam {
+    fn from(value: Vec4) -> Self {
+        ShaderParam::Vec4(value)
+    }
+}
+
+impl ShaderParam {
+    fn to_vec4(self, time: f32) -> Vec4 {
+        match self {
+            ShaderParam::Float(value) => Vec4::new(value, 0.0, 0.0, 0.0),
+            ShaderParam::Vec4(value) => value,
+            ShaderParam::Time => Vec4::new(time, 0.0, 0.0, 0.0),
+        }
+    }
+}
+
+// Named values for a pipeline's shader, in a uniform buffer. Each param gets
+// the next slot as it's declared, so declare them in the order the shader
+// expects. Changes are written out by upload, once a frame.
+pub struct ShaderParams {
+    names: Vec<String>,
+    params: Vec<ShaderParam>,
+    pub buffer: Buffer,
+    dirty: bool,
+    start: Instant,
+}
+
+#[allow(dead_code)]
+impl ShaderParams {
+    pub fn new(device: &Device) -> Self {
+        Self {
+            names: vec![],
+            params: vec![],
+            buffer: device.create_buffer_init(&BufferInitDescriptor {
+                label: Some("shader params"),
+                contents: bytemuck::cast_slice(&[Vec4::ZERO; MAX_SHADER_PARAMS]),
+                usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            }),
+            dirty: false,
+            start: Instant::now(),
+        }
+    }
+
+    pub fn layout_entry(binding: u32) -> BindGroupLayoutEntry {
+        BindGroupLayoutEntry {
+            binding,
+            visibility: ShaderStages::VERTEX | ShaderStages::FRAGMENT,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: BufferSize::new((size_of::<Vec4>() * MAX_SHADER_PARAMS) as u64),
+            },
+            count: None,
+        }
+    }
+
+    // Returns the param's slot. Declaring a name again just sets it.
+    pub fn declare(&mut self, name: &str, initial: ShaderParam) -> Result<usize, ShecvError> {
+        if let Some(slot) = self.slot(name) {
+            self.params[slot] = initial;
+            self.dirty = true;
+            return Ok(slot);
+        }
+        if self.names.len() >= MAX_SHADER_PARAMS {
+            return Err(ShecvError::Shader(format!(
+                "no room for param {}; a shader gets {}",
+                name, MAX_SHADER_PARAMS
+            )));
+        }
+        self.names.push(name.to_string());
+        self.params.push(initial);
+        self.dirty = true;
+        Ok(self.names.len() - 1)
+    }
+
+    pub fn slot(&self, name: &str) -> Option<usize> {
+        self.names.iter().position(|n| n == name)
+    }
+
+    pub fn get(&self, name: &str) -> Option<ShaderParam> {
+        self.slot(name).map(|slot| self.params[slot])
+    }
+
+    pub fn set(&mut self, name: &str, value: impl Into<ShaderParam>) -> Result<(), ShecvError> {
+        let slot = self
+            .slot(name)
+            .ok_or_else(|| ShecvError::Shader(format!("no param named {}", name)))?;
+        let value = value.into();
+        if self.params[slot] != value {
+            self.params[slot] = value;
+            self.dirty = true;
+        }
+        Ok(())
+    }
+
+    pub fn upload(&mut self, queue: &Queue) {
+        let has_time = self.params.contains(&ShaderParam::Time);
+        if !self.dirty && !has_time {
+            return;
+        }
+        let time = self.start.elapsed().as_secs_f32();
+        let mut values = [Vec4::ZERO; MAX_SHADER_PARAMS];
+        for (value, param) in values.iter_mut().zip(&self.params) {
+            *value = param.to_vec4(time);
+        }
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&values));
+        self.dirty = false;
+    }
+}
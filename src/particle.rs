@@ -0,0 +1,272 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use glam::{Quat, Vec3};
+use wgpu::Queue;
+
+use crate::{
+    error::ShecvError,
+    geo::{GeoManager, GroupId},
+    slots::SlotId,
+    types::{ColorRGBA, ComponentTransform, Instance, InstanceShape},
+};
+
+pub trait CurveValue: Copy {
+    fn lerp(self, other: Self, t: f32) -> Self;
+}
+
+impl CurveValue for f32 {
+    fn lerp(self, other: Self, t: f32) -> Self {
+        self + (other - self) * t
+    }
+}
+
+impl CurveValue for Vec3 {
+    fn lerp(self, other: Self, t: f32) -> Self {
+        Vec3::lerp(self, other, t)
+    }
+}
+
+impl CurveValue for ColorRGBA {
+    fn lerp(self, other: Self, t: f32) -> Self {
+        ColorRGBA::lerp(self, other, t)
+    }
+}
+
+// A value over a particle's life: keys at ages from 0 (born) to 1 (gone),
+// in order, with straight lines between them.
+#[derive(Clone)]
+pub struct Curve<T: CurveValue> {
+    pub keys: Vec<(f32, T)>,
+}
+
+#[allow(dead_code)]
+impl<T: CurveValue> Curve<T> {
+    pub fn constant(value: T) -> Self {
+        Self {
+            keys: vec![(0.0, value)],
+        }
+    }
+
+    pub fn linear(from: T, to: T) -> Self {
+        Self {
+            keys: vec![(0.0, from), (1.0, to)],
+        }
+    }
+
+    pub fn sample(&self, t: f32) -> Option<T> {
+        let first = self.keys.first()?;
+        if t <= first.0 {
+            return Some(first.1);
+        }
+        for pair in self.keys.windows(2) {
+            let ((t0, v0), (t1, v1)) = (pair[0], pair[1]);
+            if t <= t1 {
+                let span = t1 - t0;
+                let along = if span > 0.0 { (t - t0) / span } else { 1.0 };
+                return Some(v0.lerp(v1, along));
+            }
+        }
+        self.keys.last().map(|key| key.1)
+    }
+}
+
+#[derive(Clone)]
+pub struct EmitterSettings {
+    // particles per second while emitting
+    pub rate: f32,
+    // seconds, picked between the two for each particle
+    pub lifetime: (f32, f32),
+    // starting velocity, give or take up to spread on each axis
+    pub velocity: Vec3,
+    pub spread: Vec3,
+    // applied every second, e.g. gravity
+    pub acceleration: Vec3,
+    // scales the velocity a particle would have had
+    pub velocity_over_life: Curve<f32>,
+    // the quad's width and height
+    pub size_over_life: Curve<f32>,
+    pub color_over_life: Curve<ColorRGBA>,
+    // the cell of the group's texture sheet each quad shows
+    pub cluster_index: usize,
+    pub sub_index: usize,
+}
+
+impl Default for EmitterSettings {
+    fn default() -> Self {
+        Self {
+            rate: 20.0,
+            lifetime: (1.0, 1.5),
+            velocity: Vec3::new(0.0, 40.0, 0.0),
+            spread: Vec3::new(20.0, 10.0, 0.0),
+            acceleration: Vec3::ZERO,
+            velocity_over_life: Curve::constant(1.0),
+            size_over_life: Curve::linear(8.0, 2.0),
+            color_over_life: Curve::linear(ColorRGBA::white(), ColorRGBA::new(1.0, 1.0, 1.0, 0.0)),
+            cluster_index: 0,
+            sub_index: 0,
+        }
+    }
+}
+
+struct Particle {
+    location: Vec3,
+    velocity: Vec3,
+    age: f32,
+    lifetime: f32,
+}
+
+// Spawns, moves and ages particles on the cpu, and rewrites its instance
+// group with one quad per live particle every update. The group is the
+// emitter's alone; anything else put in it is replaced.
+pub struct ParticleEmitter {
+    pub group: GroupId,
+    // where particles are born, in the group's space
+    pub position: Vec3,
+    pub settings: EmitterSettings,
+    // stop spawning, letting live particles finish
+    pub emitting: bool,
+    particles: Vec<Particle>,
+    // part of a particle owed from the last update
+    spawn_carry: f32,
+    seed: u32,
+}
+
+#[allow(dead_code)]
+impl ParticleEmitter {
+    pub fn new(group: GroupId, position: Vec3, settings: EmitterSettings) -> Self {
+        Self {
+            group,
+            position,
+            settings,
+            emitting: true,
+            particles: vec![],
+            spawn_carry: 0.0,
+            seed: 0x9e37_79b9 ^ (group.key().index as u32 + 1).wrapping_mul(0x85eb_ca6b),
+        }
+    }
+
+    pub fn live_count(&self) -> usize {
+        self.particles.len()
+    }
+
+    // Spawn a number of particles at once, emitting or not.
+    pub fn burst(&mut self, count: usize) {
+        for _ in 0..count {
+            self.spawn();
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.particles.clear();
+        self.spawn_carry = 0.0;
+    }
+
+    // xorshift, plenty for scattering particles; 0..1
+    fn random(&mut self) -> f32 {
+        self.seed ^= self.seed << 13;
+        self.seed ^= self.seed >> 17;
+        self.seed ^= self.seed << 5;
+        (self.seed >> 8) as f32 / (1 << 24) as f32
+    }
+
+    fn spawn(&mut self) {
+        let (min, max) = self.settings.lifetime;
+        let lifetime = min + (max - min) * self.random();
+        let jitter = Vec3::new(
+            self.random() * 2.0 - 1.0,
+            self.random() * 2.0 - 1.0,
+            self.random() * 2.0 - 1.0,
+        );
+        self.particles.push(Particle {
+            location: self.position,
+            velocity: self.settings.velocity + jitter * self.settings.spread,
+            age: 0.0,
+            lifetime: lifetime.max(0.001),
+        });
+    }
+
+    pub fn update(
+        &mut self,
+        dt: Duration,
+        geos: &mut GeoManager,
+        queue: Arc<Mutex<Queue>>,
+    ) -> Result<(), ShecvError> {
+        let dt = dt.as_secs_f32();
+        if self.emitting {
+            self.spawn_carry += self.settings.rate.max(0.0) * dt;
+            while self.spawn_carry >= 1.0 {
+                self.spawn_carry -= 1.0;
+                self.spawn();
+            }
+        }
+
+        let settings = &self.settings;
+        self.particles.retain_mut(|particle| {
+            particle.age += dt;
+            if particle.age >= particle.lifetime {
+                return false;
+            }
+            let t = particle.age / particle.lifetime;
+            particle.velocity += settings.acceleration * dt;
+            let speed = settings.velocity_over_life.sample(t).unwrap_or(1.0);
+            particle.location += particle.velocity * speed * dt;
+            true
+        });
+
+        let Some(group) = geos.instance_groups.get_mut(self.group) else {
+            return Ok(());
+        };
+        let sheet = &group.sheet;
+        let instances = self
+            .particles
+            .iter()
+            .map(|particle| {
+                let t = particle.age / particle.lifetime;
+                let size = settings.size_over_life.sample(t).unwrap_or(1.0);
+                Instance {
+                    needs_update: false,
+                    // the unit square hangs down and right from its origin
+                    transform: ComponentTransform {
+                        pixel_rect: None,
+                        location: particle.location + Vec3::new(-size, size, 0.0) / 2.0,
+                        rotation: Quat::IDENTITY,
+                        scale: Vec3::new(size, size, 1.0),
+                    },
+                    tex_transform: sheet
+                        .cluster_sub_transform(settings.cluster_index, settings.sub_index),
+                    color: settings
+                        .color_over_life
+                        .sample(t)
+                        .unwrap_or(ColorRGBA::white()),
+                    shape: InstanceShape::default(),
+                    layer: 0,
+                }
+            })
+            .collect();
+        group.instance_buffer_manager.replace_all(queue, instances)
+    }
+}
+
+// Every emitter, updated with the rest of the context. GPU simulation can
+// come later as a compute job writing the same instance groups.
+#[derive(Default)]
+pub struct ParticleEmitters {
+    pub emitters: Vec<ParticleEmitter>,
+}
+
+impl ParticleEmitters {
+    pub fn update(
+        &mut self,
+        dt: Duration,
+        geos: &mut GeoManager,
+        queue: Arc<Mutex<Queue>>,
+    ) -> Result<(), ShecvError> {
+        for emitter in self.emitters.iter_mut() {
+            emitter.update(dt, geos, queue.clone())?;
+        }
+        Ok(())
+    }
+}
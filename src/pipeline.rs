@@ -0,0 +1,246 @@
+use std::{borrow::Cow, collections::HashMap, mem::size_of};
+
+use glam::{Mat4, Vec2};
+use wgpu::{
+    BindGroupLayout, BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingType, BlendState,
+    BufferBindingType, BufferSize, ColorTargetState, ColorWrites, Device, FragmentState,
+    MultisampleState, PipelineLayout, PrimitiveState, RenderPipeline, RenderPipelineDescriptor,
+    SamplerBindingType, ShaderModule, ShaderModuleDescriptor, ShaderSource, ShaderStages,
+    TextureFormat, TextureSampleType, TextureViewDimension, VertexState,
+};
+
+use crate::{
+    assets::read_to_string,
+    error::{validate, ShecvError},
+    layer::{RenderLayer, RenderLayers},
+    params::{Globals, ShaderParams},
+    types::{RenderPipelineRecord, UNIT_SQUARE_BUFFER_LAYOUT},
+};
+
+// Instance groups with the same key draw with the same pipeline. Every group
+// has the same bind group and vertex layouts, so past the shader and format
+// the only thing that tells pipelines apart is the blending of the group's
+// layer.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PipelineKey {
+    pub shader_path: String,
+    pub format: TextureFormat,
+    pub layer: RenderLayer,
+}
+
+// Pipelines for instance groups, each built once however many groups use it.
+// Reloading a shader rebuilds every pipeline made from it, so all the groups
+// sharing them change together. Pipelines stay cached after their groups are
+// removed, ready for the next group that asks.
+pub struct PipelineCache {
+    pub bind_group_layout: BindGroupLayout,
+    records: HashMap<PipelineKey, RenderPipelineRecord>,
+}
+
+#[allow(dead_code)]
+impl PipelineCache {
+    pub fn new(device: &Device) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("instance group bind group layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::VERTEX,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: BufferSize::new((size_of::<Mat4>()) as u64),
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: BufferSize::new((size_of::<Vec2>()) as u64),
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: TextureViewDimension::D2,
+                        sample_type: TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                    count: None,
+                },
+                ShaderParams::layout_entry(4),
+                Globals::layout_entry(5),
+                // every layer of the sheet, which binding 2 is the first of
+                BindGroupLayoutEntry {
+                    binding: 6,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: TextureViewDimension::D2Array,
+                        sample_type: TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+            ],
+        });
+        Self {
+            bind_group_layout,
+            records: HashMap::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn get(&self, key: &PipelineKey) -> Option<&RenderPipelineRecord> {
+        self.records.get(key)
+    }
+
+    // Build the key's pipeline unless it's already cached.
+    pub fn prepare(
+        &mut self,
+        device: &Device,
+        key: &PipelineKey,
+        layers: &RenderLayers,
+    ) -> Result<(), ShecvError> {
+        if self.records.contains_key(key) {
+            return Ok(());
+        }
+        let source = read_to_string(&key.shader_path)?;
+        let record = validate(device, &key.shader_path, || {
+            let shader_module = device.create_shader_module(ShaderModuleDescriptor {
+                label: Some(&key.shader_path),
+                source: ShaderSource::Wgsl(Cow::Borrowed(&*source)),
+            });
+            let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: None,
+                bind_group_layouts: &[&self.bind_group_layout],
+                push_constant_ranges: &[],
+            });
+            RenderPipelineRecord {
+                render_pipeline: instance_pipeline(
+                    device,
+                    &pipeline_layout,
+                    &shader_module,
+                    key,
+                    layers.get(key.layer).blend,
+                ),
+                pipeline_layout,
+                shader_module,
+                shader_path: key.shader_path.clone(),
+                format: key.format,
+            }
+        })?;
+        self.records.insert(key.clone(), record);
+        Ok(())
+    }
+
+    // Rebuild every pipeline made from shader_path. If any of them fails,
+    // they all keep what they had.
+    pub fn reload_shader(
+        &mut self,
+        device: &Device,
+        shader_path: &str,
+        layers: &RenderLayers,
+    ) -> Result<(), ShecvError> {
+        let keys: Vec<PipelineKey> = self
+            .records
+            .keys()
+            .filter(|key| key.shader_path == shader_path)
+            .cloned()
+            .collect();
+        if keys.is_empty() {
+            return Ok(());
+        }
+        let source = read_to_string(shader_path)?;
+        let mut rebuilt = vec![];
+        for key in keys {
+            let record = &self.records[&key];
+            let (shader_module, render_pipeline) = validate(device, shader_path, || {
+                let shader_module = device.create_shader_module(ShaderModuleDescriptor {
+                    label: Some(&*format!("shader {}", shader_path)),
+                    source: ShaderSource::Wgsl(Cow::Borrowed(&*source)),
+                });
+                let render_pipeline = instance_pipeline(
+                    device,
+                    &record.pipeline_layout,
+                    &shader_module,
+                    &key,
+                    layers.get(key.layer).blend,
+                );
+                (shader_module, render_pipeline)
+            })?;
+            rebuilt.push((key, shader_module, render_pipeline));
+        }
+        for (key, shader_module, render_pipeline) in rebuilt {
+            let record = self.records.get_mut(&key).unwrap();
+            record.shader_module = shader_module;
+            record.render_pipeline = render_pipeline;
+        }
+        Ok(())
+    }
+
+    // Rebuild the pipelines of a layer after its blending changed.
+    pub fn rebuild_layer(&mut self, device: &Device, layer: RenderLayer, blend: BlendState) {
+        for (key, record) in self.records.iter_mut() {
+            if key.layer == layer {
+                record.render_pipeline = instance_pipeline(
+                    device,
+                    &record.pipeline_layout,
+                    &record.shader_module,
+                    key,
+                    blend,
+                );
+            }
+        }
+    }
+}
+
+fn instance_pipeline(
+    device: &Device,
+    pipeline_layout: &PipelineLayout,
+    shader_module: &ShaderModule,
+    key: &PipelineKey,
+    blend: BlendState,
+) -> RenderPipeline {
+    device.create_render_pipeline(&RenderPipelineDescriptor {
+        label: Some(&*format!("pipeline {}", key.shader_path)),
+        layout: Some(pipeline_layout),
+        vertex: VertexState {
+            module: shader_module,
+            entry_point: "vs_main",
+            buffers: &UNIT_SQUARE_BUFFER_LAYOUT,
+            compilation_options: Default::default(),
+        },
+        fragment: Some(FragmentState {
+            module: shader_module,
+            entry_point: "fs_main",
+            targets: &[Some(ColorTargetState {
+                format: key.format,
+                blend: Some(blend),
+                write_mask: ColorWrites::ALL,
+            })],
+            compilation_options: Default::default(),
+        }),
+        primitive: PrimitiveState {
+            cull_mode: None,
+            ..Default::default()
+        },
+        depth_stencil: None,
+        multisample: MultisampleState::default(),
+        multiview: None,
+        cache: None,
+    })
+}
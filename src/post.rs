@@ -0,0 +1,305 @@
+use std::{borrow::Cow, mem::size_of, time::Instant};
+
+use glam::Vec4;
+use wgpu::{
+    BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor,
+    BindGroupLayoutEntry, BindingResource, BindingType, Buffer, BufferBindingType,
+    BufferDescriptor, BufferSize, BufferUsages, ColorTargetState, ColorWrites, CommandEncoder,
+    Device, Extent3d, FragmentState, LoadOp, MultisampleState, Operations, PipelineLayout,
+    PrimitiveState, Queue, RenderPassColorAttachment, RenderPassDescriptor, RenderPipeline,
+    RenderPipelineDescriptor, Sampler, SamplerBindingType, ShaderModuleDescriptor, ShaderSource,
+    ShaderStages, Texture, TextureDescriptor, TextureDimension, TextureFormat, TextureSampleType,
+    TextureUsages, TextureView, TextureViewDescriptor, TextureViewDimension, VertexState,
+};
+
+use crate::assets::read_to_string;
+use crate::error::{validate, ShecvError};
+
+// Put ahead of every effect's own code. An effect only writes fs_main, reading
+// the frame so far from source at the vertex's uv.
+const PRELUDE: &str = "
+struct PostUniform {
+    // in physical pixels
+    resolution: vec2<f32>,
+    // seconds since the chain was made
+    time: f32,
+    _pad: f32,
+};
+
+@group(0) @binding(0)
+var source: texture_2d<f32>;
+@group(0) @binding(1)
+var source_sampler: sampler;
+@group(0) @binding(2)
+var<uniform> post: PostUniform;
+
+struct PostVertex {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+// one triangle covering the screen
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> PostVertex {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: PostVertex;
+    out.position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
+";
+
+pub struct PostEffect {
+    pub shader_path: String,
+    pub enabled: bool,
+    pipeline: RenderPipeline,
+}
+
+// The two textures effects read from and write to in turn, sized to the frame.
+struct PostTargets {
+    width: u32,
+    height: u32,
+    #[allow(dead_code)]
+    textures: [Texture; 2],
+    views: [TextureView; 2],
+    // reading from the texture of the same index
+    bind_groups: [BindGroup; 2],
+}
+
+// Full screen effects run in order over the finished frame. With none enabled
+// the layers are drawn straight to the frame as usual.
+pub struct PostChain {
+    pub effects: Vec<PostEffect>,
+    format: TextureFormat,
+    bind_group_layout: BindGroupLayout,
+    pipeline_layout: PipelineLayout,
+    sampler: Sampler,
+    uniform: Buffer,
+    targets: Option<PostTargets>,
+    start: Instant,
+}
+
+impl PostChain {
+    pub fn new(device: &Device, format: TextureFormat) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("post bind group layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: TextureViewDimension::D2,
+                        sample_type: TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: BufferSize::new(size_of::<Vec4>() as u64),
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let uniform = device.create_buffer(&BufferDescriptor {
+            label: Some("post uniform"),
+            size: size_of::<Vec4>() as u64,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        Self {
+            effects: vec![],
+            format,
+            bind_group_layout,
+            pipeline_layout,
+            sampler,
+            uniform,
+            targets: None,
+            start: Instant::now(),
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.effects.iter().any(|effect| effect.enabled)
+    }
+
+    // Effects run in the order they're added, enabled.
+    pub fn add_effect(&mut self, device: &Device, shader_path: &str) -> Result<usize, ShecvError> {
+        let pipeline = self.pipeline(device, shader_path)?;
+        self.effects.push(PostEffect {
+            shader_path: shader_path.to_string(),
+            enabled: true,
+            pipeline,
+        });
+        Ok(self.effects.len() - 1)
+    }
+
+    pub fn reload_shader(&mut self, device: &Device, shader_path: &str) -> Result<(), ShecvError> {
+        for index in 0..self.effects.len() {
+            if self.effects[index].shader_path == shader_path {
+                self.effects[index].pipeline = self.pipeline(device, shader_path)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn pipeline(&self, device: &Device, shader_path: &str) -> Result<RenderPipeline, ShecvError> {
+        let source = format!("{}\n{}", PRELUDE, read_to_string(shader_path)?);
+        validate(device, shader_path, || {
+            let module = device.create_shader_module(ShaderModuleDescriptor {
+                label: Some(shader_path),
+                source: ShaderSource::Wgsl(Cow::Owned(source)),
+            });
+            device.create_render_pipeline(&RenderPipelineDescriptor {
+                label: Some(&*format!("post pipeline {}", shader_path)),
+                layout: Some(&self.pipeline_layout),
+                vertex: VertexState {
+                    module: &module,
+                    entry_point: "vs_main",
+                    buffers: &[],
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(FragmentState {
+                    module: &module,
+                    entry_point: "fs_main",
+                    targets: &[Some(ColorTargetState {
+                        format: self.format,
+                        blend: None,
+                        write_mask: ColorWrites::ALL,
+                    })],
+                    compilation_options: Default::default(),
+                }),
+                primitive: PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: MultisampleState::default(),
+                multiview: None,
+                cache: None,
+            })
+        })
+    }
+
+    // Where the layers should be drawn this frame. Call only while active.
+    pub fn scene_view(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        width: u32,
+        height: u32,
+    ) -> &TextureView {
+        let (width, height) = (width.max(1), height.max(1));
+        if self
+            .targets
+            .as_ref()
+            .is_none_or(|targets| targets.width != width || targets.height != height)
+        {
+            self.targets = Some(self.make_targets(device, width, height));
+        }
+        let uniform = Vec4::new(
+            width as f32,
+            height as f32,
+            self.start.elapsed().as_secs_f32(),
+            0.0,
+        );
+        queue.write_buffer(&self.uniform, 0, bytemuck::cast_slice(&[uniform]));
+        &self.targets.as_ref().unwrap().views[0]
+    }
+
+    fn make_targets(&self, device: &Device, width: u32, height: u32) -> PostTargets {
+        let textures = [0, 1].map(|_| {
+            device.create_texture(&TextureDescriptor {
+                label: Some("post target"),
+                size: Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format: self.format,
+                usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            })
+        });
+        let views = [0, 1].map(|i| textures[i].create_view(&TextureViewDescriptor::default()));
+        let bind_groups = [0, 1].map(|i| {
+            device.create_bind_group(&BindGroupDescriptor {
+                label: Some("post bind group"),
+                layout: &self.bind_group_layout,
+                entries: &[
+                    BindGroupEntry {
+                        binding: 0,
+                        resource: BindingResource::TextureView(&views[i]),
+                    },
+                    BindGroupEntry {
+                        binding: 1,
+                        resource: BindingResource::Sampler(&self.sampler),
+                    },
+                    BindGroupEntry {
+                        binding: 2,
+                        resource: self.uniform.as_entire_binding(),
+                    },
+                ],
+            })
+        });
+        PostTargets {
+            width,
+            height,
+            textures,
+            views,
+            bind_groups,
+        }
+    }
+
+    // Run the enabled effects over the scene, the last one writing to output.
+    pub fn apply(&self, encoder: &mut CommandEncoder, output: &TextureView) {
+        let Some(targets) = &self.targets else {
+            return;
+        };
+        let enabled: Vec<&PostEffect> = self.effects.iter().filter(|e| e.enabled).collect();
+        for (i, effect) in enabled.iter().enumerate() {
+            let view = match i + 1 == enabled.len() {
+                true => output,
+                false => &targets.views[(i + 1) % 2],
+            };
+            let mut pass = encoder.begin_render_pass(&RenderPassDescriptor {
+                label: Some("post pass"),
+                color_attachments: &[Some(RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: Operations {
+                        load: LoadOp::Clear(wgpu::Color::BLACK),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            pass.set_pipeline(&effect.pipeline);
+            pass.set_bind_group(0, &targets.bind_groups[i % 2], &[]);
+            pass.draw(0..3, 0..1);
+        }
+    }
+}
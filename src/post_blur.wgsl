@@ -0,0 +1,14 @@
+// a small box blur, spread over a few pixels
+const radius: f32 = 2.0;
+
+@fragment
+fn fs_main(vin: PostVertex) -> @location(0) vec4<f32> {
+    let pixel = radius / post.resolution;
+    var sum = vec4(0.0);
+    for (var x = -1; x <= 1; x++) {
+        for (var y = -1; y <= 1; y++) {
+            sum += textureSample(source, source_sampler, vin.uv + vec2(f32(x), f32(y)) * pixel);
+        }
+    }
+    return sum / 9.0;
+}
@@ -0,0 +1,18 @@
+// curved screen, scanlines and a slight flicker
+@fragment
+fn fs_main(vin: PostVertex) -> @location(0) vec4<f32> {
+    var uv = vin.uv * 2.0 - 1.0;
+    uv = uv * (1.0 + 0.04 * dot(uv.yx, uv.yx));
+    uv = uv * 0.5 + 0.5;
+    if uv.x < 0.0 || uv.x > 1.0 || uv.y < 0.0 || uv.y > 1.0 {
+        return vec4(0.0, 0.0, 0.0, 1.0);
+    }
+    // fringe red and blue apart by a pixel
+    let pixel = 1.0 / post.resolution;
+    let r = textureSampleLevel(source, source_sampler, uv + vec2(pixel.x, 0.0), 0.0).r;
+    let ga = textureSampleLevel(source, source_sampler, uv, 0.0).ga;
+    let b = textureSampleLevel(source, source_sampler, uv - vec2(pixel.x, 0.0), 0.0).b;
+    let scanline = 0.85 + 0.15 * sin(uv.y * post.resolution.y * 3.14159);
+    let flicker = 0.98 + 0.02 * sin(post.time * 60.0);
+    return vec4(vec3(r, ga.x, b) * scanline * flicker, ga.y);
+}
@@ -0,0 +1,8 @@
+// brightens midtones; raise gamma for more
+const gamma: f32 = 1.2;
+
+@fragment
+fn fs_main(vin: PostVertex) -> @location(0) vec4<f32> {
+    let color = textureSample(source, source_sampler, vin.uv);
+    return vec4(pow(color.rgb, vec3(1.0 / gamma)), color.a);
+}
@@ -0,0 +1,8 @@
+// darkens toward the corners
+@fragment
+fn fs_main(vin: PostVertex) -> @location(0) vec4<f32> {
+    let color = textureSample(source, source_sampler, vin.uv);
+    let from_center = vin.uv - vec2(0.5);
+    let shade = smoothstep(0.8, 0.3, length(from_center));
+    return vec4(color.rgb * mix(0.35, 1.0, shade), color.a);
+}
@@ -0,0 +1,172 @@
+use std::{
+    ops::Range,
+    sync::{Arc, Mutex},
+};
+
+use glam::{IVec2, UVec2};
+use wgpu::Queue;
+
+use crate::{
+    error::ShecvError,
+    geo::GeoInstances,
+    layer::RenderLayer,
+    listui::ValueFormat,
+    text::TextCollection,
+    theme::{StyleColor, Theme, ThemeRole},
+    types::{ColorRGBA, ComponentTransform, ListItemData, PixelRect, ValueStore},
+    window::State,
+};
+
+// A bar that fills with a store value, on its own rather than in a list,
+// e.g. for a loading screen or a resource meter. See State::add_progress_bar.
+pub struct ProgressBar {
+    pub key: String,
+    pub xy: IVec2,
+    pub wh: UVec2,
+    // the values shown as empty and as full
+    pub range: Range<f64>,
+    pub fill: StyleColor,
+    pub track: StyleColor,
+    // the value written over the bar, or nothing
+    pub text: Option<ValueFormat>,
+}
+
+#[allow(dead_code)]
+impl ProgressBar {
+    pub fn new(key: &str, xy: IVec2, wh: UVec2) -> Self {
+        Self {
+            key: key.to_string(),
+            xy,
+            wh,
+            range: 0.0..1.0,
+            fill: ThemeRole::Accent.into(),
+            track: ThemeRole::Disabled.into(),
+            text: Some(ValueFormat::Percent(0)),
+        }
+    }
+
+    // The track and fill into group, and the value written over them.
+    pub fn draw(
+        &self,
+        value: &dyn ListItemData,
+        group: &mut GeoInstances,
+        queue: Arc<Mutex<Queue>>,
+        texts: &mut TextCollection,
+        theme: &Theme,
+        extent: UVec2,
+    ) -> Result<(), ShecvError> {
+        let rect = PixelRect {
+            xy: self.xy,
+            wh: self.wh,
+            extent,
+        };
+        let fraction = value
+            .as_f64()
+            .map_or(0.0, |v| progress_fraction(v, &self.range));
+        add_progress_rects(
+            group,
+            queue,
+            rect,
+            fraction,
+            self.track.resolve(theme),
+            self.fill.resolve(theme),
+        )?;
+        if let Some(format) = &self.text {
+            let text = format.apply(value);
+            let metrics = TextCollection::line_metrics(self.wh.y as f32);
+            let width = texts.measure(&text, metrics).0.ceil();
+            texts.new_text(
+                (
+                    (self.xy.x as f32 + (self.wh.x as f32 - width) / 2.0) as f64,
+                    self.xy.y as f64,
+                    width as f64 + 1.0,
+                    self.wh.y as f64,
+                ),
+                &text,
+                1.0,
+                theme.color(ThemeRole::Text),
+            );
+        }
+        Ok(())
+    }
+}
+
+// How full a bar showing value is, from 0 to 1.
+pub fn progress_fraction(value: f64, range: &Range<f64>) -> f32 {
+    let span = range.end - range.start;
+    if span == 0.0 {
+        return 0.0;
+    }
+    ((value - range.start) / span).clamp(0.0, 1.0) as f32
+}
+
+// The track, then the filled part of it from the left.
+pub fn add_progress_rects(
+    group: &mut GeoInstances,
+    queue: Arc<Mutex<Queue>>,
+    rect: PixelRect,
+    fraction: f32,
+    track: ColorRGBA,
+    fill: ColorRGBA,
+) -> Result<(), ShecvError> {
+    group.add_new(
+        queue.clone(),
+        ComponentTransform::unit_square_transform_from_pixel_rect(rect),
+        0,
+        0,
+        track,
+    )?;
+    let filled = (rect.wh.x as f32 * fraction).round() as u32;
+    if filled == 0 {
+        return Ok(());
+    }
+    let fill_rect = PixelRect {
+        wh: UVec2::new(filled, rect.wh.y),
+        ..rect
+    };
+    group.add_new(
+        queue,
+        ComponentTransform::unit_square_transform_from_pixel_rect(fill_rect),
+        0,
+        0,
+        fill,
+    )?;
+    Ok(())
+}
+
+impl State<'_> {
+    #[allow(dead_code)]
+    pub fn add_progress_bar(&mut self, bar: ProgressBar) -> usize {
+        self.progress_bars.push(bar);
+        self.progress_bars.len() - 1
+    }
+
+    pub fn layout_progress_bars(&mut self, store: &ValueStore) -> Result<(), ShecvError> {
+        if self.progress_bars.is_empty() && self.progress_group.is_none() {
+            return Ok(());
+        }
+
+        let context = self.context.as_mut().ok_or_else(ShecvError::no_context)?;
+        let group_index = context.overlay_group(&mut self.progress_group, 32, RenderLayer::Ui)?;
+        let extent = context.logical_size()?;
+        let group = &mut context.geos.instance_groups[group_index];
+        group.instance_buffer_manager.clear();
+        let theme = self.themes.current();
+
+        for bar in &self.progress_bars {
+            let Some(value) = store.map.get(&bar.key) else {
+                continue;
+            };
+            bar.draw(
+                value.as_ref(),
+                group,
+                context.queue.clone(),
+                &mut context.texts,
+                theme,
+                extent,
+            )?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,55 @@
+use std::{collections::VecDeque, fmt::Display, ops::Range};
+
+use crate::types::ListItemData;
+
+// The last capacity values of something, oldest first, for a Sparkline item
+// to graph. Pushed by the app with ValueStore::push_sample. Shows as the
+// newest value.
+#[derive(Clone)]
+pub struct Samples {
+    pub values: VecDeque<f32>,
+    pub capacity: usize,
+}
+
+#[allow(dead_code)]
+impl Samples {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            values: VecDeque::with_capacity(capacity),
+            capacity: capacity.max(1),
+        }
+    }
+
+    pub fn push(&mut self, value: f32) {
+        if self.values.len() == self.capacity {
+            self.values.pop_front();
+        }
+        self.values.push_back(value);
+    }
+
+    pub fn latest(&self) -> Option<f32> {
+        self.values.back().copied()
+    }
+
+    // The lowest and highest values held, for scaling a graph. None while
+    // there are none.
+    pub fn bounds(&self) -> Option<Range<f32>> {
+        let first = self.latest()?;
+        let (min, max) = self
+            .values
+            .iter()
+            .fold((first, first), |(min, max), &v| (min.min(v), max.max(v)));
+        Some(min..max)
+    }
+}
+
+impl Display for Samples {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.latest() {
+            Some(value) => write!(f, "{:.2}", value),
+            None => write!(f, "-"),
+        }
+    }
+}
+
+impl ListItemData for Samples {}
@@ -0,0 +1,469 @@
+#[cfg(feature = "scripting")]
+use std::{cell::RefCell, rc::Rc};
+
+#[cfg(feature = "scripting")]
+use log::{info, warn};
+#[cfg(feature = "scripting")]
+use rhai::{Dynamic, Engine, FuncArgs, Scope, AST};
+
+#[cfg(feature = "scripting")]
+use crate::{
+    assets,
+    types::{ListItemData, ToAny},
+};
+use crate::{
+    error::ShecvError,
+    listui::{ListInterface, ListUiId, UiEvent},
+    slots::Slots,
+    types::ValueStore,
+    window::State,
+};
+
+// Rhai scripts for gameplay and ui logic, with the scripting feature. A
+// script's top level runs when it's loaded and again whenever the file
+// changes, so it can be edited while the app runs:
+//
+//   // menu.rhai
+//   on_button("reset volume", "reset");
+//   on_change("volume", "volume_changed");
+//
+//   fn reset() { set("volume", 0.5); }
+//   fn volume_changed(v) { set("muted", v == 0.0); }
+//
+//   state.load_script("menu.rhai", &mut store)?;
+//
+// Scripts read and write the store with get(key), set(key, value) and
+// has(key). set parses value as the type already stored, or stores a new
+// bool, int, float or string; vectors and colors are arrays. on_button runs
+// a function when a row with that label is activated and on_change when a
+// list edits a key, given the new value. print goes to the log. A file that
+// no longer compiles keeps running as it was, and its error is returned.
+//
+// Without the feature loading a script is an error and the rest does
+// nothing, so apps call it the same either way.
+#[derive(Default)]
+pub struct Scripts {
+    #[cfg(feature = "scripting")]
+    host: Option<Rc<RefCell<ScriptHost>>>,
+}
+
+#[cfg(feature = "scripting")]
+struct ScriptFile {
+    path: String,
+    ast: AST,
+    // variables the top level left behind, for its functions to use
+    scope: Scope<'static>,
+    changed: bool,
+}
+
+#[cfg(feature = "scripting")]
+#[derive(Default)]
+struct Bindings {
+    // the file running its top level, which the bindings made now belong to
+    loading: usize,
+    // (file, row label, function)
+    buttons: Vec<(usize, String, String)>,
+    // (file, store key, function)
+    changes: Vec<(usize, String, String)>,
+}
+
+#[cfg(feature = "scripting")]
+struct ScriptHost {
+    engine: Engine,
+    // the app's store, lent here while a script runs, see with_store
+    store: Rc<RefCell<ValueStore>>,
+    bindings: Rc<RefCell<Bindings>>,
+    files: Vec<ScriptFile>,
+    // variables made by lines from the console
+    console_scope: Scope<'static>,
+}
+
+#[cfg(feature = "scripting")]
+impl ScriptHost {
+    fn new() -> Self {
+        let store = Rc::new(RefCell::new(ValueStore::new()));
+        let bindings = Rc::new(RefCell::new(Bindings::default()));
+        let mut engine = Engine::new();
+        engine.on_print(|text| info!("script: {}", text));
+
+        let get_store = store.clone();
+        engine.register_fn("get", move |key: &str| {
+            get_store
+                .borrow()
+                .map
+                .get(key)
+                .map_or(Dynamic::UNIT, |value| to_dynamic(value.as_ref()))
+        });
+        let has_store = store.clone();
+        engine.register_fn("has", move |key: &str| has_store.borrow().contains(key));
+        let set_store = store.clone();
+        engine.register_fn(
+            "set",
+            move |key: &str, value: Dynamic| -> Result<(), Box<rhai::EvalAltResult>> {
+                set_value(&mut set_store.borrow_mut(), key, value).map_err(|e| e.to_string().into())
+            },
+        );
+        let button_bindings = bindings.clone();
+        engine.register_fn("on_button", move |label: &str, function: &str| {
+            let mut bindings = button_bindings.borrow_mut();
+            let file = bindings.loading;
+            bindings
+                .buttons
+                .push((file, label.to_string(), function.to_string()));
+        });
+        let change_bindings = bindings.clone();
+        engine.register_fn("on_change", move |key: &str, function: &str| {
+            let mut bindings = change_bindings.borrow_mut();
+            let file = bindings.loading;
+            bindings
+                .changes
+                .push((file, key.to_string(), function.to_string()));
+        });
+
+        Self {
+            engine,
+            store,
+            bindings,
+            files: vec![],
+            console_scope: Scope::new(),
+        }
+    }
+
+    // Scripts see store as theirs until run returns.
+    fn with_store<T>(&self, store: &mut ValueStore, run: impl FnOnce(&Self) -> T) -> T {
+        std::mem::swap(store, &mut self.store.borrow_mut());
+        let result = run(self);
+        std::mem::swap(store, &mut self.store.borrow_mut());
+        result
+    }
+
+    fn load(&mut self, path: &str, store: &mut ValueStore) -> Result<(), ShecvError> {
+        let index = match self.files.iter().position(|file| file.path == path) {
+            Some(index) => index,
+            None => {
+                self.files.push(ScriptFile {
+                    path: path.to_string(),
+                    ast: AST::empty(),
+                    scope: Scope::new(),
+                    changed: false,
+                });
+                self.files.len() - 1
+            }
+        };
+        self.run_file(index, store)
+    }
+
+    // Compile the file again and run its top level, dropping what it bound
+    // before. A file that doesn't compile is left as it was.
+    fn run_file(&mut self, index: usize, store: &mut ValueStore) -> Result<(), ShecvError> {
+        let path = self.files[index].path.clone();
+        let script_error = |message: String| ShecvError::Script(format!("{}: {}", path, message));
+        let text = assets::read_to_string(&path)?;
+        let ast = self
+            .engine
+            .compile(&text)
+            .map_err(|e| script_error(e.to_string()))?;
+        {
+            let mut bindings = self.bindings.borrow_mut();
+            bindings.buttons.retain(|(file, ..)| *file != index);
+            bindings.changes.retain(|(file, ..)| *file != index);
+            bindings.loading = index;
+        }
+        let mut scope = Scope::new();
+        let result = self.with_store(store, |host| {
+            host.engine.run_ast_with_scope(&mut scope, &ast)
+        });
+        let file = &mut self.files[index];
+        file.ast = ast;
+        file.scope = scope;
+        result.map_err(|e| script_error(e.to_string()))
+    }
+
+    fn call(
+        &mut self,
+        index: usize,
+        function: &str,
+        args: impl FuncArgs,
+        store: &mut ValueStore,
+    ) -> Result<Dynamic, ShecvError> {
+        std::mem::swap(store, &mut self.store.borrow_mut());
+        let file = &mut self.files[index];
+        let result = self
+            .engine
+            .call_fn::<Dynamic>(&mut file.scope, &file.ast, function, args);
+        std::mem::swap(store, &mut self.store.borrow_mut());
+        result.map_err(|e| ShecvError::Script(format!("{}: {}: {}", file.path, function, e)))
+    }
+
+    // The file that defines function, the last loaded when several do.
+    fn find(&self, function: &str) -> Option<usize> {
+        self.files
+            .iter()
+            .rposition(|file| file.ast.iter_functions().any(|f| f.name == function))
+    }
+
+    // Run a line from the console, which can call any loaded file's functions.
+    fn eval(&mut self, code: &str, store: &mut ValueStore) -> Result<Dynamic, ShecvError> {
+        let ast = self
+            .engine
+            .compile(code)
+            .map_err(|e| ShecvError::Script(e.to_string()))?;
+        let ast = self
+            .files
+            .iter()
+            .fold(AST::empty(), |merged, file| {
+                merged.merge(&file.ast.clone_functions_only())
+            })
+            .merge(&ast);
+        std::mem::swap(store, &mut self.store.borrow_mut());
+        let result = self
+            .engine
+            .eval_ast_with_scope::<Dynamic>(&mut self.console_scope, &ast);
+        std::mem::swap(store, &mut self.store.borrow_mut());
+        result.map_err(|e| ShecvError::Script(e.to_string()))
+    }
+}
+
+#[cfg(feature = "scripting")]
+fn to_dynamic(value: &dyn ListItemData) -> Dynamic {
+    let any = value.as_any();
+    if let Some(v) = any.downcast_ref::<bool>() {
+        Dynamic::from_bool(*v)
+    } else if let Some(v) = any.downcast_ref::<String>() {
+        Dynamic::from(v.clone())
+    } else if let Some(components) = value.components() {
+        Dynamic::from_array(components.into_iter().map(Dynamic::from_float).collect())
+    } else if let Some(v) = value.as_f64() {
+        match value.is_float() {
+            true => Dynamic::from_float(v),
+            false => Dynamic::from_int(v as i64),
+        }
+    } else {
+        Dynamic::from(value.to_string())
+    }
+}
+
+#[cfg(feature = "scripting")]
+fn set_value(store: &mut ValueStore, key: &str, value: Dynamic) -> Result<(), ShecvError> {
+    let type_error = || {
+        ShecvError::Store(format!(
+            "can't store a {} under '{}'",
+            value.type_name(),
+            key
+        ))
+    };
+    let Some(current) = store.map.get(key) else {
+        let boxed: Box<dyn ListItemData> = if let Ok(v) = value.as_bool() {
+            Box::new(v)
+        } else if let Ok(v) = value.as_int() {
+            Box::new(v)
+        } else if let Ok(v) = value.as_float() {
+            Box::new(v)
+        } else if value.is_string() {
+            Box::new(value.to_string())
+        } else {
+            return Err(type_error());
+        };
+        store.write(key, boxed);
+        return Ok(());
+    };
+    if value.is_array() {
+        let parts: Option<Vec<f64>> = value
+            .clone()
+            .into_array()
+            .map_err(|_| type_error())?
+            .iter()
+            .map(|part| {
+                part.as_float()
+                    .ok()
+                    .or(part.as_int().ok().map(|i| i as f64))
+            })
+            .collect();
+        let boxed = parts
+            .and_then(|parts| current.with_components(&parts))
+            .ok_or_else(type_error)?;
+        store.write(key, boxed);
+        return Ok(());
+    }
+    store.commit_text(key, &value.to_string())
+}
+
+#[allow(dead_code)]
+impl Scripts {
+    #[cfg(feature = "scripting")]
+    fn host(&mut self) -> Rc<RefCell<ScriptHost>> {
+        self.host
+            .get_or_insert_with(|| Rc::new(RefCell::new(ScriptHost::new())))
+            .clone()
+    }
+
+    // Whether scripts run, i.e. shecv was built with the scripting feature.
+    pub fn enabled(&self) -> bool {
+        cfg!(feature = "scripting")
+    }
+
+    // Called for each watched file that changed, so its script is run again
+    // by the next update.
+    pub fn file_changed(&mut self, path: &str) {
+        #[cfg(feature = "scripting")]
+        if let Some(host) = &self.host {
+            for file in host.borrow_mut().files.iter_mut() {
+                file.changed |= file.path == path;
+            }
+        }
+        #[cfg(not(feature = "scripting"))]
+        let _ = path;
+    }
+
+    // A button action that runs function from whichever script defines it,
+    // for UiActions:
+    //
+    //   UiActions::new().add("reset", state.scripts.action("reset"))
+    pub fn action(&mut self, function: &str) -> impl Fn(&mut ValueStore) + 'static {
+        let function = function.to_string();
+        #[cfg(feature = "scripting")]
+        {
+            let host = self.host();
+            move |store: &mut ValueStore| {
+                let mut host = host.borrow_mut();
+                let Some(index) = host.find(&function) else {
+                    warn!("no script defines '{}'", function);
+                    return;
+                };
+                if let Err(e) = host.call(index, &function, (), store) {
+                    warn!("{}", e);
+                }
+            }
+        }
+        #[cfg(not(feature = "scripting"))]
+        move |_: &mut ValueStore| log::warn!("'{}' needs the scripting feature", function)
+    }
+
+    fn update(
+        &mut self,
+        events: &[UiEvent],
+        listuis: &Slots<ListUiId, ListInterface>,
+        store: &mut ValueStore,
+    ) -> Result<(), ShecvError> {
+        #[cfg(feature = "scripting")]
+        {
+            let Some(host) = &self.host else {
+                return Ok(());
+            };
+            let mut host = host.borrow_mut();
+            let mut result = Ok(());
+            for index in 0..host.files.len() {
+                if std::mem::take(&mut host.files[index].changed) {
+                    if let Err(e) = host.run_file(index, store) {
+                        result = Err(e);
+                    }
+                }
+            }
+
+            // what the bindings ask for, before any of them run
+            let mut calls = vec![];
+            {
+                let bindings = host.bindings.borrow();
+                for event in events {
+                    match event {
+                        UiEvent::Activated { list, index } => {
+                            let Some(item) = listuis.get(*list).and_then(|l| l.entries.get(*index))
+                            else {
+                                continue;
+                            };
+                            for (file, label, function) in &bindings.buttons {
+                                if *label == item.label {
+                                    calls.push((*file, function.clone(), Dynamic::UNIT));
+                                }
+                            }
+                        }
+                        UiEvent::ValueEdited { key, .. } => {
+                            let value = store
+                                .map
+                                .get(key)
+                                .map_or(Dynamic::UNIT, |value| to_dynamic(value.as_ref()));
+                            for (file, bound, function) in &bindings.changes {
+                                if bound == key {
+                                    calls.push((*file, function.clone(), value.clone()));
+                                }
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            for (file, function, value) in calls {
+                let call = match value.is_unit() {
+                    true => host.call(file, &function, (), store),
+                    false => host.call(file, &function, (value,), store),
+                };
+                if let Err(e) = call {
+                    result = Err(e);
+                }
+            }
+            result
+        }
+        #[cfg(not(feature = "scripting"))]
+        {
+            let _ = (events, listuis, store);
+            Ok(())
+        }
+    }
+}
+
+impl State<'_> {
+    // Run a script and keep it running, see Scripts. It's run again when the
+    // file changes.
+    #[allow(dead_code)]
+    pub fn load_script(&mut self, path: &str, store: &mut ValueStore) -> Result<(), ShecvError> {
+        #[cfg(feature = "scripting")]
+        {
+            self.scripts.host().borrow_mut().load(path, store)?;
+            self.context
+                .as_mut()
+                .ok_or_else(ShecvError::no_context)?
+                .file_watcher
+                .add_script_path(path)
+        }
+        #[cfg(not(feature = "scripting"))]
+        {
+            let _ = store;
+            Err(ShecvError::Script(format!(
+                "{}: shecv was built without the scripting feature",
+                path
+            )))
+        }
+    }
+
+    // Called by the main loop each frame: runs changed scripts again, then
+    // the bindings for what the lists did since last time.
+    pub fn update_scripts(&mut self, store: &mut ValueStore) -> Result<(), ShecvError> {
+        let events = self.ui_events[self.ui_events_scripted..].to_vec();
+        self.ui_events_scripted = self.ui_events.len();
+        self.scripts.update(&events, &self.listuis, store)
+    }
+
+    // Evaluate a line of script, as the console's script command does, and
+    // return what it came to.
+    pub fn eval_script(
+        &mut self,
+        code: &str,
+        store: &mut ValueStore,
+    ) -> Result<String, ShecvError> {
+        #[cfg(feature = "scripting")]
+        {
+            let value = self.scripts.host().borrow_mut().eval(code, store)?;
+            Ok(match value.is_unit() {
+                true => String::new(),
+                false => value.to_string(),
+            })
+        }
+        #[cfg(not(feature = "scripting"))]
+        {
+            let _ = (code, store);
+            Err(ShecvError::Script(
+                "shecv was built without the scripting feature".into(),
+            ))
+        }
+    }
+}
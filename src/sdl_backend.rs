@@ -0,0 +1,274 @@
+use std::{cell::RefCell, path::PathBuf};
+
+use glam::{IVec2, UVec2, Vec2};
+use image::RgbaImage;
+
+use sdl2::{
+    event::{Event, WindowEvent},
+    keyboard::{Keycode, Mod},
+    mouse::{Cursor, SystemCursor},
+    pixels::PixelFormatEnum,
+    rect::Rect,
+    surface::Surface,
+    video::{FullscreenType, Window},
+    EventPump, Sdl,
+};
+use wgpu::SurfaceTargetUnsafe;
+
+use crate::{
+    backend::{Backend, BackendEvent, BackendWindow, FullscreenMode, Key},
+    error::ShecvError,
+};
+
+pub struct SdlBackend {
+    pub sdl: Sdl,
+    events: EventPump,
+}
+
+impl SdlBackend {
+    pub fn new() -> Result<Self, ShecvError> {
+        let sdl = sdl2::init().map_err(ShecvError::Device)?;
+        let events = sdl.event_pump().map_err(ShecvError::Device)?;
+        Ok(Self { sdl, events })
+    }
+}
+
+impl Backend for SdlBackend {
+    fn create_window(
+        &mut self,
+        width: u32,
+        height: u32,
+        title: &str,
+    ) -> Result<Box<dyn BackendWindow>, ShecvError> {
+        let video = self.sdl.video().map_err(ShecvError::Device)?;
+        let window = video
+            .window(title, width, height)
+            .position_centered()
+            .resizable()
+            .allow_highdpi()
+            .opengl()
+            .build()
+            .map_err(|e| ShecvError::Device(e.to_string()))?;
+        Ok(Box::new(window))
+    }
+
+    fn poll_events(&mut self) -> Vec<BackendEvent> {
+        self.events.poll_iter().filter_map(sdl_event).collect()
+    }
+
+    fn clipboard_text(&self) -> Option<String> {
+        let clipboard = self.sdl.video().ok()?.clipboard();
+        if !clipboard.has_clipboard_text() {
+            return None;
+        }
+        clipboard.clipboard_text().ok()
+    }
+
+    fn set_clipboard_text(&mut self, text: &str) -> Result<(), ShecvError> {
+        let video = self.sdl.video().map_err(ShecvError::Device)?;
+        video
+            .clipboard()
+            .set_clipboard_text(text)
+            .map_err(ShecvError::Device)
+    }
+}
+
+impl BackendWindow for Window {
+    fn id(&self) -> u64 {
+        Window::id(self) as u64
+    }
+
+    fn size(&self) -> (u32, u32) {
+        Window::size(self)
+    }
+
+    fn drawable_size(&self) -> (u32, u32) {
+        Window::drawable_size(self)
+    }
+
+    unsafe fn surface_target(&self) -> Result<SurfaceTargetUnsafe, ShecvError> {
+        Ok(SurfaceTargetUnsafe::from_window(self)?)
+    }
+
+    fn set_title(&mut self, title: &str) -> Result<(), ShecvError> {
+        Window::set_title(self, title).map_err(|e| ShecvError::Device(e.to_string()))
+    }
+
+    fn set_icon(&mut self, image: &RgbaImage) -> Result<(), ShecvError> {
+        // sdl copies the pixels into the icon
+        let mut pixels = image.as_raw().clone();
+        let (width, height) = image.dimensions();
+        let surface = Surface::from_data(
+            &mut pixels,
+            width,
+            height,
+            width * 4,
+            PixelFormatEnum::RGBA32,
+        )
+        .map_err(ShecvError::Device)?;
+        Window::set_icon(self, surface);
+        Ok(())
+    }
+
+    fn set_fullscreen(&mut self, mode: FullscreenMode) -> Result<(), ShecvError> {
+        let fullscreen_type = match mode {
+            FullscreenMode::Windowed => FullscreenType::Off,
+            FullscreenMode::Exclusive => FullscreenType::True,
+            FullscreenMode::Borderless => FullscreenType::Desktop,
+        };
+        Window::set_fullscreen(self, fullscreen_type).map_err(ShecvError::Device)
+    }
+
+    fn set_ime_rect(&self, xy: IVec2, wh: UVec2) {
+        self.subsystem()
+            .text_input()
+            .set_rect(Rect::new(xy.x, xy.y, wh.x, wh.y));
+    }
+
+    fn set_cursor_visible(&mut self, visible: bool) {
+        self.subsystem().sdl().mouse().show_cursor(visible);
+    }
+
+    fn set_cursor_grab(&mut self, grab: bool) -> Result<(), ShecvError> {
+        self.set_mouse_grab(grab);
+        Ok(())
+    }
+
+    fn set_relative_mouse_mode(&mut self, relative: bool) -> Result<(), ShecvError> {
+        self.subsystem()
+            .sdl()
+            .mouse()
+            .set_relative_mouse_mode(relative);
+        Ok(())
+    }
+
+    fn set_cursor_image(
+        &mut self,
+        image: Option<&RgbaImage>,
+        hotspot: UVec2,
+    ) -> Result<(), ShecvError> {
+        let cursor = match image {
+            None => Cursor::from_system(SystemCursor::Arrow),
+            Some(image) => {
+                // sdl copies the pixels into the cursor
+                let mut pixels = image.as_raw().clone();
+                let (width, height) = image.dimensions();
+                Surface::from_data(
+                    &mut pixels,
+                    width,
+                    height,
+                    width * 4,
+                    PixelFormatEnum::RGBA32,
+                )
+                .and_then(|surface| {
+                    Cursor::from_surface(surface, hotspot.x as i32, hotspot.y as i32)
+                })
+            }
+        }
+        .map_err(ShecvError::Device)?;
+        cursor.set();
+        SDL_CURSOR.with(|current| current.replace(Some(cursor)));
+        Ok(())
+    }
+}
+
+thread_local! {
+    // sdl draws the cursor last set until another is, so it's kept here
+    static SDL_CURSOR: RefCell<Option<Cursor>> = const { RefCell::new(None) };
+}
+
+fn sdl_event(event: Event) -> Option<BackendEvent> {
+    let window_id = |id: u32| (id != 0).then_some(id as u64);
+    Some(match event {
+        Event::Quit { .. } => BackendEvent::Quit,
+        Event::Window {
+            window_id: id,
+            win_event,
+            ..
+        } => {
+            let window_id = id as u64;
+            match win_event {
+                WindowEvent::Resized(..) | WindowEvent::SizeChanged(..) => {
+                    BackendEvent::Resized { window_id }
+                }
+                WindowEvent::Minimized => BackendEvent::Minimized { window_id },
+                WindowEvent::Restored | WindowEvent::Maximized => {
+                    BackendEvent::Restored { window_id }
+                }
+                WindowEvent::Close => BackendEvent::CloseRequested { window_id },
+                _ => return None,
+            }
+        }
+        Event::AppWillEnterBackground { .. } => BackendEvent::Suspended,
+        Event::AppDidEnterForeground { .. } => BackendEvent::Resumed,
+        Event::KeyDown {
+            window_id: id,
+            keycode: Some(keycode),
+            keymod,
+            ..
+        } => BackendEvent::KeyDown {
+            window_id: window_id(id),
+            key: sdl_key(keycode),
+            alt: keymod.intersects(Mod::LALTMOD | Mod::RALTMOD),
+            ctrl: keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD | Mod::LGUIMOD | Mod::RGUIMOD),
+        },
+        Event::TextInput {
+            window_id: id,
+            text,
+            ..
+        } => BackendEvent::TextInput {
+            window_id: window_id(id),
+            text,
+        },
+        Event::TextEditing {
+            window_id: id,
+            text,
+            ..
+        } => BackendEvent::TextEditing {
+            window_id: window_id(id),
+            text,
+        },
+        Event::MouseMotion {
+            window_id: id,
+            x,
+            y,
+            xrel,
+            yrel,
+            ..
+        } => BackendEvent::MouseMotion {
+            window_id: window_id(id),
+            position: Vec2::new(x as f32, y as f32),
+            delta: Vec2::new(xrel as f32, yrel as f32),
+        },
+        Event::DropFile {
+            window_id: id,
+            filename,
+            ..
+        } => BackendEvent::DroppedFile {
+            window_id: window_id(id),
+            path: PathBuf::from(filename),
+        },
+        _ => return None,
+    })
+}
+
+fn sdl_key(keycode: Keycode) -> Key {
+    match keycode {
+        Keycode::Escape => Key::Escape,
+        Keycode::Return | Keycode::KpEnter => Key::Enter,
+        Keycode::Backspace => Key::Backspace,
+        Keycode::Left => Key::Left,
+        Keycode::Right => Key::Right,
+        Keycode::Up => Key::Up,
+        Keycode::Down => Key::Down,
+        Keycode::F3 => Key::F3,
+        Keycode::F12 => Key::F12,
+        Keycode::Tab => Key::Tab,
+        Keycode::Backquote => Key::Backquote,
+        Keycode::C => Key::C,
+        Keycode::V => Key::V,
+        Keycode::Y => Key::Y,
+        Keycode::Z => Key::Z,
+        _ => Key::Other,
+    }
+}
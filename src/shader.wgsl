@@ -2,12 +2,31 @@
 var<uniform> view: mat4x4<f32>;
 @group(0) @binding(1)
 var<uniform> screen_size: vec2<f32>;
+// the first layer of textures, for shaders that only need one
 @group(0) @binding(2)
 var texture: texture_2d<f32>;
 @group(0) @binding(3)
 var texture_sampler: sampler;
+// values from GeoInstances::set_param, in the order they were declared
+@group(0) @binding(4)
+var<uniform> params: array<vec4<f32>, 16>;
+
+// the same for every pipeline, see params::Globals
+struct Globals {
+    time: f32,
+    dt: f32,
+    resolution: vec2<f32>,
+    cursor: vec2<f32>,
+    _pad: vec2<f32>,
+};
+@group(0) @binding(5)
+var<uniform> globals: Globals;
+// every layer of the group's sheet, see GeoManager::new_unit_square_array
+@group(0) @binding(6)
+var textures: texture_2d_array<f32>;
 
 struct InstanceInput {
+    @location(4) shape_kind: vec4<f32>,
     @location(5) transform_0: vec4<f32>,
     @location(6) transform_1: vec4<f32>,
     @location(7) transform_2: vec4<f32>,
@@ -17,6 +36,8 @@ struct InstanceInput {
     @location(11) tex_transform_2: vec4<f32>,
     @location(12) tex_transform_3: vec4<f32>,
     @location(13) color: vec4<f32>,
+    @location(14) shape: vec4<f32>,
+    @location(15) border_color: vec4<f32>,
 };
 
 struct VertexInput {
@@ -28,6 +49,11 @@ struct VertexOutput {
     @builtin(position) position: vec4<f32>,
     @location(0) tex_coords: vec2<f32>,
     @location(1) color: vec4<f32>,
+    // pixel position inside the instance rect, for the rounded rect distance
+    @location(2) local: vec2<f32>,
+    @location(3) shape: vec4<f32>,
+    @location(4) border_color: vec4<f32>,
+    @location(5) shape_kind: vec4<f32>,
 };
 
 @vertex
@@ -51,12 +77,61 @@ fn vs_main(
     result.position = view * transform * vec4(vin.position, 1.0);
     result.tex_coords = (tex_transform * vec4(vin.tex_coords, 0.0, 1.0)).xy;
     result.color = instance.color;
+    result.local = vin.tex_coords * instance.shape.xy;
+    result.shape = instance.shape;
+    result.border_color = instance.border_color;
+    result.shape_kind = instance.shape_kind;
     return result;
 }
 
+// distance to a rect centered on the origin with corners rounded by r
+fn rounded_rect_distance(p: vec2<f32>, half_size: vec2<f32>, r: f32) -> f32 {
+    let q = abs(p) - half_size + vec2(r);
+    return length(max(q, vec2(0.0))) + min(max(q.x, q.y), 0.0) - r;
+}
+
+// approximate distance to an ellipse centered on the origin, exact for circles
+fn ellipse_distance(p: vec2<f32>, half_size: vec2<f32>) -> f32 {
+    let r = min(half_size.x, half_size.y);
+    return (length(p / max(half_size, vec2(0.0001))) - 1.0) * r;
+}
+
 @fragment
 fn fs_main(vout: VertexOutput) -> @location(0) vec4<f32> {
-    var color = vout.color * textureSample(texture, texture_sampler, vout.tex_coords);
+    // shape_kind.z is the instance's texture layer
+    let layer = i32(vout.shape_kind.z + 0.5);
+    var color = vout.color * textureSample(textures, texture_sampler, vout.tex_coords, layer);
+
+    // shape.z is the corner radius and shape.w the border width, both in pixels.
+    // shape_kind.x is 0 for a rounded rect and 1 for an ellipse, shape_kind.y the sweep.
+    // distances are in pixels too, so a half pixel either side of the edge is antialiased.
+    let is_ellipse = vout.shape_kind.x > 0.5;
+    let sweep = vout.shape_kind.y;
+    if vout.shape.z > 0.0 || vout.shape.w > 0.0 || is_ellipse || sweep < 1.0 {
+        let half_size = vout.shape.xy * 0.5;
+        let p = vout.local - half_size;
+        var d: f32;
+        if is_ellipse {
+            d = ellipse_distance(p, half_size);
+        } else {
+            let radius = min(vout.shape.z, min(half_size.x, half_size.y));
+            d = rounded_rect_distance(p, half_size, radius);
+        }
+        if vout.shape.w > 0.0 {
+            let inside_border = clamp(0.5 - (d + vout.shape.w), 0.0, 1.0);
+            color = mix(vout.border_color, color, inside_border);
+        }
+        color.a *= clamp(0.5 - d, 0.0, 1.0);
+
+        // local y points down, so this angle runs clockwise from the top
+        if sweep < 1.0 {
+            let turn = fract(atan2(p.x, -p.y) / 6.2831853 + 1.0);
+            if turn > sweep {
+                color.a = 0.0;
+            }
+        }
+    }
+
     color = vec4(color.rgb * color.a, color.a);
     return color;
 }
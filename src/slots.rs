@@ -0,0 +1,264 @@
+use std::{
+    fmt::Debug,
+    iter::FilterMap,
+    marker::PhantomData,
+    ops::{Index, IndexMut},
+    slice,
+};
+
+// An index plus the generation of the value that was there when it was
+// taken, so a key to a removed value stops working even after its slot is
+// reused.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct SlotKey {
+    pub index: usize,
+    pub generation: u32,
+}
+
+// Implemented by the handle types each Slots is keyed with, so a list's
+// handle can't be used to look up a group.
+pub trait SlotId: Copy + Debug {
+    fn from_key(key: SlotKey) -> Self;
+    fn key(self) -> SlotKey;
+}
+
+pub struct Slot<T> {
+    generation: u32,
+    value: Option<T>,
+}
+
+// A Vec whose handles stay valid when other values are removed. Removed
+// slots are reused by later inserts, with the generation bumped so old
+// handles to them find nothing.
+pub struct Slots<K: SlotId, T> {
+    slots: Vec<Slot<T>>,
+    free: Vec<usize>,
+    id: PhantomData<K>,
+}
+
+impl<K: SlotId, T> Default for Slots<K, T> {
+    fn default() -> Self {
+        Self {
+            slots: vec![],
+            free: vec![],
+            id: PhantomData,
+        }
+    }
+}
+
+#[allow(dead_code)]
+impl<K: SlotId, T> Slots<K, T> {
+    pub fn insert(&mut self, value: T) -> K {
+        let index = match self.free.pop() {
+            Some(index) => {
+                self.slots[index].value = Some(value);
+                index
+            }
+            None => {
+                self.slots.push(Slot {
+                    generation: 0,
+                    value: Some(value),
+                });
+                self.slots.len() - 1
+            }
+        };
+        K::from_key(SlotKey {
+            index,
+            generation: self.slots[index].generation,
+        })
+    }
+
+    pub fn remove(&mut self, id: K) -> Option<T> {
+        self.get(id)?;
+        let index = id.key().index;
+        let slot = &mut self.slots[index];
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free.push(index);
+        slot.value.take()
+    }
+
+    pub fn get(&self, id: K) -> Option<&T> {
+        let key = id.key();
+        let slot = self.slots.get(key.index)?;
+        (slot.generation == key.generation)
+            .then_some(slot.value.as_ref())
+            .flatten()
+    }
+
+    pub fn get_mut(&mut self, id: K) -> Option<&mut T> {
+        let key = id.key();
+        let slot = self.slots.get_mut(key.index)?;
+        (slot.generation == key.generation)
+            .then_some(slot.value.as_mut())
+            .flatten()
+    }
+
+    pub fn contains(&self, id: K) -> bool {
+        self.get(id).is_some()
+    }
+
+    pub fn len(&self) -> usize {
+        self.slots.len() - self.free.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // Handles to every value, in slot order.
+    pub fn ids(&self) -> impl DoubleEndedIterator<Item = K> + '_ {
+        self.slots.iter().enumerate().filter_map(|(index, slot)| {
+            slot.value.as_ref().map(|_| {
+                K::from_key(SlotKey {
+                    index,
+                    generation: slot.generation,
+                })
+            })
+        })
+    }
+
+    pub fn iter(&self) -> Iter<'_, T> {
+        let value: fn(&Slot<T>) -> Option<&T> = |slot| slot.value.as_ref();
+        self.slots.iter().filter_map(value)
+    }
+
+    pub fn iter_mut(&mut self) -> IterMut<'_, T> {
+        let value: fn(&mut Slot<T>) -> Option<&mut T> = |slot| slot.value.as_mut();
+        self.slots.iter_mut().filter_map(value)
+    }
+
+    // Values with their handles.
+    pub fn entries_mut(&mut self) -> impl Iterator<Item = (K, &mut T)> {
+        self.slots
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(index, slot)| {
+                let generation = slot.generation;
+                slot.value
+                    .as_mut()
+                    .map(|value| (K::from_key(SlotKey { index, generation }), value))
+            })
+    }
+}
+
+pub type Iter<'a, T> = FilterMap<slice::Iter<'a, Slot<T>>, fn(&'a Slot<T>) -> Option<&'a T>>;
+pub type IterMut<'a, T> =
+    FilterMap<slice::IterMut<'a, Slot<T>>, fn(&'a mut Slot<T>) -> Option<&'a mut T>>;
+
+impl<'a, K: SlotId, T> IntoIterator for &'a Slots<K, T> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, K: SlotId, T> IntoIterator for &'a mut Slots<K, T> {
+    type Item = &'a mut T;
+    type IntoIter = IterMut<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+// Indexing with a stale handle panics, like indexing a Vec out of bounds.
+impl<K: SlotId, T> Index<K> for Slots<K, T> {
+    type Output = T;
+
+    fn index(&self, id: K) -> &T {
+        self.get(id)
+            .unwrap_or_else(|| panic!("nothing at {:?}", id))
+    }
+}
+
+impl<K: SlotId, T> IndexMut<K> for Slots<K, T> {
+    fn index_mut(&mut self, id: K) -> &mut T {
+        self.get_mut(id)
+            .unwrap_or_else(|| panic!("nothing at {:?}", id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Copy, Clone, PartialEq)]
+    struct Id(SlotKey);
+
+    impl SlotId for Id {
+        fn from_key(key: SlotKey) -> Self {
+            Id(key)
+        }
+
+        fn key(self) -> SlotKey {
+            self.0
+        }
+    }
+
+    #[test]
+    fn removing_leaves_other_ids_working() {
+        let mut slots = Slots::<Id, &str>::default();
+        let a = slots.insert("a");
+        let b = slots.insert("b");
+        let c = slots.insert("c");
+        assert_eq!(slots.remove(b), Some("b"));
+        assert_eq!(slots.get(a), Some(&"a"));
+        assert_eq!(slots.get(c), Some(&"c"));
+        assert_eq!(slots.len(), 2);
+        assert_eq!(slots.iter().copied().collect::<Vec<_>>(), ["a", "c"]);
+    }
+
+    #[test]
+    fn reused_slots_get_a_new_generation() {
+        let mut slots = Slots::<Id, &str>::default();
+        let old = slots.insert("old");
+        slots.remove(old);
+        let new = slots.insert("new");
+        assert_eq!(new.0.index, old.0.index);
+        assert_ne!(new.0.generation, old.0.generation);
+        assert_eq!(slots.get(old), None);
+        assert!(!slots.contains(old));
+        assert_eq!(slots.get(new), Some(&"new"));
+    }
+
+    #[test]
+    fn stale_ids_find_nothing() {
+        let mut slots = Slots::<Id, u32>::default();
+        let id = slots.insert(1);
+        assert_eq!(slots.remove(id), Some(1));
+        assert_eq!(slots.remove(id), None);
+        assert_eq!(slots.get_mut(id), None);
+        assert!(slots.is_empty());
+        // removing twice doesn't free the slot twice
+        let a = slots.insert(2);
+        let b = slots.insert(3);
+        assert_ne!(a.0.index, b.0.index);
+    }
+
+    #[test]
+    fn ids_and_entries_match_values() {
+        let mut slots = Slots::<Id, u32>::default();
+        let ids: Vec<Id> = (0..4).map(|n| slots.insert(n)).collect();
+        slots.remove(ids[1]);
+        assert_eq!(slots.ids().collect::<Vec<_>>(), [ids[0], ids[2], ids[3]]);
+        for (id, value) in slots.entries_mut() {
+            *value += 10;
+            assert_eq!(
+                ids.iter().position(|i| *i == id),
+                Some(*value as usize - 10)
+            );
+        }
+        assert_eq!(slots[ids[3]], 13);
+    }
+
+    #[test]
+    #[should_panic]
+    fn indexing_with_a_stale_id_panics() {
+        let mut slots = Slots::<Id, u32>::default();
+        let id = slots.insert(1);
+        slots.remove(id);
+        let _ = slots[id];
+    }
+}
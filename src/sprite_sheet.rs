@@ -0,0 +1,369 @@
+use std::{ops::RangeInclusive, path::Path, time::Duration};
+
+use glam::UVec2;
+use log::warn;
+use serde::{
+    de::{MapAccess, Visitor},
+    Deserialize, Deserializer,
+};
+
+use crate::{
+    anim::{Animation, AnimationFrame, AnimationMode},
+    assets,
+    error::ShecvError,
+    types::{SamplerSettings, TextureSheetClusterDefinition, TextureSheetDefinition},
+};
+
+// how long frames show when the export doesn't say, as in Aseprite
+const DEFAULT_FRAME_DURATION: Duration = Duration::from_millis(100);
+
+// The parts of Aseprite's and TexturePacker's json that are read.
+#[derive(Deserialize)]
+struct SheetJson {
+    frames: FramesJson,
+    meta: MetaJson,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum FramesJson {
+    Hash(OrderedFrames),
+    Array(Vec<NamedFrameJson>),
+}
+
+// The hash form's frames, kept in file order, which is export order.
+struct OrderedFrames(Vec<(String, FrameJson)>);
+
+impl<'de> Deserialize<'de> for OrderedFrames {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct FramesVisitor;
+
+        impl<'de> Visitor<'de> for FramesVisitor {
+            type Value = OrderedFrames;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("frames by name")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+                let mut frames = vec![];
+                while let Some(entry) = map.next_entry()? {
+                    frames.push(entry);
+                }
+                Ok(OrderedFrames(frames))
+            }
+        }
+
+        deserializer.deserialize_map(FramesVisitor)
+    }
+}
+
+#[derive(Deserialize)]
+struct NamedFrameJson {
+    #[serde(default)]
+    filename: String,
+    #[serde(flatten)]
+    frame: FrameJson,
+}
+
+#[derive(Deserialize)]
+struct FrameJson {
+    frame: RectJson,
+    #[serde(default)]
+    rotated: bool,
+    // in milliseconds, only in Aseprite's exports
+    duration: Option<u32>,
+}
+
+#[derive(Deserialize)]
+struct RectJson {
+    x: u32,
+    y: u32,
+    w: u32,
+    h: u32,
+}
+
+#[derive(Deserialize)]
+struct MetaJson {
+    image: String,
+    #[serde(default, rename = "frameTags")]
+    frame_tags: Vec<TagJson>,
+}
+
+#[derive(Deserialize)]
+struct TagJson {
+    #[serde(default)]
+    name: String,
+    from: u32,
+    to: u32,
+    direction: Option<String>,
+}
+
+pub struct SpriteFrame {
+    pub name: String,
+    pub duration: Duration,
+}
+
+// A named run of frames from Aseprite, played as an Animation.
+pub struct SpriteTag {
+    pub name: String,
+    pub frames: RangeInclusive<usize>,
+    pub mode: AnimationMode,
+    pub reverse: bool,
+}
+
+// Sheet metadata exported as JSON by Aseprite or TexturePacker, in their hash
+// or array forms. Every frame is a cluster of its own, in export order, so
+// frames can be anywhere in the image and any size:
+//
+//   let sprites = SpriteSheet::load("art/hero.json")?;
+//   let group = geos.new_unit_square(.., sprites.sheet(), ..)?;
+//   let index = geos.instance_groups[group].add_new(.., sprites.frame("hero 0").unwrap(), 0, ..)?;
+//   let instance = geos.instance_id(group, index).unwrap();
+//   animators.add(instance, sprites.animation("walk").unwrap());
+//
+// Trimmed frames draw as trimmed, stretched over the instance. Rotated ones
+// aren't turned back, so export without rotation.
+pub struct SpriteSheet {
+    // the image the frames are in, next to the json
+    pub image_path: String,
+    pub clusters: Vec<TextureSheetClusterDefinition>,
+    pub frames: Vec<SpriteFrame>,
+    pub tags: Vec<SpriteTag>,
+}
+
+#[allow(dead_code)]
+impl SpriteSheet {
+    pub fn load(path: &str) -> Result<Self, ShecvError> {
+        let text = assets::read_to_string(path)?;
+        Self::parse(&text, path)
+    }
+
+    // path is where the json came from, which the image is found next to.
+    pub fn parse(text: &str, path: &str) -> Result<Self, ShecvError> {
+        let parse_error = |message: &str| ShecvError::Parse(format!("{}: {}", path, message));
+        let json: SheetJson =
+            serde_json::from_str(text).map_err(|e| parse_error(&e.to_string()))?;
+        let image = json.meta.image;
+        let image_path = match Path::new(path).parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir.join(&image).to_string_lossy().into(),
+            _ => image,
+        };
+
+        // the hash form names frames by key, the array form with filename
+        let entries = match json.frames {
+            FramesJson::Hash(frames) => frames.0,
+            FramesJson::Array(frames) => frames
+                .into_iter()
+                .map(|named| (named.filename, named.frame))
+                .collect(),
+        };
+
+        let mut clusters = vec![];
+        let mut frames = vec![];
+        for (name, frame) in entries {
+            if frame.rotated {
+                warn!(
+                    "{}: frame '{}' is rotated, which isn't supported",
+                    path, name
+                );
+            }
+            let RectJson { x, y, w, h } = frame.frame;
+            clusters.push(TextureSheetClusterDefinition {
+                label: name.clone(),
+                offset: UVec2::new(x, y),
+                cluster_size: UVec2::new(w, h),
+                sub_size: UVec2::new(w, h),
+                spacing: UVec2::ZERO,
+            });
+            let duration = frame.duration.map_or(DEFAULT_FRAME_DURATION, |ms| {
+                Duration::from_millis(ms as u64)
+            });
+            frames.push(SpriteFrame { name, duration });
+        }
+
+        let mut tags = vec![];
+        for tag in json.meta.frame_tags {
+            let (from, to) = (tag.from as usize, tag.to as usize);
+            if from > to || to >= frames.len() {
+                return Err(parse_error(&format!(
+                    "tag '{}' is past the frames",
+                    tag.name
+                )));
+            }
+            let (mode, reverse) = match tag.direction.as_deref().unwrap_or("forward") {
+                "reverse" => (AnimationMode::Loop, true),
+                "pingpong" => (AnimationMode::PingPong, false),
+                "pingpong_reverse" => (AnimationMode::PingPong, true),
+                _ => (AnimationMode::Loop, false),
+            };
+            tags.push(SpriteTag {
+                name: tag.name,
+                frames: from..=to,
+                mode,
+                reverse,
+            });
+        }
+
+        Ok(Self {
+            image_path,
+            clusters,
+            frames,
+            tags,
+        })
+    }
+
+    pub fn sheet(&self) -> TextureSheetDefinition {
+        TextureSheetDefinition {
+            path: self.image_path.clone(),
+            clusters: self.clusters.clone(),
+            image: None,
+            sampler: SamplerSettings::default(),
+            mipmaps: false,
+            fallback: false,
+        }
+    }
+
+    // The cluster of a frame, by the name it was exported with.
+    pub fn frame(&self, name: &str) -> Option<usize> {
+        self.frames.iter().position(|frame| frame.name == name)
+    }
+
+    // A tag's frames at their exported durations.
+    pub fn animation(&self, tag: &str) -> Option<Animation> {
+        let tag = self.tags.iter().find(|t| t.name == tag)?;
+        let mut indices: Vec<usize> = tag.frames.clone().collect();
+        if tag.reverse {
+            indices.reverse();
+        }
+        Some(self.frames_animation(indices, tag.mode))
+    }
+
+    // Every frame in order, for sheets without tags.
+    pub fn all_frames(&self, mode: AnimationMode) -> Animation {
+        self.frames_animation((0..self.frames.len()).collect(), mode)
+    }
+
+    fn frames_animation(&self, indices: Vec<usize>, mode: AnimationMode) -> Animation {
+        Animation {
+            cluster_index: indices.first().copied().unwrap_or(0),
+            frames: indices
+                .into_iter()
+                .map(|index| AnimationFrame {
+                    sub_index: 0,
+                    duration: self.frames[index].duration,
+                    cluster_index: Some(index),
+                })
+                .collect(),
+            mode,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HASH: &str = r#"{
+        "frames": {
+            "hero 2": { "frame": { "x": 32, "y": 0, "w": 16, "h": 16 }, "duration": 50 },
+            "hero 0": { "frame": { "x": 0, "y": 0, "w": 16, "h": 16 }, "duration": 100 },
+            "hero 1": { "frame": { "x": 16, "y": 0, "w": 16, "h": 24 }, "rotated": false }
+        },
+        "meta": {
+            "image": "hero.png",
+            "frameTags": [
+                { "name": "walk", "from": 0, "to": 2, "direction": "pingpong" },
+                { "name": "back", "from": 1, "to": 2, "direction": "reverse" }
+            ]
+        }
+    }"#;
+
+    const ARRAY: &str = r#"{
+        "frames": [
+            { "filename": "a.png", "frame": { "x": 0, "y": 0, "w": 8, "h": 8 } },
+            { "filename": "b.png", "frame": { "x": 8, "y": 0, "w": 8, "h": 4 }, "rotated": true }
+        ],
+        "meta": { "image": "sheet.png" }
+    }"#;
+
+    #[test]
+    fn hash_frames_keep_file_order() {
+        let sheet = SpriteSheet::parse(HASH, "art/hero.json").unwrap();
+        let names: Vec<&str> = sheet.frames.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, ["hero 2", "hero 0", "hero 1"]);
+        assert_eq!(sheet.clusters[0].offset, UVec2::new(32, 0));
+        assert_eq!(sheet.clusters[2].cluster_size, UVec2::new(16, 24));
+        assert_eq!(sheet.frames[0].duration, Duration::from_millis(50));
+        assert_eq!(sheet.frames[2].duration, DEFAULT_FRAME_DURATION);
+        assert_eq!(sheet.frame("hero 0"), Some(1));
+    }
+
+    #[test]
+    fn image_is_found_next_to_the_json() {
+        let sheet = SpriteSheet::parse(HASH, "art/hero.json").unwrap();
+        assert_eq!(Path::new(&sheet.image_path), Path::new("art/hero.png"));
+        let sheet = SpriteSheet::parse(HASH, "hero.json").unwrap();
+        assert_eq!(sheet.image_path, "hero.png");
+    }
+
+    #[test]
+    fn array_frames_are_named_by_filename() {
+        let sheet = SpriteSheet::parse(ARRAY, "sheet.json").unwrap();
+        assert_eq!(sheet.frames.len(), 2);
+        assert_eq!(sheet.frame("b.png"), Some(1));
+        assert_eq!(sheet.clusters[1].sub_size, UVec2::new(8, 4));
+        assert!(sheet.tags.is_empty());
+        assert_eq!(sheet.all_frames(AnimationMode::Loop).frames.len(), 2);
+    }
+
+    #[test]
+    fn tags_play_in_their_direction() {
+        let sheet = SpriteSheet::parse(HASH, "hero.json").unwrap();
+        let walk = sheet.animation("walk").unwrap();
+        assert!(matches!(walk.mode, AnimationMode::PingPong));
+        let clusters: Vec<_> = walk.frames.iter().map(|f| f.cluster_index).collect();
+        assert_eq!(clusters, [Some(0), Some(1), Some(2)]);
+        let back = sheet.animation("back").unwrap();
+        let clusters: Vec<_> = back.frames.iter().map(|f| f.cluster_index).collect();
+        assert_eq!(clusters, [Some(2), Some(1)]);
+        assert_eq!(back.cluster_index, 2);
+        assert!(sheet.animation("run").is_none());
+    }
+
+    #[test]
+    fn tags_past_the_frames_are_errors() {
+        let json = HASH.replace(r#""from": 1, "to": 2"#, r#""from": 1, "to": 3"#);
+        assert!(SpriteSheet::parse(&json, "hero.json").is_err());
+        let json = HASH.replace(r#""from": 1, "to": 2"#, r#""from": 2, "to": 1"#);
+        assert!(SpriteSheet::parse(&json, "hero.json").is_err());
+    }
+
+    #[test]
+    fn missing_fields_are_errors() {
+        let no_image = r#"{ "frames": [], "meta": {} }"#;
+        assert!(SpriteSheet::parse(no_image, "a.json").is_err());
+        let no_rect = r#"{ "frames": { "a": { "duration": 10 } }, "meta": { "image": "a.png" } }"#;
+        assert!(SpriteSheet::parse(no_rect, "a.json").is_err());
+        assert!(SpriteSheet::parse("{", "a.json").is_err());
+    }
+
+    #[test]
+    fn escaped_names_decode_surrogate_pairs() {
+        let json = r#"{
+            "frames": { "\ud83d\ude00 \u00e9": { "frame": { "x": 0, "y": 0, "w": 1, "h": 1 } } },
+            "meta": { "image": "a.png" }
+        }"#;
+        let sheet = SpriteSheet::parse(json, "a.json").unwrap();
+        assert_eq!(sheet.frames[0].name, "\u{1f600} \u{e9}");
+    }
+
+    #[test]
+    fn deep_nesting_is_an_error_not_an_overflow() {
+        let json = format!(
+            r#"{{ "frames": {}, "meta": {{ "image": "a.png" }} }}"#,
+            "[".repeat(100_000)
+        );
+        assert!(SpriteSheet::parse(&json, "a.json").is_err());
+    }
+}
@@ -1,12 +1,17 @@
-use std::{error::Error, sync::Arc};
+use std::{fs, io, ops::Range, sync::Arc};
 
+use glam::{IVec2, UVec2, Vec2};
 use glyphon::{
-    Attrs, Buffer, Cache, Family, FontSystem, Metrics, Shaping, SwashCache, TextArea, TextAtlas,
-    TextBounds, TextRenderer, Viewport,
+    fontdb, Attrs, Buffer, Cache, ColorMode, Family, FamilyOwned, FontSystem, Metrics, Shaping,
+    Style, SwashCache, TextArea, TextAtlas, TextBounds, TextRenderer, Viewport, Weight,
 };
+use log::warn;
 use wgpu::{MultisampleState, TextureFormat};
 
-use crate::types::ColorRGBA;
+use crate::assets;
+use crate::error::ShecvError;
+use crate::layer::RenderLayer;
+use crate::types::{Border, ColorRGBA};
 
 pub struct TextLabel {
     pub buffer: Buffer,
@@ -15,17 +20,212 @@ pub struct TextLabel {
     pub scale: f64,
     pub bounds: TextBounds,
     pub color: ColorRGBA,
+    pub layer: RenderLayer,
+    pub effect: TextEffect,
+}
+
+#[derive(Copy, Clone)]
+pub struct TextShadow {
+    // in pixels, down and to the right
+    pub offset: Vec2,
+    pub color: ColorRGBA,
+}
+
+// Copies of a label drawn under it: a shadow, and an outline of eight copies
+// border.width pixels out around it. Runs with a color of their own, and
+// color emoji, keep their colors in the copies.
+#[derive(Default, Copy, Clone)]
+pub struct TextEffect {
+    pub shadow: Option<TextShadow>,
+    pub outline: Option<Border>,
+}
+
+impl TextEffect {
+    // Where the copies go and their colors, in drawing order.
+    fn copies(&self) -> Vec<(Vec2, ColorRGBA)> {
+        let mut copies = vec![];
+        if let Some(shadow) = self.shadow {
+            copies.push((shadow.offset, shadow.color));
+        }
+        if let Some(outline) = self.outline {
+            for (x, y) in [
+                (-1, -1),
+                (0, -1),
+                (1, -1),
+                (-1, 0),
+                (1, 0),
+                (-1, 1),
+                (0, 1),
+                (1, 1),
+            ] {
+                let offset = Vec2::new(x as f32, y as f32) * outline.width;
+                copies.push((offset, outline.color));
+            }
+        }
+        copies
+    }
+}
+
+// A run of text with its own attributes inside one TextLabel.
+// Unset attributes fall back to the label's.
+#[derive(Clone, Default)]
+pub struct TextSpan {
+    pub text: String,
+    pub color: Option<ColorRGBA>,
+    pub bold: bool,
+    pub italic: bool,
+    pub monospace: bool,
+    // relative to the label's font size
+    pub size: Option<f32>,
+}
+
+#[allow(dead_code)]
+impl TextSpan {
+    pub fn new(text: &str) -> Self {
+        Self {
+            text: text.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn color(mut self, color: ColorRGBA) -> Self {
+        self.color = Some(color);
+        self
+    }
+
+    pub fn bold(mut self) -> Self {
+        self.bold = true;
+        self
+    }
+
+    pub fn italic(mut self) -> Self {
+        self.italic = true;
+        self
+    }
+
+    pub fn monospace(mut self) -> Self {
+        self.monospace = true;
+        self
+    }
+
+    pub fn size(mut self, size: f32) -> Self {
+        self.size = Some(size);
+        self
+    }
+
+    // Spans from a small tag markup:
+    //   [b]bold[/] [i]italic[/] [mono]code[/] [#ff8000]orange[/] [size=1.5]big[/]
+    // Tags nest, [/] closes the innermost one, and [[ is a literal [.
+    pub fn parse_markup(markup: &str) -> Result<Vec<TextSpan>, ShecvError> {
+        let mut spans = vec![];
+        let mut stack = vec![TextSpan::default()];
+        let mut text = String::new();
+        let mut rest = markup;
+
+        while let Some(open) = rest.find('[') {
+            text.push_str(&rest[..open]);
+            rest = &rest[open + 1..];
+            if let Some(after) = rest.strip_prefix('[') {
+                text.push('[');
+                rest = after;
+                continue;
+            }
+            let close = rest
+                .find(']')
+                .ok_or_else(|| ShecvError::Parse(format!("unclosed tag in '{}'", markup)))?;
+            let tag = &rest[..close];
+            rest = &rest[close + 1..];
+
+            // a tag ends the current run
+            let current = stack.last().unwrap().clone();
+            if !text.is_empty() {
+                spans.push(TextSpan {
+                    text: std::mem::take(&mut text),
+                    ..current.clone()
+                });
+            }
+
+            if tag == "/" {
+                if stack.len() == 1 {
+                    return Err(ShecvError::Parse(format!("unmatched [/] in '{}'", markup)));
+                }
+                stack.pop();
+                continue;
+            }
+            let mut span = current;
+            match tag {
+                "b" => span.bold = true,
+                "i" => span.italic = true,
+                "mono" => span.monospace = true,
+                _ if tag.starts_with('#') => span.color = Some(ColorRGBA::from_hex(tag)?),
+                _ if tag.starts_with("size=") => {
+                    span.size = Some(tag[5..].parse().map_err(|_| {
+                        ShecvError::Parse(format!("bad size [{}] in '{}'", tag, markup))
+                    })?)
+                }
+                _ => {
+                    return Err(ShecvError::Parse(format!(
+                        "unknown tag [{}] in '{}'",
+                        tag, markup
+                    )))
+                }
+            }
+            stack.push(span);
+        }
+        text.push_str(rest);
+        if !text.is_empty() {
+            spans.push(TextSpan {
+                text,
+                ..stack.last().unwrap().clone()
+            });
+        }
+
+        Ok(spans)
+    }
+
+    fn attrs<'a>(&self, metrics: Metrics, family: Family<'a>) -> Attrs<'a> {
+        let mut attrs = Attrs::new().family(if self.monospace {
+            Family::Monospace
+        } else {
+            family
+        });
+        if let Some(color) = self.color {
+            attrs = attrs.color(color.to_glyphon_color());
+        }
+        if self.bold {
+            attrs = attrs.weight(Weight::BOLD);
+        }
+        if self.italic {
+            attrs = attrs.style(Style::Italic);
+        }
+        if let Some(size) = self.size {
+            attrs = attrs.metrics(Metrics::new(
+                metrics.font_size * size,
+                metrics.line_height * size,
+            ));
+        }
+        attrs
+    }
 }
 
 pub struct TextCollection {
     pub texts: Vec<TextLabel>,
     pub font_system: FontSystem,
-    pub text_renderer: TextRenderer,
+    // one per RenderLayer, so each layer's labels can be drawn on their own
+    pub text_renderers: [TextRenderer; RenderLayer::ALL.len()],
     pub swashcache: SwashCache,
     #[allow(dead_code)]
     pub cache: Cache,
     pub atlas: TextAtlas,
     pub viewport: Viewport,
+    // Families tried in order for new labels; the first one loaded is used.
+    // Glyphs missing from it still fall back through cosmic-text's own search.
+    pub font_fallback: Vec<String>,
+    pub default_family: FamilyOwned,
+    // the color font emoji are drawn from, see enable_emoji
+    pub emoji_family: Option<String>,
+    // labels are laid out in logical pixels and scaled up when prepared
+    pub scale_factor: f32,
 }
 
 impl TextCollection {
@@ -33,33 +233,189 @@ impl TextCollection {
         device: Arc<std::sync::Mutex<wgpu::Device>>,
         queue: Arc<std::sync::Mutex<wgpu::Queue>>,
         swapchain_format: TextureFormat,
-    ) -> Self {
-        let device = device.lock().unwrap();
-        let queue = queue.lock().unwrap();
+    ) -> Result<Self, ShecvError> {
+        let device = device.lock()?;
+        let queue = queue.lock()?;
 
-        let font_system = FontSystem::new();
+        let mut font_system = FontSystem::new();
+        // the font compiled in, so labels show where there are no system
+        // fonts; a file at its path replaces it
+        let had_fonts = font_system.db().faces().next().is_some();
+        match assets::read(assets::DEFAULT_FONT) {
+            Ok(data) => {
+                let ids = font_system
+                    .db_mut()
+                    .load_font_source(fontdb::Source::Binary(Arc::new(data)));
+                let family = ids
+                    .first()
+                    .and_then(|id| font_system.db().face(*id))
+                    .and_then(|face| face.families.first())
+                    .map(|(family, _)| family.clone());
+                if let (false, Some(family)) = (had_fonts, family) {
+                    font_system.db_mut().set_sans_serif_family(family);
+                }
+            }
+            Err(e) => warn!("failed to read default font: {}", e),
+        }
         let swashcache = SwashCache::new();
         let cache = Cache::new(&device);
-        let mut atlas = TextAtlas::new(&device, &queue, &cache, swapchain_format);
-        let text_renderer =
-            TextRenderer::new(&mut atlas, &device, MultisampleState::default(), None);
+        // color glyphs blend in linear space on srgb targets, as-is on others
+        let color_mode = match swapchain_format.is_srgb() {
+            true => ColorMode::Accurate,
+            false => ColorMode::Web,
+        };
+        let mut atlas =
+            TextAtlas::with_color_mode(&device, &queue, &cache, swapchain_format, color_mode);
+        let text_renderers = RenderLayer::ALL
+            .map(|_| TextRenderer::new(&mut atlas, &device, MultisampleState::default(), None));
         let viewport = Viewport::new(&device, &cache);
 
-        TextCollection {
+        Ok(TextCollection {
             texts: vec![],
             font_system,
-            text_renderer,
+            text_renderers,
             swashcache,
             cache,
             atlas,
             viewport,
+            font_fallback: vec![],
+            default_family: FamilyOwned::SansSerif,
+            emoji_family: None,
+            scale_factor: 1.0,
+        })
+    }
+
+    // Register a font from memory. Returns the families it provides.
+    #[allow(dead_code)]
+    pub fn load_font_bytes(&mut self, data: Vec<u8>) -> Result<Vec<String>, ShecvError> {
+        let ids = self
+            .font_system
+            .db_mut()
+            .load_font_source(fontdb::Source::Binary(Arc::new(data)));
+        if ids.is_empty() {
+            return Err(ShecvError::Parse(
+                "font data contains no usable faces".into(),
+            ));
         }
+
+        let mut families: Vec<String> = vec![];
+        for id in ids.iter() {
+            let Some(face) = self.font_system.db().face(*id) else {
+                continue;
+            };
+            for (family, _) in &face.families {
+                if !families.contains(family) {
+                    families.push(family.clone());
+                }
+            }
+        }
+        // the new font may come earlier in the fallback chain than the current one
+        self.resolve_default_family();
+        Ok(families)
+    }
+
+    #[allow(dead_code)]
+    pub fn load_font_file(&mut self, path: &str) -> Result<Vec<String>, ShecvError> {
+        let data = fs::read(path).map_err(|e| {
+            ShecvError::Io(io::Error::new(
+                e.kind(),
+                format!("failed to read font '{}': {}", path, e),
+            ))
+        })?;
+        self.load_font_bytes(data)
+            .map_err(|e| ShecvError::Parse(format!("failed to load font '{}': {}", path, e)))
+    }
+
+    // Returns false if the family is not loaded; labels then keep using sans-serif.
+    #[allow(dead_code)]
+    pub fn set_default_family(&mut self, family: &str) -> bool {
+        self.set_font_fallback(&[family]).is_some()
+    }
+
+    // Set the families to try in order. Returns the one now in use, if any is loaded.
+    // Fonts loaded later are checked against the same chain.
+    pub fn set_font_fallback(&mut self, families: &[&str]) -> Option<String> {
+        self.font_fallback = families.iter().map(|family| family.to_string()).collect();
+        self.resolve_default_family()
+    }
+
+    fn resolve_default_family(&mut self) -> Option<String> {
+        let db = self.font_system.db();
+        let found = self
+            .font_fallback
+            .iter()
+            .find(|name| {
+                db.faces()
+                    .any(|face| face.families.iter().any(|(family, _)| family == *name))
+            })
+            .cloned();
+        self.default_family = match &found {
+            Some(name) => FamilyOwned::Name(name.clone()),
+            None => FamilyOwned::SansSerif,
+        };
+        found
+    }
+
+    // Draw emoji from a color emoji font, rather than whichever font has them
+    // first, which may be monochrome or have none at all. Uses family, or
+    // the first of EMOJI_FAMILIES that's installed, and returns it; None if
+    // it isn't loaded, leaving emoji to the fallback search as before.
+    #[allow(dead_code)]
+    pub fn enable_emoji(&mut self, family: Option<&str>) -> Option<String> {
+        let candidates = match family {
+            Some(family) => vec![family],
+            None => EMOJI_FAMILIES.to_vec(),
+        };
+        let db = self.font_system.db();
+        self.emoji_family = candidates
+            .into_iter()
+            .find(|name| {
+                db.faces()
+                    .any(|face| face.families.iter().any(|(family, _)| family == name))
+            })
+            .map(str::to_string);
+        self.emoji_family.clone()
+    }
+
+    #[allow(dead_code)]
+    pub fn disable_emoji(&mut self) {
+        self.emoji_family = None;
     }
 
     pub fn clear(&mut self) {
         self.texts.clear();
     }
 
+    // The metrics new_text uses for a label of the given height.
+    pub fn line_metrics(height: f32) -> Metrics {
+        Metrics::new(height * 0.8, height)
+    }
+
+    // Width and height of text as new_text would lay it out on one line,
+    // without adding a label to the collection.
+    pub fn measure(&mut self, text: &str, metrics: Metrics) -> (f32, f32) {
+        let mut buffer = Buffer::new(&mut self.font_system, metrics);
+        buffer.set_size(&mut self.font_system, None, None);
+        self.set_buffer_text(&mut buffer, text);
+        self.measure_buffer(buffer)
+    }
+
+    pub fn measure_spans(&mut self, spans: &[TextSpan], metrics: Metrics) -> (f32, f32) {
+        let mut buffer = Buffer::new(&mut self.font_system, metrics);
+        buffer.set_size(&mut self.font_system, None, None);
+        self.set_buffer_spans(&mut buffer, spans, metrics);
+        self.measure_buffer(buffer)
+    }
+
+    fn measure_buffer(&mut self, mut buffer: Buffer) -> (f32, f32) {
+        buffer.shape_until_scroll(&mut self.font_system, false);
+        buffer
+            .layout_runs()
+            .fold((0.0, 0.0), |(width, height), run| {
+                (width.max(run.line_w), height + run.line_height)
+            })
+    }
+
     pub fn new_text(
         &mut self,
         rect: (f64, f64, f64, f64),
@@ -67,18 +423,44 @@ impl TextCollection {
         text_scale_factor: f64,
         color: ColorRGBA,
     ) -> usize {
-        let text2 = if text.parse::<f64>().is_ok() {
-            let floatval = text.parse::<f64>().unwrap();
-            format!("{:.2}", floatval)
-        } else {
-            text.to_string()
-        };
+        let mut buffer = self.sized_buffer(rect);
+        self.set_buffer_text(&mut buffer, text);
+        self.push_label(buffer, rect, text_scale_factor, color)
+    }
+
+    // A label made of differently styled runs, see TextSpan.
+    pub fn new_rich_text(
+        &mut self,
+        rect: (f64, f64, f64, f64),
+        spans: &[TextSpan],
+        text_scale_factor: f64,
+        color: ColorRGBA,
+    ) -> usize {
+        let mut buffer = self.sized_buffer(rect);
+        let metrics = buffer.metrics();
+        self.set_buffer_spans(&mut buffer, spans, metrics);
+        self.push_label(buffer, rect, text_scale_factor, color)
+    }
 
+    fn set_buffer_text(&mut self, buffer: &mut Buffer, text: &str) {
+        let family = self.default_family.as_family();
+        let emoji = self.emoji_family.as_deref().map(Family::Name);
+        let runs = [(text, Attrs::new().family(family))];
+        set_runs(buffer, &mut self.font_system, runs, family, emoji);
+    }
+
+    fn set_buffer_spans(&mut self, buffer: &mut Buffer, spans: &[TextSpan], metrics: Metrics) {
+        let family = self.default_family.as_family();
+        let emoji = self.emoji_family.as_deref().map(Family::Name);
+        let runs = spans
+            .iter()
+            .map(|span| (span.text.as_str(), span.attrs(metrics, family)));
+        set_runs(buffer, &mut self.font_system, runs, family, emoji);
+    }
+
+    fn sized_buffer(&mut self, rect: (f64, f64, f64, f64)) -> Buffer {
         let display_scale_factor = 1.0f64;
-        let mut buffer = Buffer::new(
-            &mut self.font_system,
-            Metrics::new((rect.3 * 0.8) as f32, rect.3 as f32),
-        );
+        let mut buffer = Buffer::new(&mut self.font_system, Self::line_metrics(rect.3 as f32));
         let physical_width = (rect.2 * display_scale_factor) as f32;
         let physical_height = (rect.3 * display_scale_factor) as f32;
         buffer.set_size(
@@ -86,12 +468,16 @@ impl TextCollection {
             Some(physical_width),
             Some(physical_height),
         );
-        buffer.set_text(
-            &mut self.font_system,
-            text2.as_str(),
-            Attrs::new().family(Family::SansSerif),
-            Shaping::Advanced,
-        );
+        buffer
+    }
+
+    fn push_label(
+        &mut self,
+        mut buffer: Buffer,
+        rect: (f64, f64, f64, f64),
+        text_scale_factor: f64,
+        color: ColorRGBA,
+    ) -> usize {
         buffer.shape_until_scroll(&mut self.font_system, false);
 
         self.texts.push(TextLabel {
@@ -101,11 +487,31 @@ impl TextCollection {
             scale: text_scale_factor,
             bounds: TextBounds::default(),
             color,
+            layer: RenderLayer::default(),
+            effect: TextEffect::default(),
         });
 
         self.texts.len() - 1
     }
 
+    pub fn set_layer(&mut self, index: usize, layer: RenderLayer) {
+        self.texts[index].layer = layer;
+    }
+
+    pub fn set_effect(&mut self, index: usize, effect: TextEffect) {
+        self.texts[index].effect = effect;
+    }
+
+    // Cut the label off outside a rect, in logical pixels.
+    pub fn set_clip(&mut self, index: usize, xy: IVec2, wh: UVec2) {
+        self.texts[index].bounds = TextBounds {
+            left: xy.x,
+            top: xy.y,
+            right: xy.x + wh.x as i32,
+            bottom: xy.y + wh.y as i32,
+        };
+    }
+
     pub fn trim_atlas(&mut self) {
         self.atlas.trim();
     }
@@ -116,26 +522,118 @@ impl TextCollection {
         queue: Arc<std::sync::Mutex<wgpu::Queue>>,
         _screen_width: u32,
         _screen_height: u32,
-    ) -> Result<(), Box<dyn Error>> {
-        let device = device.lock().unwrap();
-        let queue = queue.lock().unwrap();
+    ) -> Result<(), ShecvError> {
+        let device = device.lock()?;
+        let queue = queue.lock()?;
 
-        self.text_renderer.prepare(
-            &device,
-            &queue,
-            &mut self.font_system,
-            &mut self.atlas,
-            &self.viewport,
-            self.texts.iter().map(|t| TextArea {
-                buffer: &t.buffer,
-                left: t.left as f32,
-                top: t.top as f32,
-                scale: t.scale as f32,
-                bounds: t.bounds,
-                default_color: t.color.to_glyphon_color(),
-            }),
-            &mut self.swashcache,
-        )?;
+        let scale_factor = self.scale_factor;
+        for (layer, text_renderer) in RenderLayer::ALL.iter().zip(self.text_renderers.iter_mut()) {
+            text_renderer.prepare(
+                &device,
+                &queue,
+                &mut self.font_system,
+                &mut self.atlas,
+                &self.viewport,
+                // effect copies go first, so the label draws over them
+                self.texts
+                    .iter()
+                    .filter(|t| t.layer == *layer)
+                    .flat_map(|t| {
+                        let copies = t.effect.copies().into_iter();
+                        copies
+                            .chain([(Vec2::ZERO, t.color)])
+                            .map(move |(offset, color)| TextArea {
+                                buffer: &t.buffer,
+                                left: (t.left as f32 + offset.x) * scale_factor,
+                                top: (t.top as f32 + offset.y) * scale_factor,
+                                scale: t.scale as f32 * scale_factor,
+                                bounds: TextBounds {
+                                    left: (t.bounds.left as f32 * scale_factor) as i32,
+                                    top: (t.bounds.top as f32 * scale_factor) as i32,
+                                    right: (t.bounds.right as f32 * scale_factor).ceil() as i32,
+                                    bottom: (t.bounds.bottom as f32 * scale_factor).ceil() as i32,
+                                },
+                                default_color: color.to_glyphon_color(),
+                            })
+                    }),
+                &mut self.swashcache,
+            )?;
+        }
+        Ok(())
+    }
+
+    // Draw the labels of one layer, as prepared.
+    pub fn render<'pass>(
+        &'pass self,
+        layer: RenderLayer,
+        pass: &mut wgpu::RenderPass<'pass>,
+    ) -> Result<(), ShecvError> {
+        self.text_renderers[layer as usize].render(&self.atlas, &self.viewport, pass)?;
         Ok(())
     }
 }
+
+// Color emoji fonts, in the order enable_emoji looks for them.
+pub const EMOJI_FAMILIES: [&str; 5] = [
+    "Noto Color Emoji",
+    "Apple Color Emoji",
+    "Segoe UI Emoji",
+    "Twemoji Mozilla",
+    "JoyPixels",
+];
+
+// Shape runs of text into a buffer, drawing the emoji in them from the emoji
+// family when there is one.
+fn set_runs<'r>(
+    buffer: &mut Buffer,
+    font_system: &mut FontSystem,
+    runs: impl IntoIterator<Item = (&'r str, Attrs<'r>)>,
+    family: Family<'r>,
+    emoji: Option<Family<'r>>,
+) {
+    buffer.set_rich_text(
+        font_system,
+        runs.into_iter().flat_map(|(text, attrs)| {
+            emoji_runs(text, emoji.is_some())
+                .into_iter()
+                .map(move |(range, is_emoji)| match (is_emoji, emoji) {
+                    (true, Some(emoji)) => (&text[range], attrs.family(emoji)),
+                    _ => (&text[range], attrs),
+                })
+        }),
+        Attrs::new().family(family),
+        Shaping::Advanced,
+    );
+}
+
+// The text split into runs of emoji and of everything else, or one run if
+// split is false. Joiners, variation selectors and tags stay with the run
+// before them, so sequences like flags and families hold together.
+fn emoji_runs(text: &str, split: bool) -> Vec<(Range<usize>, bool)> {
+    if !split {
+        return vec![(0..text.len(), false)];
+    }
+    let mut runs: Vec<(Range<usize>, bool)> = vec![];
+    for (i, c) in text.char_indices() {
+        let end = i + c.len_utf8();
+        let joins = matches!(
+            c,
+            '\u{200D}' | '\u{FE0F}' | '\u{20E3}' | '\u{E0020}'..='\u{E007F}'
+        );
+        match runs.last_mut() {
+            Some((range, run_emoji)) if joins || *run_emoji == is_emoji(c) => range.end = end,
+            _ => runs.push((i..end, is_emoji(c))),
+        }
+    }
+    runs
+}
+
+fn is_emoji(c: char) -> bool {
+    matches!(
+        c,
+        '\u{1F000}'..='\u{1FAFF}'
+            | '\u{2300}'..='\u{23FF}'
+            | '\u{2600}'..='\u{27BF}'
+            | '\u{2B05}'..='\u{2B55}'
+    )
+}
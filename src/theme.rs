@@ -0,0 +1,407 @@
+use std::fs::read_to_string;
+
+use glam::Vec2;
+use log::warn;
+use toml::Table;
+
+use crate::{
+    error::ShecvError,
+    text::TextShadow,
+    types::{Border, ColorRGBA},
+    window::State,
+};
+
+// What a color is for. Styles name roles, and the current theme decides
+// the actual colors.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ThemeRole {
+    // behind a whole list
+    Background,
+    // unselected rows and their text
+    Surface,
+    Text,
+    // the selected row and its text
+    Selection,
+    SelectionText,
+    // rows that were just activated
+    Accent,
+    AccentText,
+    // rows that can't be used
+    Disabled,
+    DisabledText,
+    Border,
+}
+
+impl ThemeRole {
+    pub const ALL: [ThemeRole; 10] = [
+        ThemeRole::Background,
+        ThemeRole::Surface,
+        ThemeRole::Text,
+        ThemeRole::Selection,
+        ThemeRole::SelectionText,
+        ThemeRole::Accent,
+        ThemeRole::AccentText,
+        ThemeRole::Disabled,
+        ThemeRole::DisabledText,
+        ThemeRole::Border,
+    ];
+
+    // as written in theme and ui files
+    pub fn name(self) -> &'static str {
+        match self {
+            ThemeRole::Background => "background",
+            ThemeRole::Surface => "surface",
+            ThemeRole::Text => "text",
+            ThemeRole::Selection => "selection",
+            ThemeRole::SelectionText => "selection_text",
+            ThemeRole::Accent => "accent",
+            ThemeRole::AccentText => "accent_text",
+            ThemeRole::Disabled => "disabled",
+            ThemeRole::DisabledText => "disabled_text",
+            ThemeRole::Border => "border",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<ThemeRole> {
+        ThemeRole::ALL.into_iter().find(|role| role.name() == name)
+    }
+}
+
+// A style color either follows the theme or stays fixed.
+#[derive(Copy, Clone)]
+pub enum StyleColor {
+    Role(ThemeRole),
+    Fixed(ColorRGBA),
+}
+
+impl StyleColor {
+    pub fn resolve(&self, theme: &Theme) -> ColorRGBA {
+        match self {
+            StyleColor::Role(role) => theme.color(*role),
+            StyleColor::Fixed(color) => *color,
+        }
+    }
+
+    // A role name, or a "#rrggbb" or "#rrggbbaa" color.
+    pub fn parse(text: &str) -> Result<Self, ShecvError> {
+        match ThemeRole::from_name(text) {
+            Some(role) => Ok(StyleColor::Role(role)),
+            None => ColorRGBA::from_hex(text).map(StyleColor::Fixed),
+        }
+    }
+}
+
+impl From<ThemeRole> for StyleColor {
+    fn from(role: ThemeRole) -> Self {
+        StyleColor::Role(role)
+    }
+}
+
+impl From<ColorRGBA> for StyleColor {
+    fn from(color: ColorRGBA) -> Self {
+        StyleColor::Fixed(color)
+    }
+}
+
+#[derive(Copy, Clone)]
+pub struct StyleBorder {
+    pub width: f32,
+    pub color: StyleColor,
+}
+
+impl StyleBorder {
+    pub fn resolve(&self, theme: &Theme) -> Border {
+        Border {
+            width: self.width,
+            color: self.color.resolve(theme),
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+pub struct StyleTextShadow {
+    pub offset: Vec2,
+    pub color: StyleColor,
+}
+
+impl StyleTextShadow {
+    pub fn resolve(&self, theme: &Theme) -> TextShadow {
+        TextShadow {
+            offset: self.offset,
+            color: self.color.resolve(theme),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Theme {
+    pub name: String,
+    colors: [ColorRGBA; ThemeRole::ALL.len()],
+}
+
+impl Theme {
+    // The greys lists have always used.
+    pub fn dark() -> Self {
+        let mut theme = Self {
+            name: "dark".to_string(),
+            colors: [ColorRGBA::white(); ThemeRole::ALL.len()],
+        };
+        theme.set(ThemeRole::Background, ColorRGBA::grey_darkest());
+        theme.set(ThemeRole::Surface, ColorRGBA::grey_dark());
+        theme.set(ThemeRole::Text, ColorRGBA::grey_light());
+        theme.set(ThemeRole::Selection, ColorRGBA::grey_medium());
+        theme.set(ThemeRole::SelectionText, ColorRGBA::white());
+        theme.set(ThemeRole::Accent, ColorRGBA::grey_lighter());
+        theme.set(ThemeRole::AccentText, ColorRGBA::white());
+        theme.set(ThemeRole::Disabled, ColorRGBA::grey_darker());
+        theme.set(ThemeRole::DisabledText, ColorRGBA::grey_dark());
+        theme.set(ThemeRole::Border, ColorRGBA::grey_light());
+        theme
+    }
+
+    pub fn light() -> Self {
+        let grey = |v: f32| ColorRGBA::new(v, v, v, 1.0);
+        let mut theme = Self {
+            name: "light".to_string(),
+            colors: [ColorRGBA::black(); ThemeRole::ALL.len()],
+        };
+        theme.set(ThemeRole::Background, grey(0.85));
+        theme.set(ThemeRole::Surface, grey(0.75));
+        theme.set(ThemeRole::Text, grey(0.15));
+        theme.set(ThemeRole::Selection, grey(0.55));
+        theme.set(ThemeRole::SelectionText, ColorRGBA::black());
+        theme.set(ThemeRole::Accent, grey(0.35));
+        theme.set(ThemeRole::AccentText, ColorRGBA::white());
+        theme.set(ThemeRole::Disabled, grey(0.8));
+        theme.set(ThemeRole::DisabledText, grey(0.55));
+        theme.set(ThemeRole::Border, grey(0.3));
+        theme
+    }
+
+    // White on black, with yellow and cyan rows, for low vision.
+    pub fn high_contrast() -> Self {
+        let hex = |hex: &str| ColorRGBA::from_hex(hex).unwrap();
+        let mut theme = Self {
+            name: "high_contrast".to_string(),
+            colors: [ColorRGBA::black(); ThemeRole::ALL.len()],
+        };
+        theme.set(ThemeRole::Text, ColorRGBA::white());
+        theme.set(ThemeRole::Selection, hex("#ffff00"));
+        theme.set(ThemeRole::Accent, hex("#00ffff"));
+        theme.set(ThemeRole::DisabledText, hex("#a0a0a0"));
+        theme.set(ThemeRole::Border, ColorRGBA::white());
+        theme
+    }
+
+    // Black on white, with dark blue and dark red rows.
+    pub fn high_contrast_light() -> Self {
+        let hex = |hex: &str| ColorRGBA::from_hex(hex).unwrap();
+        let mut theme = Self {
+            name: "high_contrast_light".to_string(),
+            colors: [ColorRGBA::white(); ThemeRole::ALL.len()],
+        };
+        theme.set(ThemeRole::Text, ColorRGBA::black());
+        theme.set(ThemeRole::Selection, hex("#00008b"));
+        theme.set(ThemeRole::Accent, hex("#8b0000"));
+        theme.set(ThemeRole::DisabledText, hex("#595959"));
+        theme.set(ThemeRole::Border, ColorRGBA::black());
+        theme
+    }
+
+    // Dark, with blue selection and orange accents from the Okabe-Ito
+    // palette, which stay apart under every common kind of color blindness.
+    pub fn colorblind() -> Self {
+        let mut theme = Self::dark();
+        theme.name = "colorblind".to_string();
+        theme.set(ThemeRole::Text, ColorRGBA::from_hex("#d0d0d0").unwrap());
+        theme.set(ThemeRole::Selection, okabe_ito(4));
+        theme.set(ThemeRole::Accent, okabe_ito(0));
+        theme.set(ThemeRole::AccentText, ColorRGBA::black());
+        theme
+    }
+
+    // Whether the background is bright, i.e. text on it is dark.
+    pub fn is_light(&self) -> bool {
+        self.color(ThemeRole::Background).relative_luminance() > 0.18
+    }
+
+    pub fn color(&self, role: ThemeRole) -> ColorRGBA {
+        self.colors[role as usize]
+    }
+
+    pub fn set(&mut self, role: ThemeRole, color: ColorRGBA) {
+        self.colors[role as usize] = color;
+    }
+}
+
+// Eight colors told apart with any common color blindness, for things
+// like graph lines and map markers: orange, sky blue, bluish green, yellow,
+// blue, vermillion, reddish purple and black. Past eight they repeat.
+pub fn okabe_ito(index: usize) -> ColorRGBA {
+    const OKABE_ITO: [&str; 8] = [
+        "#e69f00", "#56b4e9", "#009e73", "#f0e442", "#0072b2", "#d55e00", "#cc79a7", "#000000",
+    ];
+    ColorRGBA::from_hex(OKABE_ITO[index % OKABE_ITO.len()]).unwrap()
+}
+
+// The known themes and which one lists are drawn with. Starts with "dark",
+// "light", "colorblind", "high_contrast" and "high_contrast_light", using
+// dark. With high_contrast on, lists are drawn with whichever high contrast
+// theme is nearest the current one, light or dark.
+#[derive(Clone)]
+pub struct Themes {
+    pub themes: Vec<Theme>,
+    current: usize,
+    pub high_contrast: bool,
+}
+
+impl Default for Themes {
+    fn default() -> Self {
+        Self {
+            themes: vec![
+                Theme::dark(),
+                Theme::light(),
+                Theme::colorblind(),
+                Theme::high_contrast(),
+                Theme::high_contrast_light(),
+            ],
+            current: 0,
+            high_contrast: false,
+        }
+    }
+}
+
+#[allow(dead_code)]
+impl Themes {
+    pub fn current(&self) -> &Theme {
+        let theme = &self.themes[self.current];
+        if !self.high_contrast {
+            return theme;
+        }
+        let name = match theme.is_light() {
+            true => "high_contrast_light",
+            false => "high_contrast",
+        };
+        self.themes
+            .iter()
+            .find(|theme| theme.name == name)
+            .unwrap_or(theme)
+    }
+
+    // Replaces any theme with the same name.
+    pub fn add(&mut self, theme: Theme) {
+        match self.themes.iter().position(|t| t.name == theme.name) {
+            Some(index) => self.themes[index] = theme,
+            None => self.themes.push(theme),
+        }
+    }
+
+    pub fn set_current(&mut self, name: &str) -> Result<(), ShecvError> {
+        self.current = self
+            .themes
+            .iter()
+            .position(|theme| theme.name == name)
+            .ok_or_else(|| ShecvError::Parse(format!("no theme named '{}'", name)))?;
+        Ok(())
+    }
+
+    // Adds every [[theme]] in a TOML file, each with a name and a
+    // "#rrggbb" color per role name. Roles left out keep the colors of
+    // the theme named in based_on, or of dark.
+    //
+    //   [[theme]]
+    //   name = "dusk"
+    //   based_on = "dark"
+    //   selection = "#3a2d5c"
+    //   accent = "#8a63d2"
+    pub fn load_file(&mut self, path: &str) -> Result<(), ShecvError> {
+        let text = read_to_string(path)?;
+        let parse_error = |message: String| ShecvError::Parse(format!("{}: {}", path, message));
+        let document = text
+            .parse::<Table>()
+            .map_err(|e| parse_error(e.to_string()))?;
+        let Some(entries) = document.get("theme") else {
+            return Ok(());
+        };
+        let entries = entries
+            .as_array()
+            .ok_or_else(|| parse_error("'theme' should be an array of tables".into()))?;
+        for entry in entries {
+            let entry = entry
+                .as_table()
+                .ok_or_else(|| parse_error("'theme' should be an array of tables".into()))?;
+            let name = entry
+                .get("name")
+                .and_then(|name| name.as_str())
+                .ok_or_else(|| parse_error("a theme needs a name".into()))?;
+            let mut theme = match entry.get("based_on").and_then(|base| base.as_str()) {
+                Some(base) => self
+                    .themes
+                    .iter()
+                    .find(|theme| theme.name == base)
+                    .cloned()
+                    .ok_or_else(|| parse_error(format!("no theme named '{}'", base)))?,
+                None => Theme::dark(),
+            };
+            theme.name = name.to_string();
+            for (key, value) in entry {
+                if key == "name" || key == "based_on" {
+                    continue;
+                }
+                let role = ThemeRole::from_name(key)
+                    .ok_or_else(|| parse_error(format!("unknown role '{}'", key)))?;
+                let color = value
+                    .as_str()
+                    .ok_or_else(|| parse_error(format!("'{}' should be a color", key)))
+                    .and_then(|color| {
+                        ColorRGBA::from_hex(color).map_err(|e| parse_error(e.to_string()))
+                    })?;
+                theme.set(role, color);
+            }
+            self.add(theme);
+        }
+        Ok(())
+    }
+}
+
+#[allow(dead_code)]
+impl State<'_> {
+    // Every list follows the theme from its next layout.
+    pub fn set_theme(&mut self, name: &str) -> Result<(), ShecvError> {
+        self.themes.set_current(name)
+    }
+
+    // Between dark and light; any other theme goes back to dark.
+    pub fn toggle_theme(&mut self) {
+        let next = match self.themes.current().name.as_str() {
+            "dark" => "light",
+            _ => "dark",
+        };
+        let _ = self.themes.set_current(next);
+    }
+
+    // Lists draw with the high contrast theme nearest the current one until
+    // it is switched off, whatever theme is set meanwhile.
+    pub fn set_high_contrast(&mut self, high_contrast: bool) {
+        self.themes.high_contrast = high_contrast;
+    }
+
+    pub fn toggle_high_contrast(&mut self) {
+        self.themes.high_contrast = !self.themes.high_contrast;
+    }
+
+    // Warn about every list whose text is too close to the color behind it
+    // in the current theme, see ListStyle::contrast_warnings. Worth calling
+    // after building lists or changing theme. Returns how many were found.
+    pub fn check_contrast(&self) -> usize {
+        let theme = self.themes.current();
+        let mut found = 0;
+        for id in self.listuis.ids() {
+            for warning in self.listuis[id].style.contrast_warnings(theme) {
+                warn!("list {:?} in theme '{}': {}", id, theme.name, warning);
+                found += 1;
+            }
+        }
+        found
+    }
+}
@@ -0,0 +1,235 @@
+use std::{
+    hint::spin_loop,
+    mem::size_of,
+    sync::mpsc::{channel, Receiver, TryRecvError},
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+use wgpu::{
+    Buffer, BufferAddress, BufferAsyncError, BufferDescriptor, BufferUsages, CommandEncoder,
+    Device, Features, Maintain, MapMode, QuerySet, QuerySetDescriptor, QueryType, Queue,
+    RenderPassTimestampWrites,
+};
+
+// Timestamps for the start and end of each timed pass.
+#[derive(Copy, Clone)]
+pub enum TimedPass {
+    Geo,
+    Text,
+}
+
+const QUERY_COUNT: u32 = 4;
+
+// Filled in by Context::render. The gpu times lag a frame or two behind, and
+// stay None unless gpu timing was enabled and the adapter supports it.
+#[derive(Default, Copy, Clone)]
+pub struct RenderStats {
+    pub cpu_render: Duration,
+    pub geo_pass: Option<Duration>,
+    pub text_pass: Option<Duration>,
+}
+
+// Writes timestamps at the edges of the geo and text passes, then copies them
+// into a mappable buffer. The buffer is read without blocking on a later frame;
+// frames rendered while it is still mapped are not timed.
+pub struct GpuTimer {
+    query_set: QuerySet,
+    resolve_buffer: Buffer,
+    read_buffer: Buffer,
+    // nanoseconds per timestamp tick
+    period: f32,
+    mapping: Option<Receiver<Result<(), BufferAsyncError>>>,
+}
+
+impl GpuTimer {
+    // None when the device was created without timestamp query support.
+    pub fn new(device: &Device, queue: &Queue) -> Option<Self> {
+        if !device.features().contains(Features::TIMESTAMP_QUERY) {
+            return None;
+        }
+        let size = (QUERY_COUNT as usize * size_of::<u64>()) as BufferAddress;
+        Some(Self {
+            query_set: device.create_query_set(&QuerySetDescriptor {
+                label: Some("gpu timer queries"),
+                ty: QueryType::Timestamp,
+                count: QUERY_COUNT,
+            }),
+            resolve_buffer: device.create_buffer(&BufferDescriptor {
+                label: Some("gpu timer resolve"),
+                size,
+                usage: BufferUsages::QUERY_RESOLVE | BufferUsages::COPY_SRC,
+                mapped_at_creation: false,
+            }),
+            read_buffer: device.create_buffer(&BufferDescriptor {
+                label: Some("gpu timer read"),
+                size,
+                usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+                mapped_at_creation: false,
+            }),
+            period: queue.get_timestamp_period(),
+            mapping: None,
+        })
+    }
+
+    // Whether this frame can be timed, i.e. the last results have been read.
+    pub fn is_idle(&self) -> bool {
+        self.mapping.is_none()
+    }
+
+    // A timed pass can be spread over several render passes: the first
+    // marks the beginning and the last the end, so anything in between is
+    // counted too.
+    pub fn writes(
+        &self,
+        pass: TimedPass,
+        begins: bool,
+        ends: bool,
+    ) -> Option<RenderPassTimestampWrites<'_>> {
+        let first = pass as u32 * 2;
+        (begins || ends).then_some(RenderPassTimestampWrites {
+            query_set: &self.query_set,
+            beginning_of_pass_write_index: begins.then_some(first),
+            end_of_pass_write_index: ends.then_some(first + 1),
+        })
+    }
+
+    pub fn resolve(&self, encoder: &mut CommandEncoder) {
+        encoder.resolve_query_set(&self.query_set, 0..QUERY_COUNT, &self.resolve_buffer, 0);
+        encoder.copy_buffer_to_buffer(
+            &self.resolve_buffer,
+            0,
+            &self.read_buffer,
+            0,
+            self.read_buffer.size(),
+        );
+    }
+
+    // Call after the encoder holding resolve() has been submitted.
+    pub fn begin_read(&mut self) {
+        let (sender, receiver) = channel();
+        self.read_buffer
+            .slice(..)
+            .map_async(MapMode::Read, move |result| {
+                let _ = sender.send(result);
+            });
+        self.mapping = Some(receiver);
+    }
+
+    // Returns the geo and text pass durations once a read has finished.
+    pub fn try_read(&mut self, device: &Device) -> Option<(Duration, Duration)> {
+        let receiver = self.mapping.as_ref()?;
+        device.poll(Maintain::Poll);
+        match receiver.try_recv() {
+            Err(TryRecvError::Empty) => return None,
+            Err(TryRecvError::Disconnected) | Ok(Err(_)) => {
+                self.mapping = None;
+                return None;
+            }
+            Ok(Ok(())) => {}
+        }
+
+        let times: Vec<u64> = {
+            let data = self.read_buffer.slice(..).get_mapped_range();
+            bytemuck::cast_slice(&data).to_vec()
+        };
+        self.read_buffer.unmap();
+        self.mapping = None;
+
+        let span = |first: usize| {
+            let ticks = times[first + 1].saturating_sub(times[first]);
+            Duration::from_nanos((ticks as f64 * self.period as f64) as u64)
+        };
+        Some((span(0), span(2)))
+    }
+}
+
+// How well a FramePacer kept to its target, over the frames so far.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct PacerStats {
+    pub frames: u64,
+    // how far past their deadline frames ended
+    pub mean_jitter: Duration,
+    pub max_jitter: Duration,
+    // frames that ran past their deadline before wait was even called
+    pub missed: u64,
+}
+
+// Holds the main loop to a target frame rate. thread::sleep oversleeps by up
+// to a millisecond or more, so wait sleeps until shortly before the deadline
+// and spins the rest of the way. Uncapped, wait returns at once, for when
+// vsync already paces presenting.
+pub struct FramePacer {
+    pub target_fps: f64,
+    pub uncapped: bool,
+    // how long before the deadline sleeping gives way to spinning
+    pub spin: Duration,
+    deadline: Option<Instant>,
+    stats: PacerStats,
+}
+
+impl Default for FramePacer {
+    fn default() -> Self {
+        Self::new(60.0)
+    }
+}
+
+#[allow(dead_code)]
+impl FramePacer {
+    pub fn new(target_fps: f64) -> Self {
+        Self {
+            target_fps,
+            uncapped: false,
+            spin: Duration::from_micros(1500),
+            deadline: None,
+            stats: PacerStats::default(),
+        }
+    }
+
+    pub fn frame_time(&self) -> Duration {
+        Duration::from_secs_f64(1.0 / self.target_fps.max(1.0))
+    }
+
+    pub fn stats(&self) -> PacerStats {
+        self.stats
+    }
+
+    // Block until the current frame is due to end. Deadlines follow on from
+    // each other so the rate doesn't drift, unless a frame ran a whole frame
+    // late, which starts them over from now.
+    pub fn wait(&mut self) {
+        let now = Instant::now();
+        if self.uncapped {
+            self.deadline = None;
+            return;
+        }
+        let frame_time = self.frame_time();
+        let deadline = *self.deadline.get_or_insert(now + frame_time);
+        if now > deadline {
+            self.stats.missed += 1;
+        } else {
+            if let Some(sleep_for) = (deadline - now).checked_sub(self.spin) {
+                sleep(sleep_for);
+            }
+            while Instant::now() < deadline {
+                spin_loop();
+            }
+        }
+
+        let end = Instant::now();
+        let jitter = end.saturating_duration_since(deadline);
+        let stats = &mut self.stats;
+        stats.frames += 1;
+        stats.max_jitter = stats.max_jitter.max(jitter);
+        // a running mean, so it settles within a few hundred frames
+        let weight = 1.0 / stats.frames.min(256) as f64;
+        stats.mean_jitter = Duration::from_secs_f64(
+            stats.mean_jitter.as_secs_f64() * (1.0 - weight) + jitter.as_secs_f64() * weight,
+        );
+
+        self.deadline = Some(match end > deadline + frame_time {
+            true => end + frame_time,
+            false => deadline + frame_time,
+        });
+    }
+}
@@ -0,0 +1,94 @@
+use std::time::Instant;
+
+use glam::{IVec2, UVec2};
+
+use crate::{
+    error::ShecvError,
+    layer::RenderLayer,
+    text::TextCollection,
+    types::{InstanceShape, PixelRect, ValueStore},
+    window::State,
+};
+
+// Tooltips float beside the selected item of a focused list once it has been
+// selected for the list's tooltip_delay. They go right of the list, or left
+// when there is no room, and are kept on screen vertically.
+impl State<'_> {
+    pub fn layout_tooltips(&mut self, store: &ValueStore) -> Result<(), ShecvError> {
+        let context = self.context.as_mut().ok_or_else(ShecvError::no_context)?;
+        let group_index = context.overlay_group(&mut self.tooltip_group, 16, RenderLayer::Ui)?;
+        context.geos.instance_groups[group_index]
+            .instance_buffer_manager
+            .clear();
+        let extent = context.logical_size()?;
+        let theme = self.themes.current();
+
+        for listui in self.listuis.iter_mut() {
+            // restart the delay whenever the selection moves
+            if listui.selected_since.0 != listui.selected_index {
+                listui.selected_since = (listui.selected_index, Instant::now());
+            }
+            if !listui.focused || listui.is_editing() || !listui.is_visible(store) {
+                continue;
+            }
+            if listui.selected_since.1.elapsed() < listui.tooltip_delay {
+                continue;
+            }
+            let Some(tooltip) = listui
+                .entries
+                .get(listui.selected_index as usize)
+                .and_then(|item| item.tooltip.as_ref())
+            else {
+                continue;
+            };
+            let Some((row_xy, row_wh)) = listui.layout.selected_row else {
+                continue;
+            };
+
+            let line_height = 20.0;
+            let pad = 6;
+            let text_wh = context
+                .texts
+                .measure(tooltip, TextCollection::line_metrics(line_height));
+            let wh = UVec2::new(
+                text_wh.0.ceil() as u32 + pad * 2,
+                text_wh.1.ceil().max(line_height) as u32 + pad,
+            );
+
+            let list_right = listui.layout.xy.x + listui.layout.wh.x as i32;
+            let x = if list_right + wh.x as i32 <= extent.x as i32 {
+                list_right
+            } else {
+                listui.layout.xy.x - wh.x as i32
+            };
+            let y = (row_xy.y + row_wh.y as i32 / 2 - wh.y as i32 / 2)
+                .min(extent.y as i32 - wh.y as i32)
+                .max(0);
+            let xy = IVec2::new(x.max(0), y);
+
+            context.geos.instance_groups[group_index].add_shape(
+                context.queue.clone(),
+                PixelRect { xy, wh, extent },
+                InstanceShape::rounded(
+                    wh,
+                    listui.style.corner_radius,
+                    listui.style.border.map(|border| border.resolve(theme)),
+                ),
+                listui.style.li_selected_bg.resolve(theme),
+            )?;
+            context.texts.new_text(
+                (
+                    (xy.x + pad as i32) as f64,
+                    (xy.y + pad as i32 / 2) as f64,
+                    text_wh.0.ceil() as f64 + 1.0,
+                    line_height as f64,
+                ),
+                tooltip,
+                1.0,
+                listui.style.li_selected.resolve(theme),
+            );
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,311 @@
+use std::{
+    f32::consts::PI,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use wgpu::Queue;
+
+use crate::{
+    error::ShecvError,
+    geo::{GeoManager, InstanceId},
+    types::{ColorRGBA, ComponentTransform},
+    window::Context,
+};
+
+// Shapes how a tween moves from 0 to 1 over its duration.
+#[derive(Default, Copy, Clone, PartialEq, Eq)]
+#[allow(dead_code)]
+pub enum Easing {
+    #[default]
+    Linear,
+    QuadIn,
+    QuadOut,
+    QuadInOut,
+    CubicIn,
+    CubicOut,
+    CubicInOut,
+    SineInOut,
+    // overshoots slightly before settling
+    BackOut,
+}
+
+impl Easing {
+    pub fn apply(self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::QuadIn => t * t,
+            Easing::QuadOut => 1.0 - (1.0 - t) * (1.0 - t),
+            Easing::QuadInOut if t < 0.5 => 2.0 * t * t,
+            Easing::QuadInOut => 1.0 - (-2.0 * t + 2.0).powi(2) / 2.0,
+            Easing::CubicIn => t * t * t,
+            Easing::CubicOut => 1.0 - (1.0 - t).powi(3),
+            Easing::CubicInOut if t < 0.5 => 4.0 * t * t * t,
+            Easing::CubicInOut => 1.0 - (-2.0 * t + 2.0).powi(3) / 2.0,
+            Easing::SineInOut => -((PI * t).cos() - 1.0) / 2.0,
+            Easing::BackOut => {
+                let c1 = 1.70158;
+                let c3 = c1 + 1.0;
+                1.0 + c3 * (t - 1.0).powi(3) + c1 * (t - 1.0).powi(2)
+            }
+        }
+    }
+}
+
+// What a tween changes. Values are not written anywhere; read them back with
+// Tweens::value.
+#[allow(dead_code)]
+pub enum TweenTarget {
+    Transform {
+        instance: InstanceId,
+        from: ComponentTransform,
+        to: ComponentTransform,
+    },
+    Color {
+        instance: InstanceId,
+        from: ColorRGBA,
+        to: ColorRGBA,
+    },
+    // toward color and back to base, pulses times over the duration
+    Pulse {
+        instance: InstanceId,
+        base: ColorRGBA,
+        color: ColorRGBA,
+        pulses: u32,
+    },
+    Value {
+        from: f32,
+        to: f32,
+    },
+}
+
+pub struct Tween {
+    pub target: TweenTarget,
+    pub duration: Duration,
+    pub easing: Easing,
+    pub elapsed: Duration,
+    pub finished: bool,
+    on_complete: Option<Box<dyn FnMut()>>,
+}
+
+impl Tween {
+    // Eased progress from 0 to 1.
+    pub fn progress(&self) -> f32 {
+        if self.duration.is_zero() {
+            return 1.0;
+        }
+        self.easing
+            .apply(self.elapsed.as_secs_f32() / self.duration.as_secs_f32())
+    }
+
+    pub fn value(&self) -> Option<f32> {
+        match self.target {
+            TweenTarget::Value { from, to } => Some(from + (to - from) * self.progress()),
+            _ => None,
+        }
+    }
+}
+
+// Tweens are kept in slots like Animators, so an index stays valid after
+// others are removed. Finished tweens stay until removed, holding their
+// final value.
+#[derive(Default)]
+pub struct Tweens {
+    pub slots: Vec<Option<Tween>>,
+}
+
+#[allow(dead_code)]
+impl Tweens {
+    pub fn add(&mut self, target: TweenTarget, duration: Duration, easing: Easing) -> usize {
+        self.add_with_completion(target, duration, easing, None)
+    }
+
+    // on_complete runs once, on the update that finishes the tween.
+    pub fn add_with_completion(
+        &mut self,
+        target: TweenTarget,
+        duration: Duration,
+        easing: Easing,
+        on_complete: Option<Box<dyn FnMut()>>,
+    ) -> usize {
+        let tween = Tween {
+            target,
+            duration,
+            easing,
+            elapsed: Duration::ZERO,
+            finished: false,
+            on_complete,
+        };
+        match self.slots.iter().position(|slot| slot.is_none()) {
+            Some(index) => {
+                self.slots[index] = Some(tween);
+                index
+            }
+            None => {
+                self.slots.push(Some(tween));
+                self.slots.len() - 1
+            }
+        }
+    }
+
+    pub fn remove(&mut self, index: usize) -> Option<Tween> {
+        self.slots.get_mut(index).and_then(|slot| slot.take())
+    }
+
+    pub fn get(&self, index: usize) -> Option<&Tween> {
+        self.slots.get(index).and_then(|slot| slot.as_ref())
+    }
+
+    // The current value of a TweenTarget::Value tween.
+    pub fn value(&self, index: usize) -> Option<f32> {
+        self.get(index).and_then(Tween::value)
+    }
+
+    pub fn is_finished(&self, index: usize) -> bool {
+        self.get(index).is_none_or(|tween| tween.finished)
+    }
+
+    // Advance every tween and write transforms and colors into the instance buffers.
+    pub fn update(
+        &mut self,
+        dt: Duration,
+        geos: &mut GeoManager,
+        queue: Arc<Mutex<Queue>>,
+    ) -> Result<(), ShecvError> {
+        for tween in self.slots.iter_mut().flatten() {
+            if tween.finished {
+                continue;
+            }
+            tween.elapsed = (tween.elapsed + dt).min(tween.duration);
+            let t = tween.progress();
+
+            match &tween.target {
+                TweenTarget::Transform { instance, from, to } => {
+                    if let Some(manager) = geos.instance_manager(*instance) {
+                        let transform = ComponentTransform {
+                            pixel_rect: to.pixel_rect,
+                            location: from.location.lerp(to.location, t),
+                            rotation: from.rotation.slerp(to.rotation, t),
+                            scale: from.scale.lerp(to.scale, t),
+                        };
+                        manager.set_transform(queue.clone(), instance.index, transform)?;
+                    }
+                }
+                TweenTarget::Color { instance, from, to } => {
+                    if let Some(manager) = geos.instance_manager(*instance) {
+                        manager.set_color(queue.clone(), instance.index, from.lerp(*to, t))?;
+                    }
+                }
+                TweenTarget::Pulse {
+                    instance,
+                    base,
+                    color,
+                    pulses,
+                } => {
+                    if let Some(manager) = geos.instance_manager(*instance) {
+                        let mix = (PI * *pulses as f32 * t).sin().powi(2);
+                        manager.set_color(queue.clone(), instance.index, base.lerp(*color, mix))?;
+                    }
+                }
+                TweenTarget::Value { .. } => {}
+            }
+
+            if tween.elapsed >= tween.duration {
+                tween.finished = true;
+                if let Some(on_complete) = tween.on_complete.as_mut() {
+                    on_complete();
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// Stop the color tweens running on instance, returning the color it rests
+// at once they are done.
+fn stop_color_tweens(tweens: &mut Tweens, instance: InstanceId) -> Option<ColorRGBA> {
+    let mut base = None;
+    for slot in tweens.slots.iter_mut() {
+        let rest = match slot.as_ref().map(|tween| (&tween.target, tween.finished)) {
+            Some((
+                TweenTarget::Color {
+                    instance: i, to, ..
+                },
+                false,
+            )) if *i == instance => *to,
+            Some((
+                TweenTarget::Pulse {
+                    instance: i, base, ..
+                },
+                false,
+            )) if *i == instance => *base,
+            _ => continue,
+        };
+        base = Some(rest);
+        *slot = None;
+    }
+    base
+}
+
+// Color changes over time for one instance, through Context::tweens. Each
+// starts from the color the instance rests at, and replaces any of these
+// already running on it, so flashing an instance twice doesn't leave it
+// stuck part way. None if the instance is gone.
+#[allow(dead_code)]
+impl InstanceId {
+    pub fn fade_color(
+        self,
+        context: &mut Context,
+        to: ColorRGBA,
+        duration: Duration,
+        easing: Easing,
+    ) -> Option<usize> {
+        let from = self.resting_color(context)?;
+        let target = TweenTarget::Color {
+            instance: self,
+            from,
+            to,
+        };
+        Some(context.tweens.add(target, duration, easing))
+    }
+
+    // Jump to color, then fade back.
+    pub fn flash(
+        self,
+        context: &mut Context,
+        color: ColorRGBA,
+        duration: Duration,
+    ) -> Option<usize> {
+        let base = self.resting_color(context)?;
+        let target = TweenTarget::Color {
+            instance: self,
+            from: color,
+            to: base,
+        };
+        Some(context.tweens.add(target, duration, Easing::QuadOut))
+    }
+
+    // Swell to color and back, pulses times, e.g. to draw the eye.
+    pub fn pulse(
+        self,
+        context: &mut Context,
+        color: ColorRGBA,
+        pulses: u32,
+        duration: Duration,
+    ) -> Option<usize> {
+        let base = self.resting_color(context)?;
+        let target = TweenTarget::Pulse {
+            instance: self,
+            base,
+            color,
+            pulses: pulses.max(1),
+        };
+        Some(context.tweens.add(target, duration, Easing::Linear))
+    }
+
+    fn resting_color(self, context: &mut Context) -> Option<ColorRGBA> {
+        let current = context.geos.instance_manager(self)?.data[self.index].color;
+        Some(stop_color_tweens(&mut context.tweens, self).unwrap_or(current))
+    }
+}
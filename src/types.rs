@@ -5,32 +5,193 @@ use std::{
     collections::HashMap,
     marker::PhantomData,
     mem::size_of,
-    ops::Deref,
+    ops::{Deref, Range},
     rc::Rc,
     sync::{Arc, Mutex},
 };
 
 use bytemuck::{ByteEq, ByteHash, Pod, Zeroable};
-use glam::{Mat4, Vec2, Vec3};
+use glam::{Mat4, Vec2, Vec3, Vec4};
+use image::RgbaImage;
 use wgpu::{
     util::{BufferInitDescriptor, DeviceExt},
-    Sampler, Texture, TextureView,
+    AddressMode, FilterMode, Sampler, SamplerDescriptor, Texture, TextureView,
 };
 use wgpu::{
     Buffer, BufferAddress, BufferUsages, Device, PipelineLayout, Queue, RenderPipeline,
     ShaderModule, TextureFormat, VertexAttribute, VertexBufferLayout, VertexFormat, VertexStepMode,
 };
 
-use crate::listui::{ListInterface, OperatorResult};
+use crate::{
+    derived::Derived,
+    error::ShecvError,
+    expr::eval_numeric,
+    history::History,
+    listui::{ListInterface, OperatorResult},
+    samples::Samples,
+    window::FlowCommand,
+};
+
+// Shared handle to a key in the ValueStore, as held by list items.
+// Holding one does not keep the key alive: once the key is removed, load
+// gives None and is_live false, and items show "<missing>" instead.
+#[derive(Clone)]
+pub struct ValueHandle(Rc<RefCell<Value<dyn ListItemData>>>);
+
+impl ValueHandle {
+    fn new(value: Value<dyn ListItemData>) -> Self {
+        ValueHandle(Rc::new(RefCell::new(value)))
+    }
+
+    pub fn key(&self) -> String {
+        self.0.borrow().key.clone()
+    }
+
+    // None if the key has been removed from the store since this handle was made.
+    pub fn load<'a>(&self, store: &'a ValueStore) -> Option<&'a dyn ListItemData> {
+        self.0.borrow().load(store)
+    }
+
+    #[allow(dead_code)]
+    pub fn is_live(&self, store: &ValueStore) -> bool {
+        self.0.borrow().is_live(store)
+    }
+}
 
 pub struct ValueStore {
     pub map: HashMap<String, Box<dyn ListItemData>>,
+    // None until enable_history; inserting and removing keys isn't recorded,
+    // only writes over them
+    pub history: Option<History>,
+    // bumped on every write to a key, so derived keys know when to update
+    versions: HashMap<String, u64>,
+    next_version: u64,
+    derived: Vec<Derived>,
+    // sent by buttons and app code, taken by the main loop
+    commands: Vec<FlowCommand>,
 }
 
 impl ValueStore {
     pub fn new() -> Self {
         Self {
             map: HashMap::new(),
+            history: None,
+            versions: HashMap::new(),
+            next_version: 0,
+            derived: vec![],
+            commands: vec![],
+        }
+    }
+
+    // Ask the main loop for something, e.g. from a button's action.
+    pub fn send_command(&mut self, command: FlowCommand) {
+        self.commands.push(command);
+    }
+
+    // Commands sent since the last call, oldest first.
+    pub fn take_commands(&mut self) -> Vec<FlowCommand> {
+        std::mem::take(&mut self.commands)
+    }
+
+    // Changes whenever key is written; None if it never has been.
+    pub fn version(&self, key: &str) -> Option<u64> {
+        self.versions.get(key).copied()
+    }
+
+    fn touch(&mut self, key: &str) {
+        self.next_version += 1;
+        self.versions.insert(key.to_string(), self.next_version);
+    }
+
+    // Keep key computed from deps, e.g. "fps" from "frame_time". It's worked
+    // out now and again by refresh_derived after any of deps is written.
+    // Derived keys can depend on ones derived before them.
+    pub fn derive<T: ListItemData>(
+        &mut self,
+        key: &str,
+        deps: &[&str],
+        compute: impl Fn(&ValueStore) -> Option<T> + 'static,
+    ) {
+        self.derived.retain(|derived| derived.key != key);
+        self.derived.push(Derived::new(key, deps, compute));
+        self.refresh_derived();
+    }
+
+    // Recompute the derived keys whose deps changed. Called once a frame,
+    // before the lists are laid out.
+    pub fn refresh_derived(&mut self) {
+        let mut derived = std::mem::take(&mut self.derived);
+        for derived in &mut derived {
+            if let Some(value) = derived.update(self) {
+                self.map.insert(derived.key.clone(), value);
+                self.touch(&derived.key);
+            }
+        }
+        self.derived = derived;
+    }
+
+    // Keep the last limit changes for undo and redo.
+    pub fn enable_history(&mut self, limit: usize) {
+        self.history = Some(History::new(limit));
+    }
+
+    // False if there was nothing to undo, or no history.
+    pub fn undo(&mut self) -> bool {
+        let Some(history) = &mut self.history else {
+            return false;
+        };
+        let keys = history.undo(&mut self.map);
+        keys.iter().for_each(|key| self.touch(key));
+        !keys.is_empty()
+    }
+
+    pub fn redo(&mut self) -> bool {
+        let Some(history) = &mut self.history else {
+            return false;
+        };
+        let keys = history.redo(&mut self.map);
+        keys.iter().for_each(|key| self.touch(key));
+        !keys.is_empty()
+    }
+
+    // Add a value to the Samples under key, making them first with room
+    // for capacity values if the key is missing.
+    pub fn push_sample(
+        &mut self,
+        key: &str,
+        value: f32,
+        capacity: usize,
+    ) -> Result<(), ShecvError> {
+        let samples = self
+            .map
+            .entry(key.to_string())
+            .or_insert_with(|| Box::new(Samples::new(capacity)))
+            .as_any_mut()
+            .downcast_mut::<Samples>()
+            .ok_or_else(|| ShecvError::Store(format!("'{}' doesn't hold samples", key)))?;
+        samples.push(value);
+        self.touch(key);
+        Ok(())
+    }
+
+    // Make changes that undo as one step, e.g. a preset setting many keys.
+    #[allow(dead_code)]
+    pub fn transaction<R>(&mut self, f: impl FnOnce(&mut Self) -> R) -> R {
+        if let Some(history) = &mut self.history {
+            history.begin();
+        }
+        let out = f(self);
+        if let Some(history) = &mut self.history {
+            history.end();
+        }
+        out
+    }
+
+    fn replace(&mut self, key: &str, value: Box<dyn ListItemData>) {
+        let old = self.map.insert(key.to_string(), value);
+        self.touch(key);
+        if let Some(history) = &mut self.history {
+            history.record(key, old);
         }
     }
 
@@ -41,32 +202,246 @@ impl ValueStore {
         }
     }
 
-    pub fn insert<T: 'static + ListItemData>(
-        &mut self,
-        key: &str,
-        v: T,
-    ) -> Rc<RefCell<Value<dyn ListItemData>>> {
-        Rc::new(RefCell::new(Value::<dyn ListItemData>::new(
+    pub fn contains(&self, key: &str) -> bool {
+        self.map.contains_key(key)
+    }
+
+    // Handles to a removed key stay valid; loading through them yields None.
+    #[allow(dead_code)]
+    pub fn remove(&mut self, key: &str) -> Option<Box<dyn ListItemData>> {
+        self.versions.remove(key);
+        self.map.remove(key)
+    }
+
+    pub fn load_as<T: 'static + Copy>(&self, key: &str) -> Option<T> {
+        self.map
+            .get(key)?
+            .deref()
+            .as_any()
+            .downcast_ref::<T>()
+            .copied()
+    }
+
+    // Replace the value under key by parsing text as the type already stored there.
+    // Numeric types accept expressions and relative edits, see expr::eval_numeric.
+    pub fn commit_text(&mut self, key: &str, text: &str) -> Result<(), ShecvError> {
+        let current = self
+            .map
+            .get(key)
+            .ok_or_else(|| ShecvError::Store(format!("no value stored under '{}'", key)))?;
+        let value = parse_text(current.deref(), key, text)?;
+        self.replace(key, value);
+        Ok(())
+    }
+
+    // Store an already boxed value, e.g. one a list item's binding checked.
+    pub fn write(&mut self, key: &str, value: Box<dyn ListItemData>) {
+        self.replace(key, value);
+    }
+
+    pub fn insert<T: 'static + ListItemData>(&mut self, key: &str, v: T) -> ValueHandle {
+        ValueHandle::new(Value::<dyn ListItemData>::new(key, Box::new(v), self))
+    }
+
+    // A handle to key, inserting default first if nothing is stored there yet.
+    pub fn bind<T: 'static + ListItemData>(&mut self, key: &str, default: T) -> ValueHandle {
+        self.bind_boxed(key, Box::new(default))
+    }
+
+    pub fn bind_boxed(&mut self, key: &str, default: Box<dyn ListItemData>) -> ValueHandle {
+        if !self.contains(key) {
+            Value::<dyn ListItemData>::new(key, default, self);
+        }
+        self.handle(key)
+    }
+
+    // A handle to key, whether or not anything is stored there.
+    pub fn handle(&self, key: &str) -> ValueHandle {
+        ValueHandle::new(self.get(key))
+    }
+
+    #[allow(dead_code)]
+    pub fn set<T: 'static + ListItemData>(&mut self, key: &str, v: T) {
+        self.replace(key, Box::new(v));
+    }
+}
+
+// Parse text as the type of current. Numeric types accept expressions and
+// relative edits to current, see expr::eval_numeric.
+pub fn parse_text(
+    current: &dyn ListItemData,
+    key: &str,
+    text: &str,
+) -> Result<Box<dyn ListItemData>, ShecvError> {
+    if let Some(components) = current.components() {
+        return parse_components(current, &components, key, text);
+    }
+    let current = current.as_any();
+    let out_of_range = |_| ShecvError::Store(format!("'{}' is out of range for '{}'", text, key));
+    let value: Box<dyn ListItemData> =
+        if let Some(v) = current.downcast_ref::<f64>() {
+            Box::new(eval_numeric(text, *v)?)
+        } else if let Some(v) = current.downcast_ref::<f32>() {
+            Box::new(eval_numeric(text, *v as f64)? as f32)
+        } else if let Some(v) = current.downcast_ref::<i32>() {
+            Box::new(i32::try_from(integer(eval_numeric(text, *v as f64)?)?).map_err(out_of_range)?)
+        } else if let Some(v) = current.downcast_ref::<i64>() {
+            Box::new(integer(eval_numeric(text, *v as f64)?)?)
+        } else if let Some(v) = current.downcast_ref::<u32>() {
+            Box::new(u32::try_from(integer(eval_numeric(text, *v as f64)?)?).map_err(out_of_range)?)
+        } else if let Some(v) = current.downcast_ref::<u64>() {
+            Box::new(u64::try_from(integer(eval_numeric(text, *v as f64)?)?).map_err(out_of_range)?)
+        } else if current.is::<bool>() {
+            Box::new(text.trim().parse::<bool>().map_err(|_| {
+                ShecvError::Store(format!("'{}' is not true or false", text.trim()))
+            })?)
+        } else if current.is::<String>() {
+            Box::new(text.to_string())
+        } else {
+            return Err(ShecvError::Store(format!(
+                "value under '{}' cannot be edited as text",
+                key
+            )));
+        };
+    Ok(value)
+}
+
+// Components split by commas, each an expression relative to the one it
+// replaces, so "+1, , *2" moves x, keeps y and doubles z. Brackets around
+// them are fine, as Display puts there. Colors can also be typed as hex.
+fn parse_components(
+    current: &dyn ListItemData,
+    components: &[f64],
+    key: &str,
+    text: &str,
+) -> Result<Box<dyn ListItemData>, ShecvError> {
+    let text = text.trim();
+    if current.as_any().is::<ColorRGBA>() && text.starts_with('#') {
+        return Ok(Box::new(ColorRGBA::from_hex(text)?));
+    }
+    let parts: Vec<&str> = text
+        .trim_start_matches(['[', '('])
+        .trim_end_matches([']', ')'])
+        .split(',')
+        .collect();
+    if parts.len() != components.len() {
+        return Err(ShecvError::Store(format!(
+            "'{}' needs {} values split by commas",
             key,
-            Box::new(v),
-            self,
-        )))
+            components.len()
+        )));
+    }
+    let values = parts
+        .iter()
+        .zip(components)
+        .map(|(part, &current)| match part.trim() {
+            "" => Ok(current),
+            part => eval_numeric(part, current),
+        })
+        .collect::<Result<Vec<f64>, ShecvError>>()?;
+    current
+        .with_components(&values)
+        .ok_or_else(|| ShecvError::Store(format!("'{}' is out of range for '{}'", text, key)))
+}
+
+fn integer(value: f64) -> Result<i64, ShecvError> {
+    let rounded = value.round();
+    if rounded < i64::MIN as f64 || rounded > i64::MAX as f64 {
+        return Err(ShecvError::Store(format!(
+            "{} is out of integer range",
+            value
+        )));
     }
+    Ok(rounded as i64)
 }
 
 #[allow(dead_code)]
 pub trait ToAny: 'static {
     fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
 }
 
 impl<T: 'static> ToAny for T {
     fn as_any(&self) -> &dyn Any {
         self
     }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
 }
 
 pub trait ListItemData: 'static + ToAny + std::fmt::Display {}
 
+impl dyn ListItemData {
+    // Any of the numeric types as an f64, for formatting and sliders.
+    // Samples give their newest value.
+    pub fn as_f64(&self) -> Option<f64> {
+        let any = self.as_any();
+        if let Some(v) = any.downcast_ref::<Samples>() {
+            v.latest().map(f64::from)
+        } else if let Some(v) = any.downcast_ref::<f64>() {
+            Some(*v)
+        } else if let Some(v) = any.downcast_ref::<f32>() {
+            Some(*v as f64)
+        } else if let Some(v) = any.downcast_ref::<i32>() {
+            Some(*v as f64)
+        } else if let Some(v) = any.downcast_ref::<i64>() {
+            Some(*v as f64)
+        } else if let Some(v) = any.downcast_ref::<u32>() {
+            Some(*v as f64)
+        } else {
+            any.downcast_ref::<u64>().map(|v| *v as f64)
+        }
+    }
+
+    pub fn is_float(&self) -> bool {
+        let any = self.as_any();
+        any.is::<f64>() || any.is::<f32>() || any.is::<Samples>()
+    }
+
+    // The parts of a Vec2, Vec3, IVec2 or ColorRGBA, colors as r, g, b, a.
+    pub fn components(&self) -> Option<Vec<f64>> {
+        let any = self.as_any();
+        if let Some(v) = any.downcast_ref::<Vec2>() {
+            Some(v.to_array().map(f64::from).to_vec())
+        } else if let Some(v) = any.downcast_ref::<Vec3>() {
+            Some(v.to_array().map(f64::from).to_vec())
+        } else if let Some(v) = any.downcast_ref::<IVec2>() {
+            Some(v.to_array().map(f64::from).to_vec())
+        } else {
+            any.downcast_ref::<ColorRGBA>()
+                .map(|c| [c.r, c.g, c.b, c.a].map(f64::from).to_vec())
+        }
+    }
+
+    // A value of the same type made from components, which there have to be
+    // as many of as components gives. Integer parts are rounded and color
+    // channels kept between 0 and 1.
+    pub fn with_components(&self, c: &[f64]) -> Option<Box<dyn ListItemData>> {
+        let any = self.as_any();
+        let value: Box<dyn ListItemData> = match c {
+            [x, y] if any.is::<Vec2>() => Box::new(Vec2::new(*x as f32, *y as f32)),
+            [x, y] if any.is::<IVec2>() => Box::new(IVec2::new(
+                i32::try_from(integer(*x).ok()?).ok()?,
+                i32::try_from(integer(*y).ok()?).ok()?,
+            )),
+            [x, y, z] if any.is::<Vec3>() => Box::new(Vec3::new(*x as f32, *y as f32, *z as f32)),
+            [r, g, b, a] if any.is::<ColorRGBA>() => {
+                let channel = |v: f64| v.clamp(0.0, 1.0) as f32;
+                Box::new(ColorRGBA::new(
+                    channel(*r),
+                    channel(*g),
+                    channel(*b),
+                    channel(*a),
+                ))
+            }
+            _ => return None,
+        };
+        Some(value)
+    }
+}
+
 #[allow(dead_code)]
 pub struct OpFnMut {
     callback: dyn FnMut(OperatorResult),
@@ -92,6 +467,10 @@ impl ListItemData for i64 {}
 impl ListItemData for u32 {}
 impl ListItemData for u64 {}
 impl ListItemData for String {}
+impl ListItemData for Vec2 {}
+impl ListItemData for Vec3 {}
+impl ListItemData for IVec2 {}
+impl ListItemData for ColorRGBA {}
 // impl ListItemData for OpFnMut {}
 // impl ListItemData for ListInterface<'_> {}
 
@@ -108,8 +487,14 @@ impl<T: ?Sized + 'static> Value<T>
 where
     T: 'static + ListItemData,
 {
-    pub fn load<'a>(&self, store: &'a ValueStore) -> &'a dyn ListItemData {
-        store.map.get(&self.key).unwrap().deref()
+    // None if the key has been removed from the store since this handle was made.
+    pub fn load<'a>(&self, store: &'a ValueStore) -> Option<&'a dyn ListItemData> {
+        store.map.get(&self.key).map(|value| value.deref())
+    }
+
+    #[allow(dead_code)]
+    pub fn is_live(&self, store: &ValueStore) -> bool {
+        store.contains(&self.key)
     }
 
     pub fn new(
@@ -118,6 +503,7 @@ where
         store: &mut ValueStore,
     ) -> Value<dyn ListItemData> {
         store.map.insert(key.to_string(), boxed_value);
+        store.touch(key);
 
         Value {
             p: PhantomData,
@@ -128,6 +514,7 @@ where
     pub fn replace(&mut self, boxed_value: Box<dyn ListItemData>, store: &mut ValueStore) {
         store.map.remove(&self.key);
         store.map.insert(self.key.as_str().to_string(), boxed_value);
+        store.touch(&self.key);
         self.p = PhantomData;
     }
 }
@@ -141,6 +528,12 @@ pub struct ColorRGBA {
     pub a: f32,
 }
 
+impl std::fmt::Display for ColorRGBA {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.to_hex())
+    }
+}
+
 impl Default for ColorRGBA {
     fn default() -> Self {
         Self {
@@ -152,6 +545,7 @@ impl Default for ColorRGBA {
     }
 }
 
+#[allow(dead_code)]
 impl ColorRGBA {
     pub fn new(r: f32, g: f32, b: f32, a: f32) -> Self {
         Self { r, g, b, a }
@@ -166,6 +560,170 @@ impl ColorRGBA {
         )
     }
 
+    pub fn to_wgpu_color(self) -> wgpu::Color {
+        wgpu::Color {
+            r: self.r as f64,
+            g: self.g as f64,
+            b: self.b as f64,
+            a: self.a as f64,
+        }
+    }
+
+    pub fn with_alpha(self, a: f32) -> Self {
+        Self { a, ..self }
+    }
+
+    // Hue in degrees, saturation and value from 0 to 1.
+    pub fn from_hsv(h: f32, s: f32, v: f32, a: f32) -> Self {
+        let c = v * s;
+        let (r, g, b) = hue_rgb(h, c);
+        let m = v - c;
+        Self::new(r + m, g + m, b + m, a)
+    }
+
+    // Hue in degrees, saturation and lightness from 0 to 1.
+    pub fn from_hsl(h: f32, s: f32, l: f32, a: f32) -> Self {
+        let c = (1.0 - (2.0 * l - 1.0).abs()) * s;
+        let (r, g, b) = hue_rgb(h, c);
+        let m = l - c / 2.0;
+        Self::new(r + m, g + m, b + m, a)
+    }
+
+    // Hue, saturation and value, as from_hsv takes them. Greys have hue 0.
+    pub fn to_hsv(self) -> (f32, f32, f32) {
+        let (max, min) = (
+            self.r.max(self.g).max(self.b),
+            self.r.min(self.g).min(self.b),
+        );
+        let s = if max > 0.0 { (max - min) / max } else { 0.0 };
+        (self.hue(max, min), s, max)
+    }
+
+    // Hue, saturation and lightness, as from_hsl takes them.
+    pub fn to_hsl(self) -> (f32, f32, f32) {
+        let (max, min) = (
+            self.r.max(self.g).max(self.b),
+            self.r.min(self.g).min(self.b),
+        );
+        let l = (max + min) / 2.0;
+        let s = match l > 0.0 && l < 1.0 {
+            true => (max - min) / (1.0 - (2.0 * l - 1.0).abs()),
+            false => 0.0,
+        };
+        (self.hue(max, min), s, l)
+    }
+
+    fn hue(self, max: f32, min: f32) -> f32 {
+        let chroma = max - min;
+        if chroma <= 0.0 {
+            return 0.0;
+        }
+        let h = if max == self.r {
+            ((self.g - self.b) / chroma).rem_euclid(6.0)
+        } else if max == self.g {
+            (self.b - self.r) / chroma + 2.0
+        } else {
+            (self.r - self.g) / chroma + 4.0
+        };
+        h * 60.0
+    }
+
+    // Channels scaled by alpha, for blending with One, OneMinusSrcAlpha.
+    pub fn premultiplied(self) -> Self {
+        Self::new(self.r * self.a, self.g * self.a, self.b * self.a, self.a)
+    }
+
+    // Back from premultiplied; fully transparent colors come back black.
+    pub fn unpremultiplied(self) -> Self {
+        if self.a <= 0.0 {
+            return Self::new(0.0, 0.0, 0.0, 0.0);
+        }
+        Self::new(self.r / self.a, self.g / self.a, self.b / self.a, self.a)
+    }
+
+    // From sRGB channels, like hex colors and image pixels, to the linear
+    // ones light adds up in. Alpha stays as it is.
+    pub fn to_linear(self) -> Self {
+        let linear = |c: f32| match c <= 0.04045 {
+            true => c / 12.92,
+            false => ((c + 0.055) / 1.055).powf(2.4),
+        };
+        Self::new(linear(self.r), linear(self.g), linear(self.b), self.a)
+    }
+
+    pub fn to_srgb(self) -> Self {
+        let srgb = |c: f32| match c <= 0.0031308 {
+            true => c * 12.92,
+            false => 1.055 * c.powf(1.0 / 2.4) - 0.055,
+        };
+        Self::new(srgb(self.r), srgb(self.g), srgb(self.b), self.a)
+    }
+
+    // Straight blend of every channel, alpha included.
+    pub fn lerp(self, other: Self, t: f32) -> Self {
+        Self::new(
+            self.r + (other.r - self.r) * t,
+            self.g + (other.g - self.g) * t,
+            self.b + (other.b - self.b) * t,
+            self.a + (other.a - self.a) * t,
+        )
+    }
+
+    // How bright the color looks from 0 to 1, as WCAG defines it, reading
+    // the channels as sRGB like hex colors are written. Alpha is ignored.
+    pub fn relative_luminance(self) -> f32 {
+        let linear = self.to_linear();
+        0.2126 * linear.r + 0.7152 * linear.g + 0.0722 * linear.b
+    }
+
+    // From 1 for the same brightness to 21 for black against white. WCAG
+    // asks text for at least 4.5, or 3 when it is large.
+    pub fn contrast_ratio(self, other: Self) -> f32 {
+        let (a, b) = (self.relative_luminance(), other.relative_luminance());
+        (a.max(b) + 0.05) / (a.min(b) + 0.05)
+    }
+
+    // "#rrggbb", or "#rrggbbaa" when it isn't opaque.
+    pub fn to_hex(self) -> String {
+        let [r, g, b, a] = [self.r, self.g, self.b, self.a]
+            .map(|channel| (channel.clamp(0.0, 1.0) * 255.0).round() as u8);
+        match a {
+            255 => format!("#{:02x}{:02x}{:02x}", r, g, b),
+            _ => format!("#{:02x}{:02x}{:02x}{:02x}", r, g, b, a),
+        }
+    }
+
+    // "#rrggbb" or "#rrggbbaa", or short as "#rgb" or "#rgba"; the # is
+    // optional.
+    pub fn from_hex(hex: &str) -> Result<Self, ShecvError> {
+        let digits = hex.trim_start_matches('#');
+        let width = match digits.len() {
+            3 | 4 => 1,
+            6 | 8 => 2,
+            _ => 0,
+        };
+        if width == 0 || !digits.is_ascii() {
+            return Err(ShecvError::Parse(format!(
+                "'{}' is not a #rrggbb or #rrggbbaa color",
+                hex
+            )));
+        }
+        let channel = |i: usize| -> Result<f32, ShecvError> {
+            u8::from_str_radix(&digits[i * width..(i + 1) * width], 16)
+                .map(|v| match width {
+                    1 => v as f32 / 15.0,
+                    _ => v as f32 / 255.0,
+                })
+                .map_err(|_| ShecvError::Parse(format!("'{}' is not a hex color", hex)))
+        };
+        let a = if digits.len() == width * 4 {
+            channel(3)?
+        } else {
+            1.0
+        };
+        Ok(Self::new(channel(0)?, channel(1)?, channel(2)?, a))
+    }
+
     pub fn black() -> Self {
         Self {
             r: 0.0,
@@ -248,6 +806,21 @@ impl ColorRGBA {
     }
 }
 
+// r, g and b before lightness is added, for a hue in degrees and a chroma.
+#[allow(dead_code)]
+fn hue_rgb(h: f32, c: f32) -> (f32, f32, f32) {
+    let h = h.rem_euclid(360.0) / 60.0;
+    let x = c * (1.0 - (h % 2.0 - 1.0).abs());
+    match h as u32 {
+        0 => (c, x, 0.0),
+        1 => (x, c, 0.0),
+        2 => (0.0, c, x),
+        3 => (0.0, x, c),
+        4 => (x, 0.0, c),
+        _ => (c, 0.0, x),
+    }
+}
+
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct Vertex {
@@ -346,6 +919,24 @@ pub const UNIT_SQUARE_BUFFER_LAYOUT: [VertexBufferLayout<'_>; 2] = [
                 shader_location: 13,
                 format: VertexFormat::Float32x4,
             },
+            // vec4 shape: pixel size, corner radius, border width
+            VertexAttribute {
+                offset: size_of::<[f32; 36]>() as BufferAddress,
+                shader_location: 14,
+                format: VertexFormat::Float32x4,
+            },
+            // vec4 border color
+            VertexAttribute {
+                offset: size_of::<[f32; 40]>() as BufferAddress,
+                shader_location: 15,
+                format: VertexFormat::Float32x4,
+            },
+            // vec4 shape kind, sweep and texture layer
+            VertexAttribute {
+                offset: size_of::<[f32; 44]>() as BufferAddress,
+                shader_location: 4,
+                format: VertexFormat::Float32x4,
+            },
         ],
     },
 ];
@@ -368,11 +959,104 @@ pub struct GeoUniformMatrix {
     pub buffer: Buffer,
 }
 
+#[derive(Copy, Clone)]
+pub struct Border {
+    pub width: f32,
+    pub color: ColorRGBA,
+}
+
+#[derive(Default, Copy, Clone, PartialEq, Eq)]
+pub enum ShapeKind {
+    #[default]
+    RoundedRect,
+    Ellipse,
+}
+
+// Rounded corners, ellipses and borders, drawn by the shader from a signed
+// distance to the instance's rect. size is the rect in pixels; the default
+// draws a plain rect.
+#[derive(Copy, Clone)]
+pub struct InstanceShape {
+    pub size: Vec2,
+    pub kind: ShapeKind,
+    pub corner_radius: f32,
+    pub border: Option<Border>,
+    // Fraction of the shape shown, clockwise from the top; below 1 makes a radial progress fill.
+    pub sweep: f32,
+}
+
+impl Default for InstanceShape {
+    fn default() -> Self {
+        Self {
+            size: Vec2::ZERO,
+            kind: ShapeKind::RoundedRect,
+            corner_radius: 0.0,
+            border: None,
+            sweep: 1.0,
+        }
+    }
+}
+
+#[allow(dead_code)]
+impl InstanceShape {
+    pub fn rounded(wh: UVec2, corner_radius: f32, border: Option<Border>) -> Self {
+        Self {
+            size: wh.as_vec2(),
+            corner_radius,
+            border,
+            ..Default::default()
+        }
+    }
+
+    // A rect with fully rounded ends along its longer side.
+    pub fn capsule(wh: UVec2) -> Self {
+        Self::rounded(wh, wh.min_element() as f32 * 0.5, None)
+    }
+
+    pub fn ellipse(wh: UVec2) -> Self {
+        Self {
+            size: wh.as_vec2(),
+            kind: ShapeKind::Ellipse,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_border(mut self, border: Border) -> Self {
+        self.border = Some(border);
+        self
+    }
+
+    pub fn with_sweep(mut self, sweep: f32) -> Self {
+        self.sweep = sweep.clamp(0.0, 1.0);
+        self
+    }
+
+    fn to_data(self) -> (Vec4, ColorRGBA, Vec4) {
+        let (border_width, border_color) = match self.border {
+            Some(border) => (border.width, border.color),
+            None => (0.0, ColorRGBA::new(0.0, 0.0, 0.0, 0.0)),
+        };
+        let kind = match self.kind {
+            ShapeKind::RoundedRect => 0.0,
+            ShapeKind::Ellipse => 1.0,
+        };
+        (
+            Vec4::new(self.size.x, self.size.y, self.corner_radius, border_width),
+            border_color,
+            Vec4::new(kind, self.sweep, 0.0, 0.0),
+        )
+    }
+}
+
 pub struct Instance {
     pub needs_update: bool,
     pub transform: ComponentTransform,
     pub tex_transform: ComponentTransform,
     pub color: ColorRGBA,
+    pub shape: InstanceShape,
+    // the layer of the group's texture array it samples, see
+    // GeoManager::new_unit_square_array
+    pub layer: u32,
 }
 
 impl Instance {
@@ -380,6 +1064,20 @@ impl Instance {
         self.transform.location += by;
         self.needs_update = true;
     }
+
+    // What the shader sees of the instance.
+    pub fn to_data(&self) -> InstanceData {
+        let (shape, border_color, mut shape_kind) = self.shape.to_data();
+        shape_kind.z = self.layer as f32;
+        InstanceData {
+            transform: self.transform.to_mat4(),
+            tex_transform: self.tex_transform.to_mat4(),
+            color: self.color,
+            shape,
+            border_color,
+            shape_kind,
+        }
+    }
 }
 
 #[derive(Copy, Clone, Pod, Zeroable, ByteEq, ByteHash)]
@@ -388,6 +1086,11 @@ pub struct InstanceData {
     pub transform: Mat4,
     pub tex_transform: Mat4,
     pub color: ColorRGBA,
+    pub shape: Vec4,
+    pub border_color: ColorRGBA,
+    // every vertex attribute location is taken, so the texture layer rides
+    // along in z
+    pub shape_kind: Vec4,
 }
 
 impl Default for InstanceData {
@@ -396,6 +1099,9 @@ impl Default for InstanceData {
             transform: Mat4::IDENTITY,
             tex_transform: Mat4::IDENTITY,
             color: ColorRGBA::new(1.0, 1.0, 1.0, 1.0),
+            shape: Vec4::ZERO,
+            border_color: ColorRGBA::new(0.0, 0.0, 0.0, 0.0),
+            shape_kind: Vec4::new(0.0, 1.0, 0.0, 0.0),
         }
     }
 }
@@ -403,20 +1109,25 @@ impl Default for InstanceData {
 pub struct InstanceBufferManager {
     pub data: Vec<Instance>,
     pub buffer: Buffer,
+    // bumped at an index whenever the instance there is removed or replaced,
+    // so InstanceIds to it stop working. Never shrinks.
+    generations: Vec<u32>,
 }
 
 impl InstanceBufferManager {
-    pub fn new(max_instances: usize, device: Arc<Mutex<Device>>) -> Self {
-        let device = device.lock().unwrap();
+    pub fn new(max_instances: usize, device: Arc<Mutex<Device>>) -> Result<Self, ShecvError> {
+        let device = device.lock()?;
         let init_buffer_data = vec![InstanceData::default(); max_instances];
-        InstanceBufferManager {
+        Ok(InstanceBufferManager {
             data: vec![],
+            generations: vec![],
             buffer: device.create_buffer_init(&BufferInitDescriptor {
                 label: Some("instance buffer"),
                 contents: bytemuck::cast_slice(&init_buffer_data),
-                usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+                // storage too, so compute jobs can write instances
+                usage: BufferUsages::VERTEX | BufferUsages::COPY_DST | BufferUsages::STORAGE,
             }),
-        }
+        })
     }
 
     pub fn add_instance(
@@ -425,12 +1136,13 @@ impl InstanceBufferManager {
         transform: ComponentTransform,
         tex_transform: ComponentTransform,
         color: ColorRGBA,
-    ) {
-        let queue = queue.lock().unwrap();
+    ) -> Result<(), ShecvError> {
+        let queue = queue.lock()?;
         let new_data = InstanceData {
             transform: transform.to_mat4(),
             tex_transform: tex_transform.to_mat4(),
             color,
+            ..Default::default()
         };
         queue.write_buffer(
             &self.buffer,
@@ -442,21 +1154,149 @@ impl InstanceBufferManager {
             transform,
             tex_transform,
             color,
+            shape: InstanceShape::default(),
+            layer: 0,
         });
+        Ok(())
+    }
+
+    // The generation of whatever is at index, see InstanceId.
+    pub fn generation(&self, index: usize) -> u32 {
+        self.generations.get(index).copied().unwrap_or(0)
+    }
+
+    // Whether index still holds the instance it did at generation.
+    pub fn is_live(&self, index: usize, generation: u32) -> bool {
+        index < self.data.len() && self.generation(index) == generation
+    }
+
+    // The instances at these indices are gone or replaced.
+    fn retire(&mut self, indices: Range<usize>) {
+        if self.generations.len() < indices.end {
+            self.generations.resize(indices.end, 0);
+        }
+        for generation in &mut self.generations[indices] {
+            *generation = generation.wrapping_add(1);
+        }
+    }
+
+    // Write an instance's current transforms and color to the instance buffer.
+    pub fn write_instance(&self, queue: Arc<Mutex<Queue>>, index: usize) -> Result<(), ShecvError> {
+        queue.lock()?.write_buffer(
+            &self.buffer,
+            (index * size_of::<InstanceData>()) as BufferAddress,
+            bytemuck::cast_slice(&[self.data[index].to_data()]),
+        );
+        Ok(())
+    }
+
+    // Swap in a whole new set of instances with one write, for groups rebuilt
+    // every frame. Anything past the buffer's capacity is dropped.
+    pub fn replace_all(
+        &mut self,
+        queue: Arc<Mutex<Queue>>,
+        mut instances: Vec<Instance>,
+    ) -> Result<(), ShecvError> {
+        let capacity = (self.buffer.size() as usize) / size_of::<InstanceData>();
+        instances.truncate(capacity);
+        self.retire(0..self.data.len());
+        self.data = instances;
+        if self.data.is_empty() {
+            return Ok(());
+        }
+        let data: Vec<InstanceData> = self.data.iter().map(Instance::to_data).collect();
+        queue
+            .lock()?
+            .write_buffer(&self.buffer, 0, bytemuck::cast_slice(&data));
+        Ok(())
+    }
+
+    pub fn set_tex_transform(
+        &mut self,
+        queue: Arc<Mutex<Queue>>,
+        index: usize,
+        tex_transform: ComponentTransform,
+    ) -> Result<(), ShecvError> {
+        self.data[index].tex_transform = tex_transform;
+        self.write_instance(queue, index)
+    }
+
+    pub fn set_transform(
+        &mut self,
+        queue: Arc<Mutex<Queue>>,
+        index: usize,
+        transform: ComponentTransform,
+    ) -> Result<(), ShecvError> {
+        self.data[index].transform = transform;
+        self.write_instance(queue, index)
+    }
+
+    pub fn set_color(
+        &mut self,
+        queue: Arc<Mutex<Queue>>,
+        index: usize,
+        color: ColorRGBA,
+    ) -> Result<(), ShecvError> {
+        self.data[index].color = color;
+        self.write_instance(queue, index)
+    }
+
+    pub fn set_shape(
+        &mut self,
+        queue: Arc<Mutex<Queue>>,
+        index: usize,
+        shape: InstanceShape,
+    ) -> Result<(), ShecvError> {
+        self.data[index].shape = shape;
+        self.write_instance(queue, index)
+    }
+
+    pub fn set_layer(
+        &mut self,
+        queue: Arc<Mutex<Queue>>,
+        index: usize,
+        layer: u32,
+    ) -> Result<(), ShecvError> {
+        self.data[index].layer = layer;
+        self.write_instance(queue, index)
+    }
+
+    // Remove an instance by moving the last one into its place. Returns the
+    // index the moved instance had, if one moved. Ids to the removed and the
+    // moved instance both stop working.
+    pub fn swap_remove(
+        &mut self,
+        queue: Arc<Mutex<Queue>>,
+        index: usize,
+    ) -> Result<Option<usize>, ShecvError> {
+        let last = self.data.len() - 1;
+        self.data.swap_remove(index);
+        self.retire(index..index + 1);
+        self.retire(last..last + 1);
+        if index == last {
+            return Ok(None);
+        }
+        self.write_instance(queue, index)?;
+        Ok(Some(last))
     }
 
     pub fn clear(&mut self) {
-        // instance.needs_update = false;
-        // let queue = queue.lock().unwrap();
+        self.retire(0..self.data.len());
         self.data.clear();
     }
 
-    pub fn recalc_screen_instances(&mut self, queue: Arc<Mutex<Queue>>, screen: UVec2) {
+    pub fn recalc_screen_instances(
+        &mut self,
+        queue: Arc<Mutex<Queue>>,
+        screen: UVec2,
+    ) -> Result<(), ShecvError> {
         for (instance_index, instance) in self.data.iter_mut().enumerate() {
             if instance.needs_update && instance.transform.pixel_rect.is_some() {
                 instance.needs_update = false;
-                let queue = queue.lock().unwrap();
+                let queue = queue.lock()?;
                 let pr = instance.transform.pixel_rect.unwrap();
+                let (shape, border_color, mut shape_kind) = instance.shape.to_data();
+                shape_kind.z = instance.layer as f32;
                 let new_data = InstanceData {
                     transform: ComponentTransform::unit_square_transform_from_pixel_rect(
                         PixelRect {
@@ -473,6 +1313,9 @@ impl InstanceBufferManager {
                     .to_mat4(),
                     tex_transform: instance.tex_transform.to_mat4(),
                     color: instance.color,
+                    shape,
+                    border_color,
+                    shape_kind,
                 };
                 queue.write_buffer(
                     &self.buffer,
@@ -481,9 +1324,11 @@ impl InstanceBufferManager {
                 );
             }
         }
+        Ok(())
     }
 }
 
+#[derive(Clone)]
 pub struct TextureSheetClusterDefinition {
     #[allow(dead_code)]
     pub label: String,
@@ -505,9 +1350,109 @@ impl Default for TextureSheetClusterDefinition {
     }
 }
 
+// How a sheet's texture is filtered and what lies past its edges.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SamplerSettings {
+    // drawn bigger than the texture
+    pub mag_filter: FilterMode,
+    // drawn smaller
+    pub min_filter: FilterMode,
+    // between mip levels, for sheets with mipmaps
+    pub mipmap_filter: FilterMode,
+    // the finest and coarsest mip levels sampled, 0 being the full image
+    pub lod_min_clamp: f32,
+    pub lod_max_clamp: f32,
+    pub address_mode: AddressMode,
+    // up to 16, for textures seen at a slant; only with linear filtering
+    pub anisotropy: u16,
+}
+
+// Crisp texels, as pixel art wants.
+impl Default for SamplerSettings {
+    fn default() -> Self {
+        Self::pixel_art()
+    }
+}
+
+#[allow(dead_code)]
+impl SamplerSettings {
+    pub fn pixel_art() -> Self {
+        Self {
+            mag_filter: FilterMode::Nearest,
+            min_filter: FilterMode::Nearest,
+            mipmap_filter: FilterMode::Nearest,
+            lod_min_clamp: 0.0,
+            lod_max_clamp: 32.0,
+            address_mode: AddressMode::Repeat,
+            anisotropy: 1,
+        }
+    }
+
+    // Blended texels, for ui skins, photos and render targets. Clamped, so
+    // edges don't pick up the far side.
+    pub fn smooth() -> Self {
+        Self {
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            mipmap_filter: FilterMode::Linear,
+            lod_min_clamp: 0.0,
+            lod_max_clamp: 32.0,
+            address_mode: AddressMode::ClampToEdge,
+            anisotropy: 1,
+        }
+    }
+
+    pub fn with_lod_clamp(mut self, min: f32, max: f32) -> Self {
+        self.lod_min_clamp = min;
+        self.lod_max_clamp = max.max(min);
+        self
+    }
+
+    pub fn with_address_mode(mut self, address_mode: AddressMode) -> Self {
+        self.address_mode = address_mode;
+        self
+    }
+
+    pub fn with_anisotropy(mut self, anisotropy: u16) -> Self {
+        self.anisotropy = anisotropy;
+        self
+    }
+
+    pub fn to_descriptor(self) -> SamplerDescriptor<'static> {
+        // wgpu rejects anisotropy unless every filter is linear
+        let linear = [self.mag_filter, self.min_filter, self.mipmap_filter]
+            .iter()
+            .all(|&filter| filter == FilterMode::Linear);
+        SamplerDescriptor {
+            address_mode_u: self.address_mode,
+            address_mode_v: self.address_mode,
+            address_mode_w: self.address_mode,
+            mag_filter: self.mag_filter,
+            min_filter: self.min_filter,
+            mipmap_filter: self.mipmap_filter,
+            lod_min_clamp: self.lod_min_clamp,
+            lod_max_clamp: self.lod_max_clamp,
+            anisotropy_clamp: match linear {
+                true => self.anisotropy.clamp(1, 16),
+                false => 1,
+            },
+            ..Default::default()
+        }
+    }
+}
+
 pub struct TextureSheetDefinition {
     pub path: String,
     pub clusters: Vec<TextureSheetClusterDefinition>,
+    // pixels made at runtime, e.g. by an Atlas, used instead of reading path
+    pub image: Option<RgbaImage>,
+    pub sampler: SamplerSettings,
+    // smaller copies of the image made on upload, so it doesn't shimmer
+    // far away or at a slant, e.g. on perspective groups
+    pub mipmaps: bool,
+    // show assets::FALLBACK_TEXTURE when the image is missing, instead of
+    // failing to load
+    pub fallback: bool,
 }
 
 impl TextureSheetDefinition {
@@ -515,6 +1460,55 @@ impl TextureSheetDefinition {
         Self {
             path: "".to_string(),
             clusters: vec![TextureSheetClusterDefinition::default()],
+            image: None,
+            sampler: SamplerSettings::default(),
+            mipmaps: false,
+            fallback: false,
+        }
+    }
+
+    // Keep running with a placeholder when the image can't be found, e.g. in
+    // a build shipped without its art. The placeholder is one small image,
+    // so clusters past its edges show whatever the sampler clamps to.
+    #[allow(dead_code)]
+    pub fn with_fallback(mut self) -> Self {
+        self.fallback = true;
+        self
+    }
+
+    #[allow(dead_code)]
+    pub fn with_mipmaps(mut self) -> Self {
+        self.mipmaps = true;
+        self
+    }
+
+    #[allow(dead_code)]
+    pub fn with_sampler(mut self, sampler: SamplerSettings) -> Self {
+        self.sampler = sampler;
+        self
+    }
+
+    // The pixel rect of a sub image within a sheet image of size extent.
+    pub fn sub_rect(&self, cluster_index: usize, sub_index: usize, extent: UVec2) -> PixelRect {
+        let c /*cluster*/ = &self.clusters[cluster_index];
+        let rc /*row count*/ = {
+            let mut rc = 0;
+            for _ in (0..c.cluster_size.x).step_by((c.sub_size.x + c.spacing.x) as usize) {
+                rc += 1;
+            }
+            rc
+        };
+
+        let row_index = sub_index as u32 / rc;
+        let col_index = sub_index as u32 % rc;
+
+        let x_offset = c.offset.x + col_index * (c.sub_size.x + c.spacing.x);
+        let y_offset = c.offset.y + row_index * (c.sub_size.y + c.spacing.y);
+
+        PixelRect {
+            xy: IVec2::new(x_offset as i32, y_offset as i32),
+            wh: c.sub_size,
+            extent,
         }
     }
 }
@@ -525,13 +1519,23 @@ impl Default for TextureSheetDefinition {
     }
 }
 
+// A texture and how it's cut up. Sheets made by load_texture_array have a
+// layer per image, each cut up by its own definition; the rest have one.
 pub struct TextureSheet {
+    // the first layer's
     pub sheet_info: TextureSheetDefinition,
+    // the second layer's on
+    pub more_layers: Vec<TextureSheetDefinition>,
+    // the size of every layer; smaller images sit in the top left of theirs
     pub dimensions: UVec2,
+    // None when the sheet shows a render target, which owns the texture
     #[allow(dead_code)]
-    pub texture: Texture,
+    pub texture: Option<Texture>,
     pub sampler: Sampler,
+    // the first layer
     pub view: TextureView,
+    // every layer, as shader.wgsl samples it
+    pub array_view: TextureView,
 }
 
 impl TextureSheet {
@@ -540,26 +1544,111 @@ impl TextureSheet {
         cluster_index: usize,
         sub_index: usize,
     ) -> ComponentTransform {
-        let c /*cluster*/ = &self.sheet_info.clusters[cluster_index];
-        let rc /*row count*/ = {
-            let mut rc = 0;
-            for _ in (0..c.cluster_size.x).step_by((c.sub_size.x + c.spacing.x) as usize) {
-                rc += 1;
-            }
-            rc
-        };
+        ComponentTransform::tex_transform_from_pixel_rect(
+            self.cluster_sub_rect(cluster_index, sub_index),
+        )
+    }
 
-        let row_index = sub_index as u32 / rc;
-        let col_index = sub_index as u32 % rc;
+    // The pixel rect of a sub image within the sheet.
+    pub fn cluster_sub_rect(&self, cluster_index: usize, sub_index: usize) -> PixelRect {
+        self.sheet_info
+            .sub_rect(cluster_index, sub_index, self.dimensions)
+    }
 
-        let x_offset = c.offset.x + col_index * (c.sub_size.x + c.spacing.x);
-        let y_offset = c.offset.y + row_index * (c.sub_size.y + c.spacing.y);
+    pub fn layer_count(&self) -> u32 {
+        self.more_layers.len() as u32 + 1
+    }
 
-        ComponentTransform::tex_transform_from_pixel_rect(PixelRect {
-            xy: IVec2::new(x_offset as i32, y_offset as i32),
-            wh: c.sub_size,
-            extent: self.dimensions,
-        })
+    pub fn layer_info(&self, layer: u32) -> &TextureSheetDefinition {
+        match layer {
+            0 => &self.sheet_info,
+            _ => &self.more_layers[layer as usize - 1],
+        }
+    }
+
+    // As cluster_sub_transform, cut up by the layer's definition.
+    pub fn layer_sub_transform(
+        &self,
+        layer: u32,
+        cluster_index: usize,
+        sub_index: usize,
+    ) -> ComponentTransform {
+        ComponentTransform::tex_transform_from_pixel_rect(self.layer_info(layer).sub_rect(
+            cluster_index,
+            sub_index,
+            self.dimensions,
+        ))
+    }
+}
+
+// A nine-slice panel skin: a sub image cut into corners, edges and center by
+// border widths in texture pixels. Corners keep their size (times scale),
+// edges stretch along one axis and the center stretches along both.
+#[derive(Copy, Clone)]
+pub struct NineSlice {
+    pub cluster_index: usize,
+    pub sub_index: usize,
+    pub left: u32,
+    pub top: u32,
+    pub right: u32,
+    pub bottom: u32,
+    pub scale: u32,
+}
+
+impl NineSlice {
+    // Screen and texture rects of the nine pieces, row by row.
+    pub fn slices(&self, sheet: &TextureSheet, rect: PixelRect) -> Vec<(PixelRect, PixelRect)> {
+        let src = sheet.cluster_sub_rect(self.cluster_index, self.sub_index);
+
+        // the stretched middle never goes negative on small panels
+        let cuts = |start: i32, size: u32, low: u32, high: u32| -> [i32; 4] {
+            let low = low.min(size / 2) as i32;
+            let high = high.min(size - low as u32) as i32;
+            [
+                start,
+                start + low,
+                start + size as i32 - high,
+                start + size as i32,
+            ]
+        };
+        let xs = cuts(
+            rect.xy.x,
+            rect.wh.x,
+            self.left * self.scale,
+            self.right * self.scale,
+        );
+        let ys = cuts(
+            rect.xy.y,
+            rect.wh.y,
+            self.top * self.scale,
+            self.bottom * self.scale,
+        );
+        let txs = cuts(src.xy.x, src.wh.x, self.left, self.right);
+        let tys = cuts(src.xy.y, src.wh.y, self.top, self.bottom);
+
+        let mut slices = vec![];
+        for row in 0..3 {
+            for col in 0..3 {
+                let screen = PixelRect {
+                    xy: IVec2::new(xs[col], ys[row]),
+                    wh: UVec2::new(
+                        (xs[col + 1] - xs[col]) as u32,
+                        (ys[row + 1] - ys[row]) as u32,
+                    ),
+                    extent: rect.extent,
+                };
+                let tex = PixelRect {
+                    xy: IVec2::new(txs[col], tys[row]),
+                    wh: UVec2::new(
+                        (txs[col + 1] - txs[col]) as u32,
+                        (tys[row + 1] - tys[row]) as u32,
+                    ),
+                    extent: src.extent,
+                };
+                slices.push((screen, tex));
+            }
+        }
+        slices
     }
 }
 
@@ -570,6 +1659,116 @@ pub struct PixelRect {
     pub extent: UVec2,
 }
 
+// Rects combined with others keep their own extent; both are expected to be
+// on the same screen.
+#[allow(dead_code)]
+impl PixelRect {
+    pub fn new(xy: IVec2, wh: UVec2, extent: UVec2) -> Self {
+        Self { xy, wh, extent }
+    }
+
+    // The whole screen.
+    pub fn full(extent: UVec2) -> Self {
+        Self::new(IVec2::ZERO, extent, extent)
+    }
+
+    // Just past the bottom right corner.
+    pub fn max(self) -> IVec2 {
+        self.xy + self.wh.as_ivec2()
+    }
+
+    pub fn center(self) -> IVec2 {
+        self.xy + self.wh.as_ivec2() / 2
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.wh.x == 0 || self.wh.y == 0
+    }
+
+    // Left and top edges are in, right and bottom ones out.
+    pub fn contains(self, point: IVec2) -> bool {
+        point.cmpge(self.xy).all() && point.cmplt(self.max()).all()
+    }
+
+    // The part both cover, if any.
+    pub fn intersect(self, other: Self) -> Option<Self> {
+        let (xy, max) = (self.xy.max(other.xy), self.max().min(other.max()));
+        max.cmpgt(xy)
+            .all()
+            .then(|| Self::new(xy, (max - xy).as_uvec2(), self.extent))
+    }
+
+    // The smallest rect covering both.
+    pub fn union(self, other: Self) -> Self {
+        let (xy, max) = (self.xy.min(other.xy), self.max().max(other.max()));
+        Self::new(xy, (max - xy).as_uvec2(), self.extent)
+    }
+
+    // Moved in from every side, by by.x left and right and by.y top and
+    // bottom. Shrinking past nothing leaves an empty rect at the center.
+    pub fn inset(self, by: IVec2) -> Self {
+        let wh = (self.wh.as_ivec2() - by * 2).max(IVec2::ZERO);
+        let xy = self.xy + (self.wh.as_ivec2() - wh) / 2;
+        Self::new(xy, wh.as_uvec2(), self.extent)
+    }
+
+    pub fn outset(self, by: IVec2) -> Self {
+        self.inset(-by)
+    }
+
+    // Cut at x pixels from the left into a left and a right part.
+    pub fn split_h(self, x: u32) -> (Self, Self) {
+        let x = x.min(self.wh.x);
+        let left = Self::new(self.xy, UVec2::new(x, self.wh.y), self.extent);
+        let right = Self::new(
+            self.xy + IVec2::new(x as i32, 0),
+            UVec2::new(self.wh.x - x, self.wh.y),
+            self.extent,
+        );
+        (left, right)
+    }
+
+    // Cut at y pixels from the top into a top and a bottom part.
+    pub fn split_v(self, y: u32) -> (Self, Self) {
+        let y = y.min(self.wh.y);
+        let top = Self::new(self.xy, UVec2::new(self.wh.x, y), self.extent);
+        let bottom = Self::new(
+            self.xy + IVec2::new(0, y as i32),
+            UVec2::new(self.wh.x, self.wh.y - y),
+            self.extent,
+        );
+        (top, bottom)
+    }
+
+    // The top left corner and size in normalized device coordinates, -1 to
+    // 1 left to right and bottom to top, as unit squares are placed.
+    pub fn to_ndc(self) -> (Vec2, Vec2) {
+        let extent = self.extent.as_vec2();
+        (
+            pixel_to_ndc(self.xy.as_vec2(), self.extent),
+            self.wh.as_vec2() / extent * 2.0,
+        )
+    }
+
+    // The pixels between two corners in normalized device coordinates.
+    pub fn from_ndc(a: Vec2, b: Vec2, extent: UVec2) -> Self {
+        let (a, b) = (ndc_to_pixel(a, extent), ndc_to_pixel(b, extent));
+        let (xy, max) = (a.min(b).round().as_ivec2(), a.max(b).round().as_ivec2());
+        Self::new(xy, (max - xy).as_uvec2(), extent)
+    }
+}
+
+// A point in pixels from the top left to normalized device coordinates.
+pub fn pixel_to_ndc(point: Vec2, extent: UVec2) -> Vec2 {
+    let uv = point / extent.as_vec2();
+    Vec2::new(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0)
+}
+
+#[allow(dead_code)]
+pub fn ndc_to_pixel(ndc: Vec2, extent: UVec2) -> Vec2 {
+    Vec2::new(ndc.x + 1.0, 1.0 - ndc.y) / 2.0 * extent.as_vec2()
+}
+
 pub struct ComponentTransform {
     pub pixel_rect: Option<PixelRect>,
     pub location: Vec3,
@@ -614,17 +1813,10 @@ impl ComponentTransform {
         // given window pixels x, y (top left) of w, h (width, height) produce a transform
         // that positions the UNIT_SQUARE geometry as desired in render space...
 
-        let xy = Vec2::new(pixel_rect.xy.x as f32, pixel_rect.xy.y as f32);
-        let wh = Vec2::new(pixel_rect.wh.x as f32, pixel_rect.wh.y as f32);
-        let extent = Vec2::new(pixel_rect.extent.x as f32, pixel_rect.extent.y as f32);
-
-        let location = Vec3::new(
-            (xy.x / extent.x) * 2.0 - 1.0,
-            1.0 - (xy.y / extent.y) * 2.0,
-            0.0,
-        );
+        let (xy, wh) = pixel_rect.to_ndc();
+        let location = xy.extend(0.0);
         let rotation = Quat::IDENTITY;
-        let scale = Vec3::new((wh.x / extent.x) * 2.0, (wh.y / extent.y) * 2.0, 1.0);
+        let scale = wh.extend(1.0);
 
         ComponentTransform {
             pixel_rect: Some(pixel_rect),
@@ -634,3 +1826,213 @@ impl ComponentTransform {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXTENT: UVec2 = UVec2::new(100, 80);
+
+    fn rect(x: i32, y: i32, w: u32, h: u32) -> PixelRect {
+        PixelRect::new(IVec2::new(x, y), UVec2::new(w, h), EXTENT)
+    }
+
+    fn corners(rect: PixelRect) -> (IVec2, IVec2) {
+        (rect.xy, rect.max())
+    }
+
+    #[test]
+    fn intersect_overlapping_and_contained() {
+        let a = rect(0, 0, 10, 10);
+        let b = rect(5, 5, 10, 10);
+        let both = a.intersect(b).unwrap();
+        assert_eq!(corners(both), (IVec2::new(5, 5), IVec2::new(10, 10)));
+        assert_eq!(corners(b.intersect(a).unwrap()), corners(both));
+
+        let inner = rect(2, 3, 4, 5);
+        assert_eq!(corners(a.intersect(inner).unwrap()), corners(inner));
+    }
+
+    #[test]
+    fn intersect_touching_disjoint_and_empty() {
+        let a = rect(0, 0, 10, 10);
+        // edges meeting cover nothing between them
+        assert!(a.intersect(rect(10, 0, 5, 5)).is_none());
+        assert!(a.intersect(rect(0, 10, 5, 5)).is_none());
+        assert!(a.intersect(rect(20, 20, 5, 5)).is_none());
+        assert!(a.intersect(rect(5, 5, 0, 5)).is_none());
+        assert!(a.intersect(rect(-5, -5, 6, 6)).is_some());
+    }
+
+    #[test]
+    fn union_covers_both() {
+        let a = rect(0, 0, 10, 10);
+        let far = rect(20, 30, 5, 5);
+        assert_eq!(
+            corners(a.union(far)),
+            (IVec2::new(0, 0), IVec2::new(25, 35))
+        );
+        assert_eq!(corners(a.union(a)), corners(a));
+        assert_eq!(
+            corners(a.union(rect(-4, 2, 2, 2))),
+            (IVec2::new(-4, 0), IVec2::new(10, 10))
+        );
+        assert_eq!(a.union(far).extent, EXTENT);
+    }
+
+    #[test]
+    fn contains_takes_left_and_top_edges_only() {
+        let a = rect(10, 10, 5, 5);
+        assert!(a.contains(IVec2::new(10, 10)));
+        assert!(a.contains(IVec2::new(14, 14)));
+        assert!(!a.contains(IVec2::new(15, 10)));
+        assert!(!a.contains(IVec2::new(10, 15)));
+        assert!(!rect(0, 0, 0, 0).contains(IVec2::ZERO));
+    }
+
+    #[test]
+    fn inset_past_nothing_is_empty_at_the_center() {
+        let a = rect(0, 0, 10, 6);
+        assert_eq!(
+            corners(a.inset(IVec2::new(2, 1))),
+            (IVec2::new(2, 1), IVec2::new(8, 5))
+        );
+        let gone = a.inset(IVec2::new(20, 20));
+        assert!(gone.is_empty());
+        assert_eq!(gone.xy, a.center());
+        assert_eq!(
+            corners(a.outset(IVec2::ONE)),
+            (IVec2::new(-1, -1), IVec2::new(11, 7))
+        );
+    }
+
+    #[test]
+    fn splits_clamp_to_the_rect() {
+        let a = rect(10, 20, 30, 40);
+        let (left, right) = a.split_h(10);
+        assert_eq!(corners(left), (IVec2::new(10, 20), IVec2::new(20, 60)));
+        assert_eq!(corners(right), (IVec2::new(20, 20), IVec2::new(40, 60)));
+        let (top, bottom) = a.split_v(100);
+        assert_eq!(corners(top), corners(a));
+        assert!(bottom.is_empty());
+    }
+
+    #[test]
+    fn ndc_round_trip() {
+        let a = rect(10, 20, 30, 40);
+        let (corner, size) = a.to_ndc();
+        let other = corner + Vec2::new(size.x, -size.y);
+        assert_eq!(
+            corners(PixelRect::from_ndc(corner, other, EXTENT)),
+            corners(a)
+        );
+        assert_eq!(pixel_to_ndc(Vec2::ZERO, EXTENT), Vec2::new(-1.0, 1.0));
+        assert_eq!(ndc_to_pixel(Vec2::new(1.0, -1.0), EXTENT), EXTENT.as_vec2());
+    }
+
+    #[test]
+    fn handles_outlive_their_keys() {
+        let mut store = ValueStore::new();
+        let handle = store.insert("speed", 2.0_f64);
+        let copy = handle.clone();
+        assert!(handle.is_live(&store));
+        assert_eq!(handle.load(&store).and_then(|v| v.as_f64()), Some(2.0));
+        store.remove("speed");
+        assert!(!copy.is_live(&store));
+        assert!(copy.load(&store).is_none());
+        assert_eq!(copy.key(), "speed");
+        // writing the key again brings every handle back
+        store.set("speed", 3.0_f64);
+        assert_eq!(handle.load(&store).and_then(|v| v.as_f64()), Some(3.0));
+    }
+
+    fn close(a: ColorRGBA, b: ColorRGBA) -> bool {
+        [a.r - b.r, a.g - b.g, a.b - b.b, a.a - b.a]
+            .iter()
+            .all(|d| d.abs() < 1e-4)
+    }
+
+    #[test]
+    fn hex_long_and_short_forms() {
+        let orange = ColorRGBA::from_hex("#ff8000").unwrap();
+        assert!(close(orange, ColorRGBA::new(1.0, 128.0 / 255.0, 0.0, 1.0)));
+        assert!(close(
+            ColorRGBA::from_hex("f80").unwrap(),
+            ColorRGBA::new(1.0, 8.0 / 15.0, 0.0, 1.0)
+        ));
+        assert!(close(
+            ColorRGBA::from_hex("#fff8").unwrap(),
+            ColorRGBA::new(1.0, 1.0, 1.0, 8.0 / 15.0)
+        ));
+        assert!(close(
+            ColorRGBA::from_hex("#00000080").unwrap(),
+            ColorRGBA::new(0.0, 0.0, 0.0, 128.0 / 255.0)
+        ));
+    }
+
+    #[test]
+    fn bad_hex_is_an_error() {
+        for hex in [
+            "",
+            "#",
+            "#ff",
+            "#fffff",
+            "#fffffffff",
+            "#ggg",
+            "#ff80zz",
+            "#ééé",
+        ] {
+            assert!(ColorRGBA::from_hex(hex).is_err(), "{}", hex);
+        }
+    }
+
+    #[test]
+    fn hex_round_trip() {
+        assert_eq!(ColorRGBA::from_hex("#ff8000").unwrap().to_hex(), "#ff8000");
+        assert_eq!(
+            ColorRGBA::from_hex("#12345678").unwrap().to_hex(),
+            "#12345678"
+        );
+        assert_eq!(ColorRGBA::from_hex("#abc").unwrap().to_hex(), "#aabbcc");
+        // out of range channels are clamped
+        assert_eq!(ColorRGBA::new(2.0, -1.0, 0.5, 1.0).to_hex(), "#ff0080");
+    }
+
+    #[test]
+    fn hsv_round_trip() {
+        for hex in [
+            "#ff0000", "#00ff00", "#0000ff", "#ff8000", "#336699", "#c0ffee", "#7f007f",
+        ] {
+            let color = ColorRGBA::from_hex(hex).unwrap();
+            let (h, s, v) = color.to_hsv();
+            assert!(close(ColorRGBA::from_hsv(h, s, v, 1.0), color), "{}", hex);
+            let (h, s, l) = color.to_hsl();
+            assert!(close(ColorRGBA::from_hsl(h, s, l, 1.0), color), "{}", hex);
+        }
+    }
+
+    #[test]
+    fn hsv_known_values() {
+        let (h, s, v) = ColorRGBA::from_hex("#00ff00").unwrap().to_hsv();
+        assert_eq!((h, s, v), (120.0, 1.0, 1.0));
+        assert!(close(
+            ColorRGBA::from_hsv(240.0, 1.0, 1.0, 0.5),
+            ColorRGBA::new(0.0, 0.0, 1.0, 0.5)
+        ));
+        assert!(close(
+            ColorRGBA::from_hsl(0.0, 1.0, 0.5, 1.0),
+            ColorRGBA::new(1.0, 0.0, 0.0, 1.0)
+        ));
+        // magenta is past 300 degrees, not negative
+        assert_eq!(ColorRGBA::new(1.0, 0.0, 0.5, 1.0).to_hsv().0, 330.0);
+    }
+
+    #[test]
+    fn greys_have_no_hue_or_saturation() {
+        for grey in [0.0, 0.5, 1.0] {
+            let color = ColorRGBA::new(grey, grey, grey, 1.0);
+            assert_eq!(color.to_hsv(), (0.0, 0.0, grey));
+            assert_eq!(color.to_hsl(), (0.0, 0.0, grey));
+        }
+    }
+}
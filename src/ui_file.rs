@@ -0,0 +1,569 @@
+use std::{collections::HashMap, fs::read_to_string, ops::Range, rc::Rc};
+
+use glam::{IVec2, Vec2, Vec3};
+use log::warn;
+use toml::{Table, Value};
+
+use crate::{
+    builder::ListBuilder,
+    error::ShecvError,
+    listui::{ListAnchor, ListOverflow, ListPopoutBehavior, ListStyle, ListUiId, ValueFormat},
+    theme::{StyleBorder, StyleColor, StyleTextShadow, ThemeRole},
+    types::{ColorRGBA, ValueStore},
+    window::{FlowCommand, State},
+};
+
+// Lists described in a TOML file. Each [[list]] becomes a top level list,
+// and each [[list.item]] is named after the ListBuilder method it stands for:
+//
+//   [[list]]
+//   name = "hud"
+//   visible = "show_hud"
+//
+//   [[list.item]]
+//   slider = "volume"
+//   range = [0.0, 1.0]
+//   format = "percent"
+//   tooltip = "left and right to adjust"
+//
+//   [[list.item]]
+//   sublist = "options"
+//   [[list.item.list.item]]
+//   button = "reset volume"
+//   set = { volume = 0.5 }
+//
+// Buttons set store keys, parsed as the type already stored there, run a
+// named action registered in UiActions, or send the main loop a command:
+// "quit", "pause", "resume", "reload_assets", or any other name as
+// FlowCommand::Custom. Any item may have a label_key, to be labelled with
+// that key's text in the current language, see Localization, and
+// flash = true to flash as its value changes.
+//
+// A list may also have anchor ("left", "middle", "right", "top", "center",
+// "bottom", "top_left", "top_right", "bottom_left", "bottom_right", "hidden",
+// or [x, y] in pixels), margin = [x, y] from the screen edges, popout
+// ("always" or "hidden_when_unfocused"), overflow ("offscreen", "columns" or
+// "scroll") and a [list.style] table of colors (bg, selected, selected_bg, unselected,
+// unselected_bg, activated, activated_bg, disabled, disabled_bg, flash_bg),
+// corner_radius, border = { width, color }, text_shadow = { offset = [x, y],
+// color }, text_outline = { width, color }, and sizes in pixels (row_height,
+// h_padding, v_padding, font_size, min_width, max_width, column_gap). Colors
+// are theme role names like "accent", or "#rrggbb" to stay fixed. format is
+// one of "default", "precision", "percent" or "si", with digits for the
+// decimals.
+
+pub type UiAction = Rc<dyn Fn(&mut ValueStore)>;
+
+// Button actions by name. They are shared, so the lists can be rebuilt when
+// the file changes.
+#[derive(Default, Clone)]
+pub struct UiActions {
+    actions: HashMap<String, UiAction>,
+}
+
+#[allow(dead_code)]
+impl UiActions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(mut self, name: &str, action: impl Fn(&mut ValueStore) + 'static) -> Self {
+        self.actions.insert(name.to_string(), Rc::new(action));
+        self
+    }
+}
+
+// A loaded file and the lists built from it, in the order they were created.
+pub struct UiFile {
+    pub path: String,
+    actions: UiActions,
+    pub lists: Vec<ListUiId>,
+    pub changed: bool,
+}
+
+// Read path into one builder per [[list]].
+pub fn load_ui_file(path: &str, actions: &UiActions) -> Result<Vec<ListBuilder>, ShecvError> {
+    let text = read_to_string(path)?;
+    parse_ui(&text, actions).map_err(|e| match e {
+        ShecvError::Parse(message) => ShecvError::Parse(format!("{}: {}", path, message)),
+        e => e,
+    })
+}
+
+pub fn parse_ui(text: &str, actions: &UiActions) -> Result<Vec<ListBuilder>, ShecvError> {
+    let document = text
+        .parse::<Table>()
+        .map_err(|e| ShecvError::Parse(e.to_string()))?;
+    tables(&document, "list")?
+        .into_iter()
+        .map(|list| parse_list(list, None, actions))
+        .collect()
+}
+
+fn parse_list(
+    list: &Table,
+    default_name: Option<&str>,
+    actions: &UiActions,
+) -> Result<ListBuilder, ShecvError> {
+    let name = match (string(list, "name")?, default_name) {
+        (Some(name), _) | (None, Some(name)) => name,
+        (None, None) => return Err(ShecvError::Parse("a list needs a name".into())),
+    };
+    let mut builder = ListBuilder::new(name);
+    if let Some(anchor) = list.get("anchor") {
+        builder = builder.anchor(parse_anchor(anchor)?);
+    }
+    if let Some(margin) = list.get("margin") {
+        builder = builder.margin(parse_pixels("margin", margin)?);
+    }
+    match string(list, "popout")? {
+        Some("always") | None => {}
+        Some("hidden_when_unfocused") => {
+            builder = builder.popout(ListPopoutBehavior::HiddenWhenUnfocused);
+        }
+        Some(popout) => return Err(ShecvError::Parse(format!("unknown popout '{}'", popout))),
+    }
+    match string(list, "overflow")? {
+        Some("offscreen") | None => {}
+        Some("columns") => builder = builder.overflow(ListOverflow::Columns),
+        Some("scroll") => builder = builder.overflow(ListOverflow::Scroll),
+        Some(overflow) => {
+            return Err(ShecvError::Parse(format!(
+                "unknown overflow '{}'",
+                overflow
+            )));
+        }
+    }
+    if let Some(key) = string(list, "visible")? {
+        builder = builder.bind_visible(key);
+    }
+    if let Some(style) = list.get("style") {
+        let style = style
+            .as_table()
+            .ok_or_else(|| expected("style", "a table", style))?;
+        builder = builder.style(parse_style(style)?);
+    }
+    for item in tables(list, "item")? {
+        builder = parse_item(builder, item, actions)?;
+    }
+    Ok(builder)
+}
+
+fn parse_item(
+    builder: ListBuilder,
+    item: &Table,
+    actions: &UiActions,
+) -> Result<ListBuilder, ShecvError> {
+    let mut builder = if let Some(text) = string(item, "label")? {
+        builder.label(text)
+    } else if let Some(key) = string(item, "value")? {
+        builder.value(key)
+    } else if let Some(key) = string(item, "input")? {
+        builder.input(key)
+    } else if let Some(key) = string(item, "slider")? {
+        builder.slider(key, parse_range(item)?)
+    } else if let Some(key) = string(item, "progress")? {
+        builder.progress(key, parse_range(item)?)
+    } else if let Some(key) = string(item, "sparkline")? {
+        let capacity = match item.get("capacity") {
+            Some(capacity) => capacity
+                .as_integer()
+                .and_then(|capacity| usize::try_from(capacity).ok())
+                .ok_or_else(|| expected("capacity", "a positive integer", capacity))?,
+            None => 60,
+        };
+        builder.sparkline(key, capacity)
+    } else if let Some(key) = string(item, "checkbox")? {
+        builder.checkbox(key)
+    } else if let Some(key) = string(item, "choice")? {
+        let options = item
+            .get("options")
+            .ok_or_else(|| ShecvError::Parse(format!("choice '{}' needs options", key)))?;
+        let texts: Option<Vec<&str>> = options
+            .as_array()
+            .and_then(|options| options.iter().map(Value::as_str).collect());
+        let texts = texts.ok_or_else(|| expected("options", "an array of strings", options))?;
+        builder.choice(key, &texts)
+    } else if let Some(key) = string(item, "vector")? {
+        parse_vector(builder, key, item)?
+    } else if let Some(key) = string(item, "color")? {
+        let default = match string(item, "default")? {
+            Some(hex) => ColorRGBA::from_hex(hex)?,
+            None => ColorRGBA::white(),
+        };
+        builder.vector(key, default)
+    } else if let Some(label) = string(item, "button")? {
+        builder.button(label, parse_action(label, item, actions)?)
+    } else if let Some(label) = string(item, "sublist")? {
+        let sublist = match item.get("list") {
+            Some(list) => list
+                .as_table()
+                .ok_or_else(|| expected("list", "a table", list))?
+                .clone(),
+            None => Table::new(),
+        };
+        builder.sublist(label, parse_list(&sublist, Some(label), actions)?)
+    } else {
+        return Err(ShecvError::Parse(
+            "an item needs one of label, value, input, slider, checkbox, choice, progress, \
+             sparkline, vector, color, button or sublist"
+                .into(),
+        ));
+    };
+
+    if let Some(format) = string(item, "format")? {
+        let digits = match item.get("digits") {
+            Some(digits) => digits
+                .as_integer()
+                .and_then(|digits| usize::try_from(digits).ok())
+                .ok_or_else(|| expected("digits", "a positive integer", digits))?,
+            None => 0,
+        };
+        builder = builder.format(match format {
+            "default" => ValueFormat::Default,
+            "precision" => ValueFormat::Precision(digits),
+            "percent" => ValueFormat::Percent(digits),
+            "si" => ValueFormat::Si(digits),
+            _ => return Err(ShecvError::Parse(format!("unknown format '{}'", format))),
+        });
+    }
+    if let Some(tooltip) = string(item, "tooltip")? {
+        builder = builder.tooltip(tooltip);
+    }
+    if let Some(key) = string(item, "label_key")? {
+        builder = builder.label_key(key);
+    }
+    if let Some(flash) = item.get("flash") {
+        if flash
+            .as_bool()
+            .ok_or_else(|| expected("flash", "true or false", flash))?
+        {
+            builder = builder.flash_on_change();
+        }
+    }
+    Ok(builder)
+}
+
+// Bounds for sliders and progress bars, 0 to 1 unless given.
+fn parse_range(item: &Table) -> Result<Range<f64>, ShecvError> {
+    let Some(range) = item.get("range") else {
+        return Ok(0.0..1.0);
+    };
+    let bounds: Option<Vec<f64>> = range
+        .as_array()
+        .map(|bounds| bounds.iter().filter_map(number).collect());
+    match bounds.as_deref() {
+        Some([start, end]) => Ok(*start..*end),
+        _ => Err(expected("range", "[start, end]", range)),
+    }
+}
+
+// A default of two integers makes an IVec2, otherwise two or three numbers
+// make a Vec2 or Vec3. Without one it's a Vec2 at zero.
+fn parse_vector(builder: ListBuilder, key: &str, item: &Table) -> Result<ListBuilder, ShecvError> {
+    let Some(default) = item.get("default") else {
+        return Ok(builder.vector(key, Vec2::ZERO));
+    };
+    let parts = default
+        .as_array()
+        .ok_or_else(|| expected("default", "an array of numbers", default))?;
+    let numbers: Option<Vec<f64>> = parts.iter().map(number).collect();
+    let ints = parts.iter().all(|part| part.as_integer().is_some());
+    Ok(match numbers.as_deref() {
+        Some(&[x, y]) if ints => builder.vector(key, IVec2::new(x as i32, y as i32)),
+        Some(&[x, y]) => builder.vector(key, Vec2::new(x as f32, y as f32)),
+        Some(&[x, y, z]) => builder.vector(key, Vec3::new(x as f32, y as f32, z as f32)),
+        _ => return Err(expected("default", "two or three numbers", default)),
+    })
+}
+
+fn parse_action(
+    label: &str,
+    item: &Table,
+    actions: &UiActions,
+) -> Result<impl FnMut(&mut ValueStore) + 'static, ShecvError> {
+    let named = match string(item, "action")? {
+        Some(name) => Some(
+            actions
+                .actions
+                .get(name)
+                .cloned()
+                .ok_or_else(|| ShecvError::Parse(format!("no action named '{}'", name)))?,
+        ),
+        None => None,
+    };
+    let command = string(item, "command")?.map(|command| match command {
+        "quit" => FlowCommand::Quit,
+        "pause" => FlowCommand::Pause,
+        "resume" => FlowCommand::Resume,
+        "reload_assets" => FlowCommand::ReloadAssets,
+        _ => FlowCommand::Custom(command.to_string()),
+    });
+    let mut sets = vec![];
+    if let Some(set) = item.get("set") {
+        let set = set
+            .as_table()
+            .ok_or_else(|| expected("set", "a table", set))?;
+        for (key, value) in set {
+            let text = match value {
+                Value::String(text) => text.clone(),
+                Value::Integer(_) | Value::Float(_) | Value::Boolean(_) => value.to_string(),
+                _ => return Err(expected(key, "a string, number or bool", value)),
+            };
+            sets.push((key.clone(), text));
+        }
+    }
+    let label = label.to_string();
+    Ok(move |store: &mut ValueStore| {
+        for (key, text) in &sets {
+            if let Err(e) = store.commit_text(key, text) {
+                warn!("button '{}' could not set '{}': {}", label, key, e);
+            }
+        }
+        if let Some(action) = &named {
+            action(store);
+        }
+        if let Some(command) = &command {
+            store.send_command(command.clone());
+        }
+    })
+}
+
+// A name, or [x, y] for a list at that position.
+fn parse_anchor(anchor: &Value) -> Result<ListAnchor, ShecvError> {
+    let Some(name) = anchor.as_str() else {
+        return Ok(ListAnchor::Positioned(parse_pixels("anchor", anchor)?));
+    };
+    Ok(match name {
+        "left" => ListAnchor::Left,
+        "middle" => ListAnchor::Middle,
+        "right" => ListAnchor::Right,
+        "top" => ListAnchor::Top,
+        "center" => ListAnchor::Center,
+        "bottom" => ListAnchor::Bottom,
+        "top_left" => ListAnchor::TopLeft,
+        "top_right" => ListAnchor::TopRight,
+        "bottom_left" => ListAnchor::BottomLeft,
+        "bottom_right" => ListAnchor::BottomRight,
+        "hidden" => ListAnchor::Hidden,
+        _ => return Err(ShecvError::Parse(format!("unknown anchor '{}'", name))),
+    })
+}
+
+// [x, y] in whole pixels.
+fn parse_pixels(key: &str, value: &Value) -> Result<IVec2, ShecvError> {
+    let parts: Option<Vec<i32>> = value.as_array().and_then(|parts| {
+        parts
+            .iter()
+            .map(|part| part.as_integer().and_then(|v| i32::try_from(v).ok()))
+            .collect()
+    });
+    match parts.as_deref() {
+        Some(&[x, y]) => Ok(IVec2::new(x, y)),
+        _ => Err(expected(key, "[x, y] in pixels", value)),
+    }
+}
+
+fn parse_style(table: &Table) -> Result<ListStyle, ShecvError> {
+    let mut style = ListStyle::default();
+    for (key, value) in table {
+        let color = || {
+            value
+                .as_str()
+                .ok_or_else(|| expected(key, "a color", value))
+                .and_then(StyleColor::parse)
+        };
+        let pixels = || {
+            value
+                .as_integer()
+                .and_then(|v| u32::try_from(v).ok())
+                .ok_or_else(|| expected(key, "a positive integer", value))
+        };
+        match key.as_str() {
+            "bg" => style.bg = color()?,
+            "selected" => style.li_selected = color()?,
+            "selected_bg" => style.li_selected_bg = color()?,
+            "unselected" => style.li_unselected = color()?,
+            "unselected_bg" => style.li_unselected_bg = color()?,
+            "activated" => style.li_activated = color()?,
+            "activated_bg" => style.li_activated_bg = color()?,
+            "disabled" => style.li_disabled = color()?,
+            "disabled_bg" => style.li_disabled_bg = color()?,
+            "flash_bg" => style.li_flash_bg = color()?,
+            "progress" => style.progress = color()?,
+            "progress_track" => style.progress_track = color()?,
+            "corner_radius" => {
+                style.corner_radius =
+                    number(value).ok_or_else(|| expected(key, "a number", value))? as f32;
+            }
+            "row_height" => style.row_height = pixels()?,
+            "h_padding" => style.h_padding = pixels()?,
+            "v_padding" => style.v_padding = pixels()?,
+            "min_width" => style.min_width = pixels()?,
+            "max_width" => style.max_width = Some(pixels()?),
+            "column_gap" => style.column_gap = pixels()?,
+            "font_size" => {
+                style.font_size =
+                    number(value).ok_or_else(|| expected(key, "a number", value))? as f32;
+            }
+            "border" => {
+                let border = value
+                    .as_table()
+                    .ok_or_else(|| expected(key, "a table", value))?;
+                let width = border.get("width").and_then(number).unwrap_or(1.0);
+                let color = match string(border, "color")? {
+                    Some(color) => StyleColor::parse(color)?,
+                    None => ThemeRole::Border.into(),
+                };
+                style.border = Some(StyleBorder {
+                    width: width as f32,
+                    color,
+                });
+            }
+            "text_shadow" => {
+                let shadow = value
+                    .as_table()
+                    .ok_or_else(|| expected(key, "a table", value))?;
+                let offset = match shadow.get("offset") {
+                    Some(offset) => parse_pixels("offset", offset)?.as_vec2(),
+                    None => Vec2::ONE,
+                };
+                let color = match string(shadow, "color")? {
+                    Some(color) => StyleColor::parse(color)?,
+                    None => StyleColor::Fixed(ColorRGBA::black()),
+                };
+                style.text_shadow = Some(StyleTextShadow { offset, color });
+            }
+            "text_outline" => {
+                let outline = value
+                    .as_table()
+                    .ok_or_else(|| expected(key, "a table", value))?;
+                let width = outline.get("width").and_then(number).unwrap_or(1.0);
+                let color = match string(outline, "color")? {
+                    Some(color) => StyleColor::parse(color)?,
+                    None => StyleColor::Fixed(ColorRGBA::black()),
+                };
+                style.text_outline = Some(StyleBorder {
+                    width: width as f32,
+                    color,
+                });
+            }
+            _ => return Err(ShecvError::Parse(format!("unknown style '{}'", key))),
+        }
+    }
+    Ok(style)
+}
+
+// The tables of an array of tables, or none if the key is missing.
+fn tables<'t>(table: &'t Table, key: &str) -> Result<Vec<&'t Table>, ShecvError> {
+    let Some(value) = table.get(key) else {
+        return Ok(vec![]);
+    };
+    value
+        .as_array()
+        .and_then(|array| array.iter().map(Value::as_table).collect())
+        .ok_or_else(|| expected(key, "an array of tables", value))
+}
+
+fn string<'t>(table: &'t Table, key: &str) -> Result<Option<&'t str>, ShecvError> {
+    match table.get(key) {
+        Some(value) => value
+            .as_str()
+            .map(Some)
+            .ok_or_else(|| expected(key, "a string", value)),
+        None => Ok(None),
+    }
+}
+
+// Integers are accepted where floats are expected.
+fn number(value: &Value) -> Option<f64> {
+    value
+        .as_float()
+        .or_else(|| value.as_integer().map(|v| v as f64))
+}
+
+fn expected(key: &str, what: &str, found: &Value) -> ShecvError {
+    ShecvError::Parse(format!(
+        "'{}' should be {}, not {}",
+        key,
+        what,
+        found.type_str()
+    ))
+}
+
+impl State<'_> {
+    // Build the lists in path and rebuild them whenever it changes.
+    // Returns the ids of the top level lists.
+    pub fn load_ui_file(
+        &mut self,
+        path: &str,
+        actions: UiActions,
+        store: &mut ValueStore,
+    ) -> Result<Vec<ListUiId>, ShecvError> {
+        let builders = load_ui_file(path, &actions)?;
+        let (top, lists) = self.build_ui(builders, store)?;
+        self.context
+            .as_mut()
+            .ok_or_else(ShecvError::no_context)?
+            .file_watcher
+            .add_ui_path(path)?;
+        self.ui_files.push(UiFile {
+            path: path.to_string(),
+            actions,
+            lists,
+            changed: false,
+        });
+        Ok(top)
+    }
+
+    // Rebuild lists whose file changed since they were built. A file that no
+    // longer parses leaves its lists as they were.
+    pub fn reload_ui_files(&mut self, store: &mut ValueStore) -> Result<(), ShecvError> {
+        for index in 0..self.ui_files.len() {
+            if !self.ui_files[index].changed {
+                continue;
+            }
+            self.ui_files[index].changed = false;
+            let builders = load_ui_file(&self.ui_files[index].path, &self.ui_files[index].actions)?;
+
+            // the rebuilt lists take over the old ones' slots, and keep their
+            // selections where the new lists are long enough
+            let old = std::mem::take(&mut self.ui_files[index].lists);
+            let selected: Vec<i32> = old
+                .iter()
+                .map(|&list| self.listuis[list].selected_index)
+                .collect();
+            for &list in old.iter().rev() {
+                self.free_listui(list)?;
+            }
+            let (_, lists) = self.build_ui(builders, store)?;
+            for (&list, selected_index) in lists.iter().zip(selected) {
+                let listui = &mut self.listuis[list];
+                if (selected_index as usize) < listui.entries.len() {
+                    listui.selected_index = selected_index;
+                }
+            }
+            self.ui_files[index].lists = lists;
+        }
+        Ok(())
+    }
+
+    // Returns the top level lists and every list created, sublists included.
+    fn build_ui(
+        &mut self,
+        builders: Vec<ListBuilder>,
+        store: &mut ValueStore,
+    ) -> Result<(Vec<ListUiId>, Vec<ListUiId>), ShecvError> {
+        let mut top = vec![];
+        let mut lists = vec![];
+        for builder in builders {
+            // new lists take freed lists from the back before making more
+            let free = self.free_listuis.clone();
+            let before: Vec<ListUiId> = self.listuis.ids().collect();
+            top.push(builder.build(self, store)?);
+            lists.extend(free[self.free_listuis.len()..].iter().rev());
+            lists.extend(self.listuis.ids().filter(|list| !before.contains(list)));
+        }
+        Ok((top, lists))
+    }
+}
@@ -0,0 +1,138 @@
+use std::collections::HashMap;
+
+use toml::{Table, Value};
+
+use crate::{error::ShecvError, listui::ListUiId, types::ValueStore, window::State};
+
+// Where each list was left: open, focused, selected row, scroll and popout.
+// Lists are saved by the name ListBuilder gave them, or by the order they
+// were made in when they have none, so the same lists made the same way
+// after a restart pick up where they were:
+//
+//   fs::write("ui_state.toml", state.save_ui_state())?;
+//   ..
+//   if let Ok(text) = fs::read_to_string("ui_state.toml") {
+//       state.restore_ui_state(&text, &store)?;
+//   }
+//
+// Restored lists keep their selection when opened again if their resume is
+// ListResumeBehavior::LastUsed.
+#[allow(dead_code)]
+impl State<'_> {
+    pub fn save_ui_state(&self) -> String {
+        let keys = self.list_keys();
+        let key_of: HashMap<ListUiId, &String> = keys.iter().map(|(id, key)| (*id, key)).collect();
+        let lists = keys
+            .iter()
+            .map(|(id, key)| {
+                let listui = &self.listuis[*id];
+                let mut table = Table::new();
+                table.insert("name".into(), Value::String(key.clone()));
+                table.insert("open".into(), Value::Boolean(listui.open));
+                table.insert("focused".into(), Value::Boolean(listui.focused));
+                table.insert(
+                    "selected".into(),
+                    Value::Integer(listui.selected_index as i64),
+                );
+                table.insert("scroll".into(), Value::Float(listui.scroll.target() as f64));
+                table.insert("popout".into(), Value::Float(listui.popout.delta as f64));
+                if let Some(parent) = listui.parent.and_then(|parent| key_of.get(&parent)) {
+                    table.insert("parent".into(), Value::String(parent.to_string()));
+                }
+                Value::Table(table)
+            })
+            .collect();
+        let mut document = Table::new();
+        document.insert("list".into(), Value::Array(lists));
+        document.to_string()
+    }
+
+    // Lists in text that aren't around any more are passed over, and lists
+    // not in it are left as they are. Selections that can't be selected now
+    // move to the first row that can.
+    pub fn restore_ui_state(&mut self, text: &str, store: &ValueStore) -> Result<(), ShecvError> {
+        let parse_error = |message: String| ShecvError::Parse(format!("ui state: {}", message));
+        let document = text
+            .parse::<Table>()
+            .map_err(|e| parse_error(e.to_string()))?;
+        let Some(lists) = document.get("list") else {
+            return Ok(());
+        };
+        let lists = lists
+            .as_array()
+            .ok_or_else(|| parse_error("'list' should be an array of tables".into()))?;
+        let ids: HashMap<String, ListUiId> = self
+            .list_keys()
+            .into_iter()
+            .map(|(id, key)| (key, id))
+            .collect();
+        let context = self.context.as_mut().ok_or_else(ShecvError::no_context)?;
+
+        for entry in lists {
+            let entry = entry
+                .as_table()
+                .ok_or_else(|| parse_error("'list' should be an array of tables".into()))?;
+            let Some(&id) = entry
+                .get("name")
+                .and_then(Value::as_str)
+                .and_then(|name| ids.get(name))
+            else {
+                continue;
+            };
+            let number = |key: &str| {
+                entry
+                    .get(key)
+                    .and_then(|value| value.as_float().or(value.as_integer().map(|i| i as f64)))
+            };
+            let listui = &mut self.listuis[id];
+            if let Some(open) = entry.get("open").and_then(Value::as_bool) {
+                listui.open = open;
+            }
+            if let Some(focused) = entry.get("focused").and_then(Value::as_bool) {
+                listui.focused = focused;
+            }
+            if let Some(parent) = entry.get("parent").and_then(Value::as_str) {
+                listui.parent = ids.get(parent).copied();
+            }
+            if let Some(selected) = entry.get("selected").and_then(Value::as_integer) {
+                listui.selected_index = selected as i32;
+                let selectable = listui
+                    .entries
+                    .get(selected as usize)
+                    .is_some_and(|item| item.can_select(store));
+                if !selectable {
+                    listui.select_first(store);
+                }
+            }
+            if let Some(scroll) = number("scroll") {
+                listui.scroll.set(scroll as f32, &mut context.tweens);
+            }
+            if let Some(popout) = number("popout") {
+                if let Some(tween) = listui.popout.tween.take() {
+                    context.tweens.remove(tween);
+                }
+                listui.popout.delta = (popout as f32).clamp(0.0, 1.0);
+            }
+        }
+        Ok(())
+    }
+
+    // What each list in use is saved as: its name, or "#n" for the nth list
+    // when it has none or another list took the name first.
+    fn list_keys(&self) -> Vec<(ListUiId, String)> {
+        let mut keys: Vec<(ListUiId, String)> = vec![];
+        let ids = self
+            .listuis
+            .ids()
+            .filter(|id| !self.free_listuis.contains(id));
+        for (n, id) in ids.enumerate() {
+            let name = &self.listuis[id].name;
+            let key = match name.is_empty() || keys.iter().any(|(_, key)| key == name) {
+                true => format!("#{}", n),
+                false => name.clone(),
+            };
+            keys.push((id, key));
+        }
+        keys
+    }
+}
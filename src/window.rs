@@ -1,46 +1,95 @@
-use glam::{IVec2, UVec2};
+use glam::{IVec2, UVec2, Vec2, Vec3, Vec4};
 
 use glyphon::Resolution;
-use sdl2::{
-    event::{Event, WindowEvent},
-    keyboard::Keycode,
-    video::Window,
-    Sdl,
-};
+use image::RgbaImage;
+use log::{error, info, warn};
 use wgpu::{
-    CommandEncoderDescriptor, CompositeAlphaMode, Device, DeviceDescriptor, IndexFormat, Instance,
-    InstanceDescriptor, LoadOp, Operations, PresentMode, Queue, RenderPassColorAttachment,
-    RenderPassDescriptor, RequestAdapterOptions, Surface, SurfaceConfiguration, TextureFormat,
-    TextureUsages, TextureViewDescriptor,
+    Adapter, Buffer, CommandEncoder, CommandEncoderDescriptor, CompositeAlphaMode, Device,
+    DeviceDescriptor, Extent3d, Features, IndexFormat, Instance, InstanceDescriptor, LoadOp,
+    Operations, PresentMode, Queue, RenderPass, RenderPassColorAttachment, RenderPassDescriptor,
+    RenderPassTimestampWrites, RequestAdapterOptions, Surface, SurfaceConfiguration, SurfaceError,
+    SurfaceTexture, Texture, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages,
+    TextureView, TextureViewDescriptor,
 };
 
 use std::{
     cell::RefCell,
-    error::Error,
-    fs::metadata,
+    fs::{canonicalize, metadata},
+    path::{Path, PathBuf},
     rc::Rc,
     sync::{Arc, Mutex},
-    time::{Duration, SystemTime},
+    time::{Duration, Instant, SystemTime},
 };
 
 use crate::{
-    geo::{GeoManager, GeoViewType},
-    listui::{ListAnchor, ListInterface},
-    types::{TextureSheetDefinition, ValueStore},
+    access::Accessibility,
+    anim::Animators,
+    assets::{self, Assets},
+    audio::Audio,
+    backend::{
+        Backend, BackendEvent, BackendKind, BackendWindow, FullscreenMode, Key, SharedBackend,
+    },
+    background::{Background, BackgroundPass},
+    capture::{FrameCopy, Recording},
+    compute::{ComputeBindingKind, ComputeJobs},
+    console::Console,
+    debug::DebugOverlay,
+    dialog::Dialogs,
+    ecs::RenderSync,
+    error::ShecvError,
+    geo::{
+        load_texture, read_sheet_image, GeoInstances, GeoManager, GeoViewType, GroupId,
+        RenderTargetId,
+    },
+    grid::{GridId, GridInterface},
+    hud::{HudId, HudWidget},
+    layer::RenderLayer,
+    layout::{Align, LayoutNode},
+    listui::{
+        ListAnchor, ListInterface, ListItemType, ListLayout, ListMotion, ListOverflow,
+        ListPopoutBehavior, ListResumeBehavior, ListUiId, UiEvent,
+    },
+    locale::Localization,
+    notify::Notifications,
+    particle::{EmitterSettings, ParticleEmitter, ParticleEmitters},
+    pipeline::PipelineCache,
+    post::PostChain,
+    progress::{add_progress_rects, ProgressBar},
+    script::Scripts,
+    slots::Slots,
+    theme::Themes,
+    timing::{FramePacer, GpuTimer, RenderStats, TimedPass},
+    tween::Tweens,
+    types::{ColorRGBA, TextureSheetDefinition, ValueStore},
+    ui_file::UiFile,
+    world_text::{WorldLabel, WorldLabelId},
 };
 use crate::{
-    text::TextCollection,
-    types::{ComponentTransform, PixelRect},
+    text::{TextCollection, TextSpan},
+    types::{ComponentTransform, InstanceShape, PixelRect},
 };
 
+#[derive(Copy, Clone, PartialEq, Eq)]
+// What to reload when a file changes.
 enum FileWatcherAction {
-    ReloadShader,
+    Shader,
+    // picked up by State::reload_ui_files, which has the store
+    Ui,
+    // picked up by State::update_scripts, likewise
+    Script,
+    Asset,
 }
 
+// One per file, however many times or ways it was added.
 struct FileWatcherEntry {
-    path: String,
+    canonical: PathBuf,
+    // the paths it was added as, which is how shaders and ui files know it
+    paths: Vec<String>,
     last_modified: SystemTime,
-    action: FileWatcherAction,
+    actions: Vec<FileWatcherAction>,
+    // set once the file can't be read, e.g. deleted, so that's only
+    // reported once rather than every check until it's back
+    unreadable: bool,
 }
 
 pub struct FileWatcher {
@@ -52,54 +101,545 @@ impl FileWatcher {
         FileWatcher { entries: vec![] }
     }
 
-    pub fn add_path(&mut self, path: &str) {
-        let metadata = metadata(path).unwrap();
+    pub fn add_path(&mut self, path: &str) -> Result<(), ShecvError> {
+        self.watch(path, FileWatcherAction::Shader)
+    }
+
+    pub fn add_ui_path(&mut self, path: &str) -> Result<(), ShecvError> {
+        self.watch(path, FileWatcherAction::Ui)
+    }
+
+    #[allow(dead_code)]
+    pub fn add_script_path(&mut self, path: &str) -> Result<(), ShecvError> {
+        self.watch(path, FileWatcherAction::Script)
+    }
+
+    pub fn add_asset_path(&mut self, path: &str) -> Result<(), ShecvError> {
+        self.watch(path, FileWatcherAction::Asset)
+    }
+
+    // Adding a file again, even by another path to it, adds the action to
+    // its entry, so a change is only acted on once per action.
+    fn watch(&mut self, path: &str, action: FileWatcherAction) -> Result<(), ShecvError> {
+        // read from the binary, with nothing on disk to watch
+        if assets::embedded(path).is_some() && !Path::new(path).exists() {
+            return Ok(());
+        }
+        let canonical = canonicalize(path)?;
+        if let Some(entry) = self
+            .entries
+            .iter_mut()
+            .find(|entry| entry.canonical == canonical)
+        {
+            if !entry.paths.iter().any(|p| p == path) {
+                entry.paths.push(path.to_string());
+            }
+            if !entry.actions.contains(&action) {
+                entry.actions.push(action);
+            }
+            return Ok(());
+        }
+        let metadata = metadata(path)?;
         self.entries.push(FileWatcherEntry {
-            path: path.to_string(),
-            last_modified: metadata.modified().unwrap(),
-            action: FileWatcherAction::ReloadShader,
+            canonical,
+            paths: vec![path.to_string()],
+            last_modified: metadata.modified()?,
+            actions: vec![action],
+            unreadable: false,
+        });
+        Ok(())
+    }
+
+    // Have every watched file reloaded by the next check, as if it changed.
+    pub fn touch_all(&mut self) {
+        for entry in self.entries.iter_mut() {
+            entry.last_modified = SystemTime::UNIX_EPOCH;
+        }
+    }
+
+    // Stop watching a file, for every action. Returns whether it was watched.
+    #[allow(dead_code)]
+    pub fn remove_path(&mut self, path: &str) -> bool {
+        // a deleted file can't be canonicalized, but may have been added as path
+        let canonical = canonicalize(path).ok();
+        let len = self.entries.len();
+        self.entries.retain(|entry| {
+            Some(&entry.canonical) != canonical.as_ref() && !entry.paths.iter().any(|p| p == path)
+        });
+        self.entries.len() != len
+    }
+
+    #[allow(dead_code)]
+    pub fn is_watched(&self, path: &str) -> bool {
+        let canonical = canonicalize(path).ok();
+        self.entries.iter().any(|entry| {
+            Some(&entry.canonical) == canonical.as_ref() || entry.paths.iter().any(|p| p == path)
         })
     }
 }
 
-#[derive(Default, Copy, Clone)]
+// Changes to the window reported to user code through State::take_window_events.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WindowState {
+    Minimized,
+    Restored,
+    // dragged onto the window from elsewhere, e.g. a shader or ui file to load
+    FileDropped(PathBuf),
+}
+
+// What the main loop is asked to do. The window sets State::flow_command;
+// app code and ui buttons, which only get the store, send them with
+// ValueStore::send_command for the main loop to take each frame.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
 pub enum FlowCommand {
     #[default]
     None,
     Quit,
+    Pause,
+    Resume,
+    // load every watched shader and ui file again, changed or not
+    ReloadAssets,
+    // anything the app names, e.g. "restart" or "title"
+    Custom(String),
 }
 
 #[derive(Default)]
 pub struct State<'a> {
     #[allow(dead_code)]
     pub title: Option<String>,
-    pub window: Option<Window>,
+    pub window: Option<Box<dyn BackendWindow>>,
     pub flow_command: FlowCommand,
     pub context: Option<Context<'a>>,
-    pub listuis: Vec<ListInterface>,
+    pub listuis: Slots<ListUiId, ListInterface>,
+    pub grids: Slots<GridId, GridInterface>,
     pub ui_wait: Duration,
     pub last_ui_time: Option<SystemTime>,
+    pub inspect_listui: Option<ListUiId>,
+    pub inspector_group: Option<GroupId>,
+    pub tooltip_group: Option<GroupId>,
+    pub notifications: Notifications,
+    pub notification_group: Option<GroupId>,
+    pub console: Console,
+    pub console_group: Option<GroupId>,
+    pub dialogs: Dialogs,
+    pub dialog_group: Option<GroupId>,
+    pub progress_bars: Vec<ProgressBar>,
+    pub progress_group: Option<GroupId>,
+    pub hud: Slots<HudId, HudWidget>,
+    pub hud_group: Option<GroupId>,
+    pub world_labels: Slots<WorldLabelId, WorldLabel>,
+    pub debug_overlay: DebugOverlay,
+    pub window_events: Vec<WindowState>,
+    pub ui_events: Vec<UiEvent>,
+    // how many of ui_events have had their sounds played
+    ui_events_heard: usize,
+    // how many of ui_events the scripts have been told of
+    pub ui_events_scripted: usize,
+    pub scripts: Scripts,
+    pub audio: Audio,
+    pub access: Accessibility,
+    // holds the main loop to a frame rate, see State::set_vsync
+    pub pacer: FramePacer,
+    pub fullscreen: FullscreenMode,
+    ime_rect: Option<(IVec2, UVec2)>,
+    // where the cursor was last seen, in window units
+    pub mouse_position: Vec2,
+    // how far the mouse moved since take_mouse_delta, in window units
+    mouse_delta: Vec2,
+    pub ui_files: Vec<UiFile>,
+    pub themes: Themes,
+    pub locale: Localization,
+    // lists given up by a ui file reload, reused by new_listui
+    pub free_listuis: Vec<ListUiId>,
 }
 
 impl State<'_> {
-    pub fn new(width: u32, height: u32, title: &str) -> Result<(sdl2::Sdl, State), Box<dyn Error>> {
-        let sdl = sdl2::init().unwrap();
-        let video = sdl.video().unwrap();
-        let window = video
-            .window(title, width, height)
-            .position_centered()
-            .resizable()
-            .opengl()
-            .build()
-            .unwrap();
+    // Window state changes since the last call, oldest first.
+    pub fn take_window_events(&mut self) -> Vec<WindowState> {
+        std::mem::take(&mut self.window_events)
+    }
+
+    // With vsync on, presenting paces the frames and the pacer is uncapped.
+    #[allow(dead_code)]
+    pub fn set_vsync(&mut self, vsync: bool) -> Result<(), ShecvError> {
+        let context = self.context.as_mut().ok_or_else(ShecvError::no_context)?;
+        context.set_vsync(vsync)?;
+        self.pacer.uncapped = vsync;
+        Ok(())
+    }
+
+    // Shaders reload on the next frame, and ui files with them.
+    pub fn reload_assets(&mut self) {
+        if let Some(context) = self.context.as_mut() {
+            context.file_watcher.touch_all();
+        }
+    }
+
+    // How far the mouse moved since the last call. In relative mouse mode
+    // this is all there is of it.
+    #[allow(dead_code)]
+    pub fn take_mouse_delta(&mut self) -> Vec2 {
+        std::mem::take(&mut self.mouse_delta)
+    }
+
+    #[allow(dead_code)]
+    pub fn set_cursor_visible(&mut self, visible: bool) -> Result<(), ShecvError> {
+        let window = self.window.as_mut().ok_or_else(ShecvError::no_context)?;
+        window.set_cursor_visible(visible);
+        Ok(())
+    }
+
+    // Keep the cursor inside the window.
+    #[allow(dead_code)]
+    pub fn set_cursor_grab(&mut self, grab: bool) -> Result<(), ShecvError> {
+        let window = self.window.as_mut().ok_or_else(ShecvError::no_context)?;
+        window.set_cursor_grab(grab)
+    }
+
+    // Hide and hold the cursor for mouse look and the like, reading the
+    // motion with take_mouse_delta.
+    #[allow(dead_code)]
+    pub fn set_relative_mouse_mode(&mut self, relative: bool) -> Result<(), ShecvError> {
+        let window = self.window.as_mut().ok_or_else(ShecvError::no_context)?;
+        window.set_relative_mouse_mode(relative)
+    }
+
+    // Show a sub image of a texture sheet as the cursor, with its hotspot
+    // in pixels from the sub image's top left.
+    #[allow(dead_code)]
+    pub fn set_cursor_image(
+        &mut self,
+        sheet_info: &TextureSheetDefinition,
+        cluster_index: usize,
+        sub_index: usize,
+        hotspot: UVec2,
+    ) -> Result<(), ShecvError> {
+        if cluster_index >= sheet_info.clusters.len() {
+            return Err(ShecvError::Layout(format!(
+                "cursor sheet '{}' has no cluster {}",
+                sheet_info.path, cluster_index
+            )));
+        }
+        let image = read_sheet_image(sheet_info)?;
+        let rect = sheet_info.sub_rect(cluster_index, sub_index, image.dimensions().into());
+        let cursor = image::imageops::crop_imm(
+            &image,
+            rect.xy.x as u32,
+            rect.xy.y as u32,
+            rect.wh.x,
+            rect.wh.y,
+        )
+        .to_image();
+        let window = self.window.as_mut().ok_or_else(ShecvError::no_context)?;
+        window.set_cursor_image(Some(&cursor), hotspot)
+    }
+
+    // How every list's selection highlight and scrolling move, e.g.
+    // ListMotion::instant() for a reduced motion setting.
+    #[allow(dead_code)]
+    pub fn set_list_motion(&mut self, motion: ListMotion) {
+        for listui in self.listuis.iter_mut() {
+            listui.style.motion = motion;
+        }
+    }
+
+    // Also kept in State::title.
+    #[allow(dead_code)]
+    pub fn set_title(&mut self, title: &str) -> Result<(), ShecvError> {
+        let window = self.window.as_mut().ok_or_else(ShecvError::no_context)?;
+        window.set_title(title)?;
+        self.title = Some(title.to_string());
+        Ok(())
+    }
+
+    #[allow(dead_code)]
+    pub fn set_icon(&mut self, image: &RgbaImage) -> Result<(), ShecvError> {
+        let window = self.window.as_mut().ok_or_else(ShecvError::no_context)?;
+        window.set_icon(image)
+    }
+
+    // An icon from a png or the like, or one compiled in, see assets::read.
+    #[allow(dead_code)]
+    pub fn set_icon_file(&mut self, path: &str) -> Result<(), ShecvError> {
+        let bytes = assets::read(path)?;
+        let image = image::load_from_memory(&bytes)
+            .map_err(|e| ShecvError::Parse(format!("failed to decode '{}': {}", path, e)))?;
+        self.set_icon(&image.to_rgba8())
+    }
+
+    // Back to the system's arrow.
+    #[allow(dead_code)]
+    pub fn reset_cursor_image(&mut self) -> Result<(), ShecvError> {
+        let window = self.window.as_mut().ok_or_else(ShecvError::no_context)?;
+        window.set_cursor_image(None, UVec2::ZERO)
+    }
+
+    // What the lists did since the last call, oldest first.
+    pub fn take_ui_events(&mut self) -> Vec<UiEvent> {
+        self.play_ui_sounds();
+        self.ui_events_heard = 0;
+        self.ui_events_scripted = 0;
+        std::mem::take(&mut self.ui_events)
+    }
+
+    // The sounds in each list's style for the events queued since last time.
+    fn play_ui_sounds(&mut self) {
+        for event in &self.ui_events[self.ui_events_heard..] {
+            let list = match event {
+                UiEvent::SelectionChanged { list, .. }
+                | UiEvent::Activated { list, .. }
+                | UiEvent::ValueEdited { list, .. }
+                | UiEvent::ListOpened(list)
+                | UiEvent::ListClosed(list) => *list,
+            };
+            let Some(listui) = self.listuis.get(list) else {
+                continue;
+            };
+            let sounds = listui.style.sounds;
+            let sound = match event {
+                UiEvent::SelectionChanged { .. } => sounds.navigate,
+                UiEvent::Activated { .. } => sounds.activate,
+                UiEvent::ListOpened(_) => sounds.open,
+                UiEvent::ListClosed(_) => sounds.close,
+                UiEvent::ValueEdited { .. } => None,
+            };
+            if let Some(sound) = sound {
+                self.audio.play_ui_sound(sound);
+            }
+        }
+        self.ui_events_heard = self.ui_events.len();
+    }
+
+    fn set_minimized(&mut self, minimized: bool) {
+        let Some(context) = self.context.as_mut() else {
+            return;
+        };
+        if context.minimized == minimized {
+            return;
+        }
+        context.minimized = minimized;
+        self.window_events.push(if minimized {
+            WindowState::Minimized
+        } else {
+            WindowState::Restored
+        });
+    }
+
+    // Switches the window between windowed and fullscreen, then resizes the
+    // surface and lays the lists out again for the new size.
+    pub fn set_fullscreen(
+        &mut self,
+        mode: FullscreenMode,
+        store: &ValueStore,
+    ) -> Result<(), ShecvError> {
+        let window = self
+            .window
+            .as_mut()
+            .ok_or_else(|| ShecvError::Device("fullscreen needs a window".into()))?;
+        window.set_fullscreen(mode)?;
+        self.fullscreen = mode;
+        self.sync_window_size(store)
+    }
+
+    // Alt+Enter: borderless fullscreen and back.
+    pub fn toggle_fullscreen(&mut self, store: &ValueStore) -> Result<(), ShecvError> {
+        let mode = match self.fullscreen {
+            FullscreenMode::Windowed => FullscreenMode::Borderless,
+            _ => FullscreenMode::Windowed,
+        };
+        self.set_fullscreen(mode, store)
+    }
+
+    // Reads the window's current size and scale into the context.
+    fn sync_window_size(&mut self, store: &ValueStore) -> Result<(), ShecvError> {
+        let Some(window) = self.window.as_ref() else {
+            return Ok(());
+        };
+        // the window may have moved to a display with another scale
+        let (w, h) = window.drawable_size();
+        let scale_factor = window_scale_factor(window.as_ref());
+        self.set_minimized(w == 0 || h == 0);
+        let context = self.context.as_mut().ok_or_else(ShecvError::no_context)?;
+        context.resize((w, h))?;
+        context.set_scale_factor(scale_factor)?;
+        self.layout_listuis(store)
+    }
+
+    // Texts are shared by every list and overlay, so they are rebuilt for all of them at once.
+    pub fn layout_listuis(&mut self, store: &ValueStore) -> Result<(), ShecvError> {
+        self.context
+            .as_mut()
+            .ok_or_else(ShecvError::no_context)?
+            .texts
+            .clear();
+        for id in self.listuis.ids().collect::<Vec<_>>() {
+            self.layout_listui(store, id)?;
+        }
+        self.layout_grids(store)?;
+        self.layout_tooltips(store)?;
+        self.layout_progress_bars(store)?;
+        self.layout_hud(store)?;
+        self.layout_world_labels(store)?;
+        self.layout_notifications()?;
+        self.layout_dialogs()?;
+        self.layout_console()?;
+        self.layout_debug_overlay()?;
+        self.layout_inspector(store)?;
+        self.place_ime();
+        self.update_accessibility(store);
+        Ok(())
+    }
+
+    // Keeps the input method's candidate window next to the caret of the
+    // focused edit.
+    fn place_ime(&mut self) {
+        let caret = self
+            .listuis
+            .iter()
+            .filter(|listui| listui.focused && listui.is_editing())
+            .find_map(|listui| listui.layout.caret);
+        if caret == self.ime_rect {
+            return;
+        }
+        self.ime_rect = caret;
+        if let (Some(window), Some((xy, wh))) = (self.window.as_ref(), caret) {
+            window.set_ime_rect(xy, wh);
+        }
+    }
+
+    // Enter on the selected item of each focused list: commit a running edit,
+    // press a button, flip a checkbox, open a sublist or start editing.
+    pub fn activate_selected(&mut self, store: &mut ValueStore) {
+        let mut to_open = vec![];
+        for (index, listui) in self.listuis.entries_mut() {
+            if !listui.focused || !listui.is_visible(store) {
+                continue;
+            }
+            let before = listui.selected_version(store);
+            if listui.is_editing() {
+                if let Err(e) = listui.commit_edit(store) {
+                    warn!("edit rejected: {}", e);
+                }
+                self.ui_events
+                    .extend(ListInterface::edited_since(index, before, store));
+                continue;
+            }
+            let selected = listui.selected_index as usize;
+            let Some(item) = listui.entries.get_mut(selected) else {
+                continue;
+            };
+            if !item.is_enabled(store) {
+                continue;
+            }
+            if matches!(
+                item.ty,
+                ListItemType::Button
+                    | ListItemType::CheckBox
+                    | ListItemType::Choice
+                    | ListItemType::SubList
+            ) {
+                self.ui_events.push(UiEvent::Activated {
+                    list: index,
+                    index: selected,
+                });
+            }
+            match item.ty {
+                ListItemType::Button => {
+                    if let Some(action) = item.action.as_mut() {
+                        action(store);
+                    }
+                }
+                ListItemType::CheckBox => {
+                    let checked = item.read(store, |v| v.as_any().downcast_ref::<bool>().copied());
+                    if let Some(Some(checked)) = checked {
+                        if let Err(e) = item.write(store, Box::new(!checked)) {
+                            warn!("edit rejected: {}", e);
+                        }
+                    }
+                }
+                ListItemType::SubList => {
+                    if let Some(child) = item.sublist {
+                        to_open.push((index, child));
+                    }
+                }
+                ListItemType::Choice => {
+                    if let Err(e) = item.cycle_choice(1, store) {
+                        warn!("edit rejected: {}", e);
+                    }
+                }
+                _ => {
+                    listui.begin_edit(store);
+                }
+            }
+            self.ui_events
+                .extend(ListInterface::edited_since(index, before, store));
+        }
+        for (parent, child) in to_open {
+            self.open_sublist(parent, child, store);
+        }
+    }
+
+    pub fn open_sublist(&mut self, parent: ListUiId, child: ListUiId, store: &ValueStore) {
+        let replaces_parent = self.listuis[child].anchor == ListAnchor::Middle;
+        let parent_list = &mut self.listuis[parent];
+        parent_list.focused = false;
+        if replaces_parent {
+            parent_list.open = false;
+        }
+        let child_list = &mut self.listuis[child];
+        child_list.open = true;
+        child_list.focused = true;
+        child_list.parent = Some(parent);
+        if matches!(child_list.resume, ListResumeBehavior::First) {
+            child_list.select_first(store);
+        }
+        self.ui_events.push(UiEvent::ListOpened(child));
+    }
+
+    // Close focused sublists and hand focus back to their parents.
+    // Returns false when no focused list was a sublist.
+    pub fn close_sublists(&mut self) -> bool {
+        let mut closed = vec![];
+        for (id, listui) in self.listuis.entries_mut() {
+            if let (true, Some(parent)) = (listui.focused, listui.parent) {
+                listui.open = false;
+                listui.focused = false;
+                closed.push(parent);
+                self.ui_events.push(UiEvent::ListClosed(id));
+            }
+        }
+        for &parent in &closed {
+            self.listuis[parent].open = true;
+            self.listuis[parent].focused = true;
+        }
+        !closed.is_empty()
+    }
+
+    // Opens a window with the default backend, SDL2.
+    pub fn new(
+        width: u32,
+        height: u32,
+        title: &str,
+    ) -> Result<(Box<dyn Backend>, State<'static>), ShecvError> {
+        Self::new_with_backend(BackendKind::default(), width, height, title)
+    }
+
+    pub fn new_with_backend(
+        kind: BackendKind,
+        width: u32,
+        height: u32,
+        title: &str,
+    ) -> Result<(Box<dyn Backend>, State<'static>), ShecvError> {
+        let mut backend = kind.create()?;
+        let window = backend.create_window(width, height, title)?;
 
         Ok((
-            sdl,
+            backend,
             State {
                 title: Some(title.to_string()),
                 flow_command: FlowCommand::None,
                 window: Some(window),
-                listuis: vec![],
+                listuis: Slots::default(),
                 ui_wait: Duration::from_millis(60),
                 last_ui_time: None,
                 ..Default::default()
@@ -107,268 +647,1482 @@ impl State<'_> {
         ))
     }
 
-    pub fn layout_listui(
-        &mut self,
-        store: &ValueStore,
-        listui_index: usize,
-    ) -> Result<(), Box<dyn Error>> {
+    pub fn layout_listui(&mut self, store: &ValueStore, id: ListUiId) -> Result<(), ShecvError> {
         // here i'll make the geometry instance group
         // and populate it according to the listui as specified
-        let listui = &self.listuis[listui_index];
-        let context = self.context.as_mut().unwrap();
-        let config = context.config.lock().unwrap();
-
-        // starting out, we look at the listui and determine where it will go
-        let wh = IVec2::new(60, 20);
-        let tl = {
-            match listui.anchor {
-                ListAnchor::Left => IVec2::new(0, 0),
-                ListAnchor::Middle => IVec2::new(config.width as i32 / 2 - wh.x / 2, 0),
-                ListAnchor::Right => IVec2::new(config.width as i32 - wh.x, 0),
-                ListAnchor::Hidden => IVec2::new(0, 0),
+        let listui = self
+            .listuis
+            .get_mut(id)
+            .ok_or_else(|| ShecvError::Layout(format!("no list {:?}", id)))?;
+        listui.sync_watch(store);
+        listui.translate(&self.locale);
+        let context = self.context.as_mut().ok_or_else(ShecvError::no_context)?;
+        let extent = context.logical_size()?;
+        let theme = self.themes.current();
+
+        // wh is the minimum width of the label and value columns, and the row
+        // height; rows are inset by the padding, and text is centered in them
+        let style = &listui.style;
+        let wh = IVec2::new((style.min_width / 2) as i32, style.row_height as i32);
+        let (h_pad, v_pad) = (style.h_padding, style.v_padding);
+        let max_width = style.max_width;
+        let line_height = style.line_height();
+        let text_x = h_pad as f64 - 1.5;
+        let text_y = (wh.y as f32 - line_height) as f64 / 2.0 + 2.5;
+        let column_gap = listui.style.column_gap as i32;
+
+        // a list hidden through its anchor or a bound store key keeps its
+        // instances, but its group isn't drawn
+        let group = &mut context.geos.instance_groups[listui.render_group];
+        let visible = listui.is_visible(store);
+        group.set_visible(visible);
+        if !visible {
+            listui.layout = ListLayout::default();
+            return Ok(());
+        }
+        group.instance_buffer_manager.clear();
+
+        // lists that hide when unfocused slide out, and stop drawing once gone
+        let shown = match listui.popout.behavior {
+            ListPopoutBehavior::AlwaysVisible => 1.0,
+            ListPopoutBehavior::HiddenWhenUnfocused if listui.focused => 1.0,
+            ListPopoutBehavior::HiddenWhenUnfocused => 0.0,
+        };
+        listui.popout.update(shown, &mut context.tweens);
+        if listui.popout.delta <= 0.0 {
+            listui.layout = ListLayout::default();
+            return Ok(());
+        }
+        // rows watching their value flash as it changes
+        let flash_duration = listui.style.flash_duration;
+        for item in listui
+            .entries
+            .iter_mut()
+            .filter(|item| item.flash_on_change)
+        {
+            item.update_flash(store, flash_duration, &mut context.tweens);
+        }
+
+        // measure every row first, so the value column lines up and the anchor
+        // can account for the full width of the list
+        let metrics = TextCollection::line_metrics(line_height);
+        // icons are squares as tall as a row's inside; if any item has one,
+        // every label moves over by its width so they stay lined up
+        let icon_side = (wh.y as u32).saturating_sub(v_pad * 2);
+        let icon_width = match listui
+            .entries
+            .iter()
+            .any(|item| item.icon.is_some() && item.is_visible(store))
+        {
+            true => (icon_side + h_pad) as f32,
+            false => 0.0,
+        };
+        let mut rows = vec![];
+        let (mut label_column, mut value_column) = (wh.x as f32, wh.x as f32);
+        for (i, item) in listui.entries.iter().enumerate() {
+            if !item.is_visible(store) {
+                continue;
             }
+            let selected = listui.selected_index == i as i32;
+            let label = item.display_label(store);
+            let value = match (&listui.edit_text, selected) {
+                (Some(edit_text), true) => format!(
+                    "{}{}_",
+                    edit_text,
+                    listui.composition.as_deref().unwrap_or_default()
+                ),
+                _ => item.display_value(store),
+            };
+            // markup that fails to parse is shown as written
+            let spans = match (item.markup, listui.is_editing() && selected) {
+                (true, false) => TextSpan::parse_markup(&value).ok(),
+                _ => None,
+            };
+            // the part of a vector that Left and Right step stands out
+            let spans = match (item.ty, selected && !listui.is_editing()) {
+                (ListItemType::Vector, true) => item
+                    .display_component_markup(
+                        store,
+                        listui.component,
+                        listui.style.li_activated_bg.resolve(theme),
+                    )
+                    .and_then(|markup| TextSpan::parse_markup(&markup).ok())
+                    .or(spans),
+                _ => spans,
+            };
+
+            let label_width = context.texts.measure(&label, metrics).0;
+            let value_width = match &spans {
+                Some(spans) => context.texts.measure_spans(spans, metrics).0,
+                None => context.texts.measure(&value, metrics).0,
+            };
+            // room for a color's swatch, a square as tall as the row's inside
+            let swatch_width = match item.swatch(store) {
+                Some(_) => (icon_side + 4) as f32,
+                None => 0.0,
+            };
+            // progress bars and graphs get some length past their text
+            let value_width = match item.ty {
+                ListItemType::Progress | ListItemType::Sparkline => value_width.max(100.0),
+                _ => value_width,
+            };
+            label_column = label_column.max(label_width.ceil() + icon_width);
+            value_column = value_column.max(value_width.ceil() + swatch_width);
+            rows.push((i, label, value, spans));
+        }
+        // past max_width the value column gives way
+        if let Some(max_width) = max_width {
+            value_column = value_column.min((max_width as f32 - label_column).max(0.0));
+        }
+        let final_x = (label_column + value_column) as i32;
+
+        // rows that don't fit on the screen wrap into more columns of final_x
+        // to the right, if the list overflows that way
+        let room = match listui.anchor {
+            ListAnchor::Positioned(xy) => extent.y as i32 - xy.y - listui.margin.y,
+            _ => extent.y as i32 - listui.margin.y * 2,
+        };
+        let per_column = match listui.overflow {
+            ListOverflow::Columns => (room / wh.y).max(1) as usize,
+            ListOverflow::Offscreen | ListOverflow::Scroll => rows.len().max(1),
+        };
+        let columns = rows.len().div_ceil(per_column).max(1) as i32;
+        let list_width = columns * final_x + (columns - 1) * column_gap;
+        let content_height = rows.len().min(per_column) as i32 * wh.y;
+        // a list that scrolls stops at the edge of the screen instead
+        let view_height = match listui.overflow {
+            ListOverflow::Scroll => content_height.min(room.max(wh.y)),
+            _ => content_height,
         };
-        let pad = 4u32;
-        let mut y_offset = 0;
-        let mut final_x = 0;
+        let tl = listui
+            .anchor
+            .position(IVec2::new(list_width, view_height), extent, listui.margin);
+        let final_y = match listui.anchor.fills_height() {
+            true => (extent.y as i32 - tl.y).max(view_height),
+            false => view_height,
+        };
+        // right anchored lists slide off to the right, the rest to the left
+        let slide = ((1.0 - listui.popout.delta) * list_width as f32) as i32;
+        let tl = match listui.anchor.is_right() {
+            true => tl + IVec2::new(slide, 0),
+            false => tl - IVec2::new(slide, 0),
+        };
+        // the top left of the kth row shown
+        let row_size = UVec2::new(final_x as u32, wh.y as u32);
+        let column_nodes = rows
+            .chunks(per_column)
+            .map(|column| {
+                LayoutNode::vbox(vec![LayoutNode::item(row_size); column.len()]).align(Align::Start)
+            })
+            .collect();
+        let row_rects = LayoutNode::hbox(column_nodes)
+            .gap(column_gap as u32)
+            .align(Align::Start)
+            .layout(PixelRect::new(
+                tl,
+                UVec2::new(list_width as u32, content_height as u32),
+                extent,
+            ));
 
-        context.geos.instance_groups[listui.render_group_index]
-            .instance_buffer_manager
-            .clear();
+        // a scrolling list follows the selection, keeping its row in view
+        let motion = listui.style.motion;
+        let selected_k = rows
+            .iter()
+            .position(|row| row.0 as i32 == listui.selected_index);
+        let scroll = match listui.overflow {
+            ListOverflow::Scroll => {
+                let mut target = listui.scroll.target();
+                if let Some(k) = selected_k {
+                    let top = (k as i32 * wh.y) as f32;
+                    target = target.min(top).max(top + (wh.y - view_height) as f32);
+                }
+                let target = target.clamp(0.0, (content_height - view_height) as f32);
+                listui
+                    .scroll
+                    .update(target, motion, &mut context.tweens)
+                    .round() as i32
+            }
+            _ => 0,
+        };
+        let row_origin = |k: usize| row_rects[k].xy - IVec2::new(0, scroll);
+        // rows scrolled out of the list aren't drawn, and those cut off by its
+        // edges are clipped, leaving out their icons, bars and swatches
+        let (view_top, view_bottom) = (tl.y, tl.y + view_height);
+        let row_shown = |row_tl: IVec2| row_tl.y + wh.y > view_top && row_tl.y < view_bottom;
+        let row_whole = |row_tl: IVec2| row_tl.y >= view_top && row_tl.y + wh.y <= view_bottom;
+        let clip_rows = |rect: PixelRect| {
+            let top = rect.xy.y.max(view_top);
+            let bottom = (rect.xy.y + rect.wh.y as i32).min(view_bottom);
+            (bottom > top).then(|| PixelRect {
+                xy: IVec2::new(rect.xy.x, top),
+                wh: UVec2::new(rect.wh.x, (bottom - top) as u32),
+                extent,
+            })
+        };
+        // a row's background, inside the list's padding
+        let row_inset = |row_tl: IVec2| PixelRect {
+            xy: row_tl + IVec2::new(h_pad as i32, v_pad as i32),
+            wh: UVec2::new(
+                (final_x as u32).saturating_sub(h_pad * 2),
+                (wh.y as u32).saturating_sub(v_pad * 2),
+            ),
+            extent,
+        };
 
-        // for each element in the listui, create a background rect and text label
-        context.texts.clear();
-        for (i, item) in listui.entries.iter().enumerate() {
+        // for each element in the listui, create a text label and value
+        let first_text = context.texts.texts.len();
+        let mut selected_row = None;
+        let mut caret = None;
+        for (k, (i, label, value, spans)) in rows.into_iter().enumerate() {
+            let item = &listui.entries[i];
             let selected = listui.selected_index == i as i32;
+            let row_tl = row_origin(k);
+            if !row_shown(row_tl) {
+                continue;
+            }
+            if selected {
+                selected_row = Some((row_tl, UVec2::new(final_x as u32, wh.y as u32)));
+            }
+            let color = if !item.is_enabled(store) || !item.is_selectable() {
+                listui.style.li_disabled
+            } else if selected {
+                listui.style.li_selected
+            } else {
+                listui.style.li_unselected
+            }
+            .resolve(theme);
 
-            let mut text_index = context.texts.new_text(
+            context.texts.new_text(
                 (
-                    tl.x as f64 + 2.5,
-                    (tl.y + y_offset) as f64 + 2.5,
-                    wh.x as f64,
-                    wh.y as f64,
+                    (row_tl.x as f32 + icon_width) as f64 + text_x,
+                    row_tl.y as f64 + text_y,
+                    (label_column - icon_width) as f64,
+                    line_height as f64,
                 ),
-                format!("{}: ", item.label).as_str(),
+                label.as_str(),
                 1.0,
-                if selected {
-                    listui.style.li_selected
-                } else {
-                    listui.style.li_unselected
-                },
+                color,
             );
 
-            // ------------------------ v -_o
-            let label_width = context.texts.texts[text_index].buffer.size().0.unwrap();
+            let value_rect = (
+                (row_tl.x as f32 + label_column) as f64 + text_x,
+                row_tl.y as f64 + text_y,
+                value_column as f64,
+                line_height as f64,
+            );
+            match spans {
+                Some(spans) => context.texts.new_rich_text(value_rect, &spans, 1.0, color),
+                None => context
+                    .texts
+                    .new_text(value_rect, value.as_str(), 1.0, color),
+            };
+            if let (true, Some(edit_text)) = (selected, &listui.edit_text) {
+                let composition = listui.composition.as_deref().unwrap_or_default();
+                let x = value_rect.0 as f32 + context.texts.measure(edit_text, metrics).0;
+                let width = context.texts.measure(composition, metrics).0.ceil();
+                caret = Some((
+                    IVec2::new(x as i32, row_tl.y),
+                    UVec2::new(width.max(1.0) as u32, wh.y as u32),
+                ));
+            }
+        }
 
-            let value_ref = item.value.borrow();
-            let value = value_ref.load(store);
+        if listui.overflow == ListOverflow::Scroll {
+            let view = UVec2::new(list_width as u32, view_height as u32);
+            for index in first_text..context.texts.texts.len() {
+                context.texts.set_clip(index, tl, view);
+            }
+        }
 
-            text_index = context.texts.new_text(
-                (
-                    (tl.x + label_width as i32) as f64 + 2.5,
-                    (tl.y + y_offset) as f64 + 2.5,
-                    wh.x as f64,
-                    wh.y as f64,
-                ),
-                format!("{}", value).as_str(),
-                1.0,
-                if selected {
-                    listui.style.li_selected
-                } else {
-                    listui.style.li_unselected
-                },
-            );
+        listui.layout = ListLayout {
+            visible: true,
+            xy: tl,
+            wh: UVec2::new(list_width as u32, final_y as u32),
+            padding: UVec2::new(h_pad, v_pad),
+            content: UVec2::new(list_width as u32, content_height as u32),
+            selected_row,
+            caret,
+        };
 
-            let value_width = context.texts.texts[text_index].buffer.size().0.unwrap();
-            let elem_width = (label_width + value_width) as u32;
+        // a background rect is created - will it work!? the answer: yes...
+        let bg_rect = PixelRect {
+            xy: IVec2::new(tl.x, tl.y),
+            wh: UVec2::new(list_width as u32, final_y as u32),
+            extent,
+        };
+        let group = &mut context.geos.instance_groups[listui.render_group];
+        match &listui.style.panel {
+            Some(panel) => {
+                let tint = listui.style.bg.resolve(theme);
+                group.add_nine_slice(context.queue.clone(), panel, bg_rect, tint)?;
+            }
+            None => {
+                let bg_index = group.add_new(
+                    context.queue.clone(),
+                    ComponentTransform::unit_square_transform_from_pixel_rect(bg_rect),
+                    0,
+                    0,
+                    listui.style.bg.resolve(theme),
+                )?;
+                if listui.style.corner_radius > 0.0 || listui.style.border.is_some() {
+                    group.instance_buffer_manager.set_shape(
+                        context.queue.clone(),
+                        bg_index,
+                        InstanceShape::rounded(
+                            bg_rect.wh,
+                            listui.style.corner_radius,
+                            listui.style.border.map(|border| border.resolve(theme)),
+                        ),
+                    )?;
+                }
+            }
+        }
 
-            if elem_width as i32 > final_x {
-                final_x = elem_width as i32;
+        // but now we need to loop again and place the foreground rects: the
+        // rows, the selection highlight over them, then what's in the rows
+        let flash_bg = listui.style.li_flash_bg.resolve(theme);
+        // rows sit inside the list's padding, so their corners are rounded less
+        let row_radius = listui.style.corner_radius - h_pad.min(v_pad) as f32;
+        let mut add_row_rect = |rect: PixelRect, color: ColorRGBA| -> Result<(), ShecvError> {
+            let Some(rect) = clip_rows(rect) else {
+                return Ok(());
+            };
+            let group = &mut context.geos.instance_groups[listui.render_group];
+            let geo_index = group.add_new(
+                context.queue.clone(),
+                ComponentTransform::unit_square_transform_from_pixel_rect(rect),
+                0,
+                0,
+                color,
+            )?;
+            if row_radius > 0.0 {
+                group.instance_buffer_manager.set_shape(
+                    context.queue.clone(),
+                    geo_index,
+                    InstanceShape::rounded(rect.wh, row_radius, None),
+                )?;
+            }
+            Ok(())
+        };
+        let shown = listui
+            .entries
+            .iter()
+            .enumerate()
+            .filter(|(_, item)| item.is_visible(store));
+        for (k, (_, item)) in shown.enumerate() {
+            let row_bg = match item.is_enabled(store) && item.is_selectable() {
+                true => listui.style.li_unselected_bg,
+                false => listui.style.li_disabled_bg,
             }
+            .resolve(theme);
+            let row_bg = row_bg.lerp(flash_bg, item.flash_amount(&context.tweens));
+            add_row_rect(row_inset(row_origin(k)), row_bg)?;
+        }
 
-            y_offset += wh.y;
+        // the highlight glides to the selected row rather than jumping there,
+        // and is kept relative to the list as it slides and scrolls
+        let selected_item =
+            selected_k.map(|k| (k, &listui.entries[listui.selected_index as usize]));
+        if let Some((k, item)) = selected_item {
+            let rect = row_inset(row_rects[k].xy);
+            let target = Vec4::new(
+                (rect.xy.x - tl.x) as f32,
+                (rect.xy.y - tl.y) as f32,
+                rect.wh.x as f32,
+                rect.wh.y as f32,
+            );
+            let cursor = listui.cursor.update(target, motion, &mut context.tweens);
+            if item.is_enabled(store) && item.is_selectable() {
+                let cursor_rect = PixelRect {
+                    xy: tl + IVec2::new(cursor.x.round() as i32, cursor.y.round() as i32 - scroll),
+                    wh: UVec2::new(cursor.z.round() as u32, cursor.w.round() as u32),
+                    extent,
+                };
+                let selected_bg = listui.style.li_selected_bg.resolve(theme);
+                let flash = item.flash_amount(&context.tweens);
+                add_row_rect(cursor_rect, selected_bg.lerp(flash_bg, flash))?;
+            }
         }
 
-        // a background rect is created - will it work!? the answer: yes...
-        let _geo_index = context.geos.instance_groups[listui.render_group_index].add_new(
-            context.queue.clone(),
-            ComponentTransform::unit_square_transform_from_pixel_rect(PixelRect {
-                xy: IVec2::new(tl.x, tl.y),
-                wh: UVec2::new(final_x as u32, config.height),
-                extent: UVec2::new(config.width, config.height),
-            }),
-            0,
-            0,
-            listui.style.bg,
-        );
+        let shown = listui
+            .entries
+            .iter()
+            .enumerate()
+            .filter(|(_, item)| item.is_visible(store));
+        for (k, (_, item)) in shown.enumerate() {
+            let row_tl = row_origin(k);
+            if !row_whole(row_tl) {
+                continue;
+            }
+            let enabled = item.is_enabled(store) && item.is_selectable();
+            let row_rect = row_inset(row_tl);
+            let group = &mut context.geos.instance_groups[listui.render_group];
+            // the bar fills the value column, under the value's text
+            let progress = match item.ty {
+                ListItemType::Progress => item.progress(store),
+                _ => None,
+            };
+            if let Some(fraction) = progress {
+                let bar = PixelRect {
+                    xy: IVec2::new(row_tl.x + label_column as i32, row_rect.xy.y),
+                    wh: UVec2::new(
+                        (final_x - label_column as i32 - h_pad as i32).max(0) as u32,
+                        row_rect.wh.y,
+                    ),
+                    extent,
+                };
+                add_progress_rects(
+                    group,
+                    context.queue.clone(),
+                    bar,
+                    fraction,
+                    listui.style.progress_track.resolve(theme),
+                    listui.style.progress.resolve(theme),
+                )?;
+            }
+            // a column per sample, growing up from the bottom of the row,
+            // behind the newest value. rows squeezed to nothing by their
+            // padding have no room for one
+            if item.ty == ListItemType::Sparkline && row_rect.wh.y > 0 {
+                let left = row_tl.x + label_column as i32;
+                let width = (final_x - label_column as i32 - h_pad as i32).max(0);
+                let column = 2;
+                let heights = item
+                    .sparkline(store, (width / column) as usize)
+                    .unwrap_or_default();
+                let right = left + width;
+                let fill = listui.style.progress.resolve(theme);
+                for (n, height) in heights.iter().rev().enumerate() {
+                    let h =
+                        ((row_rect.wh.y as f32 * height).round() as u32).clamp(1, row_rect.wh.y);
+                    let spark = PixelRect {
+                        xy: IVec2::new(
+                            right - (n as i32 + 1) * column,
+                            row_rect.xy.y + (row_rect.wh.y - h) as i32,
+                        ),
+                        wh: UVec2::new(column as u32 - 1, h),
+                        extent,
+                    };
+                    group.add_new(
+                        context.queue.clone(),
+                        ComponentTransform::unit_square_transform_from_pixel_rect(spark),
+                        0,
+                        0,
+                        fill,
+                    )?;
+                }
+            }
+            // the image is tinted like the label is when disabled
+            if let Some((cluster_index, sub_index)) = item.icon {
+                let icon_rect = PixelRect {
+                    wh: UVec2::new(icon_side, icon_side),
+                    ..row_rect
+                };
+                group.add_new(
+                    context.queue.clone(),
+                    ComponentTransform::unit_square_transform_from_pixel_rect(icon_rect),
+                    cluster_index,
+                    sub_index,
+                    match enabled {
+                        true => ColorRGBA::white(),
+                        false => listui.style.li_disabled.resolve(theme),
+                    },
+                )?;
+            }
+            if let Some(color) = item.swatch(store) {
+                let side = row_rect.wh.y;
+                let swatch = PixelRect {
+                    xy: IVec2::new(
+                        row_rect.xy.x + row_rect.wh.x as i32 - side as i32,
+                        row_rect.xy.y,
+                    ),
+                    wh: UVec2::new(side, side),
+                    extent,
+                };
+                group.add_new(
+                    context.queue.clone(),
+                    ComponentTransform::unit_square_transform_from_pixel_rect(swatch),
+                    0,
+                    0,
+                    color,
+                )?;
+            }
+        }
 
-        // but now we need to loop again and place the foreground rects
-        y_offset = 0;
-        for (i, _item) in listui.entries.iter().enumerate() {
-            let selected = listui.selected_index == i as i32;
-            let _geo_index = context.geos.instance_groups[listui.render_group_index].add_new(
+        // the search being typed sits in a box above the list, or under its
+        // rows when there's no room above
+        if let Some(search) = listui.active_search() {
+            let text = format!("find: {}", search);
+            let width = context.texts.measure(&text, metrics).0.ceil() as u32 + h_pad * 2;
+            let y = match tl.y >= wh.y {
+                true => tl.y - wh.y,
+                false => tl.y + view_height,
+            };
+            let search_rect = PixelRect {
+                xy: IVec2::new(tl.x, y),
+                wh: UVec2::new(width, wh.y as u32),
+                extent,
+            };
+            context.geos.instance_groups[listui.render_group].add_new(
                 context.queue.clone(),
-                ComponentTransform::unit_square_transform_from_pixel_rect(PixelRect {
-                    xy: IVec2::new(tl.x + pad as i32, tl.y + y_offset + pad as i32),
-                    wh: UVec2::new(final_x as u32 - pad * 2, wh.y as u32 - pad * 2),
-                    extent: UVec2::new(config.width, config.height),
-                }),
+                ComponentTransform::unit_square_transform_from_pixel_rect(search_rect),
                 0,
                 0,
-                if selected {
-                    listui.style.li_selected_bg
-                } else {
-                    listui.style.li_unselected_bg
-                },
+                listui.style.li_activated_bg.resolve(theme),
+            )?;
+            context.texts.new_text(
+                (
+                    (tl.x + h_pad as i32) as f64,
+                    y as f64 + text_y,
+                    width as f64,
+                    line_height as f64,
+                ),
+                &text,
+                1.0,
+                listui.style.li_activated.resolve(theme),
             );
-            y_offset += wh.y;
+        }
+        let effect = listui.style.text_effect(theme);
+        for index in first_text..context.texts.texts.len() {
+            context.texts.set_effect(index, effect);
+        }
+
+        // composing text is underlined until the input method commits it
+        if let (Some((xy, caret_wh)), Some(_)) = (caret, &listui.composition) {
+            let underline = PixelRect {
+                xy: IVec2::new(xy.x, xy.y + wh.y - 3),
+                wh: UVec2::new(caret_wh.x, 1),
+                extent,
+            };
+            context.geos.instance_groups[listui.render_group].add_new(
+                context.queue.clone(),
+                ComponentTransform::unit_square_transform_from_pixel_rect(underline),
+                0,
+                0,
+                listui.style.li_selected.resolve(theme),
+            )?;
         }
 
         Ok(())
     }
 
-    pub fn new_listui(&mut self) -> Result<usize, Box<dyn Error>> {
-        let context = self.context.as_mut().unwrap();
-        context.file_watcher.add_path("src/shader.wgsl");
-        let render_group_index = {
-            let shader_path = "src/shader.wgsl";
-            context.file_watcher.add_path(shader_path);
-            let config = context.config.lock().unwrap();
-            context.geos.new_unit_square(
-                GeoViewType::Orthographic,
-                512,
-                config.format,
-                (config.width, config.height),
-                TextureSheetDefinition::default(),
-                shader_path,
-            )?
-        };
+    pub fn new_listui(&mut self) -> Result<ListUiId, ShecvError> {
+        self.new_listui_with_sheet(TextureSheetDefinition::default())
+    }
+
+    // Lists drawing a nine-slice panel need a texture sheet for their render group.
+    pub fn new_listui_with_sheet(
+        &mut self,
+        sheet_info: TextureSheetDefinition,
+    ) -> Result<ListUiId, ShecvError> {
+        if let Some(id) = self.free_listuis.pop() {
+            let render_group = self.listuis[id].render_group;
+            self.listuis[id] = ListInterface::default(render_group);
+            return Ok(id);
+        }
+        let context = self.context.as_mut().ok_or_else(ShecvError::no_context)?;
+        context.file_watcher.add_path(assets::DEFAULT_SHADER)?;
+        let render_group = context.new_screen_group(512, sheet_info)?;
+
+        Ok(self.listuis.insert(ListInterface::default(render_group)))
+    }
+
+    // A list showing every store key under prefix, see
+    // ListInterface::watch_store.
+    #[allow(dead_code)]
+    pub fn new_watch_listui(
+        &mut self,
+        prefix: &str,
+        editable: bool,
+    ) -> Result<ListUiId, ShecvError> {
+        let id = self.new_listui()?;
+        let listui = &mut self.listuis[id];
+        listui.watch_store(prefix, editable);
+        listui.focused = true;
+        Ok(id)
+    }
+
+    // Empties a list and hides it until new_listui hands it out again.
+    // Freeing a list that's already free does nothing.
+    pub fn free_listui(&mut self, id: ListUiId) -> Result<(), ShecvError> {
+        if self.free_listuis.contains(&id) {
+            return Ok(());
+        }
+        let context = self.context.as_mut().ok_or_else(ShecvError::no_context)?;
+        let listui = self
+            .listuis
+            .get_mut(id)
+            .ok_or_else(|| ShecvError::Layout(format!("no list {:?}", id)))?;
+        context.geos.instance_groups[listui.render_group]
+            .instance_buffer_manager
+            .clear();
+        listui.remove_tweens(&mut context.tweens);
+        *listui = ListInterface::default(listui.render_group);
+        listui.open = false;
+        listui.focused = false;
+        self.free_listuis.push(id);
+        Ok(())
+    }
+
+    // Remove a list for good, with its render group. Lists and ui files
+    // pointing at it forget it, and a parent it was opened from gets focus
+    // back. Its id stops working, even once its slot goes to a later list.
+    #[allow(dead_code)]
+    pub fn remove_listui(&mut self, id: ListUiId) -> Result<(), ShecvError> {
+        let context = self.context.as_mut().ok_or_else(ShecvError::no_context)?;
+        let mut listui = self
+            .listuis
+            .remove(id)
+            .ok_or_else(|| ShecvError::Layout(format!("no list {:?}", id)))?;
+        listui.remove_tweens(&mut context.tweens);
+        context.geos.remove_group(listui.render_group)?;
+
+        self.free_listuis.retain(|&free| free != id);
+        for ui_file in self.ui_files.iter_mut() {
+            ui_file.lists.retain(|&list| list != id);
+        }
+        if self.inspect_listui == Some(id) {
+            self.inspect_listui = None;
+        }
+        for other in self.listuis.iter_mut() {
+            if other.parent == Some(id) {
+                other.parent = None;
+            }
+            for item in other.entries.iter_mut() {
+                if item.sublist == Some(id) {
+                    item.sublist = None;
+                }
+            }
+        }
+        if let (true, Some(parent)) = (listui.focused, listui.parent) {
+            if let Some(parent) = self.listuis.get_mut(parent) {
+                parent.open = true;
+                parent.focused = true;
+            }
+        }
+        Ok(())
+    }
+
+    // A State without an SDL window, for use with new_headless_context.
+    #[allow(dead_code)]
+    pub fn new_headless(title: &str) -> State<'static> {
+        State {
+            title: Some(title.to_string()),
+            flow_command: FlowCommand::None,
+            window: None,
+            listuis: Slots::default(),
+            ui_wait: Duration::from_millis(60),
+            last_ui_time: None,
+            ..Default::default()
+        }
+    }
 
-        self.listuis
-            .push(ListInterface::default(render_group_index));
-        Ok(self.listuis.len() - 1)
+    pub fn window_id(&self) -> Option<u64> {
+        self.window.as_ref().map(|window| window.id())
     }
 
-    pub async fn new_context(&mut self) -> Result<(), Box<dyn Error>> {
-        let window = self.window.as_ref().unwrap();
+    pub async fn new_context(&mut self) -> Result<(), ShecvError> {
+        let window = self.window.as_ref().ok_or_else(|| {
+            ShecvError::Device("new_context needs a window; see new_headless_context".into())
+        })?;
 
-        let size = window.size();
+        // the surface covers every physical pixel; layout stays in window units
+        let size = window.drawable_size();
+        let scale_factor = window_scale_factor(window.as_ref());
 
         // instance, adapter, device, queue
         let instance = Instance::new(InstanceDescriptor {
             ..Default::default()
         });
 
-        let adapter = instance
-            .request_adapter(&RequestAdapterOptions::default())
-            .await
-            .expect("wgpu request_adapter failed");
-
-        let (device, queue) = adapter
-            .request_device(
-                &DeviceDescriptor {
-                    ..Default::default()
-                },
-                None,
-            )
-            .await?;
+        let (adapter, device, queue) = request_device(&instance).await?;
 
         // surface, format, config
-        let surface = unsafe {
-            instance.create_surface_unsafe(wgpu::SurfaceTargetUnsafe::from_window(&window)?)
-        }?;
-        let swapchain_format = TextureFormat::Bgra8UnormSrgb;
-        let config = SurfaceConfiguration {
-            usage: TextureUsages::RENDER_ATTACHMENT,
-            format: swapchain_format,
-            width: size.0,
-            height: size.1,
-            present_mode: PresentMode::Fifo,
-            alpha_mode: CompositeAlphaMode::Opaque,
-            view_formats: vec![],
-            desired_maximum_frame_latency: 2,
-        };
+        let surface = unsafe { instance.create_surface_unsafe(window.surface_target()?) }?;
+        let config = surface_config(
+            TextureFormat::Bgra8UnormSrgb,
+            size.0,
+            size.1,
+            surface.get_capabilities(&adapter).usages,
+        );
         surface.configure(&device, &config);
 
-        let device_arc = Arc::<Mutex<Device>>::new(Mutex::new(device));
-        let queue_arc = Arc::<Mutex<Queue>>::new(Mutex::new(queue));
-        let texts = TextCollection::new(device_arc.clone(), queue_arc.clone(), swapchain_format);
+        let mut context = Context::new(
+            Arc::new(instance),
+            Arc::new(adapter),
+            Arc::new(Mutex::new(device)),
+            Arc::new(Mutex::new(queue)),
+            Some(surface),
+            config,
+        )?;
+        context.set_scale_factor(scale_factor)?;
+        // the surface starts with vsync, which paces frames on its own
+        self.pacer.uncapped = context.is_vsync()?;
+        self.context = Some(context);
 
-        self.context = Some(Context {
-            device: device_arc.clone(),
-            queue: queue_arc.clone(),
-            surface: Arc::<Mutex<Surface>>::new(Mutex::new(surface)),
-            config: Arc::<Mutex<SurfaceConfiguration>>::new(Mutex::new(config)),
-            swapchain_format,
-            texts,
-            geos: GeoManager::new(device_arc.clone(), queue_arc.clone(), swapchain_format),
-            file_watcher: FileWatcher::new(),
+        Ok(())
+    }
+
+    // Headless contexts render into an offscreen texture instead of a window surface,
+    // which can be read back with Context::read_back_frame.
+    #[allow(dead_code)]
+    pub async fn new_headless_context(
+        &mut self,
+        width: u32,
+        height: u32,
+    ) -> Result<(), ShecvError> {
+        let instance = Instance::new(InstanceDescriptor {
+            ..Default::default()
         });
 
+        let (adapter, device, queue) = request_device(&instance).await?;
+        // the offscreen target sets its own usage, nothing reads this one's
+        let config = surface_config(
+            TextureFormat::Rgba8UnormSrgb,
+            width,
+            height,
+            TextureUsages::empty(),
+        );
+
+        self.context = Some(Context::new(
+            Arc::new(instance),
+            Arc::new(adapter),
+            Arc::new(Mutex::new(device)),
+            Arc::new(Mutex::new(queue)),
+            None,
+            config,
+        )?);
+
         Ok(())
     }
 }
 
+impl<'a> State<'a> {
+    // Open another window that renders with this State's device and queue.
+    // It gets its own surface, lists and overlays; pass every State to
+    // process_window_events so each receives the events for its window.
+    #[allow(dead_code)]
+    pub fn new_window(
+        &self,
+        backend: &mut dyn Backend,
+        width: u32,
+        height: u32,
+        title: &str,
+    ) -> Result<State<'a>, ShecvError> {
+        let shared = self.context.as_ref().ok_or_else(ShecvError::no_context)?;
+        let window = backend.create_window(width, height, title)?;
+
+        let size = window.drawable_size();
+        let scale_factor = window_scale_factor(window.as_ref());
+        let surface = unsafe {
+            shared
+                .instance
+                .create_surface_unsafe(window.surface_target()?)
+        }?;
+        let config = surface_config(
+            shared.swapchain_format,
+            size.0,
+            size.1,
+            surface.get_capabilities(&shared.adapter).usages,
+        );
+        surface.configure(&*shared.device.lock()?, &config);
+
+        let mut context = Context::new(
+            shared.instance.clone(),
+            shared.adapter.clone(),
+            shared.device.clone(),
+            shared.queue.clone(),
+            Some(surface),
+            config,
+        )?;
+        context.set_scale_factor(scale_factor)?;
+
+        Ok(State {
+            title: Some(title.to_string()),
+            flow_command: FlowCommand::None,
+            window: Some(window),
+            context: Some(context),
+            ui_wait: Duration::from_millis(60),
+            ..Default::default()
+        })
+    }
+}
+
+// Physical pixels per window unit. HiDPI windows on macOS and Wayland have a
+// drawable larger than the window itself.
+fn window_scale_factor(window: &dyn BackendWindow) -> f32 {
+    let (width, _) = window.size();
+    let (drawable_width, _) = window.drawable_size();
+    if width == 0 || drawable_width == 0 {
+        return 1.0;
+    }
+    drawable_width as f32 / width as f32
+}
+
+async fn request_device(instance: &Instance) -> Result<(Adapter, Device, Queue), ShecvError> {
+    let adapter = instance
+        .request_adapter(&RequestAdapterOptions::default())
+        .await
+        .ok_or_else(|| ShecvError::Device("no suitable gpu adapter".into()))?;
+
+    // timestamp queries are only used when gpu timing is enabled, but have to be
+    // requested up front
+    let (device, queue) = adapter
+        .request_device(
+            &DeviceDescriptor {
+                required_features: adapter.features() & Features::TIMESTAMP_QUERY,
+                ..Default::default()
+            },
+            None,
+        )
+        .await?;
+
+    Ok((adapter, device, queue))
+}
+
+// supported is what the surface's capabilities allow; frames are only copied
+// from for screenshots and recording where the surface can be a copy source
+fn surface_config(
+    format: TextureFormat,
+    width: u32,
+    height: u32,
+    supported: TextureUsages,
+) -> SurfaceConfiguration {
+    SurfaceConfiguration {
+        usage: TextureUsages::RENDER_ATTACHMENT | (supported & TextureUsages::COPY_SRC),
+        format,
+        width,
+        height,
+        present_mode: PresentMode::Fifo,
+        alpha_mode: CompositeAlphaMode::Opaque,
+        view_formats: vec![],
+        desired_maximum_frame_latency: 2,
+    }
+}
+
+pub struct OffscreenTarget {
+    pub texture: Texture,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl OffscreenTarget {
+    fn new(device: &Device, config: &SurfaceConfiguration) -> Self {
+        let texture = device.create_texture(&TextureDescriptor {
+            label: Some("offscreen target"),
+            size: Extent3d {
+                width: config.width,
+                height: config.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: config.format,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+
+        OffscreenTarget {
+            texture,
+            width: config.width,
+            height: config.height,
+        }
+    }
+}
+
+// What a render hook gets to see of the frame being drawn.
+#[allow(dead_code)]
+pub struct RenderHookFrame<'f> {
+    pub device: &'f Device,
+    pub queue: &'f Queue,
+    pub view: &'f TextureView,
+    pub format: TextureFormat,
+    pub width: u32,
+    pub height: u32,
+}
+
+// Render hooks record their own passes into the frame's encoder,
+// either before the geo pass or between the geo and text passes.
+pub type RenderHook = Box<dyn FnMut(&RenderHookFrame, &mut CommandEncoder)>;
+
+// Given the shader's path and why it didn't build.
+pub type ShaderErrorCallback = Box<dyn FnMut(&str, &ShecvError)>;
+
+// Lost and outdated surfaces are reconfigured and tried once more. Returns None
+// when the frame should be skipped, e.g. on a timeout or while still outdated.
+fn acquire_frame(
+    surface: &Surface,
+    device: &Device,
+    config: &SurfaceConfiguration,
+) -> Result<Option<SurfaceTexture>, ShecvError> {
+    match surface.get_current_texture() {
+        Ok(frame) => Ok(Some(frame)),
+        Err(SurfaceError::Lost | SurfaceError::Outdated) => {
+            surface.configure(device, config);
+            match surface.get_current_texture() {
+                Ok(frame) => Ok(Some(frame)),
+                Err(SurfaceError::Timeout | SurfaceError::Outdated) => Ok(None),
+                Err(e) => Err(e.into()),
+            }
+        }
+        Err(SurfaceError::Timeout) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn color_pass<'e>(
+    encoder: &'e mut CommandEncoder,
+    view: &'e TextureView,
+    load: LoadOp<wgpu::Color>,
+    timestamp_writes: Option<RenderPassTimestampWrites<'e>>,
+) -> RenderPass<'e> {
+    encoder.begin_render_pass(&RenderPassDescriptor {
+        label: None,
+        color_attachments: &[Some(RenderPassColorAttachment {
+            view,
+            resolve_target: None,
+            ops: Operations {
+                load,
+                store: wgpu::StoreOp::Store,
+            },
+        })],
+        depth_stencil_attachment: None,
+        timestamp_writes,
+        occlusion_query_set: None,
+    })
+}
+
+// Draws whatever culling left of the group.
+fn draw_group<'p>(pass: &mut RenderPass<'p>, ig: &'p GeoInstances, pipelines: &'p PipelineCache) {
+    if ig.draw_ranges.is_empty() {
+        return;
+    }
+    let Some(record) = pipelines.get(&ig.pipeline) else {
+        return;
+    };
+    pass.set_pipeline(&record.render_pipeline);
+    pass.set_bind_group(0, &ig.bind_group, &[]);
+    pass.set_index_buffer(ig.index_buffer.slice(..), IndexFormat::Uint16);
+    pass.set_vertex_buffer(0, ig.vertex_buffer.slice(..));
+    pass.set_vertex_buffer(1, ig.instance_buffer_manager.buffer.slice(..));
+    for range in ig.draw_ranges.iter() {
+        pass.draw_indexed(0..ig.index_count, 0, range.clone());
+    }
+}
+
 pub struct Context<'a> {
+    pub instance: Arc<Instance>,
+    pub adapter: Arc<Adapter>,
     pub device: Arc<Mutex<Device>>,
     pub queue: Arc<Mutex<Queue>>,
-    pub surface: Arc<Mutex<Surface<'a>>>,
+    pub surface: Option<Arc<Mutex<Surface<'a>>>>,
+    pub offscreen: Option<OffscreenTarget>,
     pub config: Arc<Mutex<SurfaceConfiguration>>,
     #[allow(dead_code)]
     pub swapchain_format: TextureFormat,
     pub texts: TextCollection,
+    pub post: PostChain,
+    pub background: BackgroundPass,
+    pub compute: ComputeJobs,
     pub geos: GeoManager,
     pub file_watcher: FileWatcher,
+    pub pre_pass_hooks: Vec<RenderHook>,
+    pub post_pass_hooks: Vec<RenderHook>,
+    pub animators: Animators,
+    pub tweens: Tweens,
+    // shader reloads that failed, until taken; the window shows them as
+    // notifications
+    pub shader_errors: Vec<(String, ShecvError)>,
+    // watched files that couldn't be read, likewise. each is only added
+    // again after it has been read since
+    pub watch_errors: Vec<(String, ShecvError)>,
+    // called with the path and error as a shader reload fails
+    pub on_shader_error: Option<ShaderErrorCallback>,
+    // where the next frame is saved, see request_screenshot
+    pub screenshot: Option<PathBuf>,
+    // frames being saved, see start_recording
+    pub recording: Option<Recording>,
+    pub particles: ParticleEmitters,
+    pub render_sync: RenderSync,
+    pub last_update: Option<Instant>,
+    pub suspended: bool,
+    // physical pixels per logical pixel. the surface config is in physical
+    // pixels, while lists, overlays, lines and text are laid out in logical ones
+    pub scale_factor: f32,
+    // nothing is rendered while the window is minimized or has no area
+    pub minimized: bool,
+    pub gpu_timer: Option<GpuTimer>,
+    pub render_stats: RenderStats,
+    pub assets: Assets,
+}
+
+impl<'a> Context<'a> {
+    // The instance, device and queue may be shared with other windows' contexts.
+    fn new(
+        instance: Arc<Instance>,
+        adapter: Arc<Adapter>,
+        device_arc: Arc<Mutex<Device>>,
+        queue_arc: Arc<Mutex<Queue>>,
+        surface: Option<Surface<'a>>,
+        config: SurfaceConfiguration,
+    ) -> Result<Self, ShecvError> {
+        let swapchain_format = config.format;
+        let offscreen = match surface {
+            Some(_) => None,
+            None => Some(OffscreenTarget::new(&*device_arc.lock()?, &config)),
+        };
+        let texts = TextCollection::new(device_arc.clone(), queue_arc.clone(), swapchain_format)?;
+        let post = PostChain::new(&*device_arc.lock()?, swapchain_format);
+        let background = BackgroundPass::new(&*device_arc.lock()?, swapchain_format);
+
+        Ok(Context {
+            instance,
+            adapter,
+            device: device_arc.clone(),
+            queue: queue_arc.clone(),
+            surface: surface.map(|surface| Arc::<Mutex<Surface>>::new(Mutex::new(surface))),
+            offscreen,
+            config: Arc::<Mutex<SurfaceConfiguration>>::new(Mutex::new(config)),
+            swapchain_format,
+            texts,
+            post,
+            background,
+            compute: ComputeJobs::default(),
+            geos: GeoManager::new(device_arc.clone(), queue_arc.clone(), swapchain_format)?,
+            file_watcher: FileWatcher::new(),
+            pre_pass_hooks: vec![],
+            post_pass_hooks: vec![],
+            animators: Animators::default(),
+            tweens: Tweens::default(),
+            shader_errors: vec![],
+            watch_errors: vec![],
+            on_shader_error: None,
+            screenshot: None,
+            recording: None,
+            particles: ParticleEmitters::default(),
+            render_sync: RenderSync::default(),
+            last_update: None,
+            suspended: false,
+            scale_factor: 1.0,
+            minimized: false,
+            gpu_timer: None,
+            render_stats: RenderStats::default(),
+            assets: Assets::default(),
+        })
+    }
+
+    // Time the geo and text passes on the gpu, reported through render_stats.
+    // Returns false if the adapter has no timestamp query support.
+    pub fn enable_gpu_timing(&mut self) -> Result<bool, ShecvError> {
+        if self.gpu_timer.is_none() {
+            self.gpu_timer = GpuTimer::new(&*self.device.lock()?, &*self.queue.lock()?);
+        }
+        Ok(self.gpu_timer.is_some())
+    }
+
+    #[allow(dead_code)]
+    pub fn add_pre_pass_hook(
+        &mut self,
+        hook: impl FnMut(&RenderHookFrame, &mut CommandEncoder) + 'static,
+    ) {
+        self.pre_pass_hooks.push(Box::new(hook));
+    }
+
+    #[allow(dead_code)]
+    pub fn add_post_pass_hook(
+        &mut self,
+        hook: impl FnMut(&RenderHookFrame, &mut CommandEncoder) + 'static,
+    ) {
+        self.post_pass_hooks.push(Box::new(hook));
+    }
+
+    // What the frame is cleared to before the background and every layer.
+    #[allow(dead_code)]
+    pub fn set_clear_color(&mut self, color: ColorRGBA) {
+        self.geos.layers.background = color;
+    }
+
+    // Draw a gradient or skybox behind every layer, or nothing again with
+    // Background::None.
+    #[allow(dead_code)]
+    pub fn set_background(&mut self, background: Background) -> Result<(), ShecvError> {
+        // gradients still need a texture bound
+        let white = load_texture(
+            self.device.clone(),
+            self.queue.clone(),
+            TextureSheetDefinition::none(),
+        )?;
+        self.background
+            .set(&*self.device.lock()?, background, &white);
+        Ok(())
+    }
+
+    // Load an equirectangular image and show it around the perspective camera.
+    #[allow(dead_code)]
+    pub fn set_skybox(&mut self, path: &str) -> Result<(), ShecvError> {
+        let sheet = load_texture(
+            self.device.clone(),
+            self.queue.clone(),
+            TextureSheetDefinition {
+                path: path.to_string(),
+                ..TextureSheetDefinition::none()
+            },
+        )?;
+        self.set_background(Background::Skybox(Box::new(sheet)))
+    }
+
+    #[allow(dead_code)]
+    pub fn on_shader_error(&mut self, callback: impl FnMut(&str, &ShecvError) + 'static) {
+        self.on_shader_error = Some(Box::new(callback));
+    }
 }
 
 impl Context<'_> {
-    pub fn check_watched_files(&mut self) -> Result<(), Box<dyn Error>> {
+    // Returns the paths that changed and were acted on, as they were added.
+    pub fn check_watched_files(&mut self) -> Result<Vec<String>, ShecvError> {
+        let mut changed = vec![];
         for fwe in self.file_watcher.entries.iter_mut() {
-            let metadata = metadata(&*fwe.path)?;
-            if metadata.modified().unwrap() > fwe.last_modified {
-                match fwe.action {
-                    FileWatcherAction::ReloadShader => {
-                        self.geos.reload_shader(self.device.clone(), &fwe.path)?;
+            // one missing file doesn't stop the rest being checked
+            let modified = match metadata(&fwe.canonical).and_then(|m| m.modified()) {
+                Ok(modified) => modified,
+                Err(e) => {
+                    if !fwe.unreadable {
+                        fwe.unreadable = true;
+                        warn!("can't read watched file {}: {}", fwe.canonical.display(), e);
+                        self.watch_errors.push((fwe.paths[0].clone(), e.into()));
                     }
+                    continue;
                 }
+            };
+            fwe.unreadable = false;
+            if modified <= fwe.last_modified {
+                continue;
+            }
+            // remember the new time first, so a file that fails to load
+            // is not retried every frame until it changes again
+            fwe.last_modified = modified;
+            let mut failed = false;
+            for action in fwe.actions.iter() {
+                match action {
+                    FileWatcherAction::Shader => {
+                        for path in fwe.paths.iter() {
+                            let reload = (|| {
+                                self.geos.reload_shader(self.device.clone(), path)?;
+                                self.post.reload_shader(&*self.device.lock()?, path)?;
+                                self.compute.reload_shader(&*self.device.lock()?, path)
+                            })();
+                            // whatever failed keeps drawing with its last good pipeline
+                            if let Err(e) = reload {
+                                error!("{}", e);
+                                if let Some(callback) = &mut self.on_shader_error {
+                                    callback(path, &e);
+                                }
+                                self.shader_errors.push((path.clone(), e));
+                                failed = true;
+                            }
+                        }
+                    }
+                    FileWatcherAction::Ui | FileWatcherAction::Script => {}
+                    // loaded in the background, picked up by a later update
+                    FileWatcherAction::Asset => {
+                        for path in fwe.paths.iter() {
+                            self.assets.reload(path);
+                        }
+                    }
+                }
+            }
+            if !failed {
+                changed.extend(fwe.paths.iter().cloned());
             }
         }
-        Ok(())
+        Ok(changed)
     }
 
-    pub fn update(&mut self) -> Result<(), Box<dyn Error>> {
-        self.check_watched_files()?;
-        // let config = self.config.lock().unwrap();
+    // Returns the watched files that were reloaded.
+    pub fn update(&mut self) -> Result<Vec<String>, ShecvError> {
+        for path in self.assets.take_unwatched() {
+            // a file that failed to load may not exist; it has its error
+            if let Err(e) = self.file_watcher.add_asset_path(&path) {
+                warn!("not watching asset {}: {}", path, e);
+            }
+        }
+        let reloaded = self.check_watched_files()?;
+        self.assets.poll();
+
+        let now = Instant::now();
+        let dt = self
+            .last_update
+            .map(|last_update| now - last_update)
+            .unwrap_or_default();
+        self.last_update = Some(now);
+        self.animators
+            .update(dt, &mut self.geos, self.queue.clone())?;
+        self.tweens.update(dt, &mut self.geos, self.queue.clone())?;
+        self.particles
+            .update(dt, &mut self.geos, self.queue.clone())?;
+        self.geos.update_camera(self.queue.clone())?;
+        let resolution = {
+            let config = self.config.lock()?;
+            Vec2::new(config.width as f32, config.height as f32)
+        };
+        self.geos
+            .update_globals(self.queue.clone(), dt, resolution)?;
+        self.geos.upload_params(self.queue.clone())?;
+        self.geos.cull();
+        // let config = self.config.lock()?;
         // for group in self.geos.instance_groups.iter_mut() {
         //     group.instance_buffer_manager.recalc_screen_instances(
         //         self.queue.clone(),
         //         UVec2::new(config.width, config.height),
         //     );
         // }
+        Ok(reloaded)
+    }
+
+    // For the shaders' globals.cursor, in physical pixels. The window sets it
+    // as the mouse moves; apps with a cursor of their own can set it too.
+    pub fn set_cursor_position(&mut self, position: Vec2) {
+        self.geos.globals.cursor = position;
+    }
+
+    // Add a particle emitter drawing into a new group of up to max_particles
+    // quads, each showing a cell of the sheet.
+    #[allow(dead_code)]
+    pub fn add_particle_emitter(
+        &mut self,
+        view_type: GeoViewType,
+        max_particles: usize,
+        sheet_info: TextureSheetDefinition,
+        position: Vec3,
+        settings: EmitterSettings,
+    ) -> Result<usize, ShecvError> {
+        let (format, wh) = {
+            let config = self.config.lock()?;
+            (config.format, (config.width, config.height))
+        };
+        let group = self.geos.new_unit_square(
+            view_type,
+            max_particles,
+            format,
+            wh,
+            sheet_info,
+            assets::DEFAULT_SHADER,
+        )?;
+        self.particles
+            .emitters
+            .push(ParticleEmitter::new(group, position, settings));
+        Ok(self.particles.emitters.len() - 1)
+    }
+
+    // Add a compute job, watching its shader for changes. It runs every frame
+    // once given its buffers with bind_compute.
+    #[allow(dead_code)]
+    pub fn add_compute(
+        &mut self,
+        shader_path: &str,
+        entry_point: &str,
+        bindings: &[ComputeBindingKind],
+    ) -> Result<usize, ShecvError> {
+        let index = self
+            .compute
+            .add(&*self.device.lock()?, shader_path, entry_point, bindings)?;
+        self.file_watcher.add_path(shader_path)?;
+        Ok(index)
+    }
+
+    #[allow(dead_code)]
+    pub fn bind_compute(&mut self, index: usize, buffers: &[&Buffer]) -> Result<(), ShecvError> {
+        self.compute.bind(&*self.device.lock()?, index, buffers);
         Ok(())
     }
 
-    pub fn resize(&mut self, surface: Arc<Mutex<Surface>>, size: (u32, u32)) {
-        let device = self.device.lock().unwrap();
-        let mut config = self.config.lock().unwrap();
+    // Add a full screen effect after the others, watching its shader for
+    // changes. See src/post_*.wgsl for what the shader gets to work with.
+    #[allow(dead_code)]
+    pub fn add_post_effect(&mut self, shader_path: &str) -> Result<usize, ShecvError> {
+        let index = self.post.add_effect(&*self.device.lock()?, shader_path)?;
+        self.file_watcher.add_path(shader_path)?;
+        Ok(index)
+    }
+
+    #[allow(dead_code)]
+    pub fn set_post_effect_enabled(&mut self, index: usize, enabled: bool) {
+        if let Some(effect) = self.post.effects.get_mut(index) {
+            effect.enabled = enabled;
+        }
+    }
+
+    // An offscreen texture to draw groups into, see GeoManager::set_group_target.
+    // Other groups show it with a sheet from GeoManager::target_sheet, e.g. for
+    // a minimap or a thumbnail.
+    #[allow(dead_code)]
+    pub fn create_render_target(
+        &mut self,
+        width: u32,
+        height: u32,
+    ) -> Result<RenderTargetId, ShecvError> {
+        let format = self.config.lock()?.format;
+        self.geos.new_render_target(width, height, format)
+    }
+
+    pub fn set_scale_factor(&mut self, scale_factor: f32) -> Result<(), ShecvError> {
+        let scale_factor = scale_factor.max(0.25);
+        self.scale_factor = scale_factor;
+        self.texts.scale_factor = scale_factor;
+        self.geos.scale_factor = scale_factor;
+        let (width, height) = {
+            let config = self.config.lock()?;
+            (config.width, config.height)
+        };
+        self.geos.update_view(self.queue.clone(), width, height)
+    }
+
+    // A group of unit squares placed in logical pixels and drawn with the
+    // default shader, as lists and overlays are.
+    pub fn new_screen_group(
+        &mut self,
+        capacity: usize,
+        sheet: TextureSheetDefinition,
+    ) -> Result<GroupId, ShecvError> {
+        let (format, wh) = {
+            let config = self.config.lock()?;
+            (config.format, (config.width, config.height))
+        };
+        self.geos.new_unit_square(
+            GeoViewType::Orthographic,
+            capacity,
+            format,
+            wh,
+            sheet,
+            assets::DEFAULT_SHADER,
+        )
+    }
+
+    // The untextured group an overlay draws into, made on layer the first
+    // time and kept in group from then on.
+    pub fn overlay_group(
+        &mut self,
+        group: &mut Option<GroupId>,
+        capacity: usize,
+        layer: RenderLayer,
+    ) -> Result<GroupId, ShecvError> {
+        if let Some(group) = *group {
+            return Ok(group);
+        }
+        let made = self.new_screen_group(capacity, TextureSheetDefinition::default())?;
+        self.geos.set_group_layer(made, layer)?;
+        *group = Some(made);
+        Ok(made)
+    }
+
+    // The size of the render target in logical pixels, the extent to lay out against.
+    pub fn logical_size(&self) -> Result<UVec2, ShecvError> {
+        let config = self.config.lock()?;
+        Ok(UVec2::new(
+            ((config.width as f32 / self.scale_factor).round() as u32).max(1),
+            ((config.height as f32 / self.scale_factor).round() as u32).max(1),
+        ))
+    }
+
+    // A zero sized window keeps the last config, as surfaces can't be configured
+    // to zero and layout would divide by it; see State::set_minimized.
+    pub fn is_vsync(&self) -> Result<bool, ShecvError> {
+        Ok(matches!(
+            self.config.lock()?.present_mode,
+            PresentMode::Fifo | PresentMode::FifoRelaxed | PresentMode::AutoVsync
+        ))
+    }
+
+    // Switch between waiting for vertical blank to present, and presenting
+    // as soon as a frame is done, where the surface supports it.
+    pub fn set_vsync(&mut self, vsync: bool) -> Result<(), ShecvError> {
+        let device = self.device.lock()?;
+        let mut config = self.config.lock()?;
+        config.present_mode = match vsync {
+            true => PresentMode::AutoVsync,
+            false => PresentMode::AutoNoVsync,
+        };
+        if let Some(surface) = &self.surface {
+            surface.lock()?.configure(&device, &config);
+        }
+        Ok(())
+    }
+
+    pub fn resize(&mut self, size: (u32, u32)) -> Result<(), ShecvError> {
+        if size.0 == 0 || size.1 == 0 {
+            return Ok(());
+        }
+        let device = self.device.lock()?;
+        let mut config = self.config.lock()?;
         config.width = size.0;
         config.height = size.1;
-        let surface = surface.lock().unwrap();
-        surface.configure(&device, &config);
+        // a suspended context has neither a surface nor an offscreen target
+        if let Some(surface) = &self.surface {
+            surface.lock()?.configure(&device, &config);
+        } else if self.offscreen.is_some() {
+            self.offscreen = Some(OffscreenTarget::new(&device, &config));
+        }
 
         // below functions were to resize on-screen geometry instances...
         // this is not necessary atm bc we recreate geo instances every frame
 
         self.geos
-            .update_view(self.queue.clone(), config.width, config.height);
+            .update_view(self.queue.clone(), config.width, config.height)?;
         for group in self.geos.instance_groups.iter_mut() {
             group.mark_all_for_update();
         }
+        Ok(())
     }
 
-    pub fn render(&mut self) -> Result<(), Box<dyn Error>> {
-        let surface = self.surface.clone();
-        let config = self.config.lock().unwrap();
+    // Called when the app is sent to the background. The surface is released,
+    // as mobile platforms destroy the native window behind it.
+    pub fn suspend(&mut self) {
+        self.surface = None;
+        self.suspended = true;
+    }
+
+    // Recreate the surface for the window and upload instance data again.
+    pub fn resume(&mut self, window: &dyn BackendWindow) -> Result<(), ShecvError> {
+        if !self.suspended {
+            return Ok(());
+        }
+
+        let surface = unsafe {
+            self.instance
+                .create_surface_unsafe(window.surface_target()?)
+        }?;
+        {
+            let device = self.device.lock()?;
+            let config = self.config.lock()?;
+            surface.configure(&device, &config);
+        }
+        self.surface = Some(Arc::new(Mutex::new(surface)));
+
+        let (width, height) = {
+            let config = self.config.lock()?;
+            (config.width, config.height)
+        };
+        self.geos.update_view(self.queue.clone(), width, height)?;
+        for group in self.geos.instance_groups.iter_mut() {
+            group.upload_all(self.queue.clone())?;
+        }
+        for lg in self.geos.line_groups.iter_mut() {
+            lg.upload_all(self.queue.clone())?;
+        }
+
+        self.suspended = false;
+        self.last_update = None;
+        Ok(())
+    }
+
+    pub fn render(&mut self) -> Result<(), ShecvError> {
+        if self.suspended || self.minimized {
+            return Ok(());
+        }
+        let render_start = Instant::now();
+
+        let config = self.config.lock()?;
 
         self.texts.prepare(
             self.device.clone(),
@@ -377,188 +2131,683 @@ impl Context<'_> {
             config.height,
         )?;
 
-        let device = self.device.lock().unwrap();
-        let queue = self.queue.lock().unwrap();
-        let surface = surface.lock().unwrap();
+        let device = self.device.lock()?;
+        let queue = self.queue.lock()?;
 
-        let frame = surface.get_current_texture()?;
-        let view = frame.texture.create_view(&TextureViewDescriptor::default());
+        if let Some((geo_pass, text_pass)) = self
+            .gpu_timer
+            .as_mut()
+            .and_then(|timer| timer.try_read(&device))
+        {
+            self.render_stats.geo_pass = Some(geo_pass);
+            self.render_stats.text_pass = Some(text_pass);
+        }
+        // skip timing this frame while the last results are still being read
+        let timer = self.gpu_timer.as_ref().filter(|timer| timer.is_idle());
+        let timed = timer.is_some();
+
+        // render to the window surface if there is one, otherwise to the offscreen target
+        let frame = match &self.surface {
+            Some(surface) => match acquire_frame(&*surface.lock()?, &device, &config)? {
+                Some(frame) => Some(frame),
+                None => return Ok(()),
+            },
+            None => None,
+        };
+        let view = match (&frame, &self.offscreen) {
+            (Some(frame), _) => frame.texture.create_view(&TextureViewDescriptor::default()),
+            (None, Some(offscreen)) => offscreen
+                .texture
+                .create_view(&TextureViewDescriptor::default()),
+            (None, None) => return Err(ShecvError::Device("context has no render target".into())),
+        };
+        // with post effects on, the layers are drawn for the effects to read,
+        // and the effects draw the frame
+        let post_active = self.post.is_active();
+        let scene_view = match post_active {
+            true => self
+                .post
+                .scene_view(&device, &queue, config.width, config.height),
+            false => &view,
+        };
         let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor { label: None });
+        let hook_frame = RenderHookFrame {
+            device: &device,
+            queue: &queue,
+            view: scene_view,
+            format: config.format,
+            width: config.width,
+            height: config.height,
+        };
+
+        // compute jobs run before anything is drawn, so their writes show this frame
+        self.compute.dispatch(&mut encoder);
+
+        // render targets first, so groups showing them get this frame
+        for target_id in self.geos.render_targets.ids() {
+            let target = &self.geos.render_targets[target_id];
+            let mut pass = color_pass(
+                &mut encoder,
+                &target.view,
+                LoadOp::Clear(target.clear.to_wgpu_color()),
+                None,
+            );
+            for layer in RenderLayer::ALL {
+                for ig in self.geos.instance_groups.iter() {
+                    if ig.layer == layer && ig.target == Some(target_id) {
+                        draw_group(&mut pass, ig, &self.geos.pipelines);
+                    }
+                }
+            }
+        }
+
+        // clear and draw the background, then let pre-pass hooks draw
+        // underneath every layer
         {
-            let mut pass = encoder.begin_render_pass(&RenderPassDescriptor {
-                label: None,
-                color_attachments: &[Some(RenderPassColorAttachment {
-                    view: &view,
-                    resolve_target: None,
-                    ops: Operations {
-                        load: LoadOp::Clear(wgpu::Color {
-                            r: 0.05,
-                            g: 0.01,
-                            b: 0.03,
-                            a: 1.0,
-                        }),
-                        store: wgpu::StoreOp::Store,
-                    },
-                })],
-                depth_stencil_attachment: None,
-                timestamp_writes: None,
-                occlusion_query_set: None,
-            });
-
-            // include geos in pass
-            if !self.geos.instance_groups.is_empty() {
-                for (i, ig) in self.geos.instance_groups.iter().enumerate() {
-                    pass.set_pipeline(&ig.render_pipeline_record.render_pipeline);
-                    pass.set_bind_group(0, &ig.bind_group, &[]);
-                    pass.set_index_buffer(ig.index_buffer.slice(..), IndexFormat::Uint16);
-                    pass.set_vertex_buffer(0, ig.vertex_buffer.slice(..));
-                    pass.set_vertex_buffer(1, ig.instance_buffer_manager.buffer.slice(..));
-                    pass.draw_indexed(0..6_u32, 0, 0..self.geos.num_instances(i));
+            let mut pass = color_pass(
+                &mut encoder,
+                scene_view,
+                LoadOp::Clear(self.geos.layers.background.to_wgpu_color()),
+                None,
+            );
+            self.background
+                .draw(&mut pass, &queue, self.geos.camera.view_projection());
+        }
+        for hook in self.pre_pass_hooks.iter_mut() {
+            hook(&hook_frame, &mut encoder);
+        }
+
+        let last_layer = RenderLayer::ALL.len() - 1;
+        for (layer_index, layer) in RenderLayer::ALL.into_iter().enumerate() {
+            let (first, last) = (layer_index == 0, layer_index == last_layer);
+            {
+                let load = match self.geos.layers.get(layer).clear {
+                    Some(color) => LoadOp::Clear(color.to_wgpu_color()),
+                    None => LoadOp::Load,
+                };
+                let mut pass = color_pass(
+                    &mut encoder,
+                    scene_view,
+                    load,
+                    timer.and_then(|timer| timer.writes(TimedPass::Geo, first, last)),
+                );
+
+                // include geos in pass, leaving out what was culled in update
+                for ig in self.geos.instance_groups.iter() {
+                    if ig.layer == layer && ig.target.is_none() {
+                        draw_group(&mut pass, ig, &self.geos.pipelines);
+                    }
+                }
+
+                // lines go over the instance groups, six vertices per segment
+                for lg in self.geos.line_groups.iter() {
+                    if lg.layer != layer || lg.segments.is_empty() {
+                        continue;
+                    }
+                    pass.set_pipeline(&lg.render_pipeline_record.render_pipeline);
+                    pass.set_bind_group(0, &lg.bind_group, &[]);
+                    pass.set_vertex_buffer(0, lg.buffer.slice(..));
+                    pass.draw(0..6, 0..lg.num_segments());
                 }
             }
+            {
+                let mut pass = color_pass(
+                    &mut encoder,
+                    scene_view,
+                    LoadOp::Load,
+                    timer.and_then(|timer| timer.writes(TimedPass::Text, first, last)),
+                );
+
+                // include the layer's text labels in pass
+                self.texts.render(layer, &mut pass)?;
+            }
 
-            // include text labels in pass
-            self.texts
-                .text_renderer
-                .render(&self.texts.atlas, &self.texts.viewport, &mut pass)?;
+            // post-pass hooks draw over the world but under the ui
+            if layer == RenderLayer::World {
+                for hook in self.post_pass_hooks.iter_mut() {
+                    hook(&hook_frame, &mut encoder);
+                }
+            }
         }
 
+        if post_active {
+            self.post.apply(&mut encoder, &view);
+        }
+        if let Some(timer) = timer {
+            timer.resolve(&mut encoder);
+        }
+        // screenshots copy the finished frame before it is shown
+        let target = match &frame {
+            Some(frame) if config.usage.contains(TextureUsages::COPY_SRC) => Some(&frame.texture),
+            Some(_) => None,
+            None => self.offscreen.as_ref().map(|offscreen| &offscreen.texture),
+        };
+        let screenshot = match (self.screenshot.take(), target) {
+            (Some(path), Some(texture)) => Some((
+                path,
+                FrameCopy::record(&device, &mut encoder, texture, config.width, config.height),
+            )),
+            (Some(path), None) => {
+                warn!(
+                    "screenshot {} failed: this surface can't be copied from",
+                    path.display()
+                );
+                None
+            }
+            _ => None,
+        };
+        let due = self
+            .recording
+            .as_mut()
+            .is_some_and(|recording| recording.wants_frame());
+        let recorded = match target {
+            Some(texture) if due => Some(FrameCopy::record(
+                &device,
+                &mut encoder,
+                texture,
+                config.width,
+                config.height,
+            )),
+            _ => None,
+        };
+
         queue.submit(Some(encoder.finish()));
-        frame.present();
+        if let Some(recording) = self.recording.as_mut() {
+            if let Some(copy) = recorded {
+                recording.push(copy);
+            }
+            recording.collect(&device, config.format);
+        }
+        if let Some((path, copy)) = screenshot {
+            let saved = copy
+                .read(&device, config.format)
+                .and_then(|image| Ok(image.save(&path)?));
+            match saved {
+                Ok(()) => info!("saved screenshot {}", path.display()),
+                Err(e) => warn!("screenshot {} failed: {}", path.display(), e),
+            }
+        }
+        if let Some(frame) = frame {
+            frame.present();
+        }
+        if let Some(timer) = self.gpu_timer.as_mut().filter(|_| timed) {
+            timer.begin_read();
+        }
         self.texts.trim_atlas();
+        self.render_stats.cpu_render = render_start.elapsed();
 
         Ok(())
     }
+
+    // Copy the offscreen target of a headless context back to the cpu.
+    #[allow(dead_code)]
+    pub fn read_back_frame(&self) -> Result<RgbaImage, ShecvError> {
+        let offscreen = self.offscreen.as_ref().ok_or_else(|| {
+            ShecvError::Device("read_back_frame requires a headless context".into())
+        })?;
+        let device = self.device.lock()?;
+        let queue = self.queue.lock()?;
+        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor { label: None });
+        let copy = FrameCopy::record(
+            &device,
+            &mut encoder,
+            &offscreen.texture,
+            offscreen.width,
+            offscreen.height,
+        );
+        queue.submit(Some(encoder.finish()));
+        copy.read(&device, self.config.lock()?.format)
+    }
+
+    // Save the next frame rendered to path, as whatever image type its
+    // extension names.
+    #[allow(dead_code)]
+    pub fn request_screenshot(&mut self, path: &str) {
+        self.screenshot = Some(PathBuf::from(path));
+    }
+
+    // Save frames as they're rendered until stop_recording, to a folder of
+    // pngs or a .gif, see Recording. A recording already going is finished
+    // first.
+    #[allow(dead_code)]
+    pub fn start_recording(&mut self, path: &str, fps: u32) -> Result<(), ShecvError> {
+        if !self.can_copy_frames()? {
+            return Err(ShecvError::Device(
+                "recording needs a surface that can be copied from".into(),
+            ));
+        }
+        self.stop_recording()?;
+        self.recording = Some(Recording::start(path, fps)?);
+        Ok(())
+    }
+
+    // Returns how many frames were saved, or 0 if nothing was recording.
+    #[allow(dead_code)]
+    pub fn stop_recording(&mut self) -> Result<usize, ShecvError> {
+        let Some(recording) = self.recording.take() else {
+            return Ok(0);
+        };
+        let format = self.config.lock()?.format;
+        recording.finish(&*self.device.lock()?, format)
+    }
+
+    // Whether rendered frames can be read back for screenshots and recording.
+    // Headless contexts always can; window surfaces only where the platform
+    // allows copying from them.
+    pub fn can_copy_frames(&self) -> Result<bool, ShecvError> {
+        Ok(self.surface.is_none() || self.config.lock()?.usage.contains(TextureUsages::COPY_SRC))
+    }
+
+    #[allow(dead_code)]
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
 }
 
 pub fn process_events(
     state: Rc<RefCell<State>>,
-    sdl: Rc<RefCell<Sdl>>,
+    backend: SharedBackend,
     store: Rc<RefCell<ValueStore>>,
 ) -> impl FnMut() + '_ {
-    let mut events = sdl.borrow_mut().event_pump().unwrap();
+    process_window_events(vec![state], backend, store)
+}
 
+// Events for a window go to the State that owns it, or to the first State if
+// none does. Events without a window, like Quit, go to every State. Each State
+// is then updated and rendered.
+pub fn process_window_events<'a>(
+    states: Vec<Rc<RefCell<State<'a>>>>,
+    backend: SharedBackend,
+    store: Rc<RefCell<ValueStore>>,
+) -> impl FnMut() + 'a {
     move || {
-        for event in events.poll_iter() {
-            match event {
-                Event::Window {
-                    timestamp: _,
-                    window_id: _,
-                    win_event,
-                } => match win_event {
-                    WindowEvent::Resized(w, h) => {
-                        let mut state = state.borrow_mut();
-                        let context = state.context.as_mut().unwrap();
-                        context.resize(context.surface.clone(), (w as u32, h as u32));
-                        let sdl = sdl.borrow_mut();
-                        sdl.event().unwrap().flush_events(0, 0xFFFF);
-                        let mut listuis_to_update = vec![];
-                        for (index, listui) in state.listuis.iter().enumerate() {
-                            if listui.anchor != ListAnchor::Hidden {
-                                listuis_to_update.push(index);
-                            }
-                        }
-                        for index in listuis_to_update {
-                            let _ = state.layout_listui(&store.borrow_mut(), index);
-                        }
+        let frame_start = Instant::now();
+        let events = backend.borrow_mut().poll_events();
+        for (i, event) in events.iter().enumerate() {
+            // dragging a window edge sends a stream of resizes; only the last matters
+            if matches!(event, BackendEvent::Resized { .. }) && events[i + 1..].contains(event) {
+                continue;
+            }
+            match event.window_id() {
+                Some(window_id) => {
+                    let target = states
+                        .iter()
+                        .find(|state| state.borrow().window_id() == Some(window_id))
+                        .or(states.first());
+                    if let Some(state) = target {
+                        handle_event(state, event.clone(), &backend, &store);
                     }
-                    WindowEvent::Enter => {}
-                    _ => {}
-                },
-                Event::Quit { .. }
-                | Event::KeyDown {
-                    keycode: Some(Keycode::Escape),
-                    ..
-                } => {
-                    state.borrow_mut().flow_command = FlowCommand::Quit;
                 }
-                Event::KeyDown {
-                    keycode: Some(Keycode::Left),
-                    ..
-                } => {}
-                Event::KeyDown {
-                    keycode: Some(Keycode::Right),
-                    ..
-                } => {}
-                Event::KeyDown {
-                    keycode: Some(Keycode::Up),
-                    ..
-                } => {
-                    let mut state = state.borrow_mut();
-                    let input_ok = {
-                        let mut input_ok = true;
-                        if state.last_ui_time.is_some()
-                            && state.last_ui_time.unwrap() + state.ui_wait > SystemTime::now()
-                        {
-                            input_ok = false;
-                        }
-                        input_ok
-                    };
-                    if input_ok {
-                        for listui in &mut state.listuis {
-                            if listui.selected_index == 0 {
-                                listui.selected_index = (listui.entries.len() - 1) as i32;
-                            } else if listui.selected_index >= 0 {
-                                listui.selected_index -= 1;
-                            }
-                        }
-                        state.last_ui_time = Some(SystemTime::now());
+                None => {
+                    for state in &states {
+                        handle_event(state, event.clone(), &backend, &store);
                     }
                 }
-                Event::KeyDown {
-                    keycode: Some(Keycode::Down),
-                    ..
-                } => {
-                    let mut state = state.borrow_mut();
-                    let input_ok = {
-                        let mut input_ok = true;
-                        if state.last_ui_time.is_some()
-                            && state.last_ui_time.unwrap() + state.ui_wait > SystemTime::now()
-                        {
-                            input_ok = false;
-                        }
-                        input_ok
-                    };
-                    if input_ok {
-                        for listui in &mut state.listuis {
-                            if listui.selected_index == (listui.entries.len() - 1) as i32 {
-                                listui.selected_index = 0;
-                            } else if listui.selected_index >= 0 {
-                                listui.selected_index =
-                                    (listui.selected_index + 1) % listui.entries.len() as i32;
-                            }
-                        }
-                        state.last_ui_time = Some(SystemTime::now());
+            }
+        }
+
+        for state in &states {
+            let mut state = state.borrow_mut();
+            state.apply_access_actions(&mut store.borrow_mut());
+            if let Err(e) = state.reload_ui_files(&mut store.borrow_mut()) {
+                state.notifications.push_error(&format!("{}", e));
+            }
+            if let Err(e) = state.update_scripts(&mut store.borrow_mut()) {
+                state.notifications.push_error(&format!("{}", e));
+            }
+            // once a frame, rather than with every layout pass
+            state.audio.update(&store.borrow());
+            state.play_ui_sounds();
+            if let Err(e) = render_frame(&mut state, frame_start) {
+                warn!("{}", e);
+            }
+        }
+    }
+}
+
+fn handle_event(
+    state: &Rc<RefCell<State>>,
+    event: BackendEvent,
+    backend: &SharedBackend,
+    store: &Rc<RefCell<ValueStore>>,
+) {
+    // the console takes every key while it's down, even from dialogs
+    if state
+        .borrow_mut()
+        .console_event(&event, &mut store.borrow_mut())
+    {
+        return;
+    }
+    // an open dialog takes every key before anything else sees it
+    if state
+        .borrow_mut()
+        .dialog_event(&event, &mut store.borrow_mut())
+    {
+        return;
+    }
+    // a focused grid takes the arrows and Enter before the lists see them
+    if let BackendEvent::KeyDown {
+        key,
+        alt: false,
+        ctrl: false,
+        ..
+    } = event
+    {
+        if state.borrow_mut().grid_key(key, &mut store.borrow_mut()) {
+            return;
+        }
+    }
+    match event {
+        BackendEvent::Resized { .. } => {
+            let _ = state.borrow_mut().sync_window_size(&store.borrow());
+        }
+        BackendEvent::MouseMotion {
+            position, delta, ..
+        } => {
+            let mut state = state.borrow_mut();
+            state.mouse_position = position;
+            state.mouse_delta += delta;
+            if let Some(context) = state.context.as_mut() {
+                let cursor = position * context.scale_factor;
+                context.set_cursor_position(cursor);
+            }
+        }
+        BackendEvent::DroppedFile { path, .. } => {
+            state
+                .borrow_mut()
+                .window_events
+                .push(WindowState::FileDropped(path));
+        }
+        BackendEvent::Minimized { .. } => state.borrow_mut().set_minimized(true),
+        BackendEvent::Restored { .. } => state.borrow_mut().set_minimized(false),
+        // closing one of several windows asks for just that State to finish
+        BackendEvent::CloseRequested { .. } | BackendEvent::Quit => {
+            state.borrow_mut().flow_command = FlowCommand::Quit;
+        }
+        BackendEvent::Suspended => {
+            let mut state = state.borrow_mut();
+            if let Some(context) = state.context.as_mut() {
+                context.suspend();
+            }
+        }
+        BackendEvent::Resumed => {
+            let mut state = state.borrow_mut();
+            let state = &mut *state;
+            if let (Some(context), Some(window)) = (state.context.as_mut(), state.window.as_ref()) {
+                if let Err(e) = context.resume(window.as_ref()) {
+                    warn!("failed to resume rendering: {}", e);
+                }
+            }
+        }
+        BackendEvent::KeyDown {
+            key: Key::Escape, ..
+        } => {
+            // escape leaves text entry first, then backs out of sublists,
+            // and only quits from the top level
+            let mut state = state.borrow_mut();
+            let mut was_editing = false;
+            for listui in &mut state.listuis {
+                if listui.is_editing() {
+                    listui.cancel_edit();
+                    was_editing = true;
+                }
+                was_editing |= listui.clear_search();
+            }
+            if !was_editing && !state.close_sublists() {
+                state.flow_command = FlowCommand::Quit;
+            }
+        }
+        BackendEvent::KeyDown {
+            key: Key::Enter,
+            alt: true,
+            ..
+        } => {
+            let mut state = state.borrow_mut();
+            if let Err(e) = state.toggle_fullscreen(&store.borrow()) {
+                state.notifications.push_error(&format!("{}", e));
+            }
+        }
+        BackendEvent::KeyDown {
+            key: Key::Enter, ..
+        } => {
+            state
+                .borrow_mut()
+                .activate_selected(&mut store.borrow_mut());
+        }
+        BackendEvent::KeyDown {
+            key: Key::Backspace,
+            ..
+        } => {
+            let mut state = state.borrow_mut();
+            let store = store.borrow();
+            for listui in &mut state.listuis {
+                if let Some(edit_text) = listui.edit_text.as_mut() {
+                    edit_text.pop();
+                } else if listui.focused {
+                    listui.search_backspace(&store);
+                }
+            }
+        }
+        // typing into a focused list that isn't editing jumps to an item
+        BackendEvent::TextInput { text, .. } => {
+            let mut state = state.borrow_mut();
+            let state = &mut *state;
+            let store = store.borrow();
+            for (id, listui) in state.listuis.entries_mut() {
+                if let Some(edit_text) = listui.edit_text.as_mut() {
+                    edit_text.push_str(&text);
+                    listui.composition = None;
+                } else if listui.focused && listui.is_visible(&store) {
+                    let before = listui.selected_index;
+                    listui.type_ahead(&text, &store);
+                    if listui.selected_index != before {
+                        state.ui_events.push(UiEvent::SelectionChanged {
+                            list: id,
+                            index: listui.selected_index as usize,
+                        });
                     }
                 }
-                _ => {}
             }
         }
+        BackendEvent::TextEditing { text, .. } => {
+            let mut state = state.borrow_mut();
+            for listui in &mut state.listuis {
+                if listui.is_editing() {
+                    listui.composition = (!text.is_empty()).then(|| text.clone());
+                }
+            }
+        }
+        BackendEvent::KeyDown {
+            key: Key::C,
+            ctrl: true,
+            ..
+        } => {
+            let state = state.borrow();
+            let store = store.borrow();
+            let text = state
+                .listuis
+                .iter()
+                .filter(|listui| listui.focused && listui.is_visible(&store))
+                .find_map(|listui| listui.selected_text(&store));
+            if let Some(text) = text {
+                if let Err(e) = backend.borrow_mut().set_clipboard_text(&text) {
+                    warn!("copy failed: {}", e);
+                }
+            }
+        }
+        BackendEvent::KeyDown {
+            key: Key::V,
+            ctrl: true,
+            ..
+        } => {
+            let Some(text) = backend.borrow().clipboard_text() else {
+                return;
+            };
+            let mut state = state.borrow_mut();
+            let state = &mut *state;
+            let mut store = store.borrow_mut();
+            for (id, listui) in state.listuis.entries_mut() {
+                if !listui.focused || !listui.is_visible(&store) {
+                    continue;
+                }
+                let before = listui.selected_version(&store);
+                if let Err(e) = listui.paste(&text, &mut store) {
+                    state
+                        .notifications
+                        .push_error(&format!("paste rejected: {}", e));
+                }
+                state
+                    .ui_events
+                    .extend(ListInterface::edited_since(id, before, &store));
+            }
+        }
+        // text being edited has its own backspace; undo is for committed values
+        BackendEvent::KeyDown {
+            key: key @ (Key::Z | Key::Y),
+            ctrl: true,
+            ..
+        } => {
+            if state
+                .borrow()
+                .listuis
+                .iter()
+                .any(|listui| listui.is_editing())
+            {
+                return;
+            }
+            let mut store = store.borrow_mut();
+            if key == Key::Z {
+                store.undo();
+            } else {
+                store.redo();
+            }
+        }
+        BackendEvent::KeyDown { key: Key::F3, .. } => {
+            let mut state = state.borrow_mut();
+            if let Err(e) = state.toggle_debug_overlay() {
+                state.notifications.push_error(&format!("{}", e));
+            }
+        }
+        BackendEvent::KeyDown { key: Key::F12, .. } => {
+            state.borrow_mut().cycle_inspector();
+        }
+        BackendEvent::KeyDown {
+            key: key @ (Key::Left | Key::Right),
+            alt,
+            ..
+        } => {
+            let direction = if key == Key::Left { -1.0 } else { 1.0 };
+            let mut state = state.borrow_mut();
+            let mut store = store.borrow_mut();
+            let state = &mut *state;
+            for (id, listui) in state.listuis.entries_mut() {
+                if !listui.focused || listui.is_editing() || !listui.is_visible(&store) {
+                    continue;
+                }
+                let before = listui.selected_version(&store);
+                // alt picks which part of a vector the arrows step
+                if alt {
+                    listui.select_component(direction as i32, &store);
+                } else if let Err(e) = listui.adjust_selected(direction, &mut store) {
+                    state
+                        .notifications
+                        .push_error(&format!("edit rejected: {}", e));
+                }
+                state
+                    .ui_events
+                    .extend(ListInterface::edited_since(id, before, &store));
+            }
+        }
+        // Up and Down skip items that can't be selected
+        BackendEvent::KeyDown {
+            key: key @ (Key::Up | Key::Down),
+            ..
+        } => {
+            let mut state = state.borrow_mut();
+            let input_ok = {
+                let mut input_ok = true;
+                if state.last_ui_time.is_some()
+                    && state.last_ui_time.unwrap() + state.ui_wait > SystemTime::now()
+                {
+                    input_ok = false;
+                }
+                input_ok
+            };
+            if input_ok {
+                let direction = if key == Key::Up { -1 } else { 1 };
+                let store = store.borrow();
+                let state = &mut *state;
+                for (id, listui) in state.listuis.entries_mut() {
+                    if !listui.focused || listui.is_editing() || listui.entries.is_empty() {
+                        continue;
+                    }
+                    let before = listui.selected_index;
+                    listui.move_selection(direction, &store);
+                    if listui.selected_index != before {
+                        state.ui_events.push(UiEvent::SelectionChanged {
+                            list: id,
+                            index: listui.selected_index as usize,
+                        });
+                    }
+                }
+                state.last_ui_time = Some(SystemTime::now());
+            }
+        }
+        _ => {}
+    }
+}
 
-        let mut state = state.borrow_mut();
-        let context = state.context.as_mut().unwrap();
-        let _ = context.update();
-        {
-            let config = context.config.lock().unwrap();
-            context.texts.viewport.update(
-                &context.queue.clone().lock().unwrap(),
-                Resolution {
-                    width: config.width,
-                    height: config.height,
-                },
-            );
+fn render_frame(state: &mut State, frame_start: Instant) -> Result<(), ShecvError> {
+    let context = state.context.as_mut().ok_or_else(ShecvError::no_context)?;
+    if context.suspended || context.minimized {
+        return Ok(());
+    }
+    match context.update() {
+        Ok(reloaded) => {
+            for path in reloaded {
+                for ui_file in &mut state.ui_files {
+                    ui_file.changed |= ui_file.path == path;
+                }
+                state.scripts.file_changed(&path);
+                state.notifications.push(&format!("reloaded {}", path));
+            }
+        }
+        Err(e) => state.notifications.push_error(&format!("{}", e)),
+    }
+    for (_, e) in context.shader_errors.drain(..) {
+        state.notifications.push_error(&format!("{}", e));
+    }
+    for (path, e) in context.watch_errors.drain(..) {
+        state.notifications.push_error(&format!("{}: {}", path, e));
+    }
+    {
+        let config = context.config.lock()?;
+        context.texts.viewport.update(
+            &*context.queue.lock()?,
+            Resolution {
+                width: config.width,
+                height: config.height,
+            },
+        );
 
-            context
-                .texts
-                .prepare(
-                    context.device.clone(),
-                    context.queue.clone(),
-                    config.width,
-                    config.height,
-                )
-                .unwrap();
+        if let Err(e) = context.texts.prepare(
+            context.device.clone(),
+            context.queue.clone(),
+            config.width,
+            config.height,
+        ) {
+            warn!("{}", e);
         }
+    }
 
-        context.render().unwrap();
+    match context.render() {
+        Ok(()) => {}
+        Err(ShecvError::Surface(SurfaceError::OutOfMemory)) => {
+            error!("out of memory acquiring a frame, quitting");
+            state.flow_command = FlowCommand::Quit;
+        }
+        Err(e) => warn!("{}", e),
     }
+    state.debug_overlay.record(frame_start.elapsed());
+    Ok(())
 }
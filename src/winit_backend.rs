@@ -0,0 +1,309 @@
+use std::{collections::HashMap, time::Duration};
+
+use glam::{IVec2, UVec2, Vec2};
+use image::RgbaImage;
+use wgpu::SurfaceTargetUnsafe;
+use winit::{
+    application::ApplicationHandler,
+    dpi::{LogicalPosition, LogicalSize},
+    event::{DeviceEvent, DeviceId, ElementState, Ime, KeyEvent, Modifiers, WindowEvent},
+    event_loop::{ActiveEventLoop, EventLoop},
+    keyboard::{Key as WinitKey, NamedKey},
+    platform::pump_events::{EventLoopExtPumpEvents, PumpStatus},
+    window::{CursorGrabMode, CursorIcon, Fullscreen, Icon, Window, WindowId},
+};
+
+use crate::{
+    backend::{Backend, BackendEvent, BackendWindow, FullscreenMode, Key},
+    error::ShecvError,
+};
+
+// winit drives its own loop, so events are pumped once per frame and collected
+// for process_events. Pumping is not supported on web, iOS or Android.
+pub struct WinitBackend {
+    event_loop: EventLoop<()>,
+    collector: Collector,
+}
+
+impl WinitBackend {
+    pub fn new() -> Result<Self, ShecvError> {
+        let event_loop = EventLoop::new().map_err(|e| ShecvError::Device(e.to_string()))?;
+        Ok(Self {
+            event_loop,
+            collector: Collector::default(),
+        })
+    }
+}
+
+impl Backend for WinitBackend {
+    fn create_window(
+        &mut self,
+        width: u32,
+        height: u32,
+        title: &str,
+    ) -> Result<Box<dyn BackendWindow>, ShecvError> {
+        let attributes = Window::default_attributes()
+            .with_title(title)
+            .with_inner_size(LogicalSize::new(width, height));
+        // windows made outside the loop's callbacks are still supported through 0.30
+        #[allow(deprecated)]
+        let window = self
+            .event_loop
+            .create_window(attributes)
+            .map_err(|e| ShecvError::Device(e.to_string()))?;
+        // sdl has text input on by default, including composition
+        window.set_ime_allowed(true);
+        self.collector
+            .scale_factors
+            .insert(window.id().into(), window.scale_factor());
+        Ok(Box::new(window))
+    }
+
+    fn poll_events(&mut self) -> Vec<BackendEvent> {
+        let status = self
+            .event_loop
+            .pump_app_events(Some(Duration::ZERO), &mut self.collector);
+        if let PumpStatus::Exit(_) = status {
+            self.collector.events.push(BackendEvent::Quit);
+        }
+        std::mem::take(&mut self.collector.events)
+    }
+
+    // winit has no clipboard of its own
+    fn clipboard_text(&self) -> Option<String> {
+        None
+    }
+
+    fn set_clipboard_text(&mut self, _text: &str) -> Result<(), ShecvError> {
+        Err(ShecvError::Device(
+            "the winit backend has no clipboard".into(),
+        ))
+    }
+}
+
+impl BackendWindow for Window {
+    fn id(&self) -> u64 {
+        Window::id(self).into()
+    }
+
+    fn size(&self) -> (u32, u32) {
+        let size = self.inner_size().to_logical::<u32>(self.scale_factor());
+        (size.width, size.height)
+    }
+
+    fn drawable_size(&self) -> (u32, u32) {
+        let size = self.inner_size();
+        (size.width, size.height)
+    }
+
+    unsafe fn surface_target(&self) -> Result<SurfaceTargetUnsafe, ShecvError> {
+        Ok(SurfaceTargetUnsafe::from_window(self)?)
+    }
+
+    fn set_title(&mut self, title: &str) -> Result<(), ShecvError> {
+        Window::set_title(self, title);
+        Ok(())
+    }
+
+    fn set_icon(&mut self, image: &RgbaImage) -> Result<(), ShecvError> {
+        let (width, height) = image.dimensions();
+        let icon = Icon::from_rgba(image.as_raw().clone(), width, height)
+            .map_err(|e| ShecvError::Device(e.to_string()))?;
+        self.set_window_icon(Some(icon));
+        Ok(())
+    }
+
+    fn set_fullscreen(&mut self, mode: FullscreenMode) -> Result<(), ShecvError> {
+        let fullscreen = match mode {
+            FullscreenMode::Windowed => None,
+            // the largest, fastest mode of the current monitor; borderless if
+            // the platform doesn't list any
+            FullscreenMode::Exclusive => Some(
+                self.current_monitor()
+                    .and_then(|monitor| {
+                        monitor.video_modes().max_by_key(|video_mode| {
+                            let size = video_mode.size();
+                            (
+                                size.width * size.height,
+                                video_mode.refresh_rate_millihertz(),
+                            )
+                        })
+                    })
+                    .map(Fullscreen::Exclusive)
+                    .unwrap_or(Fullscreen::Borderless(None)),
+            ),
+            FullscreenMode::Borderless => Some(Fullscreen::Borderless(None)),
+        };
+        Window::set_fullscreen(self, fullscreen);
+        Ok(())
+    }
+
+    fn set_ime_rect(&self, xy: IVec2, wh: UVec2) {
+        self.set_ime_cursor_area(
+            LogicalPosition::new(xy.x, xy.y),
+            LogicalSize::new(wh.x, wh.y),
+        );
+    }
+
+    fn set_cursor_visible(&mut self, visible: bool) {
+        Window::set_cursor_visible(self, visible);
+    }
+
+    fn set_cursor_grab(&mut self, grab: bool) -> Result<(), ShecvError> {
+        let mode = match grab {
+            true => CursorGrabMode::Confined,
+            false => CursorGrabMode::None,
+        };
+        Window::set_cursor_grab(self, mode).map_err(|e| ShecvError::Device(e.to_string()))
+    }
+
+    // winit has no relative mode; the cursor is hidden and locked in place,
+    // or confined where locking isn't supported, and the deltas come from
+    // the mouse itself
+    fn set_relative_mouse_mode(&mut self, relative: bool) -> Result<(), ShecvError> {
+        Window::set_cursor_visible(self, !relative);
+        let grab = match relative {
+            true => Window::set_cursor_grab(self, CursorGrabMode::Locked)
+                .or_else(|_| Window::set_cursor_grab(self, CursorGrabMode::Confined)),
+            false => Window::set_cursor_grab(self, CursorGrabMode::None),
+        };
+        grab.map_err(|e| ShecvError::Device(e.to_string()))
+    }
+
+    // custom cursors are made by the event loop, which windows can't reach
+    fn set_cursor_image(
+        &mut self,
+        image: Option<&RgbaImage>,
+        _hotspot: UVec2,
+    ) -> Result<(), ShecvError> {
+        if image.is_some() {
+            return Err(ShecvError::Device(
+                "the winit backend has no custom cursors".into(),
+            ));
+        }
+        self.set_cursor(CursorIcon::Default);
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct Collector {
+    events: Vec<BackendEvent>,
+    modifiers: Modifiers,
+    // in window units, see MouseMotion
+    cursor: Vec2,
+    scale_factors: HashMap<u64, f64>,
+}
+
+impl ApplicationHandler for Collector {
+    fn resumed(&mut self, _event_loop: &ActiveEventLoop) {
+        self.events.push(BackendEvent::Resumed);
+    }
+
+    fn suspended(&mut self, _event_loop: &ActiveEventLoop) {
+        self.events.push(BackendEvent::Suspended);
+    }
+
+    fn window_event(&mut self, _event_loop: &ActiveEventLoop, id: WindowId, event: WindowEvent) {
+        let window_id: u64 = id.into();
+        match event {
+            WindowEvent::CloseRequested => {
+                self.events.push(BackendEvent::CloseRequested { window_id });
+            }
+            // a zero size marks the window minimized, see State::set_minimized
+            WindowEvent::Resized(_) => {
+                self.events.push(BackendEvent::Resized { window_id });
+            }
+            WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+                self.scale_factors.insert(window_id, scale_factor);
+                self.events.push(BackendEvent::Resized { window_id });
+            }
+            WindowEvent::ModifiersChanged(modifiers) => self.modifiers = modifiers,
+            WindowEvent::DroppedFile(path) => {
+                self.events.push(BackendEvent::DroppedFile {
+                    window_id: Some(window_id),
+                    path,
+                });
+            }
+            // motion is reported from device events, which keep coming in
+            // relative mode; this only keeps the position they report
+            WindowEvent::CursorMoved { position, .. } => {
+                let scale = self.scale_factors.get(&window_id).copied().unwrap_or(1.0);
+                let position = position.to_logical::<f32>(scale);
+                self.cursor = Vec2::new(position.x, position.y);
+            }
+            WindowEvent::Ime(Ime::Preedit(text, _)) => {
+                self.events.push(BackendEvent::TextEditing {
+                    window_id: Some(window_id),
+                    text,
+                });
+            }
+            WindowEvent::Ime(Ime::Commit(text)) => {
+                self.events.push(BackendEvent::TextInput {
+                    window_id: Some(window_id),
+                    text,
+                });
+            }
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        logical_key,
+                        text,
+                        state: ElementState::Pressed,
+                        ..
+                    },
+                ..
+            } => {
+                let modifiers = self.modifiers.state();
+                let ctrl = modifiers.control_key() || modifiers.super_key();
+                self.events.push(BackendEvent::KeyDown {
+                    window_id: Some(window_id),
+                    key: winit_key(&logical_key),
+                    alt: modifiers.alt_key(),
+                    ctrl,
+                });
+                // sdl sends typed text separately from key presses, and none
+                // for shortcuts or control keys; match it
+                let typed = text.filter(|text| !ctrl && !text.chars().any(char::is_control));
+                if let Some(text) = typed {
+                    self.events.push(BackendEvent::TextInput {
+                        window_id: Some(window_id),
+                        text: text.to_string(),
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn device_event(&mut self, _event_loop: &ActiveEventLoop, _id: DeviceId, event: DeviceEvent) {
+        if let DeviceEvent::MouseMotion { delta: (dx, dy) } = event {
+            self.events.push(BackendEvent::MouseMotion {
+                window_id: None,
+                position: self.cursor,
+                delta: Vec2::new(dx as f32, dy as f32),
+            });
+        }
+    }
+}
+
+fn winit_key(key: &WinitKey) -> Key {
+    match key {
+        WinitKey::Named(NamedKey::Escape) => Key::Escape,
+        WinitKey::Named(NamedKey::Enter) => Key::Enter,
+        WinitKey::Named(NamedKey::Backspace) => Key::Backspace,
+        WinitKey::Named(NamedKey::ArrowLeft) => Key::Left,
+        WinitKey::Named(NamedKey::ArrowRight) => Key::Right,
+        WinitKey::Named(NamedKey::ArrowUp) => Key::Up,
+        WinitKey::Named(NamedKey::ArrowDown) => Key::Down,
+        WinitKey::Named(NamedKey::F3) => Key::F3,
+        WinitKey::Named(NamedKey::F12) => Key::F12,
+        WinitKey::Named(NamedKey::Tab) => Key::Tab,
+        WinitKey::Character(c) if c.as_str() == "`" => Key::Backquote,
+        WinitKey::Character(c) if c.eq_ignore_ascii_case("c") => Key::C,
+        WinitKey::Character(c) if c.eq_ignore_ascii_case("v") => Key::V,
+        WinitKey::Character(c) if c.eq_ignore_ascii_case("y") => Key::Y,
+        WinitKey::Character(c) if c.eq_ignore_ascii_case("z") => Key::Z,
+        _ => Key::Other,
+    }
+}
@@ -0,0 +1,195 @@
+use glam::{Vec2, Vec3};
+
+use crate::{
+    error::ShecvError,
+    layer::RenderLayer,
+    slots::{SlotId, SlotKey},
+    text::{TextCollection, TextEffect},
+    theme::{StyleColor, ThemeRole},
+    types::ValueStore,
+    window::State,
+};
+
+// A handle to a label in State::world_labels.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct WorldLabelId(SlotKey);
+
+impl SlotId for WorldLabelId {
+    fn from_key(key: SlotKey) -> Self {
+        WorldLabelId(key)
+    }
+
+    fn key(self) -> SlotKey {
+        self.0
+    }
+}
+
+// Where a label is pinned, and so which camera moves it.
+#[allow(dead_code)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum LabelAnchor {
+    // a point seen through GeoManager::camera, as perspective groups are
+    Perspective(Vec3),
+    // a point seen through GeoManager::world_camera, as world groups are
+    World(Vec2),
+}
+
+// Text pinned to a point in a scene rather than the screen, e.g. a nameplate
+// or a note on a model. Each layout the point is taken through its camera to
+// the screen, and the label drawn there on the world layer, centered over
+// it and always facing the viewer. Labels behind the camera or off screen
+// aren't drawn. Nothing hides them behind geometry; they draw over the
+// world groups, nearest last.
+pub struct WorldLabel {
+    pub text: String,
+    pub anchor: LabelAnchor,
+    // logical pixels, or world units with scale_with_distance
+    pub size: f32,
+    // grow and shrink with distance or zoom like the scene around it,
+    // instead of keeping one size on screen
+    pub scale_with_distance: bool,
+    // logical pixels on screen from the anchor, e.g. up above a head
+    pub offset: Vec2,
+    pub color: StyleColor,
+    pub effect: TextEffect,
+    pub visible_key: Option<String>,
+    // where it was last drawn, top left and size in logical pixels
+    pub rect: Option<(Vec2, Vec2)>,
+}
+
+#[allow(dead_code)]
+impl WorldLabel {
+    pub fn new(text: &str, anchor: LabelAnchor) -> Self {
+        Self {
+            text: text.to_string(),
+            anchor,
+            size: 16.0,
+            scale_with_distance: false,
+            offset: Vec2::ZERO,
+            color: ThemeRole::Text.into(),
+            effect: TextEffect::default(),
+            visible_key: None,
+            rect: None,
+        }
+    }
+
+    pub fn size(mut self, size: f32) -> Self {
+        self.size = size;
+        self
+    }
+
+    // size is in world units from here on.
+    pub fn scaled(mut self) -> Self {
+        self.scale_with_distance = true;
+        self
+    }
+
+    pub fn offset(mut self, offset: Vec2) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    pub fn color(mut self, color: impl Into<StyleColor>) -> Self {
+        self.color = color.into();
+        self
+    }
+
+    pub fn effect(mut self, effect: TextEffect) -> Self {
+        self.effect = effect;
+        self
+    }
+
+    pub fn bind_visible(mut self, key: &str) -> Self {
+        self.visible_key = Some(key.to_string());
+        self
+    }
+}
+
+// labels smaller than this on screen aren't worth drawing
+const MIN_PIXEL_SIZE: f32 = 4.0;
+
+#[allow(dead_code)]
+impl State<'_> {
+    pub fn add_world_label(&mut self, label: WorldLabel) -> WorldLabelId {
+        self.world_labels.insert(label)
+    }
+
+    pub fn remove_world_label(&mut self, id: WorldLabelId) -> Option<WorldLabel> {
+        self.world_labels.remove(id)
+    }
+
+    // Called by layout_listuis, after the cameras have moved for the frame.
+    pub fn layout_world_labels(&mut self, store: &ValueStore) -> Result<(), ShecvError> {
+        if self.world_labels.is_empty() {
+            return Ok(());
+        }
+        let context = self.context.as_mut().ok_or_else(ShecvError::no_context)?;
+        let extent = context.logical_size()?.as_vec2();
+        let camera = context.geos.camera;
+        let view_projection = camera.view_projection();
+        let world_camera = context.geos.world_camera;
+        let theme = self.themes.current();
+
+        // where and how big on screen, and how far away
+        let mut placed = vec![];
+        for (id, label) in self.world_labels.entries_mut() {
+            label.rect = None;
+            let visible = match &label.visible_key {
+                Some(key) => store.load_as::<bool>(key).unwrap_or(true),
+                None => true,
+            };
+            if !visible {
+                continue;
+            }
+            let (point, pixels_per_unit, depth) = match label.anchor {
+                LabelAnchor::Perspective(position) => {
+                    let clip = view_projection * position.extend(1.0);
+                    if clip.w <= camera.near {
+                        continue;
+                    }
+                    let ndc = clip.truncate() / clip.w;
+                    let point = Vec2::new(ndc.x + 1.0, 1.0 - ndc.y) * 0.5 * extent;
+                    let pixels_per_unit = extent.y / (2.0 * (camera.fov_y / 2.0).tan() * clip.w);
+                    (point, pixels_per_unit, clip.w)
+                }
+                LabelAnchor::World(position) => (
+                    world_camera.world_to_screen(position),
+                    world_camera.zoom,
+                    0.0,
+                ),
+            };
+            let size = match label.scale_with_distance {
+                true => label.size * pixels_per_unit,
+                false => label.size,
+            };
+            if size < MIN_PIXEL_SIZE {
+                continue;
+            }
+            placed.push((id, point + label.offset, size, depth));
+        }
+        // far ones first, so near ones draw over them
+        placed.sort_by(|a, b| b.3.total_cmp(&a.3));
+
+        for (id, center, size, _) in placed {
+            let label = &mut self.world_labels[id];
+            let line_height = size / 0.8;
+            let metrics = TextCollection::line_metrics(line_height);
+            let width = context.texts.measure(&label.text, metrics).0.ceil() + 1.0;
+            let wh = Vec2::new(width, line_height.ceil());
+            let xy = center - wh / 2.0;
+            if xy.x > extent.x || xy.y > extent.y || xy.x + wh.x < 0.0 || xy.y + wh.y < 0.0 {
+                continue;
+            }
+            let index = context.texts.new_text(
+                (xy.x as f64, xy.y as f64, wh.x as f64, wh.y as f64),
+                &label.text,
+                1.0,
+                label.color.resolve(theme),
+            );
+            context.texts.set_layer(index, RenderLayer::World);
+            context.texts.set_effect(index, label.effect);
+            label.rect = Some((xy, wh));
+        }
+        Ok(())
+    }
+}